@@ -0,0 +1,48 @@
+//! Compare the throughput of the json decoders on the responses of the fixtures:
+//!
+//! ```text
+//! cargo bench --bench decode --features test-util,simd-json
+//! ```
+
+use std::{hint::black_box, time::Instant};
+
+use hyper::body::Bytes;
+#[cfg(feature = "simd-json")]
+use neutral::decode::SimdJsonDecoder;
+use neutral::{
+    decode::{JsonDecoder, SerdeJsonDecoder},
+    testing::fixtures,
+};
+
+const ROUNDS: usize = 2_000;
+
+fn main() {
+    let bodies = [
+        serde_json::to_vec(&fixtures::ip_info()),
+        serde_json::to_vec(&fixtures::ip_probe()),
+        serde_json::to_vec(&fixtures::ip_blocklist()),
+        serde_json::to_vec(&fixtures::phone_validate()),
+        serde_json::to_vec(&fixtures::hlr_lookup()),
+    ]
+    .map(|body| Bytes::from(body.unwrap()));
+
+    let decoders: Vec<(&str, Box<dyn JsonDecoder>)> = vec![
+        ("serde_json", Box::new(SerdeJsonDecoder)),
+        #[cfg(feature = "simd-json")]
+        ("simd-json", Box::new(SimdJsonDecoder)),
+    ];
+    for (name, decoder) in decoders {
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            for body in &bodies {
+                black_box(decoder.decode(body.clone()).unwrap());
+            }
+        }
+        println!(
+            "{}: decoded {} bodies in {:?}",
+            name,
+            ROUNDS * bodies.len(),
+            start.elapsed()
+        );
+    }
+}
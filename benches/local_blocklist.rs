@@ -0,0 +1,64 @@
+//! Time the indexing of a blocklist the size of the IPv4 one, and lookups in it:
+//!
+//! ```text
+//! cargo bench --bench local_blocklist
+//! ```
+
+use std::{
+    hint::black_box,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::Instant,
+};
+
+use neutral::{
+    blocklist::{BlocklistCategories, IpBlocklistEntry, IpNetwork},
+    local_blocklist::BlocklistSnapshot,
+};
+
+fn main() {
+    // The IPv4 list holds a few hundred thousand ranges, mostly single addresses.
+    let mut entries: Vec<_> = (0..200_000u32)
+        .map(|i| IpBlocklistEntry {
+            network: IpNetwork {
+                addr: IpAddr::V4(Ipv4Addr::from(i.wrapping_mul(2_654_435_761))),
+                prefix_len: if i % 10 == 0 { 24 } else { 32 },
+            },
+            categories: BlocklistCategories::default(),
+            last_seen: None,
+        })
+        .collect();
+    entries.extend((0..20_000u128).map(|i| IpBlocklistEntry {
+        network: IpNetwork {
+            addr: IpAddr::V6(Ipv6Addr::from((0x2001_0db8u128 << 96) | (i << 64))),
+            prefix_len: 64,
+        },
+        categories: BlocklistCategories::default(),
+        last_seen: None,
+    }));
+
+    let start = Instant::now();
+    let snapshot = BlocklistSnapshot::from_entries(entries.clone());
+    let built = start.elapsed();
+
+    let start = Instant::now();
+    let mut listed = 0;
+    for entry in &entries {
+        listed += snapshot.contains(entry.network.addr).is_some() as usize;
+        listed += snapshot
+            .contains(IpAddr::V4(Ipv4Addr::from(
+                (listed as u32).wrapping_mul(7919),
+            )))
+            .is_some() as usize;
+    }
+    let looked_up = start.elapsed();
+    black_box(listed);
+
+    println!(
+        "indexed {} ranges in {:?} ({} bytes), {} lookups in {:?}",
+        snapshot.len(),
+        built,
+        snapshot.heap_size(),
+        entries.len() * 2,
+        looked_up
+    );
+}
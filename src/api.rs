@@ -0,0 +1,169 @@
+//! # Api module
+//! One object safe trait per endpoint, so code calling neutrinoapi.com can be tested against a fake client.
+//!
+//! Each trait is implemented by [Neutral](../struct.Neutral.html), sending the call with a default [CallContext](../context/struct.CallContext.html),
+//! and by the struct of a reusable endpoint, sending the call as configured, e.g. with its context or timeout.
+//! The structs of the one-shot endpoints, such as [SmsVerify](../sms_verify/struct.SmsVerify.html), don't implement it:
+//! sending them by reference would let a configured call be sent twice.
+//!
+//! ```ignore
+//! async fn country_of(api: &dyn IpInfoApi, ip_addr: IpAddr) -> Result<String, Error> {
+//!     Ok(api.send(ip_addr).await?.country)
+//! }
+//!
+//! let country = country_of(&neutral, ip_addr).await?;
+//! let country = country_of(&neutral.ip_info().timeout(Duration::from_secs(1)), ip_addr).await?;
+//! ```
+
+use std::net::IpAddr;
+
+use futures::future::BoxFuture;
+use neutral_types::{
+    hlr_lookup::HlrLookupResponse, ip_blocklist::IpBlocklistResponse, ip_info::IpInfoResponse,
+    ip_probe::IpProbeResponse, phone_validate::PhoneValidateResponse,
+};
+
+use crate::{
+    bin_lookup::{BinLookup, BinLookupResponse},
+    email_verify::{EmailVerify, EmailVerifyResponse},
+    hlr_lookup::HlrLookup,
+    ip_blocklist::IpBlocklist,
+    ip_info::IpInfo,
+    ip_probe::IpProbe,
+    phone_validate::PhoneValidate,
+    phone_verify::{PhoneVerifyOptions, PhoneVerifyResponse},
+    sms_message::SmsMessageResponse,
+    sms_verify::{SmsVerifyOptions, SmsVerifyResponse},
+    ua_lookup::{UaLookup, UaLookupResponse},
+    verify_security_code::{VerifySecurityCode, VerifySecurityCodeResponse},
+    Error, Neutral,
+};
+
+/// Declare the trait `$api` of the endpoint `$endpoint`, implemented by [Neutral](../struct.Neutral.html) through its `$accessor` accessor.
+///
+/// `ref` endpoints are also implemented by their struct, sent by reference. `owned` endpoints are one-shot, their `send` consumes them.
+macro_rules! endpoint_api {
+    ($api:ident, $endpoint:ident, $accessor:ident, $kind:ident, ($($arg:ident: $ty:ty),*) -> $response:ty) => {
+        #[doc = concat!("Send a call to the ", stringify!($endpoint), " endpoint, see [", stringify!($endpoint), "::send](../", stringify!($accessor), "/struct.", stringify!($endpoint), ".html#method.send).")]
+        pub trait $api: Send + Sync {
+            fn send(&self, $($arg: $ty),*) -> BoxFuture<'_, Result<$response, Error>>;
+        }
+
+        impl $api for Neutral {
+            fn send(&self, $($arg: $ty),*) -> BoxFuture<'_, Result<$response, Error>> {
+                Box::pin(async move { self.$accessor().send($($arg),*).await })
+            }
+        }
+
+        endpoint_api!(@endpoint $kind, $api, $endpoint, ($($arg: $ty),*) -> $response);
+    };
+    (@endpoint ref, $api:ident, $endpoint:ident, ($($arg:ident: $ty:ty),*) -> $response:ty) => {
+        impl $api for $endpoint<'_> {
+            fn send(&self, $($arg: $ty),*) -> BoxFuture<'_, Result<$response, Error>> {
+                Box::pin($endpoint::send(self, $($arg),*))
+            }
+        }
+    };
+    (@endpoint owned, $api:ident, $endpoint:ident, ($($arg:ident: $ty:ty),*) -> $response:ty) => {};
+}
+
+endpoint_api!(BinLookupApi, BinLookup, bin_lookup, ref, (bin_number: String, customer_ip: Option<IpAddr>) -> BinLookupResponse);
+endpoint_api!(EmailVerifyApi, EmailVerify, email_verify, ref, (email: String) -> EmailVerifyResponse);
+endpoint_api!(HlrLookupApi, HlrLookup, hlr_lookup, ref, (phone_number: String) -> HlrLookupResponse);
+endpoint_api!(IpBlocklistApi, IpBlocklist, ip_blocklist, ref, (ip_addr: IpAddr) -> IpBlocklistResponse);
+endpoint_api!(IpInfoApi, IpInfo, ip_info, ref, (ip_addr: IpAddr) -> IpInfoResponse);
+endpoint_api!(IpProbeApi, IpProbe, ip_probe, ref, (ip_addr: IpAddr) -> IpProbeResponse);
+endpoint_api!(PhoneValidateApi, PhoneValidate, phone_validate, ref, (phone_number: String) -> PhoneValidateResponse);
+endpoint_api!(PhoneVerifyApi, PhoneVerify, phone_verify, owned, (phone_number: String, options: PhoneVerifyOptions) -> PhoneVerifyResponse);
+endpoint_api!(SmsMessageApi, SmsMessage, sms_message, owned, (number: String, message: String, country_code: Option<String>) -> SmsMessageResponse);
+endpoint_api!(SmsVerifyApi, SmsVerify, sms_verify, owned, (phone_number: String, options: SmsVerifyOptions) -> SmsVerifyResponse);
+endpoint_api!(UaLookupApi, UaLookup, ua_lookup, ref, (ua: String) -> UaLookupResponse);
+endpoint_api!(VerifySecurityCodeApi, VerifySecurityCode, verify_security_code, ref, (security_code: String, limit_by_phone_number: Option<String>) -> VerifySecurityCodeResponse);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ApiAuth;
+    use mockito::{mock, Matcher};
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    /// A fake ip info endpoint answering from a map, recording every call.
+    struct FakeIpInfo {
+        countries: HashMap<IpAddr, &'static str>,
+        calls: Mutex<Vec<IpAddr>>,
+    }
+
+    impl IpInfoApi for FakeIpInfo {
+        fn send(&self, ip_addr: IpAddr) -> BoxFuture<'_, Result<IpInfoResponse, Error>> {
+            Box::pin(async move {
+                self.calls.lock().unwrap().push(ip_addr);
+                let mut response: IpInfoResponse =
+                    serde_json::from_str(crate::fixtures::IP_INFO_BODY)?;
+                response.country = self
+                    .countries
+                    .get(&ip_addr)
+                    .copied()
+                    .unwrap_or_default()
+                    .to_owned();
+                Ok(response)
+            })
+        }
+    }
+
+    /// Application code written against the trait.
+    async fn countries_of(api: &dyn IpInfoApi, ip_addrs: &[IpAddr]) -> Result<Vec<String>, Error> {
+        let mut countries = Vec::new();
+        for ip_addr in ip_addrs {
+            countries.push(api.send(*ip_addr).await?.country);
+        }
+        Ok(countries)
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_fake_behind_an_arc() {
+        let fake = Arc::new(FakeIpInfo {
+            countries: HashMap::from([(ip("192.0.2.1"), "France"), (ip("192.0.2.2"), "Japan")]),
+            calls: Mutex::new(Vec::new()),
+        });
+        let api: Arc<dyn IpInfoApi> = fake.clone();
+
+        let countries = countries_of(api.as_ref(), &[ip("192.0.2.1"), ip("192.0.2.2")])
+            .await
+            .unwrap();
+        assert_eq!(countries, vec!["France", "Japan"]);
+        assert_eq!(
+            *fake.calls.lock().unwrap(),
+            vec![ip("192.0.2.1"), ip("192.0.2.2")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_neutral_and_endpoint_implement_the_trait() {
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.190$".into()))
+            .with_status(200)
+            .with_body(crate::fixtures::IP_INFO_BODY)
+            .expect(2)
+            .create();
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        let from_client = countries_of(&neutral, &[ip("203.0.113.190")]).await;
+        let endpoint = neutral.ip_info().timeout(Duration::from_secs(5));
+        let from_endpoint = countries_of(&endpoint, &[ip("203.0.113.190")]).await;
+        assert_eq!(from_client.unwrap(), from_endpoint.unwrap());
+        _m.assert();
+    }
+}
@@ -0,0 +1,47 @@
+//! # Audit module
+//! Keep a trail of the calls sent to neutrinoapi.com, with the [CallContext](../context/struct.CallContext.html) they were issued with.
+//!
+//! Implement [AuditSink](./trait.AuditSink.html) and register it with [NeutralBuilder::audit](../builder/struct.NeutralBuilder.html#method.audit).
+
+use std::{fmt, sync::Arc, time::Duration};
+
+use http::StatusCode;
+
+use crate::context::CallContext;
+
+/// Describes a completed call.
+#[derive(Debug, Clone)]
+pub struct AuditEvent<'a> {
+    /// Name of the neutrinoapi.com endpoint.
+    pub endpoint: &'a str,
+    /// Status of the response, `None` when no response was received.
+    pub status: Option<StatusCode>,
+    /// Time spent by the call.
+    pub elapsed: Duration,
+    /// Context the call was issued with.
+    pub context: &'a CallContext,
+}
+
+/// Receive an event for every call sent to neutrinoapi.com.
+pub trait AuditSink: Send + Sync {
+    /// Called once a call completed.
+    fn on_call(&self, event: &AuditEvent<'_>);
+}
+
+/// The sinks registered on a client.
+#[derive(Clone, Default)]
+pub(crate) struct AuditSinks(pub(crate) Vec<Arc<dyn AuditSink>>);
+
+impl fmt::Debug for AuditSinks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AuditSinks").field(&self.0.len()).finish()
+    }
+}
+
+impl AuditSink for AuditSinks {
+    fn on_call(&self, event: &AuditEvent<'_>) {
+        for sink in &self.0 {
+            sink.on_call(event);
+        }
+    }
+}
@@ -0,0 +1,357 @@
+//! # Batch module
+//! Run a batch of [reports](../report/index.html), keeping track of the failed inputs to replay only them later.
+//!
+//! With [Batch::manifest](./struct.Batch.html#method.manifest), a run writes a failure manifest: one JSON line per failed input, with its index in the batch,
+//! the class of its error and the number of runs it failed.
+//! [Batch::resume_from_manifest](./struct.Batch.html#method.resume_from_manifest) submits again only the inputs of a manifest,
+//! passes new reports to the sink with their original index, then rewrites the manifest with the inputs which still fail.
+//!
+//! ```ignore
+//! let summary = neutral
+//!     .batch()
+//!     .manifest("enrichment.failures.jsonl")
+//!     .run(items, &mut sink)
+//!     .await?;
+//!
+//! // The next night
+//! let summary = neutral
+//!     .batch()
+//!     .manifest("enrichment.failures.jsonl")
+//!     .resume_from_manifest("enrichment.failures.jsonl", &mut sink)
+//!     .await?;
+//! ```
+//!
+//! Every line carries the [MANIFEST_VERSION](./constant.MANIFEST_VERSION.html) it was written with.
+//! Readers ignore unknown fields, so a manifest written by a newer version stays readable as long as its entries keep the fields of this one.
+
+use std::collections::HashMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::{Path, PathBuf};
+
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    report::{indexed_concurrent, Report, ReportRequest},
+    Error, Neutral,
+};
+
+/// Version of the manifest entries written by this crate.
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// Receive the successful reports of a batch.
+pub trait BatchSink {
+    /// Called once per successful report, with the index of its input in the original batch.
+    fn on_report(&mut self, index: usize, report: Report);
+}
+
+impl<F> BatchSink for F
+where
+    F: FnMut(usize, Report),
+{
+    fn on_report(&mut self, index: usize, report: Report) {
+        self(index, report)
+    }
+}
+
+/// A failed input, as written in a failure manifest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Version of the manifest format the entry was written with.
+    pub version: u32,
+    /// Index of the input in the original batch.
+    pub index: usize,
+    pub input: ReportRequest,
+    /// Class of the last error, e.g. `server` or `timeout`.
+    pub error_class: String,
+    /// Number of runs in which the input failed.
+    pub attempts: u32,
+}
+
+/// Outcome of a batch run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BatchSummary {
+    /// Number of inputs passed to the sink.
+    pub succeeded: usize,
+    /// Number of inputs which failed.
+    pub failed: usize,
+}
+
+/// Run reports of many inputs, see the [batch](./index.html) module.
+pub struct Batch<'a> {
+    pub(crate) neutral: &'a Neutral,
+    concurrency: usize,
+    #[cfg(not(target_arch = "wasm32"))]
+    manifest: Option<PathBuf>,
+}
+
+impl<'a> Batch<'a> {
+    pub(crate) fn new(neutral: &'a Neutral) -> Self {
+        Batch {
+            neutral,
+            concurrency: 4,
+            #[cfg(not(target_arch = "wasm32"))]
+            manifest: None,
+        }
+    }
+
+    /// Set the maximum number of reports fetched at the same time, 4 by default.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Write the failure manifest of the run to `path`, replacing any existing file.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn manifest<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.manifest = Some(path.into());
+        self
+    }
+
+    /// Fetch the reports of `items`, passing the successful ones to `sink`.
+    pub async fn run<S: BatchSink>(
+        &self,
+        items: Vec<ReportRequest>,
+        sink: &mut S,
+    ) -> Result<BatchSummary, Error> {
+        let entries = items
+            .into_iter()
+            .enumerate()
+            .map(|(index, input)| (index, input, 0))
+            .collect();
+        self.submit(entries, sink).await
+    }
+
+    /// Fetch again the reports of the inputs listed in the manifest at `path`, passing the successful ones to `sink`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn resume_from_manifest<P: AsRef<Path>, S: BatchSink>(
+        &self,
+        path: P,
+        sink: &mut S,
+    ) -> Result<BatchSummary, Error> {
+        let manifest = tokio::fs::read_to_string(path).await?;
+        let entries = manifest
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let entry: ManifestEntry = serde_json::from_str(line)?;
+                Ok((entry.index, entry.input, entry.attempts))
+            })
+            .collect::<Result<_, Error>>()?;
+        self.submit(entries, sink).await
+    }
+
+    async fn submit<S: BatchSink>(
+        &self,
+        entries: Vec<(usize, ReportRequest, u32)>,
+        sink: &mut S,
+    ) -> Result<BatchSummary, Error> {
+        let inputs: HashMap<usize, (ReportRequest, u32)> = entries
+            .iter()
+            .map(|(index, input, attempts)| (*index, (input.clone(), *attempts)))
+            .collect();
+
+        let mut results = indexed_concurrent(entries, self.concurrency, |(index, input, _)| {
+            let report = self.neutral.report(input);
+            async move { (index, report.await) }
+        });
+
+        let mut summary = BatchSummary::default();
+        let mut failures = Vec::new();
+        while let Some((_, (index, result))) = results.next().await {
+            match result {
+                Ok(report) => {
+                    summary.succeeded += 1;
+                    sink.on_report(index, report);
+                }
+                Err(err) => {
+                    summary.failed += 1;
+                    let (input, attempts) = inputs[&index].clone();
+                    failures.push(ManifestEntry {
+                        version: MANIFEST_VERSION,
+                        index,
+                        input,
+                        error_class: error_class(&err).to_owned(),
+                        attempts: attempts + 1,
+                    });
+                }
+            }
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = &self.manifest {
+            failures.sort_by_key(|entry| entry.index);
+            let mut manifest = String::new();
+            for entry in &failures {
+                manifest.push_str(&serde_json::to_string(entry)?);
+                manifest.push('\n');
+            }
+            tokio::fs::write(path, manifest).await?;
+        }
+        Ok(summary)
+    }
+}
+
+/// Returns the class of `err` written in a manifest.
+fn error_class(err: &Error) -> &'static str {
+    match err {
+        Error::Hyper(_)
+        | Error::Connect(_)
+        | Error::Service(_)
+        | Error::TooManyRedirects { .. } => "transport",
+        Error::Timeout(_) => "timeout",
+        Error::RateLimited { .. } => "rate_limited",
+        Error::ServerError(_) => "server",
+        Error::Neutrino(_) => "client",
+        Error::Unauthorized(_) => "unauthorized",
+        Error::FeatureNotEnabled { .. }
+        | Error::InsufficientCredit(_)
+        | Error::QuotaExceeded(_) => "account",
+        Error::InvalidPhoneNumber(_) | Error::InputTooLarge { .. } | Error::BogonAddress(_) => {
+            "input"
+        }
+        Error::CircuitOpen { .. } => "circuit_open",
+        _ => "other",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ApiAuth;
+    use mockito::{mock, Matcher};
+
+    fn body(email: &str) -> String {
+        format!(
+            r#"
+            {{
+                "valid": true,
+                "verified": true,
+                "email": "{}",
+                "domain": "example.com",
+                "provider": "example.com",
+                "is_freemail": false,
+                "is_disposable": false,
+                "is_personal": true,
+                "is_catch_all": false,
+                "is_deferred": false,
+                "syntax_error": false,
+                "domain_error": false,
+                "typos_fixed": false,
+                "smtp_status": "ok",
+                "smtp_response": "250 2.1.5 Ok"
+            }}"#,
+            email
+        )
+    }
+
+    #[test]
+    fn test_manifest_entry_is_forward_readable() {
+        let line = r#"{"version": 2, "index": 3, "input": {"kind": "ip_full", "value": "203.0.113.80"}, "error_class": "server", "attempts": 2, "next_retry_at": 1700000000}"#;
+        let entry: ManifestEntry = serde_json::from_str(line).unwrap();
+        assert_eq!(
+            entry,
+            ManifestEntry {
+                version: 2,
+                index: 3,
+                input: ReportRequest::IpFull("203.0.113.80".parse().unwrap()),
+                error_class: "server".to_owned(),
+                attempts: 2,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resume_from_manifest() {
+        let is_failing = |i: usize| i.is_multiple_of(4);
+        let email = |i: usize| {
+            if is_failing(i) {
+                format!("batch-fail-{}@example.com", i)
+            } else {
+                format!("batch-ok-{}@example.com", i)
+            }
+        };
+
+        let _ok = mock("GET", "/email-verify")
+            .match_query(Matcher::Regex("email=batch-ok-".into()))
+            .with_status(200)
+            .with_body(body("batch-ok@example.com"))
+            .expect(15)
+            .create();
+        let failing = mock("GET", "/email-verify")
+            .match_query(Matcher::Regex("email=batch-fail-".into()))
+            .with_status(503)
+            .with_body("unavailable")
+            .expect(5)
+            .create();
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+        let path = std::env::temp_dir().join(format!(
+            "neutral-batch-manifest-{}.jsonl",
+            std::process::id()
+        ));
+
+        let mut reported = Vec::new();
+        let mut sink = |index: usize, _report: Report| reported.push(index);
+        let items = (0..20)
+            .map(|i| ReportRequest::EmailFull(email(i)))
+            .collect();
+        let summary = neutral
+            .batch()
+            .manifest(&path)
+            .run(items, &mut sink)
+            .await
+            .unwrap();
+        assert_eq!(
+            summary,
+            BatchSummary {
+                succeeded: 15,
+                failed: 5
+            }
+        );
+        failing.assert();
+        drop(failing);
+
+        let manifest = std::fs::read_to_string(&path).unwrap();
+        let entries: Vec<ManifestEntry> = manifest
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 5);
+        assert_eq!(entries[1].index, 4);
+        assert_eq!(entries[1].input, ReportRequest::EmailFull(email(4)));
+        assert_eq!(entries[1].error_class, "server");
+        assert_eq!(entries[1].attempts, 1);
+
+        let recovered = mock("GET", "/email-verify")
+            .match_query(Matcher::Regex("email=batch-fail-".into()))
+            .with_status(200)
+            .with_body(body("batch-fail@example.com"))
+            .expect(5)
+            .create();
+        let summary = neutral
+            .batch()
+            .manifest(&path)
+            .resume_from_manifest(&path, &mut sink)
+            .await
+            .unwrap();
+        assert_eq!(
+            summary,
+            BatchSummary {
+                succeeded: 5,
+                failed: 0
+            }
+        );
+        recovered.assert();
+        _ok.assert();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+        std::fs::remove_file(&path).unwrap();
+
+        reported.sort_unstable();
+        assert_eq!(reported, (0..20).collect::<Vec<_>>());
+    }
+}
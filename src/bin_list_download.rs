@@ -0,0 +1,193 @@
+//! # Bin list download module
+//! Quoted from [neutrinoapi.com](https://www.neutrinoapi.com/api/bin-list-download):
+//!
+//! Download our entire BIN database for direct use on your own systems.
+//!
+//! The list is a CSV file of tens of megabytes: it is streamed as a [ByteStream](../download/struct.ByteStream.html) and never buffered in memory.
+//!
+//! ```ignore
+//! let written = neutral
+//!     .bin_list_download()
+//!     .include_iso3(true)
+//!     .download_to("bin-list.csv")
+//!     .await?;
+//! ```
+
+use hyper::{Body, Request};
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+use std::time::Duration;
+
+use crate::{context::CallContext, download::ByteStream, Error, Neutral};
+
+#[cfg(test)]
+use mockito;
+
+pub struct BinListDownload<'a> {
+    pub(crate) neutral: &'a Neutral,
+    pub(crate) context: CallContext,
+    pub(crate) include_iso3: bool,
+    pub(crate) include_8digit: bool,
+}
+
+impl<'a> BinListDownload<'a> {
+    /// Attach a [CallContext](../context/struct.CallContext.html) to the calls.
+    pub fn context(mut self, context: CallContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Bound each attempt of the call by `timeout` instead of the client default, see [CallContext::timeout](../context/struct.CallContext.html#structfield.timeout).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.context.timeout = Some(timeout);
+        self
+    }
+
+    /// Include the ISO 3166-1 alpha-3 country code of the issuers, `false` by default.
+    pub fn include_iso3(mut self, include_iso3: bool) -> Self {
+        self.include_iso3 = include_iso3;
+        self
+    }
+
+    /// Include the 8 digit BINs, `false` by default.
+    pub fn include_8digit(mut self, include_8digit: bool) -> Self {
+        self.include_8digit = include_8digit;
+        self
+    }
+
+    /// Send a bin list download request to neutrinoapi.com, returns the CSV file as a stream of chunks.
+    pub async fn send(&self) -> Result<ByteStream, Error> {
+        let response = self
+            .neutral
+            .execute_stream("bin-list-download", &self.context, || {
+                build_request(
+                    self.neutral,
+                    self.include_iso3,
+                    self.include_8digit,
+                    &self.context,
+                )
+            })
+            .await?;
+        Ok(ByteStream::new(response.into_body()))
+    }
+
+    /// Download the CSV file to `path`, replacing any existing file, and returns the number of bytes written.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn download_to<P: AsRef<Path>>(&self, path: P) -> Result<u64, Error> {
+        self.send().await?.write_to(path).await
+    }
+}
+
+/// Build the bin list download request, without sending it.
+pub(crate) fn build_request(
+    neutral: &Neutral,
+    include_iso3: bool,
+    include_8digit: bool,
+    context: &CallContext,
+) -> Result<Request<Body>, Error> {
+    let params = form_urlencoded::Serializer::new(String::new())
+        .append_pair("include-iso3", &include_iso3.to_string())
+        .append_pair("include-8digit", &include_8digit.to_string())
+        .finish();
+    neutral.get_request("/bin-list-download", params, context)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ApiAuth;
+    use futures::StreamExt;
+    use mockito::{mock, Matcher};
+
+    fn csv_chunk(i: usize) -> String {
+        format!(
+            "{:06},VISA,DEBIT,CLASSIC,EXAMPLE BANK {},FR,FRA,EUR\n",
+            400000 + i,
+            i
+        )
+    }
+
+    #[test]
+    fn test_bin_list_download_build_request() {
+        let neutral = Neutral::try_new(
+            "https://neutrinoapi.net",
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+        let request = build_request(&neutral, true, false, &CallContext::default()).unwrap();
+        assert_eq!(
+            request.uri().path_and_query().unwrap().as_str(),
+            "/bin-list-download?include-iso3=true&include-8digit=false"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bin_list_download_to() {
+        let _m = mock("GET", "/bin-list-download")
+            .match_query(Matcher::UrlEncoded("include-8digit".into(), "true".into()))
+            .with_status(200)
+            .with_header("content-type", "text/csv")
+            .with_body_from_fn(|w| {
+                for i in 0..500 {
+                    w.write_all(csv_chunk(i).as_bytes())?;
+                    w.flush()?;
+                }
+                Ok(())
+            })
+            .expect(2)
+            .create();
+        let expected: String = (0..500).map(csv_chunk).collect();
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        let mut stream = neutral
+            .bin_list_download()
+            .include_8digit(true)
+            .send()
+            .await
+            .unwrap();
+        let mut chunks = 0;
+        let mut received = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            received.extend_from_slice(&chunk.unwrap());
+            chunks += 1;
+        }
+        assert!(chunks > 1, "the body is not buffered");
+        assert_eq!(received, expected.as_bytes());
+
+        let path =
+            std::env::temp_dir().join(format!("neutral-bin-list-{}.csv", std::process::id()));
+        let written = neutral
+            .bin_list_download()
+            .include_8digit(true)
+            .download_to(&path)
+            .await
+            .unwrap();
+        assert_eq!(written, expected.len() as u64);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), expected);
+        std::fs::remove_file(&path).unwrap();
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_bin_list_download_error() {
+        let _m = mock("GET", "/bin-list-download")
+            .match_query(Matcher::UrlEncoded("include-iso3".into(), "true".into()))
+            .with_status(403)
+            .with_body(r#"{"api-error": 2, "api-error-msg": "INVALID API KEY"}"#)
+            .create();
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        let result = neutral.bin_list_download().include_iso3(true).send().await;
+        assert!(matches!(result, Err(Error::Unauthorized(_))));
+    }
+}
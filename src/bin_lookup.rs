@@ -0,0 +1,362 @@
+//! # Bin lookup module
+//! Quoted from [neutrinoapi.com](https://www.neutrinoapi.com/api/bin-lookup):
+//!
+//! Perform a BIN (Bank Identification Number) or IIN (Issuer Identification Number) lookup.
+//!
+//! Only send the first 6 to 8 digits of a card number. With a customer ip address, the response also tells whether
+//! the ip address is located in the country of the issuer and whether it is listed in a blocklist.
+
+use hyper::{body::Bytes, Body, Request};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{net::IpAddr, time::Duration};
+
+use crate::{context::CallContext, meta::ResponseMeta, params, pipeline::Fetched, Error, Neutral};
+
+#[cfg(test)]
+use mockito;
+
+/// Type of a card.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum CardType {
+    #[serde(alias = "debit")]
+    Debit,
+    #[serde(alias = "credit")]
+    Credit,
+    #[serde(alias = "prepaid")]
+    Prepaid,
+    /// A type not known by this version of the crate.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Response of bin lookup neutrinoapi.com endpoint
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BinLookupResponse {
+    #[serde(alias = "valid", alias = "is_valid")]
+    pub is_valid: bool,
+    /// The card brand, e.g. `VISA` or `MASTERCARD`.
+    pub card_brand: String,
+    pub card_type: CardType,
+    /// The card category, e.g. `CLASSIC` or `PLATINUM`.
+    #[serde(default)]
+    pub card_category: String,
+    #[serde(default)]
+    pub issuer: String,
+    #[serde(default)]
+    pub issuer_website: String,
+    #[serde(default)]
+    pub issuer_phone: String,
+    #[serde(default)]
+    pub country: String,
+    /// ISO 3166-1 alpha-2 country code of the issuer.
+    #[serde(default)]
+    pub country_code: String,
+    /// ISO 3166-1 alpha-3 country code of the issuer.
+    #[serde(default)]
+    pub country_code3: String,
+    #[serde(default)]
+    pub currency_code: String,
+    /// Whether the customer ip address is located in the country of the issuer, only set with a customer ip address.
+    #[serde(default)]
+    pub ip_matches_bin: Option<bool>,
+    /// Whether the customer ip address is listed in a blocklist, only set with a customer ip address.
+    #[serde(default)]
+    pub ip_blocklisted: Option<bool>,
+    /// The blocklists listing the customer ip address, only set with a customer ip address.
+    #[serde(default)]
+    pub ip_blocklists: Option<Vec<String>>,
+}
+
+/// Parameters of a bin lookup call, e.g. to persist a pending call and send it later with [BinLookup::send_params].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BinLookupParams {
+    pub bin_number: String,
+    /// See [BinLookup::send].
+    #[serde(default)]
+    pub customer_ip: Option<IpAddr>,
+}
+
+pub struct BinLookup<'a> {
+    pub(crate) neutral: &'a Neutral,
+    pub(crate) context: CallContext,
+}
+
+impl<'a> BinLookup<'a> {
+    /// Attach a [CallContext](../context/struct.CallContext.html) to the calls.
+    pub fn context(mut self, context: CallContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Bound each attempt of the call by `timeout` instead of the client default, see [CallContext::timeout](../context/struct.CallContext.html#structfield.timeout).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.context.timeout = Some(timeout);
+        self
+    }
+
+    /// Send a bin lookup request to neutrinoapi.com
+    pub async fn send(
+        &self,
+        bin_number: String,
+        customer_ip: Option<IpAddr>,
+    ) -> Result<BinLookupResponse, Error> {
+        self.send_with_meta(bin_number, customer_ip)
+            .await
+            .map(|(_, response)| response)
+    }
+
+    /// Same as [send](#method.send), with the bin number and customer ip address of `params`.
+    pub async fn send_params(&self, params: &BinLookupParams) -> Result<BinLookupResponse, Error> {
+        self.send(params.bin_number.clone(), params.customer_ip)
+            .await
+    }
+
+    /// Same as [send](#method.send), also returning the [ResponseMeta](../meta/struct.ResponseMeta.html) of the response.
+    pub async fn send_with_meta(
+        &self,
+        bin_number: String,
+        customer_ip: Option<IpAddr>,
+    ) -> Result<(ResponseMeta, BinLookupResponse), Error> {
+        let fetched = self.fetch(bin_number, customer_ip).await?;
+        let response: BinLookupResponse = self.neutral.decode("bin-lookup", &fetched.body)?;
+        Ok((fetched.meta, response))
+    }
+
+    /// Same as [send](#method.send), returning the raw body of the response.
+    pub async fn send_raw(
+        &self,
+        bin_number: String,
+        customer_ip: Option<IpAddr>,
+    ) -> Result<Bytes, Error> {
+        Ok(self.fetch(bin_number, customer_ip).await?.body)
+    }
+
+    /// Same as [send](#method.send), decoding the response into any `T`, e.g. a struct holding fields unknown to [BinLookupResponse].
+    pub async fn send_as<T: DeserializeOwned>(
+        &self,
+        bin_number: String,
+        customer_ip: Option<IpAddr>,
+    ) -> Result<T, Error> {
+        let fetched = self.fetch(bin_number, customer_ip).await?;
+        self.neutral.decode("bin-lookup", &fetched.body)
+    }
+
+    /// Same as [send](#method.send), decoding the response into a [serde_json::Value].
+    pub async fn send_value(
+        &self,
+        bin_number: String,
+        customer_ip: Option<IpAddr>,
+    ) -> Result<serde_json::Value, Error> {
+        self.send_as(bin_number, customer_ip).await
+    }
+
+    /// Send the request, without decoding its response.
+    async fn fetch(
+        &self,
+        bin_number: String,
+        customer_ip: Option<IpAddr>,
+    ) -> Result<Fetched, Error> {
+        let canonical_input = match customer_ip {
+            Some(customer_ip) => format!("{}:{}", bin_number, customer_ip),
+            None => bin_number.clone(),
+        };
+        self.neutral
+            .execute("bin-lookup", Some(&canonical_input), &self.context, || {
+                build_request(self.neutral, &bin_number, customer_ip, &self.context)
+            })
+            .await
+    }
+}
+
+/// Build the bin lookup request of `bin_number`, without sending it.
+pub(crate) fn build_request(
+    neutral: &Neutral,
+    bin_number: &str,
+    customer_ip: Option<IpAddr>,
+    context: &CallContext,
+) -> Result<Request<Body>, Error> {
+    let mut params = params();
+    params.append_pair("bin-number", bin_number);
+    if let Some(customer_ip) = customer_ip {
+        params.append_pair("customer-ip", &customer_ip.to_string());
+    }
+    neutral.get_request("/bin-lookup", params.finish(), context)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ApiAuth;
+    use mockito::{mock, Matcher};
+
+    const BIN_LOOKUP_BODY: &str = r#"
+    {
+        "valid": true,
+        "card_brand": "VISA",
+        "card_type": "DEBIT",
+        "card_category": "CLASSIC",
+        "issuer": "EXAMPLE BANK",
+        "issuer_website": "https://bank.example",
+        "issuer_phone": "+33 1 23 45 67 89",
+        "country": "France",
+        "country_code": "FR",
+        "country_code3": "FRA",
+        "currency_code": "EUR"
+    }"#;
+
+    const BIN_LOOKUP_WITH_IP_BODY: &str = r#"
+    {
+        "valid": true,
+        "card_brand": "MASTERCARD",
+        "card_type": "PREPAID",
+        "card_category": "STANDARD",
+        "issuer": "EXAMPLE BANK",
+        "issuer_website": "",
+        "issuer_phone": "",
+        "country": "France",
+        "country_code": "FR",
+        "country_code3": "FRA",
+        "currency_code": "EUR",
+        "ip_matches_bin": false,
+        "ip_blocklisted": true,
+        "ip_blocklists": ["tor", "proxy"]
+    }"#;
+
+    #[test]
+    fn test_bin_lookup_build_request() {
+        struct TestingData {
+            pub name: String,
+            pub args: Option<IpAddr>,
+            pub expected: &'static str,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Without customer ip".to_owned(),
+                args: None,
+                expected: "/bin-lookup?bin-number=48334884",
+            },
+            TestingData {
+                name: "With an ipv6 customer ip".to_owned(),
+                args: Some("2001:db8::1".parse().unwrap()),
+                expected: "/bin-lookup?bin-number=48334884&customer-ip=2001%3Adb8%3A%3A1",
+            },
+        ];
+
+        let neutral = Neutral::try_new(
+            "https://neutrinoapi.net",
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        for test in &tests {
+            let request =
+                build_request(&neutral, "48334884", test.args, &CallContext::default()).unwrap();
+            assert_eq!(
+                request.uri().path_and_query().unwrap().as_str(),
+                test.expected,
+                "{}",
+                test.name
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bin_lookup() {
+        struct TestingData {
+            pub name: String,
+            pub customer_ip: Option<IpAddr>,
+            pub expected: (CardType, Option<bool>, Option<bool>, Option<Vec<String>>),
+        }
+
+        let _without_ip = mock("GET", "/bin-lookup")
+            .match_query(Matcher::Regex("bin-number=48334884$".into()))
+            .with_status(200)
+            .with_body(BIN_LOOKUP_BODY)
+            .create();
+        let _with_ip = mock("GET", "/bin-lookup")
+            .match_query(Matcher::Regex(
+                "bin-number=48334884&customer-ip=203.0.113.110".into(),
+            ))
+            .with_status(200)
+            .with_body(BIN_LOOKUP_WITH_IP_BODY)
+            .create();
+
+        let tests = vec![
+            TestingData {
+                name: "Without customer ip".to_owned(),
+                customer_ip: None,
+                expected: (CardType::Debit, None, None, None),
+            },
+            TestingData {
+                name: "With customer ip".to_owned(),
+                customer_ip: Some("203.0.113.110".parse().unwrap()),
+                expected: (
+                    CardType::Prepaid,
+                    Some(false),
+                    Some(true),
+                    Some(vec!["tor".to_owned(), "proxy".to_owned()]),
+                ),
+            },
+        ];
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        for test in tests {
+            let response = neutral
+                .bin_lookup()
+                .send("48334884".to_owned(), test.customer_ip)
+                .await
+                .unwrap();
+            assert!(response.is_valid, "{}", test.name);
+            assert_eq!(response.country_code, "FR", "{}", test.name);
+            assert_eq!(
+                (
+                    response.card_type,
+                    response.ip_matches_bin,
+                    response.ip_blocklisted,
+                    response.ip_blocklists
+                ),
+                test.expected,
+                "{}",
+                test.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_unknown_card_type() {
+        let card_type: CardType = serde_json::from_str(r#""CHARGE CARD""#).unwrap();
+        assert_eq!(card_type, CardType::Unknown);
+    }
+
+    #[tokio::test]
+    async fn test_bin_lookup_params() {
+        let params = BinLookupParams {
+            bin_number: "48334884".to_owned(),
+            customer_ip: Some("203.0.113.251".parse().unwrap()),
+        };
+        let json = serde_json::to_string(&params).unwrap();
+        assert_eq!(
+            serde_json::from_str::<BinLookupParams>(&json).unwrap(),
+            params
+        );
+
+        let (neutral, requests) = crate::fixtures::recording(BIN_LOOKUP_WITH_IP_BODY);
+        neutral.bin_lookup().send_params(&params).await.unwrap();
+        neutral
+            .bin_lookup()
+            .send(params.bin_number.clone(), params.customer_ip)
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0], requests[1]);
+    }
+}
@@ -0,0 +1,482 @@
+//! # Blocking module
+//! A synchronous client, enabled with the `blocking` cargo feature.
+//!
+//! [Neutral](./struct.Neutral.html) wraps the async [Neutral](../struct.Neutral.html) with a current-thread tokio runtime,
+//! blocking the calling thread until each call completes. The responses and errors are the same as the async API.
+//!
+//! It exposes the endpoints answering a single JSON response, the streaming, batch and multi endpoints are only available on the async client, see [as_async](./struct.Neutral.html#method.as_async).
+//!
+//! Its methods must not be called from an async context, where blocking the thread would stall the other tasks.
+//!
+//! ```no_run
+//! use neutral::{blocking::Neutral, ApiAuth};
+//!
+//! let api_auth = ApiAuth::new("user".to_owned(), "api-key".to_owned());
+//! let neutral = Neutral::try_new("https://neutrinoapi.net", api_auth)?;
+//! let ip_info = neutral.ip_info().send("1.1.1.1".parse().unwrap())?;
+//! println!("{}", ip_info.country);
+//! # Ok::<(), neutral::error::Error>(())
+//! ```
+
+use std::{net::IpAddr, sync::Arc, time::Duration};
+
+use neutral_types::{
+    hlr_lookup::HlrLookupResponse, ip_blocklist::IpBlocklistResponse, ip_info::IpInfoResponse,
+    ip_probe::IpProbeResponse, phone_validate::PhoneValidateResponse,
+};
+use tokio::runtime::Runtime;
+
+use crate::{
+    bin_lookup::BinLookupResponse,
+    builder::NeutralBuilder,
+    context::CallContext,
+    email_verify::EmailVerifyResponse,
+    hlr_lookup::HlrLookupOptions,
+    ip_blocklist::IpBlocklistOptions,
+    ip_info::IpInfoOptions,
+    meta::ResponseMeta,
+    phone_verify::{PhoneVerifyOptions, PhoneVerifyResponse},
+    sms_message::SmsMessageResponse,
+    sms_verify::{SmsVerifyOptions, SmsVerifyResponse},
+    ua_lookup::UaLookupResponse,
+    verify_security_code::VerifySecurityCodeResponse,
+    ApiAuth, Error,
+};
+
+/// A synchronous client to consume features provided by neutrinoapi.com
+#[derive(Debug, Clone)]
+pub struct Neutral {
+    inner: crate::Neutral,
+    runtime: Arc<Runtime>,
+}
+
+impl Neutral {
+    /// Create a new blocking Neutral instance. Needs some credentials to be authorized.
+    pub fn try_new(uri: &str, auth: ApiAuth) -> Result<Self, Error> {
+        Self::from_builder(NeutralBuilder::new(uri, auth))
+    }
+
+    /// Build a blocking Neutral instance from a configured [NeutralBuilder](../builder/struct.NeutralBuilder.html).
+    pub fn from_builder(builder: NeutralBuilder) -> Result<Self, Error> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let inner = {
+            let _guard = runtime.enter();
+            builder.build()?
+        };
+        Ok(Neutral {
+            inner,
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    /// Returns a client sending `auth` as credentials and sharing everything else with this one, see [Neutral::with_auth](../struct.Neutral.html#method.with_auth).
+    pub fn with_auth(&self, auth: ApiAuth) -> Self {
+        Neutral {
+            inner: self.inner.with_auth(auth),
+            runtime: self.runtime.clone(),
+        }
+    }
+
+    /// Returns the async client sharing the configuration and the caches of this one.
+    pub fn as_async(&self) -> &crate::Neutral {
+        &self.inner
+    }
+}
+
+/// Declare the blocking counterpart of the async endpoint `$name` of the `$module` module, along with its `$module` accessor on [Neutral](./struct.Neutral.html).
+macro_rules! blocking_endpoint {
+    ($name:ident, $module:ident) => {
+        #[doc = concat!("Blocking counterpart of [", stringify!($name), "](../", stringify!($module), "/struct.", stringify!($name), ".html).")]
+        pub struct $name<'a> {
+            inner: crate::$module::$name<'a>,
+            runtime: &'a Runtime,
+        }
+
+        impl<'a> $name<'a> {
+            /// Attach a [CallContext](../context/struct.CallContext.html) to the calls.
+            pub fn context(mut self, context: CallContext) -> Self {
+                self.inner = self.inner.context(context);
+                self
+            }
+
+            /// Bound each attempt of the call by `timeout` instead of the client default, see [CallContext::timeout](../context/struct.CallContext.html#structfield.timeout).
+            pub fn timeout(mut self, timeout: Duration) -> Self {
+                self.inner = self.inner.timeout(timeout);
+                self
+            }
+        }
+
+        impl Neutral {
+            #[doc = concat!("Returns an instance of ", stringify!($name))]
+            pub fn $module(&self) -> $name<'_> {
+                $name {
+                    inner: self.inner.$module(),
+                    runtime: &self.runtime,
+                }
+            }
+        }
+    };
+}
+
+blocking_endpoint!(PhoneValidate, phone_validate);
+blocking_endpoint!(PhoneVerify, phone_verify);
+blocking_endpoint!(SmsMessage, sms_message);
+blocking_endpoint!(SmsVerify, sms_verify);
+blocking_endpoint!(VerifySecurityCode, verify_security_code);
+blocking_endpoint!(IpInfo, ip_info);
+blocking_endpoint!(IpBlocklist, ip_blocklist);
+blocking_endpoint!(IpProbe, ip_probe);
+blocking_endpoint!(HlrLookup, hlr_lookup);
+blocking_endpoint!(EmailVerify, email_verify);
+blocking_endpoint!(BinLookup, bin_lookup);
+blocking_endpoint!(UaLookup, ua_lookup);
+
+impl<'a> PhoneValidate<'a> {
+    /// Parse national numbers as numbers of `country_code`, see [PhoneValidate::country_code](../phone_validate/struct.PhoneValidate.html#method.country_code).
+    pub fn country_code(mut self, country_code: impl Into<String>) -> Self {
+        self.inner = self.inner.country_code(country_code);
+        self
+    }
+
+    /// Parse national numbers as numbers of the country of `ip_addr`, see [PhoneValidate::ip](../phone_validate/struct.PhoneValidate.html#method.ip).
+    pub fn ip(mut self, ip_addr: IpAddr) -> Self {
+        self.inner = self.inner.ip(ip_addr);
+        self
+    }
+
+    /// Send an phone validate request to neutrinoapi.com
+    pub fn send(&self, phone_number: String) -> Result<PhoneValidateResponse, Error> {
+        self.runtime.block_on(self.inner.send(phone_number))
+    }
+
+    /// Same as [send](#method.send), also returning the [ResponseMeta](../meta/struct.ResponseMeta.html) of the response.
+    pub fn send_with_meta(
+        &self,
+        phone_number: String,
+    ) -> Result<(ResponseMeta, PhoneValidateResponse), Error> {
+        self.runtime
+            .block_on(self.inner.send_with_meta(phone_number))
+    }
+}
+
+impl<'a> PhoneVerify<'a> {
+    /// Send a phone verify request to neutrinoapi.com
+    pub fn send(
+        self,
+        phone_number: String,
+        options: PhoneVerifyOptions,
+    ) -> Result<PhoneVerifyResponse, Error> {
+        self.runtime
+            .block_on(self.inner.send(phone_number, options))
+    }
+
+    /// Same as [send](#method.send), also returning the [ResponseMeta](../meta/struct.ResponseMeta.html) of the response.
+    pub fn send_with_meta(
+        self,
+        phone_number: String,
+        options: PhoneVerifyOptions,
+    ) -> Result<(ResponseMeta, PhoneVerifyResponse), Error> {
+        self.runtime
+            .block_on(self.inner.send_with_meta(phone_number, options))
+    }
+}
+
+impl<'a> SmsMessage<'a> {
+    /// Send a sms message request to neutrinoapi.com
+    pub fn send(
+        self,
+        number: String,
+        message: String,
+        country_code: Option<String>,
+    ) -> Result<SmsMessageResponse, Error> {
+        self.runtime
+            .block_on(self.inner.send(number, message, country_code))
+    }
+
+    /// Same as [send](#method.send), also returning the [ResponseMeta](../meta/struct.ResponseMeta.html) of the response.
+    pub fn send_with_meta(
+        self,
+        number: String,
+        message: String,
+        country_code: Option<String>,
+    ) -> Result<(ResponseMeta, SmsMessageResponse), Error> {
+        self.runtime
+            .block_on(self.inner.send_with_meta(number, message, country_code))
+    }
+}
+
+impl<'a> SmsVerify<'a> {
+    /// Send a sms verify request to neutrinoapi.com
+    pub fn send(
+        self,
+        phone_number: String,
+        options: SmsVerifyOptions,
+    ) -> Result<SmsVerifyResponse, Error> {
+        self.runtime
+            .block_on(self.inner.send(phone_number, options))
+    }
+
+    /// Same as [send](#method.send), also returning the [ResponseMeta](../meta/struct.ResponseMeta.html) of the response.
+    pub fn send_with_meta(
+        self,
+        phone_number: String,
+        options: SmsVerifyOptions,
+    ) -> Result<(ResponseMeta, SmsVerifyResponse), Error> {
+        self.runtime
+            .block_on(self.inner.send_with_meta(phone_number, options))
+    }
+}
+
+impl<'a> VerifySecurityCode<'a> {
+    /// Send a verify security code request to neutrinoapi.com
+    pub fn send(
+        &self,
+        security_code: String,
+        limit_by_phone_number: Option<String>,
+    ) -> Result<VerifySecurityCodeResponse, Error> {
+        self.runtime
+            .block_on(self.inner.send(security_code, limit_by_phone_number))
+    }
+
+    /// Same as [send](#method.send), also returning the [ResponseMeta](../meta/struct.ResponseMeta.html) of the response.
+    pub fn send_with_meta(
+        &self,
+        security_code: String,
+        limit_by_phone_number: Option<String>,
+    ) -> Result<(ResponseMeta, VerifySecurityCodeResponse), Error> {
+        self.runtime.block_on(
+            self.inner
+                .send_with_meta(security_code, limit_by_phone_number),
+        )
+    }
+}
+
+impl<'a> IpInfo<'a> {
+    /// Send an ip info request to neutrinoapi.com
+    pub fn send(&self, ip_addr: IpAddr) -> Result<IpInfoResponse, Error> {
+        self.runtime.block_on(self.inner.send(ip_addr))
+    }
+
+    /// Same as [send](#method.send), with `options`.
+    pub fn send_with(
+        &self,
+        ip_addr: IpAddr,
+        options: IpInfoOptions,
+    ) -> Result<IpInfoResponse, Error> {
+        self.runtime
+            .block_on(self.inner.send_with(ip_addr, options))
+    }
+
+    /// Same as [send](#method.send), also returning the [ResponseMeta](../meta/struct.ResponseMeta.html) of the response.
+    pub fn send_with_meta(&self, ip_addr: IpAddr) -> Result<(ResponseMeta, IpInfoResponse), Error> {
+        self.runtime.block_on(self.inner.send_with_meta(ip_addr))
+    }
+}
+
+impl<'a> IpBlocklist<'a> {
+    /// Send an ip blocklist request to neutrinoapi.com
+    pub fn send(&self, ip_addr: IpAddr) -> Result<IpBlocklistResponse, Error> {
+        self.runtime.block_on(self.inner.send(ip_addr))
+    }
+
+    /// Same as [send](#method.send), with `options`.
+    pub fn send_with(
+        &self,
+        ip_addr: IpAddr,
+        options: IpBlocklistOptions,
+    ) -> Result<IpBlocklistResponse, Error> {
+        self.runtime
+            .block_on(self.inner.send_with(ip_addr, options))
+    }
+
+    /// Same as [send](#method.send), also returning the [ResponseMeta](../meta/struct.ResponseMeta.html) of the response.
+    pub fn send_with_meta(
+        &self,
+        ip_addr: IpAddr,
+    ) -> Result<(ResponseMeta, IpBlocklistResponse), Error> {
+        self.runtime.block_on(self.inner.send_with_meta(ip_addr))
+    }
+}
+
+impl<'a> IpProbe<'a> {
+    /// Send an ip probe request to neutrinoapi.com
+    pub fn send(&self, ip_addr: IpAddr) -> Result<IpProbeResponse, Error> {
+        self.runtime.block_on(self.inner.send(ip_addr))
+    }
+
+    /// Same as [send](#method.send), also returning the [ResponseMeta](../meta/struct.ResponseMeta.html) of the response.
+    pub fn send_with_meta(
+        &self,
+        ip_addr: IpAddr,
+    ) -> Result<(ResponseMeta, IpProbeResponse), Error> {
+        self.runtime.block_on(self.inner.send_with_meta(ip_addr))
+    }
+}
+
+impl<'a> HlrLookup<'a> {
+    /// Send an hlr lookup request to neutrinoapi.com
+    pub fn send(&self, phone_number: String) -> Result<HlrLookupResponse, Error> {
+        self.runtime.block_on(self.inner.send(phone_number))
+    }
+
+    /// Same as [send](#method.send), with `options`.
+    pub fn send_with(
+        &self,
+        phone_number: String,
+        options: HlrLookupOptions,
+    ) -> Result<HlrLookupResponse, Error> {
+        self.runtime
+            .block_on(self.inner.send_with(phone_number, options))
+    }
+
+    /// Same as [send](#method.send), also returning the [ResponseMeta](../meta/struct.ResponseMeta.html) of the response.
+    pub fn send_with_meta(
+        &self,
+        phone_number: String,
+    ) -> Result<(ResponseMeta, HlrLookupResponse), Error> {
+        self.runtime
+            .block_on(self.inner.send_with_meta(phone_number))
+    }
+}
+
+impl<'a> EmailVerify<'a> {
+    /// Ask neutrinoapi.com to fix the typos of the address, see [EmailVerify::fix_typos](../email_verify/struct.EmailVerify.html#method.fix_typos).
+    pub fn fix_typos(mut self, fix_typos: bool) -> Self {
+        self.inner = self.inner.fix_typos(fix_typos);
+        self
+    }
+
+    /// Send an email verify request to neutrinoapi.com
+    pub fn send(&self, email: String) -> Result<EmailVerifyResponse, Error> {
+        self.runtime.block_on(self.inner.send(email))
+    }
+
+    /// Same as [send](#method.send), also returning the [ResponseMeta](../meta/struct.ResponseMeta.html) of the response.
+    pub fn send_with_meta(
+        &self,
+        email: String,
+    ) -> Result<(ResponseMeta, EmailVerifyResponse), Error> {
+        self.runtime.block_on(self.inner.send_with_meta(email))
+    }
+}
+
+impl<'a> BinLookup<'a> {
+    /// Send a bin lookup request to neutrinoapi.com
+    pub fn send(
+        &self,
+        bin_number: String,
+        customer_ip: Option<IpAddr>,
+    ) -> Result<BinLookupResponse, Error> {
+        self.runtime
+            .block_on(self.inner.send(bin_number, customer_ip))
+    }
+
+    /// Same as [send](#method.send), also returning the [ResponseMeta](../meta/struct.ResponseMeta.html) of the response.
+    pub fn send_with_meta(
+        &self,
+        bin_number: String,
+        customer_ip: Option<IpAddr>,
+    ) -> Result<(ResponseMeta, BinLookupResponse), Error> {
+        self.runtime
+            .block_on(self.inner.send_with_meta(bin_number, customer_ip))
+    }
+}
+
+impl<'a> UaLookup<'a> {
+    /// Hint the brand of the device, e.g. `Samsung`.
+    pub fn device_brand(mut self, device_brand: String) -> Self {
+        self.inner = self.inner.device_brand(device_brand);
+        self
+    }
+
+    /// Hint the model of the device, e.g. `SM-G998B`.
+    pub fn device_model(mut self, device_model: String) -> Self {
+        self.inner = self.inner.device_model(device_model);
+        self
+    }
+
+    /// Hint the operating system of the device, e.g. `Android 12`.
+    pub fn os(mut self, os: String) -> Self {
+        self.inner = self.inner.os(os);
+        self
+    }
+
+    /// Send a ua lookup request to neutrinoapi.com
+    pub fn send(&self, ua: String) -> Result<UaLookupResponse, Error> {
+        self.runtime.block_on(self.inner.send(ua))
+    }
+
+    /// Same as [send](#method.send), also returning the [ResponseMeta](../meta/struct.ResponseMeta.html) of the response.
+    pub fn send_with_meta(&self, ua: String) -> Result<(ResponseMeta, UaLookupResponse), Error> {
+        self.runtime.block_on(self.inner.send_with_meta(ua))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mockito::{mock, Matcher};
+
+    fn neutral() -> Neutral {
+        Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_blocking_ip_info() {
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.180$".into()))
+            .with_status(200)
+            .with_body(crate::fixtures::IP_INFO_BODY)
+            .expect(2)
+            .create();
+
+        let neutral = neutral();
+        let ip_addr: IpAddr = "203.0.113.180".parse().unwrap();
+        let first = neutral.ip_info().send(ip_addr).unwrap();
+        let (meta, second) = neutral
+            .ip_info()
+            .timeout(Duration::from_secs(5))
+            .send_with_meta(ip_addr)
+            .unwrap();
+        assert_eq!(first, second);
+        assert_eq!(meta.status, http::StatusCode::OK);
+        _m.assert();
+    }
+
+    #[test]
+    fn test_blocking_phone_validate() {
+        let _m = mock("GET", "/phone-validate")
+            .match_query(Matcher::Regex("number=%2B33698765433".into()))
+            .with_status(200)
+            .with_body(crate::fixtures::PHONE_VALIDATE_BODY)
+            .expect(1)
+            .create();
+
+        let response = neutral()
+            .phone_validate()
+            .send("+33698765433".to_owned())
+            .unwrap();
+        assert!(response.is_valid);
+        _m.assert();
+    }
+
+    #[test]
+    fn test_blocking_error() {
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.181$".into()))
+            .with_status(503)
+            .with_body("service unavailable")
+            .expect(1)
+            .create();
+
+        let result = neutral().ip_info().send("203.0.113.181".parse().unwrap());
+        assert!(matches!(result, Err(Error::ServerError(_))));
+        _m.assert();
+    }
+}
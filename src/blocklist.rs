@@ -0,0 +1,588 @@
+//! # Blocklist module
+//! Parse the CSV file of the [ip blocklist download](../ip_blocklist_download/index.html) into typed entries,
+//! and the blocklists and sensors of an [ip blocklist](../ip_blocklist/index.html) response into typed values.
+//!
+//! The file is parsed line by line as its chunks arrive, so the memory stays flat whatever its size.
+//! Its first line is a header naming the columns: the `ip` or `cidr` column is required, the `is-*` category columns and the `last-seen` column are optional.
+//! A malformed line yields an [Error::MalformedBlocklistLine](../error/enum.Error.html#variant.MalformedBlocklistLine) item and the stream goes on with the next line.
+//!
+//! ```ignore
+//! let mut entries = neutral.ip_blocklist_download().cidr(true).entries().await?;
+//! while let Some(entry) = entries.next().await {
+//!     let entry = entry?;
+//!     // ...
+//! }
+//! ```
+//!
+//! The [IpBlocklistTyped](./trait.IpBlocklistTyped.html) extension trait exposes the blocklists of a response as [BlocklistCategory](./enum.BlocklistCategory.html) values:
+//!
+//! ```ignore
+//! use neutral::blocklist::{BlocklistCategory, IpBlocklistTyped};
+//!
+//! let ip_blocklist_response = neutral.ip_blocklist().send(ip_addr).await?;
+//! if ip_blocklist_response.categories().contains(&BlocklistCategory::Tor) {
+//!     // ...
+//! }
+//! ```
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    net::IpAddr,
+    pin::Pin,
+    str::FromStr,
+    task::{Context, Poll},
+};
+
+use futures::Stream;
+use neutral_types::{ip_blocklist::IpBlocklistResponse, NeutrinoSensor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{download::ByteStream, Error};
+
+/// A range of ip addresses, written `<addr>/<prefix_len>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IpNetwork {
+    pub addr: IpAddr,
+    pub prefix_len: u8,
+}
+
+impl IpNetwork {
+    /// Returns true when `ip_addr` belongs to the range.
+    pub fn contains(&self, ip_addr: IpAddr) -> bool {
+        match (self.addr, ip_addr) {
+            (IpAddr::V4(network), IpAddr::V4(ip_addr)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip_addr) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip_addr)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip_addr) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl From<IpAddr> for IpNetwork {
+    fn from(addr: IpAddr) -> Self {
+        let prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+        IpNetwork { addr, prefix_len }
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = String;
+
+    /// Parse a range such as `192.0.2.0/24`, or a single address as a full length range.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| format!("invalid ip address `{}`", addr))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len
+                .parse()
+                .ok()
+                .filter(|prefix_len| *prefix_len <= max)
+                .ok_or_else(|| format!("invalid prefix length `{}`", prefix_len))?,
+            None => max,
+        };
+        Ok(IpNetwork { addr, prefix_len })
+    }
+}
+
+impl fmt::Display for IpNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+/// The categories of a listed range, false when their column is missing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BlocklistCategories {
+    pub is_proxy: bool,
+    pub is_tor: bool,
+    pub is_vpn: bool,
+    pub is_malware: bool,
+    pub is_spyware: bool,
+    pub is_dshield: bool,
+    pub is_hijacked: bool,
+    pub is_spider: bool,
+    pub is_bot: bool,
+    pub is_spam_bot: bool,
+    pub is_exploit_bot: bool,
+}
+
+impl BlocklistCategories {
+    /// Returns the flag of the category column `name`, e.g. `is-tor`.
+    fn flag(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "is-proxy" => Some(&mut self.is_proxy),
+            "is-tor" => Some(&mut self.is_tor),
+            "is-vpn" => Some(&mut self.is_vpn),
+            "is-malware" => Some(&mut self.is_malware),
+            "is-spyware" => Some(&mut self.is_spyware),
+            "is-dshield" => Some(&mut self.is_dshield),
+            "is-hijacked" => Some(&mut self.is_hijacked),
+            "is-spider" => Some(&mut self.is_spider),
+            "is-bot" => Some(&mut self.is_bot),
+            "is-spam-bot" => Some(&mut self.is_spam_bot),
+            "is-exploit-bot" => Some(&mut self.is_exploit_bot),
+            _ => None,
+        }
+    }
+}
+
+/// A blocklist of neutrinoapi.com, written in kebab case by the api, e.g. `spam-bot`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BlocklistCategory {
+    Proxy,
+    Tor,
+    Vpn,
+    Malware,
+    Spyware,
+    Spam,
+    Dshield,
+    Hijacked,
+    Spider,
+    Bot,
+    SpamBot,
+    ExploitBot,
+    /// A blocklist unknown to this version of the crate.
+    Other(String),
+}
+
+impl BlocklistCategory {
+    /// Returns the name of the blocklist as written by the api.
+    pub fn as_str(&self) -> &str {
+        match self {
+            BlocklistCategory::Proxy => "proxy",
+            BlocklistCategory::Tor => "tor",
+            BlocklistCategory::Vpn => "vpn",
+            BlocklistCategory::Malware => "malware",
+            BlocklistCategory::Spyware => "spyware",
+            BlocklistCategory::Spam => "spam",
+            BlocklistCategory::Dshield => "dshield",
+            BlocklistCategory::Hijacked => "hijacked",
+            BlocklistCategory::Spider => "spider",
+            BlocklistCategory::Bot => "bot",
+            BlocklistCategory::SpamBot => "spam-bot",
+            BlocklistCategory::ExploitBot => "exploit-bot",
+            BlocklistCategory::Other(name) => name,
+        }
+    }
+}
+
+impl From<&str> for BlocklistCategory {
+    /// Map a blocklist name, in any case, to its category. An unknown name is kept in [BlocklistCategory::Other].
+    fn from(name: &str) -> Self {
+        match name.trim().to_ascii_lowercase().replace('_', "-").as_str() {
+            "proxy" => BlocklistCategory::Proxy,
+            "tor" => BlocklistCategory::Tor,
+            "vpn" => BlocklistCategory::Vpn,
+            "malware" => BlocklistCategory::Malware,
+            "spyware" => BlocklistCategory::Spyware,
+            "spam" => BlocklistCategory::Spam,
+            "dshield" => BlocklistCategory::Dshield,
+            "hijacked" => BlocklistCategory::Hijacked,
+            "spider" => BlocklistCategory::Spider,
+            "bot" => BlocklistCategory::Bot,
+            "spam-bot" => BlocklistCategory::SpamBot,
+            "exploit-bot" => BlocklistCategory::ExploitBot,
+            _ => BlocklistCategory::Other(name.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for BlocklistCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for BlocklistCategory {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for BlocklistCategory {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(BlocklistCategory::from(name.as_str()))
+    }
+}
+
+/// A sensor of neutrinoapi.com which detected the ip address.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Sensor {
+    pub id: usize,
+    /// The blocklist fed by the sensor.
+    pub blocklist: BlocklistCategory,
+    pub description: String,
+}
+
+impl From<&NeutrinoSensor> for Sensor {
+    fn from(sensor: &NeutrinoSensor) -> Self {
+        Sensor {
+            id: sensor.id,
+            blocklist: BlocklistCategory::from(sensor.blocklist.as_str()),
+            description: sensor.description.clone(),
+        }
+    }
+}
+
+/// Typed blocklists and sensors of an ip blocklist response.
+pub trait IpBlocklistTyped {
+    /// Returns the blocklists listing the ip address.
+    fn categories(&self) -> Vec<BlocklistCategory>;
+
+    /// Returns the sensors which detected the ip address.
+    fn typed_sensors(&self) -> Vec<Sensor>;
+}
+
+impl IpBlocklistTyped for IpBlocklistResponse {
+    fn categories(&self) -> Vec<BlocklistCategory> {
+        self.blocklists
+            .iter()
+            .map(|name| BlocklistCategory::from(name.as_str()))
+            .collect()
+    }
+
+    fn typed_sensors(&self) -> Vec<Sensor> {
+        self.sensors.iter().map(Sensor::from).collect()
+    }
+}
+
+/// A line of the ip blocklist file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpBlocklistEntry {
+    /// The listed address, as a full length range when the file is not grouped in CIDR ranges.
+    pub network: IpNetwork,
+    pub categories: BlocklistCategories,
+    /// Unix time, in seconds, at which the range was last seen.
+    pub last_seen: Option<u64>,
+}
+
+/// The index of each known column of the file.
+#[derive(Debug)]
+struct Header {
+    columns: Vec<String>,
+    network: usize,
+}
+
+impl Header {
+    fn parse(line: &str) -> Result<Self, String> {
+        let columns: Vec<String> = fields(line)
+            .map(|column| column.to_ascii_lowercase().replace('_', "-"))
+            .collect();
+        let network = columns
+            .iter()
+            .position(|column| column == "ip" || column == "cidr")
+            .ok_or_else(|| "the header has no `ip` or `cidr` column".to_owned())?;
+        Ok(Header { columns, network })
+    }
+
+    fn entry(&self, line: &str) -> Result<IpBlocklistEntry, String> {
+        let values: Vec<&str> = fields(line).collect();
+        if values.len() != self.columns.len() {
+            return Err(format!(
+                "expected {} fields, found {}",
+                self.columns.len(),
+                values.len()
+            ));
+        }
+
+        let network = values[self.network].parse()?;
+        let mut categories = BlocklistCategories::default();
+        let mut last_seen = None;
+        for (column, value) in self.columns.iter().zip(values) {
+            if let Some(flag) = categories.flag(column) {
+                *flag = match value {
+                    "true" | "1" => true,
+                    "false" | "0" | "" => false,
+                    value => return Err(format!("invalid `{}` flag `{}`", column, value)),
+                };
+            } else if column == "last-seen" && !value.is_empty() {
+                last_seen = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("invalid `last-seen` timestamp `{}`", value))?,
+                );
+            }
+        }
+        Ok(IpBlocklistEntry {
+            network,
+            categories,
+            last_seen,
+        })
+    }
+}
+
+/// Returns the trimmed and unquoted fields of a CSV `line`.
+fn fields(line: &str) -> impl Iterator<Item = &str> {
+    line.split(',').map(|field| field.trim().trim_matches('"'))
+}
+
+/// The entries of an ip blocklist file, parsed as its chunks arrive.
+#[derive(Debug)]
+pub struct EntryStream {
+    body: Option<ByteStream>,
+    /// The bytes of the line being received.
+    partial: Vec<u8>,
+    /// The entries parsed from the received lines, not yielded yet.
+    parsed: VecDeque<Result<IpBlocklistEntry, Error>>,
+    header: Option<Header>,
+    /// The header is invalid, no line can be read.
+    is_broken: bool,
+    line: usize,
+}
+
+impl EntryStream {
+    pub(crate) fn new(body: ByteStream) -> Self {
+        EntryStream {
+            body: Some(body),
+            partial: Vec::new(),
+            parsed: VecDeque::new(),
+            header: None,
+            is_broken: false,
+            line: 0,
+        }
+    }
+
+    fn push_chunk(&mut self, chunk: &[u8]) {
+        let mut rest = chunk;
+        while let Some(end) = rest.iter().position(|byte| *byte == b'\n') {
+            self.partial.extend_from_slice(&rest[..end]);
+            let line = std::mem::take(&mut self.partial);
+            self.push_line(&line);
+            rest = &rest[end + 1..];
+        }
+        self.partial.extend_from_slice(rest);
+    }
+
+    fn push_line(&mut self, line: &[u8]) {
+        if self.is_broken {
+            return;
+        }
+        self.line += 1;
+        let result = std::str::from_utf8(line)
+            .map_err(|_| "the line is not valid utf-8".to_owned())
+            .map(|line| line.trim_end_matches('\r'));
+        let result = match (result, &self.header) {
+            (Ok(""), _) => return,
+            (Ok(line), None) => match Header::parse(line) {
+                Ok(header) => {
+                    self.header = Some(header);
+                    return;
+                }
+                Err(reason) => {
+                    self.is_broken = true;
+                    self.body = None;
+                    Err(reason)
+                }
+            },
+            (Ok(line), Some(header)) => header.entry(line),
+            (Err(reason), _) => Err(reason),
+        };
+        self.parsed
+            .push_back(result.map_err(|reason| Error::MalformedBlocklistLine {
+                line: self.line,
+                reason,
+            }));
+    }
+}
+
+impl Stream for EntryStream {
+    type Item = Result<IpBlocklistEntry, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(entry) = self.parsed.pop_front() {
+                return Poll::Ready(Some(entry));
+            }
+            let body = match self.body.as_mut() {
+                Some(body) => body,
+                None => return Poll::Ready(None),
+            };
+            match Pin::new(body).poll_next(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Some(Ok(chunk))) => self.push_chunk(&chunk),
+                Poll::Ready(Some(Err(err))) => {
+                    self.body = None;
+                    return Poll::Ready(Some(Err(err)));
+                }
+                Poll::Ready(None) => {
+                    self.body = None;
+                    let line = std::mem::take(&mut self.partial);
+                    self.push_line(&line);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ip_network() {
+        struct TestingData {
+            pub name: String,
+            pub args: &'static str,
+            pub expected: Result<&'static str, ()>,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Using a range".to_owned(),
+                args: "192.0.2.0/24",
+                expected: Ok("192.0.2.0/24"),
+            },
+            TestingData {
+                name: "Using a single address".to_owned(),
+                args: "2001:db8::1",
+                expected: Ok("2001:db8::1/128"),
+            },
+            TestingData {
+                name: "Using a too long prefix".to_owned(),
+                args: "192.0.2.0/33",
+                expected: Err(()),
+            },
+            TestingData {
+                name: "Using an invalid address".to_owned(),
+                args: "192.0.2/24",
+                expected: Err(()),
+            },
+        ];
+
+        for test in &tests {
+            let network = test.args.parse::<IpNetwork>();
+            assert_eq!(
+                network.map(|network| network.to_string()).map_err(|_| ()),
+                test.expected.map(str::to_owned),
+                "{}",
+                test.name
+            );
+        }
+
+        let network: IpNetwork = "192.0.2.0/24".parse().unwrap();
+        assert!(network.contains("192.0.2.200".parse().unwrap()));
+        assert!(!network.contains("192.0.3.1".parse().unwrap()));
+        assert!(!network.contains("2001:db8::1".parse().unwrap()));
+        let any: IpNetwork = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains("203.0.113.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_blocklist_category() {
+        struct TestingData {
+            pub name: String,
+            pub args: &'static str,
+            pub expected: BlocklistCategory,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Single word".to_owned(),
+                args: "tor",
+                expected: BlocklistCategory::Tor,
+            },
+            TestingData {
+                name: "Kebab case".to_owned(),
+                args: "exploit-bot",
+                expected: BlocklistCategory::ExploitBot,
+            },
+            TestingData {
+                name: "Snake case and upper case".to_owned(),
+                args: "SPAM_BOT",
+                expected: BlocklistCategory::SpamBot,
+            },
+            TestingData {
+                name: "Unknown blocklist".to_owned(),
+                args: "crypto-miner",
+                expected: BlocklistCategory::Other("crypto-miner".to_owned()),
+            },
+        ];
+
+        for test in &tests {
+            let category = BlocklistCategory::from(test.args);
+            assert_eq!(category, test.expected, "{}", test.name);
+            let json = serde_json::to_string(&category).unwrap();
+            assert_eq!(
+                serde_json::from_str::<BlocklistCategory>(&json).unwrap(),
+                test.expected,
+                "{} round trip",
+                test.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_ip_blocklist_typed() {
+        let body = r#"
+        {
+            "ip": "203.0.113.215",
+            "is_listed": true,
+            "last_seen": 1637758053,
+            "list_count": 4,
+            "blocklists": ["tor", "spam-bot", "malware", "crypto-miner"],
+            "sensors": [
+                {"id": 7, "blocklist": "tor", "description": "Tor exit node"},
+                {"id": 12, "blocklist": "spam-bot", "description": "Spam trap"}
+            ],
+            "is_proxy": false,
+            "is_tor": true,
+            "is_vpn": false,
+            "is_malware": true,
+            "is_spyware": false,
+            "is_dshield": false,
+            "is_hijacked": false,
+            "is_spider": false,
+            "is_bot": false,
+            "is_spam_bot": true,
+            "is_exploit_bot": false
+        }"#;
+        let response: IpBlocklistResponse = serde_json::from_str(body).unwrap();
+
+        assert_eq!(
+            response.categories(),
+            vec![
+                BlocklistCategory::Tor,
+                BlocklistCategory::SpamBot,
+                BlocklistCategory::Malware,
+                BlocklistCategory::Other("crypto-miner".to_owned()),
+            ]
+        );
+        let sensors = vec![
+            Sensor {
+                id: 7,
+                blocklist: BlocklistCategory::Tor,
+                description: "Tor exit node".to_owned(),
+            },
+            Sensor {
+                id: 12,
+                blocklist: BlocklistCategory::SpamBot,
+                description: "Spam trap".to_owned(),
+            },
+        ];
+        assert_eq!(response.typed_sensors(), sensors);
+
+        let value: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(
+            serde_json::from_value::<Vec<Sensor>>(value["sensors"].clone()).unwrap(),
+            sensors,
+            "Deserialized straight from the response"
+        );
+    }
+}
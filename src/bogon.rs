@@ -0,0 +1,228 @@
+//! # Bogon module
+//! Reject the ip addresses neutrinoapi.com can only answer as bogons before sending any request, saving the credit of the call.
+//!
+//! The check is disabled by default and enabled with [NeutralBuilder::reject_bogons](../builder/struct.NeutralBuilder.html#method.reject_bogons).
+//! An ip info, ip probe or ip blocklist call of a [bogon](./fn.is_bogon.html) address then fails with
+//! [Error::BogonAddress](../error/enum.Error.html#variant.BogonAddress), without a request.
+//!
+//! ```ignore
+//! let neutral = Neutral::builder("https://neutrinoapi.net", api_auth)
+//!     .reject_bogons(true)
+//!     .build()?;
+//! match neutral.ip_info().send(ip_addr).await {
+//!     Err(Error::BogonAddress(_)) => println!("{} is not routable", ip_addr),
+//!     result => handle(result?),
+//! }
+//! ```
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::{blocklist::IpNetwork, Error};
+
+const fn v4(a: u8, b: u8, c: u8, d: u8, prefix_len: u8) -> IpNetwork {
+    IpNetwork {
+        addr: IpAddr::V4(Ipv4Addr::new(a, b, c, d)),
+        prefix_len,
+    }
+}
+
+const fn v6(segment: u16, prefix_len: u8) -> IpNetwork {
+    IpNetwork {
+        addr: IpAddr::V6(Ipv6Addr::new(segment, 0, 0, 0, 0, 0, 0, 0)),
+        prefix_len,
+    }
+}
+
+/// The ranges which are not routable on the internet.
+const BOGONS: [IpNetwork; 22] = [
+    // This network.
+    v4(0, 0, 0, 0, 8),
+    // Private.
+    v4(10, 0, 0, 0, 8),
+    // Shared address space of carrier-grade NAT.
+    v4(100, 64, 0, 0, 10),
+    // Loopback.
+    v4(127, 0, 0, 0, 8),
+    // Link-local.
+    v4(169, 254, 0, 0, 16),
+    // Private.
+    v4(172, 16, 0, 0, 12),
+    // IETF protocol assignments.
+    v4(192, 0, 0, 0, 24),
+    // Documentation, TEST-NET-1.
+    v4(192, 0, 2, 0, 24),
+    // Private.
+    v4(192, 168, 0, 0, 16),
+    // Benchmarking.
+    v4(198, 18, 0, 0, 15),
+    // Documentation, TEST-NET-2.
+    v4(198, 51, 100, 0, 24),
+    // Documentation, TEST-NET-3.
+    v4(203, 0, 113, 0, 24),
+    // Multicast.
+    v4(224, 0, 0, 0, 4),
+    // Reserved, broadcast included.
+    v4(240, 0, 0, 0, 4),
+    // Unspecified.
+    IpNetwork {
+        addr: IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        prefix_len: 128,
+    },
+    // Loopback.
+    IpNetwork {
+        addr: IpAddr::V6(Ipv6Addr::LOCALHOST),
+        prefix_len: 128,
+    },
+    // Discard only.
+    v6(0x0100, 64),
+    // Documentation.
+    IpNetwork {
+        addr: IpAddr::V6(Ipv6Addr::new(0x2001, 0x0db8, 0, 0, 0, 0, 0, 0)),
+        prefix_len: 32,
+    },
+    // Unique local.
+    v6(0xfc00, 7),
+    // Link-local.
+    v6(0xfe80, 10),
+    // Site-local, deprecated.
+    v6(0xfec0, 10),
+    // Multicast.
+    v6(0xff00, 8),
+];
+
+/// Returns true when `ip_addr` is not routable on the internet: private, loopback, link-local, multicast, documentation or otherwise reserved.
+///
+/// An IPv4-mapped IPv6 address is checked as its IPv4 address.
+pub fn is_bogon(ip_addr: IpAddr) -> bool {
+    let ip_addr = match ip_addr {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip_addr, IpAddr::V4),
+        IpAddr::V4(_) => ip_addr,
+    };
+    BOGONS.iter().any(|network| network.contains(ip_addr))
+}
+
+/// Returns [Error::BogonAddress](../error/enum.Error.html#variant.BogonAddress) when `reject` is set and `ip_addr` is a bogon.
+pub(crate) fn check(reject: bool, ip_addr: IpAddr) -> Result<(), Error> {
+    if reject && is_bogon(ip_addr) {
+        return Err(Error::BogonAddress(ip_addr));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{fixtures::IP_INFO_BODY, ApiAuth, Neutral};
+    use mockito::{mock, Matcher};
+
+    #[test]
+    fn test_is_bogon() {
+        struct TestingData {
+            pub name: String,
+            pub args: &'static str,
+            pub expected: bool,
+        }
+
+        let tests = vec![
+            ("Using a private 10/8 address", "10.0.0.5", true),
+            ("Using a private 172.16/12 address", "172.31.255.1", true),
+            ("Using a private 192.168/16 address", "192.168.1.1", true),
+            ("Using an IPv4 loopback", "127.0.0.1", true),
+            ("Using an IPv4 link-local", "169.254.10.20", true),
+            ("Using an IPv4 multicast", "224.0.0.251", true),
+            ("Using a TEST-NET-1 address", "192.0.2.10", true),
+            ("Using a TEST-NET-3 address", "203.0.113.7", true),
+            ("Using a carrier-grade NAT address", "100.64.3.4", true),
+            ("Using the broadcast address", "255.255.255.255", true),
+            ("Using the IPv4 unspecified address", "0.0.0.0", true),
+            ("Using an IPv6 loopback", "::1", true),
+            ("Using the IPv6 unspecified address", "::", true),
+            ("Using an IPv6 link-local", "fe80::1", true),
+            ("Using an IPv6 unique local", "fd12:3456:789a::1", true),
+            ("Using an IPv6 multicast", "ff02::1", true),
+            ("Using an IPv6 documentation address", "2001:db8::1", true),
+            (
+                "Using an IPv4-mapped private address",
+                "::ffff:10.0.0.5",
+                true,
+            ),
+            ("Using a public IPv4 address", "8.8.8.8", false),
+            (
+                "Using a public IPv4 next to a private range",
+                "172.32.0.1",
+                false,
+            ),
+            ("Using a public IPv6 address", "2606:4700:4700::1111", false),
+            (
+                "Using an IPv4-mapped public address",
+                "::ffff:1.1.1.1",
+                false,
+            ),
+        ]
+        .into_iter()
+        .map(|(name, args, expected)| TestingData {
+            name: name.to_owned(),
+            args,
+            expected,
+        });
+
+        for test in tests {
+            assert_eq!(
+                is_bogon(test.args.parse().unwrap()),
+                test.expected,
+                "{}",
+                test.name
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reject_bogons() {
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=198.41.0.4$".into()))
+            .with_status(200)
+            .with_body(IP_INFO_BODY)
+            .expect(1)
+            .create();
+        let _bogon = mock("GET", Matcher::Regex("^/ip-(info|probe|blocklist)".into()))
+            .match_query(Matcher::Regex("ip=10.0.0.5$".into()))
+            .expect(0)
+            .create();
+
+        let neutral = Neutral::builder(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .reject_bogons(true)
+        .build()
+        .unwrap();
+
+        let bogon: IpAddr = "10.0.0.5".parse().unwrap();
+        assert!(matches!(
+            neutral.ip_info().send(bogon).await,
+            Err(Error::BogonAddress(ip_addr)) if ip_addr == bogon
+        ));
+        assert!(matches!(
+            neutral.ip_probe().send(bogon).await,
+            Err(Error::BogonAddress(_))
+        ));
+        assert!(matches!(
+            neutral.ip_blocklist().send(bogon).await,
+            Err(Error::BogonAddress(_))
+        ));
+
+        neutral
+            .ip_info()
+            .send("198.41.0.4".parse().unwrap())
+            .await
+            .unwrap();
+        _m.assert();
+        _bogon.assert();
+    }
+
+    #[test]
+    fn test_check() {
+        assert!(check(false, "127.0.0.1".parse().unwrap()).is_ok());
+        assert!(check(true, "1.1.1.1".parse().unwrap()).is_ok());
+    }
+}
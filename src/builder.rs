@@ -0,0 +1,1520 @@
+//! # Builder module
+//! Configure how a [Neutral](../struct.Neutral.html) client is initialized.
+//!
+//! [build](./struct.NeutralBuilder.html#method.build) is lazy: it never touches the network and the first request pays for the connection.
+//! [build_and_verify](./struct.NeutralBuilder.html#method.build_and_verify) performs a cheap authenticated call so misconfigured credentials are caught at startup.
+//! The connection opened by the verification call is kept in the pool, which also warms the client up for the next request.
+//!
+//! The path of the base uri prefixes the path of every endpoint, so `https://gateway.internal/neutrino/` sends ip info calls to `https://gateway.internal/neutrino/ip-info`.
+//!
+//! Https is required by default, whatever the scheme of the base uri: building a client with an `http` uri fails with [ConfigError::InsecureTransport](../error/enum.ConfigError.html#variant.InsecureTransport).
+//! Plain http to a loopback address (`localhost`, `127.0.0.1`, `::1`) stays allowed for testing, use [require_https](./struct.NeutralBuilder.html#method.require_https) to opt out for other hosts.
+
+use std::{
+    net::IpAddr,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use http::{header::HeaderName, uri::Scheme, HeaderMap, HeaderValue, Uri};
+#[cfg(not(target_arch = "wasm32"))]
+use hyper::{client::connect::Connect, Client};
+use hyper::{service::Service, Body, Request, Response};
+use tokio::sync::Semaphore;
+
+use crate::{
+    audit::{AuditSink, AuditSinks},
+    cache::{CacheConfig, CacheStore, MigrateFn, NegativeCacheConfig, SecondTier, TtlCache},
+    decode::{Decoder, JsonDecoder},
+    error::{BoxError, ConfigError},
+    intercept::Interceptors,
+    metrics::{MetricsSink, MetricsSinks},
+    pipeline::{CircuitBreaker, CircuitBreakerConfig, RateLimitConfig, RetryConfig},
+    runtime::{Runtime, RuntimeConfig},
+    signing::{CanonicalRequest, Signing},
+    spawn::Spawner,
+    time::Instant,
+    transport::Transport,
+    ApiAuth, Error, Neutral,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    proxy::{Proxy, ProxyConnector},
+    resolve::{Resolve, Resolver},
+    tls::{self, HttpProtocol},
+};
+
+/// Default maximum size in bytes of a buffered response body, see [NeutralBuilder::max_response_size](./struct.NeutralBuilder.html#method.max_response_size).
+pub const DEFAULT_MAX_RESPONSE_SIZE: usize = 4 * 1024 * 1024;
+
+/// Configure and build a [Neutral](../struct.Neutral.html) instance.
+#[derive(Debug, Clone)]
+pub struct NeutralBuilder {
+    uri: String,
+    failover: Vec<String>,
+    auth: ApiAuth,
+    cache: Option<CacheConfig>,
+    negative_cache: Option<NegativeCacheConfig>,
+    cache_store: Option<SecondTier>,
+    cache_migrate: Option<MigrateFn>,
+    metrics: MetricsSinks,
+    metric_labels: Vec<String>,
+    audit: AuditSinks,
+    signing: Signing,
+    interceptors: Interceptors,
+    decoder: Decoder,
+    spawner: Spawner,
+    rate_limit: Option<RateLimitConfig>,
+    identity_rate_limit: Option<RateLimitConfig>,
+    circuit_breaker: Option<CircuitBreakerConfig>,
+    retry: Option<RetryConfig>,
+    timeout: Option<Duration>,
+    #[cfg(not(target_arch = "wasm32"))]
+    connect_timeout: Option<Duration>,
+    #[cfg(not(target_arch = "wasm32"))]
+    resolver: Option<Arc<dyn Resolve>>,
+    max_response_size: usize,
+    max_redirects: usize,
+    max_in_flight: Option<usize>,
+    require_https: bool,
+    params_in_body: bool,
+    reject_bogons: bool,
+    default_headers: HeaderMap,
+    #[cfg(not(target_arch = "wasm32"))]
+    protocol: HttpProtocol,
+    client: Option<Transport>,
+    #[cfg(not(target_arch = "wasm32"))]
+    proxy: Option<Proxy>,
+    #[cfg(not(target_arch = "wasm32"))]
+    proxy_from_env: bool,
+    #[cfg(feature = "har")]
+    har: Option<crate::har::HarConfig>,
+    #[cfg(feature = "record-replay")]
+    cassette: Option<crate::cassette::Cassette>,
+}
+
+/// Describes the outcome of a successful credentials verification.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerificationReport {
+    /// Time spent by the verification call, connection establishment included.
+    pub latency: Duration,
+}
+
+impl NeutralBuilder {
+    /// Create a new builder using the base uri of neutrinoapi and your credentials.
+    pub fn new(uri: &str, auth: ApiAuth) -> Self {
+        NeutralBuilder {
+            uri: uri.to_owned(),
+            failover: Vec::new(),
+            auth,
+            cache: None,
+            negative_cache: None,
+            cache_store: None,
+            cache_migrate: None,
+            metrics: MetricsSinks::default(),
+            metric_labels: Vec::new(),
+            audit: AuditSinks::default(),
+            signing: Signing::default(),
+            interceptors: Interceptors::default(),
+            decoder: Decoder::default(),
+            spawner: Spawner::default(),
+            rate_limit: None,
+            identity_rate_limit: None,
+            circuit_breaker: None,
+            retry: None,
+            timeout: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            connect_timeout: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            resolver: None,
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+            max_redirects: 0,
+            max_in_flight: None,
+            require_https: true,
+            params_in_body: false,
+            reject_bogons: false,
+            default_headers: HeaderMap::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            protocol: HttpProtocol::default(),
+            client: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            proxy: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            proxy_from_env: false,
+            #[cfg(feature = "har")]
+            har: None,
+            #[cfg(feature = "record-replay")]
+            cassette: None,
+        }
+    }
+
+    /// Record the traffic in memory to export it as a HAR document, see the [har](../har/index.html) module.
+    #[cfg(feature = "har")]
+    pub fn har(mut self, config: crate::har::HarConfig) -> Self {
+        self.har = Some(config);
+        self
+    }
+
+    /// Send a request to `uri` when the previous hosts failed, it can be called several times to add several hosts, see the [region](../region/index.html) module.
+    ///
+    /// Every failover uri is checked like the base uri when building the client.
+    pub fn failover(mut self, uri: &str) -> Self {
+        self.failover.push(uri.to_owned());
+        self
+    }
+
+    /// Record the traffic into fixture files or replay it from them, see the [cassette](../cassette/index.html) module.
+    #[cfg(feature = "record-replay")]
+    pub fn cassette(mut self, cassette: crate::cassette::Cassette) -> Self {
+        self.cassette = Some(cassette);
+        self
+    }
+
+    /// Require https to reach neutrinoapi.com, `true` by default. Plain http to a loopback address is always allowed.
+    pub fn require_https(mut self, require_https: bool) -> Self {
+        self.require_https = require_https;
+        self
+    }
+
+    /// Send the phone numbers, email addresses and security codes in a POST form body instead of the query string, `false` by default.
+    ///
+    /// Keeps them out of the access logs of proxies and servers, which record the query string. Applies to phone validate, phone verify,
+    /// sms verify, verify security code, hlr lookup and email verify.
+    pub fn params_in_body(mut self, params_in_body: bool) -> Self {
+        self.params_in_body = params_in_body;
+        self
+    }
+
+    /// Fail the ip info, ip probe and ip blocklist calls of a bogon address with [Error::BogonAddress](../error/enum.Error.html#variant.BogonAddress)
+    /// instead of sending them, `false` by default, see the [bogon](../bogon/index.html) module.
+    pub fn reject_bogons(mut self, reject_bogons: bool) -> Self {
+        self.reject_bogons = reject_bogons;
+        self
+    }
+
+    /// Add a `name` header valued `value` to every request, after the credentials, it can be called several times to add several headers.
+    ///
+    /// The `user-id` and `api-key` credential headers cannot be set this way: building then fails with [ConfigError::ReservedHeader](../error/enum.ConfigError.html#variant.ReservedHeader).
+    pub fn default_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.default_headers.append(name, value);
+        self
+    }
+
+    /// Send the requests with `client` instead of a client using the TLS backend of the crate, e.g. to share a connection pool.
+    ///
+    /// The connector of `client` decides whether plain http is allowed, [require_https](#method.require_https) still checks the base uri.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn client<C>(mut self, client: Client<C>) -> Self
+    where
+        C: Connect + Clone + Send + Sync + 'static,
+    {
+        self.client = Some(Transport::new(client));
+        self
+    }
+
+    /// Send the requests with the tower `service` instead of a hyper client, e.g. a client wrapped in timeout, load shedding or tracing layers.
+    ///
+    /// The service receives every attempt of a call once it is signed, and its response goes through the status code handling and the decoding of the crate.
+    /// An error of the service is returned as [Error::Hyper](../error/enum.Error.html#variant.Hyper) or [Error::Timeout](../error/enum.Error.html#variant.Timeout) when it is one,
+    /// as [Error::Service](../error/enum.Error.html#variant.Service) otherwise.
+    /// Like with a [client](#method.client), the service decides whether plain http is allowed.
+    pub fn service<S>(mut self, service: S) -> Self
+    where
+        S: Service<Request<Body>, Response = Response<Body>> + Clone + Send + Sync + 'static,
+        S::Future: Send,
+        S::Error: Into<BoxError>,
+    {
+        self.client = Some(Transport::from_service(service));
+        self
+    }
+
+    /// Send the requests with the reqwest `client`, sharing its connection pool, proxies and TLS configuration.
+    ///
+    /// Its responses go through the status code handling and the decoding of the crate, so the calls succeed and fail like with the hyper client.
+    /// An error of the client is returned as [Error::Service](../error/enum.Error.html#variant.Service), retried and failed over when it is a connection failure or a timeout.
+    /// Like with a [client](#method.client), the reqwest client decides whether plain http is allowed.
+    /// Build it with `redirect::Policy::none()` so redirects are handled by the crate as with the hyper client, see [max_redirects](#method.max_redirects).
+    #[cfg(feature = "reqwest-backend")]
+    pub fn reqwest(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(Transport::from_reqwest(client));
+        self
+    }
+
+    /// Speak HTTP/1.1 only, instead of negotiating HTTP/2 with ALPN, see the [tls](../tls/index.html) module.
+    ///
+    /// Has no effect with a [client](#method.client) or a [service](#method.service).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn http1_only(mut self) -> Self {
+        self.protocol = HttpProtocol::Http1Only;
+        self
+    }
+
+    /// Speak HTTP/2 only, multiplexing the concurrent calls over a single connection, see the [tls](../tls/index.html) module.
+    ///
+    /// Over plain http, HTTP/2 is spoken with prior knowledge, the server must accept it.
+    /// Has no effect with a [client](#method.client) or a [service](#method.service).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn http2_only(mut self) -> Self {
+        self.protocol = HttpProtocol::Http2Only;
+        self
+    }
+
+    /// Reach neutrinoapi.com through `proxy`, see the [proxy](../proxy/index.html) module.
+    ///
+    /// Has no effect with a [client](#method.client) or a [service](#method.service).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Read the proxy from the environment when building, unless set with [proxy](#method.proxy), `false` by default.
+    ///
+    /// See [Proxy::from_env](../proxy/struct.Proxy.html#method.from_env), has no effect with a [client](#method.client) or a [service](#method.service).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn proxy_from_env(mut self, proxy_from_env: bool) -> Self {
+        self.proxy_from_env = proxy_from_env;
+        self
+    }
+
+    /// Bound each request by `timeout`, none by default.
+    ///
+    /// The deadline covers the response head and the body, except the body of a streamed download.
+    /// Override it for a single call with the `timeout` method of the endpoints.
+    /// A request exceeding it fails with [Error::Timeout](../error/enum.Error.html#variant.Timeout).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Bound the connection to neutrinoapi.com by `connect_timeout`, none by default.
+    ///
+    /// The deadline covers the name resolution, the TCP connection and the tunnel of the [proxy](#method.proxy), not the TLS handshake.
+    /// It only applies when a new connection is opened, and a request exceeding it fails with
+    /// [ConnectError::Timeout](../error/enum.ConnectError.html#variant.Timeout) whatever its [timeout](#method.timeout).
+    /// Has no effect with a [client](#method.client) or a [service](#method.service).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    /// Resolve the host names with `resolver` instead of the system resolver, see the [resolve](../resolve/index.html) module.
+    ///
+    /// Has no effect with a [client](#method.client) or a [service](#method.service).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn resolver(mut self, resolver: Arc<dyn Resolve>) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    /// Bound the size of a response body to `max_response_size` bytes, [DEFAULT_MAX_RESPONSE_SIZE] by default.
+    ///
+    /// The body is abandoned as soon as it exceeds the limit, which fails the call with [Error::BodyTooLarge](../error/enum.Error.html#variant.BodyTooLarge).
+    /// The bodies of streamed downloads are not bounded.
+    pub fn max_response_size(mut self, max_response_size: usize) -> Self {
+        self.max_response_size = max_response_size;
+        self
+    }
+
+    /// Follow up to `max_redirects` redirects of a response, e.g. to the regional host of a gateway, `0` by default.
+    ///
+    /// A redirect is sent with the method and the body of the request, the credential headers are only sent to the scheme and authority of the request.
+    /// A 303 answering a request other than a GET is not followed. A response still redirecting after `max_redirects` redirects fails
+    /// with [Error::TooManyRedirects](../error/enum.Error.html#variant.TooManyRedirects), while a redirect is returned as an error response when none is followed.
+    pub fn max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    /// Send at most `max_in_flight` requests at once, across every endpoint of the client and its clones, unbounded by default.
+    ///
+    /// A request waits for one of the others to complete before it is sent. The wait counts in the [timeout](#method.timeout) of the request
+    /// and ends when its call is cancelled. A request is in flight until its response body is received, except the body of a streamed download.
+    /// `0` is handled as `1`.
+    pub fn max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = Some(max_in_flight.max(1));
+        self
+    }
+
+    /// Set how background tasks are spawned, see the [spawn](../spawn/index.html) module.
+    pub fn spawner(mut self, spawner: Spawner) -> Self {
+        self.spawner = spawner;
+        self
+    }
+
+    /// Register a sink receiving metrics events, it can be called several times to register several sinks.
+    pub fn metrics(mut self, sink: Arc<dyn MetricsSink>) -> Self {
+        self.metrics.0.push(sink);
+        self
+    }
+
+    /// Allow the given [CallContext](../context/struct.CallContext.html) keys as metrics labels, none by default.
+    ///
+    /// `tenant` selects the tenant of the context, any other key selects a tag. Keys must be valid metrics label names.
+    /// Only allow keys with a bounded set of values, every distinct value creates new series.
+    pub fn metric_labels<I, K>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        K: Into<String>,
+    {
+        self.metric_labels = keys.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Register a sink receiving an event for every call, it can be called several times to register several sinks.
+    pub fn audit(mut self, sink: Arc<dyn AuditSink>) -> Self {
+        self.audit.0.push(sink);
+        self
+    }
+
+    /// Decode every response body with `decoder`, see the [decode](../decode/index.html) module.
+    pub fn json_decoder(mut self, decoder: Arc<dyn JsonDecoder>) -> Self {
+        self.decoder = Decoder(Some(decoder));
+        self
+    }
+
+    /// Emit the headers in the fixed order documented in the [signing](../signing/index.html) module, `false` by default.
+    pub fn canonical_header_order(mut self, canonical_header_order: bool) -> Self {
+        self.signing.canonical_order = canonical_header_order;
+        self
+    }
+
+    /// Add a `name` header to every request, valued by `signer` from the final [CanonicalRequest](../signing/struct.CanonicalRequest.html).
+    ///
+    /// Headers are then emitted in canonical order, see the [signing](../signing/index.html) module.
+    pub fn sign_with<F>(mut self, name: HeaderName, signer: F) -> Self
+    where
+        F: Fn(&CanonicalRequest) -> HeaderValue + Send + Sync + 'static,
+    {
+        self.signing.canonical_order = true;
+        self.signing.signer = Some((name, Arc::new(signer)));
+        self
+    }
+
+    /// Edit every request with `interceptor` just before it is sent, it can be called several times to register several interceptors,
+    /// which run in registration order, see the [intercept](../intercept/index.html) module.
+    pub fn interceptor<F>(mut self, interceptor: F) -> Self
+    where
+        F: Fn(&mut Request<Body>) + Send + Sync + 'static,
+    {
+        self.interceptors.0.push(Arc::new(interceptor));
+        self
+    }
+
+    /// Enable the response cache of ip info, ip probe and ip blocklist, see the [cache](../cache/index.html) module.
+    pub fn cache(mut self, config: CacheConfig) -> Self {
+        self.cache = Some(config);
+        self
+    }
+
+    /// Enable the negative cache, answering repeated invalid inputs locally for `config.ttl`.
+    pub fn negative_cache(mut self, config: NegativeCacheConfig) -> Self {
+        self.negative_cache = Some(config);
+        self
+    }
+
+    /// Limit the rate of calls sent to neutrinoapi.com, see the [pipeline](../pipeline/index.html) module.
+    pub fn rate_limit(mut self, config: RateLimitConfig) -> Self {
+        self.rate_limit = Some(config);
+        self
+    }
+
+    /// Limit the rate of calls of every identity to `permits` per `per` period, on top of the global [rate_limit](#method.rate_limit).
+    ///
+    /// An identity is the user id of the credentials, it is only kept hashed.
+    pub fn rate_limit_per_identity(mut self, permits: u32, per: Duration) -> Self {
+        self.identity_rate_limit = Some(RateLimitConfig { permits, per });
+        self
+    }
+
+    /// Fail fast while an endpoint keeps failing, see the [pipeline](../pipeline/index.html) module.
+    pub fn circuit_breaker(mut self, config: CircuitBreakerConfig) -> Self {
+        self.circuit_breaker = Some(config);
+        self
+    }
+
+    /// Send again the calls failing with a retryable error, see the [pipeline](../pipeline/index.html) module.
+    pub fn retry(mut self, config: RetryConfig) -> Self {
+        self.retry = Some(config);
+        self
+    }
+
+    /// Persist successful responses in `store` for `ttl`, as a second tier behind the response cache, see the [cache](../cache/index.html) module.
+    pub fn cache_store(mut self, store: Arc<dyn CacheStore>, ttl: Duration) -> Self {
+        self.cache_store = Some(SecondTier {
+            store,
+            ttl,
+            migrate: None,
+        });
+        self
+    }
+
+    /// Upgrade the cache store records of other versions with `migrate` instead of ignoring them.
+    ///
+    /// Has no effect without a [cache_store](#method.cache_store).
+    pub fn cache_migrate(mut self, migrate: MigrateFn) -> Self {
+        self.cache_migrate = Some(migrate);
+        self
+    }
+
+    /// Build a Neutral instance without any network activity.
+    pub fn build(self) -> Result<Neutral, Error> {
+        let uri = self.uri.parse::<Uri>()?;
+        check_base_uri(&uri)?;
+        check_transport(&uri, self.require_https)?;
+        let failover = self
+            .failover
+            .iter()
+            .map(|uri| {
+                let uri = uri.parse::<Uri>()?;
+                check_base_uri(&uri)?;
+                check_transport(&uri, self.require_https)?;
+                Ok(uri)
+            })
+            .collect::<Result<Vec<Uri>, Error>>()?;
+        check_default_headers(&self.default_headers)?;
+
+        let client = match self.client {
+            Some(client) => client,
+            #[cfg(target_arch = "wasm32")]
+            None => Transport::fetch(),
+            #[cfg(not(target_arch = "wasm32"))]
+            None => {
+                let proxy = match self.proxy {
+                    Some(proxy) => Some(proxy),
+                    None if self.proxy_from_env => Proxy::from_env()?,
+                    None => None,
+                };
+                // Plain http is refused unless opted out, or to reach a loopback base uri.
+                let https_only = self.require_https
+                    && std::iter::once(&uri)
+                        .chain(&failover)
+                        .all(|uri| uri.scheme() == Some(&Scheme::HTTPS) || !is_loopback(uri));
+                let connector =
+                    ProxyConnector::new(proxy, Resolver::new(self.resolver), self.connect_timeout);
+                let https = tls::connector(https_only, connector, self.protocol);
+                Transport::new(
+                    Client::builder()
+                        .http2_only(self.protocol == HttpProtocol::Http2Only)
+                        .build::<_, hyper::Body>(https),
+                )
+            }
+        };
+        #[cfg(feature = "record-replay")]
+        let client = match self.cassette {
+            Some(cassette) => cassette.transport(client),
+            None => client,
+        };
+        let runtime = Runtime::new(RuntimeConfig {
+            uri,
+            failover,
+            rate_limit: self.rate_limit,
+            identity_rate_limit: self.identity_rate_limit,
+            require_https: self.require_https,
+            cache: self.cache,
+            negative_cache: self.negative_cache,
+        });
+        Ok(Neutral {
+            runtime: Arc::new(RwLock::new(Arc::new(runtime))),
+            auth: self.auth,
+            client,
+            cache: self
+                .cache
+                .map(|config| Arc::new(TtlCache::new(config.ttl, config.capacity))),
+            negative_cache: self
+                .negative_cache
+                .map(|config| Arc::new(TtlCache::new(config.ttl, config.capacity))),
+            cache_store: self.cache_store.map(|cache_store| SecondTier {
+                migrate: self.cache_migrate,
+                ..cache_store
+            }),
+            metrics: self.metrics,
+            metric_labels: self.metric_labels.into(),
+            audit: self.audit,
+            signing: self.signing,
+            interceptors: self.interceptors,
+            decoder: self.decoder,
+            spawner: self.spawner,
+            singleflight: Arc::default(),
+            breaker: self
+                .circuit_breaker
+                .map(|config| Arc::new(CircuitBreaker::new(config))),
+            retry: self.retry,
+            timeout: self.timeout,
+            max_response_size: self.max_response_size,
+            max_redirects: self.max_redirects,
+            in_flight: self
+                .max_in_flight
+                .map(|max_in_flight| Arc::new(Semaphore::new(max_in_flight))),
+            params_in_body: self.params_in_body,
+            reject_bogons: self.reject_bogons,
+            default_headers: Arc::new(self.default_headers),
+            #[cfg(test)]
+            probe: Default::default(),
+            capabilities: Arc::default(),
+            usage: Arc::default(),
+            #[cfg(feature = "har")]
+            har: self
+                .har
+                .map(|config| Arc::new(crate::har::HarRecorder::new(config))),
+        })
+    }
+
+    /// Build a Neutral instance then verify the credentials against neutrinoapi.com.
+    ///
+    /// Returns [Error::Unauthorized](../error/enum.Error.html#variant.Unauthorized) when the credentials are rejected.
+    pub async fn build_and_verify(self) -> Result<(Neutral, VerificationReport), Error> {
+        let neutral = self.build()?;
+        let start = Instant::now();
+        neutral.verify_credentials().await?;
+        let report = VerificationReport {
+            latency: start.elapsed(),
+        };
+        Ok((neutral, report))
+    }
+}
+
+/// Check that `uri` can prefix the uri of every request: it needs an http or https scheme and an authority.
+pub(crate) fn check_base_uri(uri: &Uri) -> Result<(), Error> {
+    let reason = match uri.scheme_str() {
+        None => "it has no scheme, e.g. https://neutrinoapi.net",
+        Some("http" | "https") if uri.authority().is_none() => "it has no authority",
+        Some("http" | "https") => return Ok(()),
+        Some(_) => "only the http and https schemes are supported",
+    };
+    Err(Error::InvalidBaseUri {
+        uri: uri.to_string(),
+        reason,
+    })
+}
+
+/// Check that `headers` does not override the credentials.
+fn check_default_headers(headers: &HeaderMap) -> Result<(), ConfigError> {
+    match headers
+        .keys()
+        .find(|name| *name == "user-id" || *name == "api-key")
+    {
+        Some(name) => Err(ConfigError::ReservedHeader(name.clone())),
+        None => Ok(()),
+    }
+}
+
+/// Check that `uri` does not send credentials in cleartext, unless to a loopback address.
+pub(crate) fn check_transport(uri: &Uri, require_https: bool) -> Result<(), ConfigError> {
+    if require_https && uri.scheme() != Some(&Scheme::HTTPS) && !is_loopback(uri) {
+        return Err(ConfigError::InsecureTransport(uri.clone()));
+    }
+    Ok(())
+}
+
+fn is_loopback(uri: &Uri) -> bool {
+    match uri.host() {
+        Some("localhost") => true,
+        Some(host) => host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .map(|ip_addr| ip_addr.is_loopback())
+            .unwrap_or(false),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mockito::{mock, Matcher};
+
+    #[tokio::test]
+    async fn test_build_is_lazy() {
+        let _m = mock("GET", Matcher::Any).expect(0).create();
+
+        let neutral = NeutralBuilder::new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .build();
+
+        assert!(neutral.is_ok());
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_build_and_verify() {
+        let _ok = mock("GET", "/phone-validate")
+            .match_query(Matcher::Any)
+            .match_header("api-key", "test")
+            .with_status(200)
+            .with_body(r#"{"valid": false}"#)
+            .create();
+
+        let _denied = mock("GET", "/phone-validate")
+            .match_query(Matcher::Any)
+            .match_header("api-key", "wrong")
+            .with_status(403)
+            .with_body(r#"{"api-error": 2, "api-error-msg": "INVALID API KEY"}"#)
+            .create();
+
+        let verified = NeutralBuilder::new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .build_and_verify()
+        .await;
+        assert!(verified.is_ok(), "Using a valid api key");
+
+        let verified = NeutralBuilder::new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "wrong".to_string()),
+        )
+        .build_and_verify()
+        .await;
+        assert!(
+            matches!(verified, Err(Error::Unauthorized(_))),
+            "Using a wrong api key"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_credentials() {
+        let _ok = mock("GET", "/phone-validate")
+            .match_query(Matcher::Exact("number=0".into()))
+            .match_header("api-key", "verify-ok")
+            .with_status(200)
+            .with_body(r#"{"valid": false}"#)
+            .expect(2)
+            .create();
+
+        let _denied = mock("GET", "/phone-validate")
+            .match_query(Matcher::Exact("number=0".into()))
+            .match_header("api-key", "verify-denied")
+            .with_status(403)
+            .with_body(r#"{"api-error": 2, "api-error-msg": "INVALID API KEY"}"#)
+            .expect(1)
+            .create();
+
+        let neutral = NeutralBuilder::new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "verify-ok".to_string()),
+        )
+        .cache(CacheConfig::default())
+        .build()
+        .unwrap();
+        assert!(neutral.verify_credentials().await.is_ok());
+        // Never answered from the cache.
+        assert!(neutral.verify_credentials().await.is_ok());
+        _ok.assert();
+
+        let neutral = NeutralBuilder::new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "verify-denied".to_string()),
+        )
+        .retry(RetryConfig::default())
+        .build()
+        .unwrap();
+        let err = neutral.verify_credentials().await.unwrap_err();
+        assert!(matches!(err, Error::Unauthorized(_)), "{:?}", err);
+        assert!(err.is_auth_error());
+        // A rejected key is not retried.
+        _denied.assert();
+
+        // Nothing listens on this port.
+        let neutral = NeutralBuilder::new(
+            "http://127.0.0.1:1",
+            ApiAuth::new("User".to_string(), "verify-ok".to_string()),
+        )
+        .build()
+        .unwrap();
+        let err = neutral.verify_credentials().await.unwrap_err();
+        assert!(matches!(err, Error::Hyper(_)), "{:?}", err);
+        assert!(!err.is_auth_error());
+        assert!(err.is_retryable());
+    }
+
+    /// Resolve every host to the address of the mockito server.
+    #[derive(Debug)]
+    struct MockitoResolver;
+
+    impl crate::resolve::Resolve for MockitoResolver {
+        fn resolve(&self, _host: &str) -> crate::resolve::ResolveFuture {
+            Box::pin(async { Ok(vec![mockito::server_address().ip()]) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_require_https_opt_out_sends_plain_http() {
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.5$".into()))
+            .with_status(200)
+            .with_body(crate::fixtures::IP_INFO_BODY)
+            .expect(1)
+            .create();
+
+        let base = format!("http://neutral.test:{}", mockito::server_address().port());
+        let neutral =
+            NeutralBuilder::new(&base, ApiAuth::new("User".to_string(), "test".to_string()))
+                .require_https(false)
+                .resolver(Arc::new(MockitoResolver))
+                .build()
+                .unwrap();
+        neutral
+            .ip_info()
+            .send("203.0.113.5".parse().unwrap())
+            .await
+            .unwrap();
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_warm_up() {
+        let _warm_up = mock("HEAD", "/")
+            .match_header("user-id", Matcher::Missing)
+            .match_header("api-key", Matcher::Missing)
+            .match_header("x-gateway-token", "warm")
+            .match_header("x-intercepted", "true")
+            .with_status(404)
+            .expect(2)
+            .create();
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.3$".into()))
+            .with_status(200)
+            .with_body(crate::fixtures::IP_INFO_BODY)
+            .expect(1)
+            .create();
+
+        let neutral = NeutralBuilder::new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .default_header(
+            HeaderName::from_static("x-gateway-token"),
+            HeaderValue::from_static("warm"),
+        )
+        .interceptor(|req| {
+            req.headers_mut()
+                .insert("x-intercepted", HeaderValue::from_static("true"));
+        })
+        .max_in_flight(1)
+        .build()
+        .unwrap();
+        // The status of the warm-up is ignored.
+        let (first, second) = tokio::join!(neutral.warm_up(), neutral.warm_up());
+        assert!(first.is_ok() && second.is_ok());
+        neutral
+            .ip_info()
+            .send("203.0.113.3".parse().unwrap())
+            .await
+            .unwrap();
+        _warm_up.assert();
+        _m.assert();
+        assert_eq!(neutral.usage().requests, 1);
+    }
+
+    #[tokio::test]
+    async fn test_warm_up_errors() {
+        use hyper::service::service_fn;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let warm_ups = Arc::new(AtomicUsize::new(0));
+        let service = service_fn({
+            let warm_ups = warm_ups.clone();
+            move |req: Request<Body>| {
+                let warm_ups = warm_ups.clone();
+                async move {
+                    if req.method() == http::Method::HEAD {
+                        warm_ups.fetch_add(1, Ordering::SeqCst);
+                        return Err(BoxError::from("connection refused"));
+                    }
+                    Ok(Response::new(Body::from(crate::fixtures::IP_INFO_BODY)))
+                }
+            }
+        });
+        let neutral = NeutralBuilder::new(
+            "http://127.0.0.1",
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .service(service)
+        .build()
+        .unwrap();
+
+        assert!(matches!(neutral.warm_up().await, Err(Error::Service(_))));
+        // A failed warm-up doesn't affect the following calls.
+        assert!(neutral
+            .ip_info()
+            .send("203.0.113.4".parse().unwrap())
+            .await
+            .is_ok());
+        assert!(neutral.warm_up().await.is_err());
+
+        let cancellation = crate::context::CancellationToken::new();
+        let refresh = neutral
+            .keep_warm(Duration::from_millis(20), cancellation.clone())
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(70)).await;
+        cancellation.cancel();
+        assert_eq!(refresh.await, Some(()));
+        assert!(warm_ups.load(Ordering::SeqCst) >= 4);
+    }
+
+    #[test]
+    fn test_require_https() {
+        struct TestingData {
+            pub name: String,
+            pub uri: String,
+            pub require_https: bool,
+            pub is_ok: bool,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Using https".to_owned(),
+                uri: "https://neutrinoapi.net".to_owned(),
+                require_https: true,
+                is_ok: true,
+            },
+            TestingData {
+                name: "Using http".to_owned(),
+                uri: "http://neutrinoapi.net".to_owned(),
+                require_https: true,
+                is_ok: false,
+            },
+            TestingData {
+                name: "Using http with https not required".to_owned(),
+                uri: "http://neutrinoapi.net".to_owned(),
+                require_https: false,
+                is_ok: true,
+            },
+            TestingData {
+                name: "Using http to localhost".to_owned(),
+                uri: "http://localhost:1234".to_owned(),
+                require_https: true,
+                is_ok: true,
+            },
+            TestingData {
+                name: "Using http to an ipv4 loopback".to_owned(),
+                uri: "http://127.0.0.1:1234".to_owned(),
+                require_https: true,
+                is_ok: true,
+            },
+            TestingData {
+                name: "Using http to an ipv6 loopback".to_owned(),
+                uri: "http://[::1]:1234".to_owned(),
+                require_https: true,
+                is_ok: true,
+            },
+        ];
+
+        for test in &tests {
+            let neutral = NeutralBuilder::new(
+                &test.uri,
+                ApiAuth::new("User".to_string(), "test".to_string()),
+            )
+            .require_https(test.require_https)
+            .build();
+
+            if test.is_ok {
+                assert!(neutral.is_ok(), "{}", test.name);
+            } else {
+                assert!(
+                    matches!(
+                        neutral,
+                        Err(Error::Config(ConfigError::InsecureTransport(_)))
+                    ),
+                    "{}",
+                    test.name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_base_uri() {
+        struct TestingData {
+            pub name: String,
+            pub args: &'static str,
+            pub expected: Option<&'static str>,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Using a uri without scheme".to_owned(),
+                args: "neutrinoapi.net",
+                expected: Some(
+                    r#"invalid base uri "neutrinoapi.net": it has no scheme, e.g. https://neutrinoapi.net"#,
+                ),
+            },
+            TestingData {
+                name: "Using a path".to_owned(),
+                args: "/just/a/path",
+                expected: Some(
+                    r#"invalid base uri "/just/a/path": it has no scheme, e.g. https://neutrinoapi.net"#,
+                ),
+            },
+            TestingData {
+                name: "Using an unsupported scheme".to_owned(),
+                args: "ftp://neutrinoapi.net",
+                expected: Some(
+                    r#"invalid base uri "ftp://neutrinoapi.net/": only the http and https schemes are supported"#,
+                ),
+            },
+            TestingData {
+                name: "Using an https uri".to_owned(),
+                args: "https://neutrinoapi.net",
+                expected: None,
+            },
+        ];
+
+        for test in tests {
+            let auth = ApiAuth::new("User".to_string(), "test".to_string());
+            let result = Neutral::try_new(test.args, auth.clone());
+            match test.expected {
+                Some(expected) => {
+                    let err = result.unwrap_err();
+                    assert!(
+                        matches!(err, Error::InvalidBaseUri { .. }),
+                        "{}: {:?}",
+                        test.name,
+                        err
+                    );
+                    assert_eq!(err.to_string(), expected, "{}", test.name);
+                }
+                None => assert!(result.is_ok(), "{}", test.name),
+            }
+
+            // A failover uri is checked like the base uri.
+            let result = NeutralBuilder::new("https://neutrinoapi.net", auth)
+                .failover(test.args)
+                .build();
+            assert_eq!(result.is_ok(), test.expected.is_none(), "{}", test.name);
+        }
+
+        assert!(matches!(
+            Neutral::try_new(
+                "https:/neutrinoapi.net",
+                ApiAuth::new("User".to_string(), "test".to_string())
+            ),
+            Err(Error::InvalidBaseUri { .. }) | Err(Error::InvalidUri(_))
+        ));
+    }
+
+    #[test]
+    fn test_endpoint_uri_of_invalid_base_uri() {
+        let base: Uri = "/just/a/path".parse().unwrap();
+        assert!(matches!(
+            crate::endpoint_uri(&base, "/ip-info"),
+            Err(Error::InvalidBaseUri { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_try_new_with_client() {
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.150$".into()))
+            .with_status(200)
+            .with_body(crate::fixtures::IP_INFO_BODY)
+            .expect(1)
+            .create();
+
+        let client = Client::builder().build_http::<hyper::Body>();
+        let neutral = Neutral::try_new_with_client(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+            client,
+        )
+        .unwrap();
+
+        let response = neutral
+            .ip_info()
+            .send("203.0.113.150".parse().unwrap())
+            .await
+            .unwrap();
+        assert!(response.is_valid);
+        _m.assert();
+    }
+
+    /// A layer counting the requests and tagging them with a header.
+    #[derive(Clone)]
+    struct Counting<S> {
+        inner: S,
+        count: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    impl<S: Service<Request<Body>>> Service<Request<Body>> for Counting<S> {
+        type Response = S::Response;
+        type Error = S::Error;
+        type Future = S::Future;
+
+        fn poll_ready(
+            &mut self,
+            cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<Result<(), Self::Error>> {
+            self.inner.poll_ready(cx)
+        }
+
+        fn call(&mut self, mut req: Request<Body>) -> Self::Future {
+            self.count.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            req.headers_mut()
+                .insert("x-layer", HeaderValue::from_static("counted"));
+            self.inner.call(req)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_try_new_with_service() {
+        let _ok = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.151$".into()))
+            .match_header("x-layer", "counted")
+            .with_status(200)
+            .with_body(crate::fixtures::IP_INFO_BODY)
+            .expect(1)
+            .create();
+        let _invalid = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.152$".into()))
+            .match_header("x-layer", "counted")
+            .with_status(400)
+            .with_body(r#"{"api-error": 3}"#)
+            .expect(1)
+            .create();
+
+        let count = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let service = Counting {
+            inner: Client::builder().build_http::<hyper::Body>(),
+            count: count.clone(),
+        };
+        let neutral = Neutral::try_new_with_service(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+            service,
+        )
+        .unwrap();
+
+        let response = neutral
+            .ip_info()
+            .send("203.0.113.151".parse().unwrap())
+            .await
+            .unwrap();
+        assert!(response.is_valid);
+        let err = neutral
+            .ip_info()
+            .send("203.0.113.152".parse().unwrap())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Neutrino(_)), "{:?}", err);
+
+        assert_eq!(count.load(std::sync::atomic::Ordering::SeqCst), 2);
+        _ok.assert();
+        _invalid.assert();
+    }
+
+    #[test]
+    fn test_endpoint_uri() {
+        struct TestingData {
+            pub name: String,
+            pub args: (&'static str, &'static str),
+            pub expected: &'static str,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Using a bare host".to_owned(),
+                args: ("https://neutrinoapi.net", "/ip-info?ip=1"),
+                expected: "https://neutrinoapi.net/ip-info?ip=1",
+            },
+            TestingData {
+                name: "Using a bare host with a trailing slash".to_owned(),
+                args: ("https://neutrinoapi.net/", "/ip-info"),
+                expected: "https://neutrinoapi.net/ip-info",
+            },
+            TestingData {
+                name: "Using a prefix with a trailing slash".to_owned(),
+                args: ("https://gateway.internal/neutrino/", "/ip-info?ip=1"),
+                expected: "https://gateway.internal/neutrino/ip-info?ip=1",
+            },
+            TestingData {
+                name: "Using a prefix without a trailing slash".to_owned(),
+                args: ("https://gateway.internal/neutrino", "ip-info"),
+                expected: "https://gateway.internal/neutrino/ip-info",
+            },
+        ];
+
+        for test in &tests {
+            let (base, path_and_query) = test.args;
+            let uri = crate::endpoint_uri(&base.parse().unwrap(), path_and_query).unwrap();
+            assert_eq!(uri.to_string(), test.expected, "{}", test.name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_base_path_prefix() {
+        let _m = mock("GET", "/neutrino/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.153$".into()))
+            .with_status(200)
+            .with_body(crate::fixtures::IP_INFO_BODY)
+            .expect(2)
+            .create();
+
+        for base in ["neutrino/", "neutrino"] {
+            let neutral = Neutral::try_new(
+                &format!("{}/{}", mockito::server_url(), base),
+                ApiAuth::new("User".to_string(), "test".to_string()),
+            )
+            .unwrap();
+            let response = neutral
+                .ip_info()
+                .send("203.0.113.153".parse().unwrap())
+                .await;
+            assert!(response.is_ok(), "{}: {:?}", base, response);
+        }
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_max_response_size() {
+        let limit = crate::fixtures::IP_INFO_BODY.len() + 64;
+        let padded = |len: usize| format!("{:<len$}", crate::fixtures::IP_INFO_BODY, len = len);
+        let oversized = padded(limit + 1);
+        let _m = [
+            mock("GET", "/ip-info")
+                .match_query(Matcher::Regex("ip=203.0.113.224$".into()))
+                .with_status(200)
+                .with_body(padded(limit))
+                .create(),
+            mock("GET", "/ip-info")
+                .match_query(Matcher::Regex("ip=203.0.113.225$".into()))
+                .with_status(200)
+                .with_body(&oversized)
+                .create(),
+            mock("GET", "/ip-info")
+                .match_query(Matcher::Regex("ip=203.0.113.226$".into()))
+                .with_status(200)
+                .with_body_from_fn(move |w| w.write_all(oversized.as_bytes()))
+                .create(),
+        ];
+
+        let neutral = NeutralBuilder::new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .max_response_size(limit)
+        .build()
+        .unwrap();
+
+        let result = neutral
+            .ip_info()
+            .send("203.0.113.224".parse().unwrap())
+            .await;
+        assert!(result.is_ok(), "At the limit: {:?}", result);
+
+        for (ip_addr, name) in [
+            ("203.0.113.225", "With a content-length"),
+            ("203.0.113.226", "Chunked"),
+        ] {
+            let result = neutral.ip_info().send(ip_addr.parse().unwrap()).await;
+            assert!(
+                matches!(
+                    result,
+                    Err(Error::BodyTooLarge { limit: l, received_at_least }) if l == limit && received_at_least > limit
+                ),
+                "{}: {:?}",
+                name,
+                result
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_default_headers() {
+        let mocks: Vec<_> = [
+            ("/ip-info", crate::fixtures::IP_INFO_BODY),
+            ("/ip-probe", crate::fixtures::IP_PROBE_BODY),
+        ]
+        .iter()
+        .map(|(path, body)| {
+            mock("GET", *path)
+                .match_query(Matcher::Regex("ip=203.0.113.154$".into()))
+                .match_header("api-key", "test")
+                .match_header("x-internal-team", "fraud")
+                .match_header("x-correlation-id", "7f9c")
+                .with_status(200)
+                .with_body(*body)
+                .expect(1)
+                .create()
+        })
+        .collect();
+
+        let neutral = NeutralBuilder::new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .default_header(
+            HeaderName::from_static("x-internal-team"),
+            HeaderValue::from_static("fraud"),
+        )
+        .default_header(
+            HeaderName::from_static("x-correlation-id"),
+            HeaderValue::from_static("7f9c"),
+        )
+        .build()
+        .unwrap();
+
+        let ip_addr = "203.0.113.154".parse().unwrap();
+        assert!(neutral.ip_info().send(ip_addr).await.is_ok());
+        assert!(neutral.ip_probe().send(ip_addr).await.is_ok());
+        for mock in mocks {
+            mock.assert();
+        }
+
+        for reserved in ["user-id", "api-key"] {
+            let result = NeutralBuilder::new(
+                "https://neutrinoapi.net",
+                ApiAuth::new("User".to_string(), "test".to_string()),
+            )
+            .default_header(
+                HeaderName::from_static(reserved),
+                HeaderValue::from_static("spoofed"),
+            )
+            .build();
+            assert!(
+                matches!(result, Err(Error::Config(ConfigError::ReservedHeader(ref name))) if name == reserved),
+                "{}",
+                reserved
+            );
+        }
+    }
+
+    #[test]
+    fn test_build_keeps_the_given_uri() {
+        let neutral = NeutralBuilder::new(
+            "https://neutrinoapi.net",
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .build()
+        .unwrap();
+        assert_eq!(neutral.authority().unwrap(), "neutrinoapi.net");
+        assert_eq!(neutral.scheme(), Some(Scheme::HTTPS));
+    }
+
+    #[tokio::test]
+    async fn test_protocols_over_http1() {
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.155$".into()))
+            .with_status(200)
+            .with_body(crate::fixtures::IP_INFO_BODY)
+            .expect(2)
+            .create();
+
+        let builder = NeutralBuilder::new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        );
+        assert_eq!(builder.protocol, HttpProtocol::Adaptive);
+        assert_eq!(
+            builder.clone().http2_only().protocol,
+            HttpProtocol::Http2Only
+        );
+
+        for builder in [builder.clone(), builder.http1_only()] {
+            let protocol = builder.protocol;
+            let result = builder
+                .build()
+                .unwrap()
+                .ip_info()
+                .send("203.0.113.155".parse().unwrap())
+                .await;
+            assert!(result.is_ok(), "{:?}: {:?}", protocol, result);
+        }
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_http2_only_multiplexes_calls() {
+        use hyper::{
+            service::{make_service_fn, service_fn},
+            Server, Version,
+        };
+        use std::{
+            convert::Infallible,
+            sync::atomic::{AtomicUsize, Ordering},
+        };
+
+        let connections = Arc::new(AtomicUsize::new(0));
+        let make_service = make_service_fn({
+            let connections = connections.clone();
+            move |_| {
+                connections.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    Ok::<_, Infallible>(service_fn(|req: Request<Body>| async move {
+                        assert_eq!(req.version(), Version::HTTP_2);
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        Ok::<_, Infallible>(Response::new(Body::from(
+                            crate::fixtures::IP_INFO_BODY,
+                        )))
+                    }))
+                }
+            }
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into())
+            .http2_only(true)
+            .serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        let neutral = NeutralBuilder::new(
+            &format!("http://{}", addr),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .http2_only()
+        .build()
+        .unwrap();
+        let ip_info = neutral.ip_info();
+        let results = futures::future::join_all(
+            (1..=4).map(|n| ip_info.send(format!("203.0.113.{}", 155 + n).parse().unwrap())),
+        )
+        .await;
+        for result in results {
+            assert!(result.is_ok(), "{:?}", result);
+        }
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+
+    /// Returns a client answering ip info calls after `delay`, allowing `max_in_flight` requests at once, along with the largest number of requests it served at once.
+    fn capped_neutral(
+        max_in_flight: usize,
+        delay: Duration,
+    ) -> (Neutral, Arc<std::sync::atomic::AtomicUsize>) {
+        use hyper::service::service_fn;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_overlap = Arc::new(AtomicUsize::new(0));
+        let service = service_fn({
+            let max_overlap = max_overlap.clone();
+            move |_: Request<Body>| {
+                let (in_flight, max_overlap) = (in_flight.clone(), max_overlap.clone());
+                async move {
+                    let overlap = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_overlap.fetch_max(overlap, Ordering::SeqCst);
+                    tokio::time::sleep(delay).await;
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                    Ok::<_, hyper::Error>(Response::new(Body::from(crate::fixtures::IP_INFO_BODY)))
+                }
+            }
+        });
+        let neutral = NeutralBuilder::new(
+            "http://127.0.0.1",
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .service(service)
+        .max_in_flight(max_in_flight)
+        .build()
+        .unwrap();
+        (neutral, max_overlap)
+    }
+
+    #[tokio::test]
+    async fn test_max_in_flight() {
+        use std::sync::atomic::Ordering;
+
+        let (neutral, max_overlap) = capped_neutral(4, Duration::from_millis(50));
+        // Clones share the limit.
+        let clone = neutral.clone();
+        let calls = (0..20).map(|n| {
+            let neutral = if n % 2 == 0 { &neutral } else { &clone };
+            let ip_addr = format!("203.0.113.{}", 200 + n).parse().unwrap();
+            tokio::spawn({
+                let neutral = neutral.clone();
+                async move { neutral.ip_info().send(ip_addr).await }
+            })
+        });
+
+        let start = Instant::now();
+        for result in futures::future::join_all(calls).await {
+            assert!(result.unwrap().is_ok());
+        }
+        assert_eq!(max_overlap.load(Ordering::SeqCst), 4);
+        // 20 requests, 4 at a time.
+        assert!(start.elapsed() >= Duration::from_millis(250));
+    }
+
+    #[tokio::test]
+    async fn test_max_in_flight_wait_times_out() {
+        let (neutral, _) = capped_neutral(1, Duration::from_millis(300));
+        let ip_info = neutral.ip_info();
+        let waiting = neutral.ip_info().timeout(Duration::from_millis(50));
+
+        let (first, second) = tokio::join!(ip_info.send("203.0.113.220".parse().unwrap()), async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            waiting.send("203.0.113.221".parse().unwrap()).await
+        });
+        assert!(first.is_ok());
+        assert!(matches!(second, Err(Error::Timeout(_))), "{:?}", second);
+    }
+
+    /// Serve ip info responses after `delay`.
+    fn serve_slowly(delay: Duration) -> std::net::SocketAddr {
+        use hyper::{
+            service::{make_service_fn, service_fn},
+            Body, Response, Server,
+        };
+        use std::convert::Infallible;
+
+        let make_service = make_service_fn(move |_| async move {
+            Ok::<_, Infallible>(service_fn(move |_| async move {
+                tokio::time::sleep(delay).await;
+                Ok::<_, Infallible>(Response::new(Body::from(crate::fixtures::IP_INFO_BODY)))
+            }))
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        addr
+    }
+
+    fn slow_neutral(timeout: Duration) -> Neutral {
+        let addr = serve_slowly(Duration::from_millis(300));
+        let neutral = NeutralBuilder::new(
+            "http://127.0.0.1",
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .timeout(timeout)
+        .build()
+        .unwrap();
+        neutral
+            .reconfigure(|config| config.uri = format!("http://{}", addr).parse().unwrap())
+            .unwrap();
+        neutral
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let neutral = slow_neutral(Duration::from_millis(50));
+
+        let start = Instant::now();
+        let result = neutral
+            .ip_info()
+            .send("203.0.113.120".parse().unwrap())
+            .await;
+        assert!(matches!(result, Err(Error::Timeout(_))));
+        assert!(start.elapsed() < Duration::from_millis(300));
+    }
+
+    #[tokio::test]
+    async fn test_timeout_per_call_override() {
+        let neutral = slow_neutral(Duration::from_millis(50));
+        let result = neutral
+            .ip_info()
+            .timeout(Duration::from_secs(5))
+            .send("203.0.113.121".parse().unwrap())
+            .await;
+        assert!(result.is_ok());
+
+        let neutral = slow_neutral(Duration::from_secs(5));
+        let result = neutral
+            .ip_info()
+            .timeout(Duration::from_millis(50))
+            .send("203.0.113.122".parse().unwrap())
+            .await;
+        assert!(matches!(result, Err(Error::Timeout(_))));
+    }
+}
@@ -0,0 +1,465 @@
+//! # Cache module
+//! In-memory caches used by [Neutral](../struct.Neutral.html) to answer repeated requests locally.
+//!
+//! Cached entries are raw response bodies keyed by endpoint, credentials and canonicalized input, so a cache hit goes through the same deserialization as a network response.
+//! The credentials are part of the key as a hash: a client built with [Neutral::with_auth](../struct.Neutral.html#method.with_auth) never gets the responses cached for other credentials.
+//!
+//! The response cache keeps successful ip info, ip probe and ip blocklist responses, it is enabled with [NeutralBuilder::cache](../builder/struct.NeutralBuilder.html#method.cache).
+//! Errors are never cached.
+//!
+//! The negative cache keeps responses for inputs judged invalid by neutrinoapi.com (`valid: false`).
+//! It is configured independently with [NeutralBuilder::negative_cache](../builder/struct.NeutralBuilder.html#method.negative_cache) and disabled by default.
+//!
+//! A [CacheStore](./trait.CacheStore.html), registered with [NeutralBuilder::cache_store](../builder/struct.NeutralBuilder.html#method.cache_store), is a second tier persisting successful responses, e.g. shared between processes.
+//! Its records are versioned envelopes, see [CACHE_RECORD_VERSION](./constant.CACHE_RECORD_VERSION.html).
+//! A record of another version is never used as is: it goes through the [MigrateFn](./type.MigrateFn.html) hook when one is registered, otherwise it is ignored with a [Warning::IncompatibleCacheRecord](../warning/enum.Warning.html#variant.IncompatibleCacheRecord) and the response is fetched again.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use hyper::body::Bytes;
+use serde::{Deserialize, Serialize};
+
+use crate::time::{self, Instant};
+
+/// Version of the records written to a [CacheStore](./trait.CacheStore.html).
+pub const CACHE_RECORD_VERSION: u32 = 1;
+
+/// Upgrade a record of another version, given as its version and its decoded JSON, into the response body it holds.
+///
+/// Returns `None` when the record cannot be upgraded, it is then ignored.
+pub type MigrateFn = fn(version: u32, record: serde_json::Value) -> Option<serde_json::Value>;
+
+/// A second tier cache persisting serialized records.
+///
+/// Records carry their own expiry, a store only has to keep the bytes.
+pub trait CacheStore: Send + Sync {
+    /// Returns the record stored under `key`.
+    fn get(&self, key: &str) -> Option<Vec<u8>>;
+
+    /// Store `record` under `key`, replacing any previous record.
+    fn put(&self, key: &str, record: Vec<u8>);
+}
+
+/// Configure the response cache of ip lookups.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CacheConfig {
+    /// How long a response is kept.
+    pub ttl: Duration,
+    /// Maximum number of kept responses, the least recently used entry is evicted first.
+    pub capacity: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            ttl: Duration::from_secs(3600),
+            capacity: 4096,
+        }
+    }
+}
+
+/// Configure the negative cache, answering repeated invalid inputs locally.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NegativeCacheConfig {
+    /// How long an invalid input is remembered.
+    pub ttl: Duration,
+    /// Maximum number of remembered inputs, the least recently used entry is evicted first.
+    pub capacity: usize,
+}
+
+impl Default for NegativeCacheConfig {
+    fn default() -> Self {
+        NegativeCacheConfig {
+            ttl: Duration::from_secs(60),
+            capacity: 1024,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    body: Bytes,
+    expires_at: Instant,
+    last_used: Instant,
+}
+
+/// A bounded cache of response bodies with a time to live.
+#[derive(Debug)]
+pub(crate) struct TtlCache {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl TtlCache {
+    pub(crate) fn new(ttl: Duration, capacity: usize) -> Self {
+        TtlCache {
+            ttl,
+            capacity,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the cached body of `key` if it has not expired yet.
+    pub(crate) fn get(&self, key: &str) -> Option<Bytes> {
+        let mut entries = self.entries.lock().unwrap();
+        let now = Instant::now();
+        match entries.get_mut(key) {
+            Some(entry) if entry.expires_at > now => {
+                entry.last_used = now;
+                Some(entry.body.clone())
+            }
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Store the body of `key`, evicting expired entries then the least recently used one when full.
+    pub(crate) fn insert(&self, key: String, body: Bytes) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        let now = Instant::now();
+
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            entries.retain(|_, entry| entry.expires_at > now);
+        }
+
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            let lru = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(lru) = lru {
+                entries.remove(&lru);
+            }
+        }
+
+        entries.insert(
+            key,
+            Entry {
+                body,
+                expires_at: now + self.ttl,
+                last_used: now,
+            },
+        );
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheRecord {
+    version: u32,
+    /// Expiry, in seconds since the unix epoch.
+    expires_at: u64,
+    body: serde_json::Value,
+}
+
+/// Describes why a record was not used.
+#[derive(Debug, PartialEq)]
+pub(crate) enum RecordError {
+    /// The record is unreadable or expired.
+    Unusable,
+    /// The record has another version and could not be migrated.
+    Incompatible(u32),
+}
+
+/// A [CacheStore](./trait.CacheStore.html) with its settings.
+#[derive(Clone)]
+pub(crate) struct SecondTier {
+    pub(crate) store: Arc<dyn CacheStore>,
+    pub(crate) ttl: Duration,
+    pub(crate) migrate: Option<MigrateFn>,
+}
+
+impl fmt::Debug for SecondTier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecondTier")
+            .field("ttl", &self.ttl)
+            .field("migrate", &self.migrate.is_some())
+            .finish()
+    }
+}
+
+fn unix_now() -> u64 {
+    time::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+impl SecondTier {
+    /// Returns the body held by the record of `key`.
+    pub(crate) fn get(&self, key: &str) -> Option<Result<Bytes, RecordError>> {
+        self.store.get(key).map(|record| self.decode(&record))
+    }
+
+    /// Store `body` under `key`, silently skipping bodies which are not JSON.
+    pub(crate) fn put(&self, key: &str, body: &Bytes) {
+        let record = serde_json::from_slice(body).map(|body| CacheRecord {
+            version: CACHE_RECORD_VERSION,
+            expires_at: unix_now() + self.ttl.as_secs(),
+            body,
+        });
+        if let Ok(record) = record.and_then(|record| serde_json::to_vec(&record)) {
+            self.store.put(key, record);
+        }
+    }
+
+    fn decode(&self, record: &[u8]) -> Result<Bytes, RecordError> {
+        let value: serde_json::Value =
+            serde_json::from_slice(record).map_err(|_| RecordError::Unusable)?;
+        let version = value
+            .get("version")
+            .and_then(serde_json::Value::as_u64)
+            .ok_or(RecordError::Unusable)? as u32;
+
+        let body = if version == CACHE_RECORD_VERSION {
+            let record: CacheRecord =
+                serde_json::from_value(value).map_err(|_| RecordError::Unusable)?;
+            if record.expires_at <= unix_now() {
+                return Err(RecordError::Unusable);
+            }
+            record.body
+        } else {
+            self.migrate
+                .and_then(|migrate| migrate(version, value))
+                .ok_or(RecordError::Incompatible(version))?
+        };
+        serde_json::to_vec(&body)
+            .map(Bytes::from)
+            .map_err(|_| RecordError::Unusable)
+    }
+}
+
+/// Build the cache key of an endpoint call from the [identity](../struct.ApiAuth.html) of its credentials and its canonicalized input.
+pub(crate) fn cache_key(endpoint: &str, identity: u64, canonical_input: &str) -> String {
+    format!("{}:{:016x}:{}", endpoint, identity, canonical_input)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{fixtures, metrics::MetricsSink, warning::Warning, ApiAuth, Neutral};
+    use mockito::{mock, Matcher};
+
+    #[derive(Default)]
+    struct MemoryStore(Mutex<HashMap<String, Vec<u8>>>);
+
+    impl CacheStore for MemoryStore {
+        fn get(&self, key: &str) -> Option<Vec<u8>> {
+            self.0.lock().unwrap().get(key).cloned()
+        }
+
+        fn put(&self, key: &str, record: Vec<u8>) {
+            self.0.lock().unwrap().insert(key.to_owned(), record);
+        }
+    }
+
+    #[derive(Default)]
+    struct Warnings(Mutex<Vec<Warning>>);
+
+    impl MetricsSink for Warnings {
+        fn on_warning(&self, _endpoint: &str, warning: &Warning) {
+            self.0.lock().unwrap().push(warning.clone());
+        }
+    }
+
+    fn old_record() -> Vec<u8> {
+        let body: serde_json::Value = serde_json::from_str(fixtures::IP_INFO_BODY).unwrap();
+        serde_json::to_vec(&serde_json::json!({ "version": 0, "payload": body })).unwrap()
+    }
+
+    fn auth() -> ApiAuth {
+        ApiAuth::new("User".to_string(), "test".to_string())
+    }
+
+    fn neutral(store: &Arc<MemoryStore>, warnings: &Arc<Warnings>) -> Neutral {
+        Neutral::builder(&mockito::server_url(), auth())
+            .cache_store(store.clone(), Duration::from_secs(60))
+            .metrics(warnings.clone())
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_cache_store_record_versions() {
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.40$".into()))
+            .with_status(200)
+            .with_body(fixtures::IP_INFO_BODY)
+            .expect(1)
+            .create();
+
+        let store = Arc::new(MemoryStore::default());
+        store.put(
+            &cache_key("ip-info", auth().identity(), "203.0.113.40"),
+            old_record(),
+        );
+        let warnings = Arc::new(Warnings::default());
+        let ip_addr = "203.0.113.40".parse().unwrap();
+
+        let neutral = neutral(&store, &warnings);
+        assert!(neutral.ip_info().send(ip_addr).await.is_ok());
+        assert_eq!(
+            *warnings.0.lock().unwrap(),
+            vec![Warning::IncompatibleCacheRecord { version: 0 }],
+            "the old record is ignored and the response fetched again"
+        );
+
+        let neutral = self::neutral(&store, &warnings);
+        assert!(neutral.ip_info().send(ip_addr).await.is_ok());
+        assert_eq!(
+            warnings.0.lock().unwrap().len(),
+            1,
+            "the record written by the previous client is served"
+        );
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_cache_store_migrate() {
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.41$".into()))
+            .expect(0)
+            .create();
+
+        let store = Arc::new(MemoryStore::default());
+        store.put(
+            &cache_key("ip-info", auth().identity(), "203.0.113.41"),
+            old_record(),
+        );
+        let neutral = Neutral::builder(&mockito::server_url(), auth())
+            .cache_store(store, Duration::from_secs(60))
+            .cache_migrate(|version, mut record| match version {
+                0 => Some(record["payload"].take()),
+                _ => None,
+            })
+            .build()
+            .unwrap();
+
+        let ip_info = neutral
+            .ip_info()
+            .send("203.0.113.41".parse().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(ip_info.city, "Roubaix");
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_cache_hit_and_expiry() {
+        let _m = mock("GET", "/ip-probe")
+            .match_query(Matcher::Regex("ip=203.0.113.210$".into()))
+            .with_status(200)
+            .with_body(fixtures::IP_PROBE_BODY)
+            .expect(2)
+            .create();
+        let _failing = mock("GET", "/ip-blocklist")
+            .match_query(Matcher::Regex("ip=203.0.113.211(&|$)".into()))
+            .with_status(500)
+            .with_body("internal error")
+            .expect(2)
+            .create();
+
+        let neutral = Neutral::builder(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .cache(CacheConfig {
+            ttl: Duration::from_millis(200),
+            capacity: 16,
+        })
+        .build()
+        .unwrap();
+        let ip_addr = "203.0.113.210".parse().unwrap();
+
+        let first = neutral.ip_probe().send(ip_addr).await.unwrap();
+        let (meta, second) = neutral.ip_probe().send_with_meta(ip_addr).await.unwrap();
+        assert!(meta.is_cached, "the second call is answered by the cache");
+        assert_eq!(first, second);
+
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        let (meta, _) = neutral.ip_probe().send_with_meta(ip_addr).await.unwrap();
+        assert!(!meta.is_cached, "the entry expired");
+        _m.assert();
+
+        for _ in 0..2 {
+            let result = neutral
+                .ip_blocklist()
+                .send("203.0.113.211".parse().unwrap())
+                .await;
+            assert!(result.is_err());
+        }
+        _failing.assert();
+    }
+
+    #[tokio::test]
+    async fn test_cache_is_keyed_by_credentials() {
+        let tenant = |user_id: &str, api_key: &str| {
+            mock("GET", "/ip-probe")
+                .match_query(Matcher::Regex("ip=203.0.113.8$".into()))
+                .match_header("user-id", user_id)
+                .match_header("api-key", api_key)
+                .with_status(200)
+                .with_body(fixtures::IP_PROBE_BODY)
+                .expect(1)
+                .create()
+        };
+        let _tenant_a = tenant("TenantA", "a-key");
+        let _tenant_b = tenant("TenantB", "b-key");
+
+        let neutral = Neutral::builder(
+            &mockito::server_url(),
+            ApiAuth::new("TenantA".to_string(), "a-key".to_string()),
+        )
+        .cache(CacheConfig {
+            ttl: Duration::from_secs(60),
+            capacity: 16,
+        })
+        .build()
+        .unwrap();
+        let ip_addr = "203.0.113.8".parse().unwrap();
+
+        neutral.ip_probe().send(ip_addr).await.unwrap();
+        let (meta, _) = neutral.ip_probe().send_with_meta(ip_addr).await.unwrap();
+        assert!(meta.is_cached);
+
+        let tenant_b = neutral.with_auth(ApiAuth::new("TenantB".to_string(), "b-key".to_string()));
+        let (meta, _) = tenant_b.ip_probe().send_with_meta(ip_addr).await.unwrap();
+        assert!(
+            !meta.is_cached,
+            "another tenant is not served the cached response"
+        );
+        let (meta, _) = tenant_b.ip_probe().send_with_meta(ip_addr).await.unwrap();
+        assert!(meta.is_cached);
+        _tenant_a.assert();
+        _tenant_b.assert();
+    }
+
+    #[test]
+    fn test_ttl_cache_expiry_and_eviction() {
+        let cache = TtlCache::new(Duration::from_millis(50), 2);
+
+        cache.insert("a".to_owned(), Bytes::from_static(b"a"));
+        cache.insert("b".to_owned(), Bytes::from_static(b"b"));
+        assert_eq!(cache.get("a"), Some(Bytes::from_static(b"a")));
+
+        cache.insert("c".to_owned(), Bytes::from_static(b"c"));
+        assert_eq!(cache.get("b"), None, "least recently used entry is evicted");
+        assert_eq!(cache.get("a"), Some(Bytes::from_static(b"a")));
+        assert_eq!(cache.get("c"), Some(Bytes::from_static(b"c")));
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(cache.get("a"), None, "entry expired");
+    }
+}
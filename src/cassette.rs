@@ -0,0 +1,340 @@
+//! # Cassette module
+//! Record the traffic with neutrinoapi.com into fixture files, then replay it in tests without any server, with the `record-replay` feature.
+//!
+//! A [Cassette](./enum.Cassette.html) is enabled with [NeutralBuilder::cassette](../builder/struct.NeutralBuilder.html#method.cassette):
+//!
+//! * [Cassette::Record](./enum.Cassette.html#variant.Record) sends the requests to the base uri and writes every request along with its response to a JSON file of the directory.
+//!   The `user-id` and `api-key` headers are never written, and the responses are requested uncompressed so the fixtures stay readable.
+//! * [Cassette::Replay](./enum.Cassette.html#variant.Replay) sends no request: every request is answered with the response of its fixture,
+//!   and fails with [Error::NoFixture](../error/enum.Error.html#variant.NoFixture) when there is none.
+//!
+//! A request matches a fixture on its method, its path and its query parameters, whatever their order. Neither its headers nor its body are matched,
+//! so calls sending their parameters in a form body, see [NeutralBuilder::params_in_body](../builder/struct.NeutralBuilder.html#method.params_in_body), share a single fixture per endpoint.
+//! Recording the same request again overwrites its fixture.
+//!
+//! ```ignore
+//! let neutral = Neutral::builder("https://neutrinoapi.net", api_auth)
+//!     .cassette(Cassette::Replay("tests/fixtures".into()))
+//!     .build()?;
+//! let ip_info_response = neutral.ip_info().send(ip_addr).await?;
+//! ```
+
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use http::{header::ACCEPT_ENCODING, response::Parts, HeaderMap, Method, Request, Response, Uri};
+use hyper::{body::Bytes, Body};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    signing::hex,
+    tls,
+    transport::{ResponseFuture, SendRequest, Transport},
+    Error,
+};
+
+const STRIPPED_HEADERS: [&str; 2] = ["user-id", "api-key"];
+
+/// Record or replay the traffic, see the [cassette](./index.html) module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cassette {
+    /// Send the requests and write the fixtures to the directory, created when missing.
+    Record(PathBuf),
+    /// Answer the requests with the fixtures of the directory, without sending them.
+    Replay(PathBuf),
+}
+
+impl Cassette {
+    /// Returns the transport recording the traffic of `inner`, or replaying it instead of using `inner`.
+    pub(crate) fn transport(self, inner: Transport) -> Transport {
+        match self {
+            Cassette::Record(dir) => Transport::from_send(Recorder { inner, dir }),
+            Cassette::Replay(dir) => Transport::from_send(Player { dir }),
+        }
+    }
+}
+
+/// A request along with its response, as written to a fixture file.
+#[derive(Debug, Serialize, Deserialize)]
+struct Fixture {
+    request: RecordedRequest,
+    response: RecordedResponse,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RecordedRequest {
+    method: String,
+    path: String,
+    /// Query parameters, sorted.
+    query: Vec<(String, String)>,
+    /// Headers, without the credentials.
+    headers: Vec<(String, String)>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RecordedResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    /// The body, hex encoded when `binary`.
+    body: String,
+    #[serde(default)]
+    binary: bool,
+}
+
+impl RecordedRequest {
+    fn new(method: &Method, uri: &Uri, headers: &HeaderMap) -> Self {
+        let mut query: Vec<(String, String)> =
+            form_urlencoded::parse(uri.query().unwrap_or_default().as_bytes())
+                .into_owned()
+                .collect();
+        query.sort();
+        RecordedRequest {
+            method: method.to_string(),
+            path: uri.path().to_owned(),
+            query,
+            headers: headers
+                .iter()
+                .filter(|(name, _)| !STRIPPED_HEADERS.contains(&name.as_str()))
+                .map(|(name, value)| {
+                    (
+                        name.to_string(),
+                        String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns what a request is matched on: its method, its path and its sorted query parameters.
+    fn key(&self) -> String {
+        let query = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(&self.query)
+            .finish();
+        format!("{} {}?{}", self.method, self.path, query)
+    }
+
+    /// Returns the name of the fixture file, readable and unique to the key.
+    fn file_name(&self) -> String {
+        let slug: String = format!("{}{}", self.method, self.path)
+            .to_ascii_lowercase()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect();
+        let digest = hex(&tls::sha256(self.key().as_bytes()));
+        format!("{}-{}.json", slug.trim_matches('-'), &digest[..16])
+    }
+}
+
+impl RecordedResponse {
+    fn new(parts: &Parts, body: &Bytes) -> Self {
+        let (body, binary) = match std::str::from_utf8(body) {
+            Ok(text) => (text.to_owned(), false),
+            Err(_) => (hex(body), true),
+        };
+        RecordedResponse {
+            status: parts.status.as_u16(),
+            headers: parts
+                .headers
+                .iter()
+                .map(|(name, value)| {
+                    (
+                        name.to_string(),
+                        String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                    )
+                })
+                .collect(),
+            body,
+            binary,
+        }
+    }
+
+    fn into_response(self) -> Result<Response<Body>, Error> {
+        let body = match self.binary {
+            true => unhex(&self.body).ok_or_else(|| {
+                Error::Io(std::io::Error::new(
+                    ErrorKind::InvalidData,
+                    "the binary body of the fixture is not hex encoded",
+                ))
+            })?,
+            false => self.body.into_bytes(),
+        };
+        let mut response = Response::builder().status(self.status);
+        for (name, value) in &self.headers {
+            response = response.header(name, value);
+        }
+        Ok(response.body(Body::from(body))?)
+    }
+}
+
+fn unhex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Send the requests with `inner` and write them along with their response to `dir`.
+struct Recorder {
+    inner: Transport,
+    dir: PathBuf,
+}
+
+impl SendRequest for Recorder {
+    fn send(&self, mut req: Request<Body>) -> ResponseFuture {
+        req.headers_mut().remove(ACCEPT_ENCODING);
+        let request = RecordedRequest::new(req.method(), req.uri(), req.headers());
+        let response = self.inner.request(req);
+        let dir = self.dir.clone();
+        Box::pin(async move {
+            let (parts, body) = response.await?.into_parts();
+            let body = hyper::body::to_bytes(body).await?;
+            let fixture = Fixture {
+                response: RecordedResponse::new(&parts, &body),
+                request,
+            };
+            write(&dir, &fixture)?;
+            Ok(Response::from_parts(parts, Body::from(body)))
+        })
+    }
+}
+
+fn write(dir: &Path, fixture: &Fixture) -> Result<(), Error> {
+    std::fs::create_dir_all(dir)?;
+    let json = serde_json::to_vec_pretty(fixture)?;
+    std::fs::write(dir.join(fixture.request.file_name()), json)?;
+    Ok(())
+}
+
+/// Answer the requests with the fixtures of `dir`.
+struct Player {
+    dir: PathBuf,
+}
+
+impl SendRequest for Player {
+    fn send(&self, req: Request<Body>) -> ResponseFuture {
+        let request = RecordedRequest::new(req.method(), req.uri(), &HeaderMap::new());
+        let path = self.dir.join(request.file_name());
+        Box::pin(async move {
+            let no_fixture = || Error::NoFixture {
+                request: request.key(),
+            };
+            let fixture: Fixture = match std::fs::read(&path) {
+                Ok(json) => serde_json::from_slice(&json)?,
+                Err(err) if err.kind() == ErrorKind::NotFound => return Err(no_fixture()),
+                Err(err) => return Err(err.into()),
+            };
+            if fixture.request.key() != request.key() {
+                return Err(no_fixture());
+            }
+            fixture.response.into_response()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{fixtures::IP_INFO_BODY, ApiAuth, Neutral};
+    use mockito::{mock, Matcher};
+
+    fn api_auth() -> ApiAuth {
+        ApiAuth::new("Recorder".to_string(), "secret-key".to_string())
+    }
+
+    #[test]
+    fn test_key_sorts_query() {
+        let request = |uri: &str| {
+            RecordedRequest::new(&Method::GET, &uri.parse().unwrap(), &HeaderMap::new())
+        };
+        let first = request("https://neutrinoapi.net/ip-info?reverse-lookup=true&ip=203.0.113.1");
+        let second = request("http://127.0.0.1:1234/ip-info?ip=203.0.113.1&reverse-lookup=true");
+
+        assert_eq!(
+            first.key(),
+            "GET /ip-info?ip=203.0.113.1&reverse-lookup=true"
+        );
+        assert_eq!(first.key(), second.key());
+        assert_eq!(first.file_name(), second.file_name());
+        assert!(first.file_name().starts_with("get-ip-info-"));
+        assert_ne!(
+            first.file_name(),
+            request("https://neutrinoapi.net/ip-info?ip=203.0.113.2").file_name()
+        );
+    }
+
+    #[test]
+    fn test_binary_body_round_trip() {
+        let parts = Response::new(()).into_parts().0;
+        let body = Bytes::from_static(&[0x00, 0xff, 0x7f, 0x80]);
+        let recorded = RecordedResponse::new(&parts, &body);
+        assert!(recorded.binary);
+        assert_eq!(recorded.body, "00ff7f80");
+        assert_eq!(unhex(&recorded.body).unwrap(), body.to_vec());
+        assert_eq!(unhex("0"), None);
+        assert_eq!(unhex("zz"), None);
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let dir = std::env::temp_dir().join(format!("neutral-cassette-{}", std::process::id()));
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.236$".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(IP_INFO_BODY)
+            .expect(1)
+            .create();
+
+        let recording = Neutral::builder(&mockito::server_url(), api_auth())
+            .cassette(Cassette::Record(dir.clone()))
+            .build()
+            .unwrap();
+        let recorded = recording
+            .ip_info()
+            .send("203.0.113.236".parse().unwrap())
+            .await
+            .unwrap();
+        _m.assert();
+
+        let files: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(files.len(), 1);
+        let fixture = std::fs::read_to_string(&files[0]).unwrap();
+        assert!(fixture.contains("203.0.113.236"));
+        assert!(!fixture.contains("Recorder") && !fixture.contains("secret-key"));
+
+        let replaying = Neutral::builder("https://neutrinoapi.net", api_auth())
+            .cassette(Cassette::Replay(dir.clone()))
+            .build()
+            .unwrap();
+        let replayed = replaying
+            .ip_info()
+            .send("203.0.113.236".parse().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&replayed).unwrap(),
+            serde_json::to_value(&recorded).unwrap()
+        );
+
+        let result = replaying
+            .ip_info()
+            .send("203.0.113.237".parse().unwrap())
+            .await;
+        match result {
+            Err(Error::NoFixture { request }) => {
+                assert_eq!(request, "GET /ip-info?ip=203.0.113.237")
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,228 @@
+//! # Context module
+//! Propagate a caller supplied context, such as a correlation id, through a neutrinoapi.com call.
+//!
+//! A [CallContext](./struct.CallContext.html) is attached to any call with its `context` method. It is:
+//!
+//! * sent as the `x-request-id` header when it holds a correlation id,
+//! * reported in every [AuditEvent](../audit/struct.AuditEvent.html),
+//! * bounding the duration of each attempt when it holds a timeout, instead of the client default set with [NeutralBuilder::timeout](../builder/struct.NeutralBuilder.html#method.timeout),
+//! * resolving the call with [Error::Cancelled](../error/enum.Error.html#variant.Cancelled) as soon as its [CancellationToken](./struct.CancellationToken.html) is cancelled, e.g. to drain the calls in flight on shutdown,
+//! * attached to the [NeutrinoError](../error/struct.NeutrinoError.html) of a failed call,
+//! * turned into metrics labels, restricted to the keys allowed with [NeutralBuilder::metric_labels](../builder/struct.NeutralBuilder.html#method.metric_labels) to keep the cardinality bounded.
+//!
+//! ```ignore
+//! let context = CallContext::new()
+//!     .with_correlation_id("4f6c1b2e")
+//!     .with_tenant("acme")
+//!     .with_tag("flow", "signup");
+//! let ip_info = neutral.ip_info().context(context).send(ip_addr).await?;
+//! ```
+
+use std::{collections::BTreeMap, time::Duration};
+
+use http::HeaderValue;
+
+pub use tokio_util::sync::CancellationToken;
+
+/// Describes who issued a call and why, independently of any tracing framework.
+///
+/// Two contexts are equal when their fields are, whatever their cancellation tokens.
+#[derive(Debug, Clone, Default)]
+pub struct CallContext {
+    /// Identify the call across systems, sent as the `x-request-id` header.
+    pub correlation_id: Option<String>,
+    /// The tenant the call is made for.
+    pub tenant: Option<String>,
+    /// Free form tags.
+    pub tags: BTreeMap<String, String>,
+    /// Deadline of each attempt of the call, overriding the client default.
+    pub timeout: Option<Duration>,
+    /// Cancel the call, whatever stage it is in.
+    pub cancellation: Option<CancellationToken>,
+}
+
+impl PartialEq for CallContext {
+    fn eq(&self, other: &Self) -> bool {
+        self.correlation_id == other.correlation_id
+            && self.tenant == other.tenant
+            && self.tags == other.tags
+            && self.timeout == other.timeout
+    }
+}
+
+impl Eq for CallContext {}
+
+impl CallContext {
+    /// Create an empty context.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the correlation id.
+    pub fn with_correlation_id(mut self, correlation_id: impl Into<String>) -> Self {
+        self.correlation_id = Some(correlation_id.into());
+        self
+    }
+
+    /// Set the tenant.
+    pub fn with_tenant(mut self, tenant: impl Into<String>) -> Self {
+        self.tenant = Some(tenant.into());
+        self
+    }
+
+    /// Set the timeout.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set the cancellation token.
+    ///
+    /// Once `token` is cancelled, the call resolves with [Error::Cancelled](../error/enum.Error.html#variant.Cancelled) and its request in flight is dropped,
+    /// which closes its connection while the other connections of the client stay usable.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Add a tag, replacing any previous value of `key`.
+    pub fn with_tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tags.insert(key.into(), value.into());
+        self
+    }
+
+    /// Returns the `x-request-id` header value derived from the correlation id.
+    ///
+    /// Returns `None` without a correlation id or when it is not a valid header value.
+    pub(crate) fn request_id(&self) -> Option<HeaderValue> {
+        self.correlation_id
+            .as_deref()
+            .and_then(|correlation_id| HeaderValue::from_str(correlation_id).ok())
+    }
+
+    /// Returns the metrics labels of the context whose key is in `allowed`.
+    ///
+    /// The `tenant` key selects the tenant, any other key selects a tag.
+    pub(crate) fn labels<'a>(&'a self, allowed: &'a [String]) -> Vec<(&'a str, &'a str)> {
+        allowed
+            .iter()
+            .filter_map(|key| {
+                let value = match key.as_str() {
+                    "tenant" => self.tenant.as_deref(),
+                    key => self.tags.get(key).map(String::as_str),
+                };
+                value.map(|value| (key.as_str(), value))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        audit::{AuditEvent, AuditSink},
+        metrics::MetricsSink,
+        ApiAuth, Error, Neutral,
+    };
+    use http::StatusCode;
+    use mockito::{mock, Matcher};
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    #[derive(Default)]
+    struct Recorder {
+        audit: Mutex<Vec<(String, Option<StatusCode>, CallContext)>>,
+        labels: Mutex<Vec<Vec<(String, String)>>>,
+    }
+
+    impl AuditSink for Recorder {
+        fn on_call(&self, event: &AuditEvent<'_>) {
+            self.audit.lock().unwrap().push((
+                event.endpoint.to_owned(),
+                event.status,
+                event.context.clone(),
+            ));
+        }
+    }
+
+    impl MetricsSink for Recorder {
+        fn on_request_with_labels(
+            &self,
+            _endpoint: &str,
+            _status: Option<StatusCode>,
+            _elapsed: Duration,
+            labels: &[(&str, &str)],
+        ) {
+            self.labels.lock().unwrap().push(
+                labels
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect(),
+            );
+        }
+    }
+
+    #[test]
+    fn test_call_context_labels() {
+        let context = CallContext::new()
+            .with_tenant("acme")
+            .with_tag("flow", "signup")
+            .with_tag("user", "42");
+        let allowed = vec!["tenant".to_owned(), "flow".to_owned(), "missing".to_owned()];
+        assert_eq!(
+            context.labels(&allowed),
+            vec![("tenant", "acme"), ("flow", "signup")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_call_context_propagation() {
+        let _m = mock("GET", "/hlr-lookup")
+            .match_query(Matcher::Regex("number=%2B33612345678".into()))
+            .match_header("x-request-id", "4f6c1b2e")
+            .with_status(500)
+            .with_body("internal error")
+            .create();
+
+        let recorder = Arc::new(Recorder::default());
+        let neutral = Neutral::builder(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .audit(recorder.clone())
+        .metrics(recorder.clone())
+        .metric_labels(["tenant"])
+        .build()
+        .unwrap();
+
+        let context = CallContext::new()
+            .with_correlation_id("4f6c1b2e")
+            .with_tenant("acme")
+            .with_tag("user", "42");
+        let result = neutral
+            .hlr_lookup()
+            .context(context.clone())
+            .send("+33612345678".to_owned())
+            .await;
+
+        match result {
+            Err(Error::ServerError(err)) => assert_eq!(*err.context, context),
+            other => panic!("expected a server error, got {:?}", other),
+        }
+        assert_eq!(
+            *recorder.audit.lock().unwrap(),
+            vec![(
+                "hlr-lookup".to_owned(),
+                Some(StatusCode::INTERNAL_SERVER_ERROR),
+                context
+            )]
+        );
+        assert_eq!(
+            *recorder.labels.lock().unwrap(),
+            vec![vec![("tenant".to_owned(), "acme".to_owned())]]
+        );
+    }
+}
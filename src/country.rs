@@ -0,0 +1,298 @@
+//! # Country module
+//! Typed country and currency codes.
+//!
+//! The response types keep their raw `String` fields, the [TypedCodes](./trait.TypedCodes.html) extension trait parses them on demand,
+//! so an alpha-2 country code can't be mixed up with an alpha-3 one. An empty or malformed code is `None`,
+//! a well-formed code is accepted even when it is not assigned.
+//!
+//! ```ignore
+//! use neutral::country::TypedCodes;
+//!
+//! let ip_info_response = neutral.ip_info().send(ip_addr).await?;
+//! if let Some(currency) = ip_info_response.currency() {
+//!     println!("prices in {}", currency);
+//! }
+//! ```
+
+use std::{fmt, str::FromStr};
+
+use neutral_types::{
+    hlr_lookup::HlrLookupResponse, ip_info::IpInfoResponse, ip_probe::IpProbeResponse,
+    phone_validate::PhoneValidateResponse,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A code which is not made of the expected number of ASCII letters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidCode {
+    pub code: String,
+    /// The number of letters of a valid code.
+    pub expected_len: usize,
+}
+
+impl fmt::Display for InvalidCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} is not a code of {} ASCII letters",
+            self.code, self.expected_len
+        )
+    }
+}
+
+impl std::error::Error for InvalidCode {}
+
+/// Declare the code `$name` of `$len` ASCII letters.
+macro_rules! code {
+    ($(#[$doc:meta])* $name:ident, $len:literal) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub struct $name(String);
+
+        impl $name {
+            #[doc = concat!("Parse a ", stringify!($len), " ASCII letters code, returns `None` for anything else.")]
+            ///
+            /// Lowercase codes are accepted and normalized to uppercase.
+            pub fn parse(code: &str) -> Option<Self> {
+                if code.len() == $len && code.chars().all(|c| c.is_ascii_alphabetic()) {
+                    Some($name(code.to_ascii_uppercase()))
+                } else {
+                    None
+                }
+            }
+
+            /// Returns the code as a string slice.
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = InvalidCode;
+
+            fn from_str(code: &str) -> Result<Self, Self::Err> {
+                Self::parse(code).ok_or_else(|| InvalidCode {
+                    code: code.to_owned(),
+                    expected_len: $len,
+                })
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(&self.0)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                String::deserialize(deserializer)?
+                    .parse()
+                    .map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+
+code!(
+    /// An ISO 3166-1 alpha-2 country code, e.g. `FR`.
+    CountryCode,
+    2
+);
+code!(
+    /// An ISO 3166-1 alpha-3 country code, e.g. `FRA`.
+    CountryCode3,
+    3
+);
+code!(
+    /// An ISO 4217 currency code, e.g. `EUR`.
+    CurrencyCode,
+    3
+);
+
+/// Deserialize an empty string as `None` and any other string as a code, for fields declared with
+/// `#[serde(default, deserialize_with = "neutral::country::empty_as_none")]`.
+pub fn empty_as_none<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    match Option::<String>::deserialize(deserializer)?.as_deref() {
+        None | Some("") => Ok(None),
+        Some(code) => code.parse().map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+/// Typed country and currency codes of a response, `None` when empty or malformed.
+pub trait TypedCodes {
+    /// Returns the alpha-2 code of the country.
+    fn country_alpha2(&self) -> Option<CountryCode>;
+
+    /// Returns the alpha-3 code of the country.
+    fn country_alpha3(&self) -> Option<CountryCode3>;
+
+    /// Returns the code of the currency of the country.
+    fn currency(&self) -> Option<CurrencyCode>;
+}
+
+/// Implement [TypedCodes] for the responses holding `country_code`, `country_code3` and `currency_code` fields.
+macro_rules! typed_codes {
+    ($($response:ty),*) => {
+        $(
+            impl TypedCodes for $response {
+                fn country_alpha2(&self) -> Option<CountryCode> {
+                    CountryCode::parse(&self.country_code)
+                }
+
+                fn country_alpha3(&self) -> Option<CountryCode3> {
+                    CountryCode3::parse(&self.country_code3)
+                }
+
+                fn currency(&self) -> Option<CurrencyCode> {
+                    CurrencyCode::parse(&self.currency_code)
+                }
+            }
+        )*
+    };
+}
+
+typed_codes!(
+    IpInfoResponse,
+    IpProbeResponse,
+    HlrLookupResponse,
+    PhoneValidateResponse
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::{IP_INFO_BODY, PHONE_VALIDATE_BODY};
+
+    #[test]
+    fn test_country_code_parse() {
+        assert_eq!(CountryCode::parse("FR").unwrap().as_str(), "FR");
+        assert_eq!(CountryCode::parse("fr").unwrap().as_str(), "FR");
+        assert_eq!(CountryCode::parse(""), None);
+        assert_eq!(CountryCode::parse("FRA"), None);
+        assert_eq!(CountryCode::parse("F1"), None);
+    }
+
+    #[test]
+    fn test_codes_from_str() {
+        assert_eq!("fra".parse::<CountryCode3>().unwrap().as_str(), "FRA");
+        assert_eq!("XK".parse::<CountryCode>().unwrap().as_str(), "XK");
+        assert_eq!(
+            "EU".parse::<CurrencyCode>(),
+            Err(InvalidCode {
+                code: "EU".to_owned(),
+                expected_len: 3
+            })
+        );
+        assert!("".parse::<CountryCode>().is_err());
+        assert!("EUR ".parse::<CurrencyCode>().is_err());
+    }
+
+    #[test]
+    fn test_codes_serde() {
+        #[derive(Debug, Deserialize, Serialize)]
+        struct Codes {
+            #[serde(default, deserialize_with = "empty_as_none")]
+            country: Option<CountryCode>,
+            #[serde(default, deserialize_with = "empty_as_none")]
+            currency: Option<CurrencyCode>,
+        }
+
+        let codes: Codes = serde_json::from_str(r#"{"country": "", "currency": "eur"}"#).unwrap();
+        assert_eq!(codes.country, None);
+        assert_eq!(codes.currency, CurrencyCode::parse("EUR"));
+        assert_eq!(
+            serde_json::to_string(&codes).unwrap(),
+            r#"{"country":null,"currency":"EUR"}"#
+        );
+        assert!(serde_json::from_str::<Codes>(r#"{"country": "FRA"}"#).is_err());
+        assert!(serde_json::from_str::<CountryCode>(r#""FR""#).is_ok());
+        assert!(serde_json::from_str::<CountryCode>(r#""""#).is_err());
+    }
+
+    #[test]
+    fn test_typed_codes() {
+        struct TestingData {
+            pub name: String,
+            pub args: (&'static str, &'static str, &'static str),
+            pub expected: (
+                Option<&'static str>,
+                Option<&'static str>,
+                Option<&'static str>,
+            ),
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Using uppercase codes".to_owned(),
+                args: ("FR", "FRA", "EUR"),
+                expected: (Some("FR"), Some("FRA"), Some("EUR")),
+            },
+            TestingData {
+                name: "Using empty codes".to_owned(),
+                args: ("", "", ""),
+                expected: (None, None, None),
+            },
+            TestingData {
+                name: "Using lowercase codes".to_owned(),
+                args: ("fr", "fra", "eur"),
+                expected: (Some("FR"), Some("FRA"), Some("EUR")),
+            },
+            TestingData {
+                name: "Using codes of the wrong length".to_owned(),
+                args: ("FRA", "FR", "EURO"),
+                expected: (None, None, None),
+            },
+        ];
+
+        let assert_codes = |codes: &dyn TypedCodes, test: &TestingData| {
+            let (alpha2, alpha3, currency) = test.expected;
+            assert_eq!(
+                codes.country_alpha2().as_ref().map(CountryCode::as_str),
+                alpha2,
+                "{}",
+                test.name
+            );
+            assert_eq!(
+                codes.country_alpha3().as_ref().map(CountryCode3::as_str),
+                alpha3,
+                "{}",
+                test.name
+            );
+            assert_eq!(
+                codes.currency().as_ref().map(CurrencyCode::as_str),
+                currency,
+                "{}",
+                test.name
+            );
+        };
+
+        for test in &tests {
+            let (country_code, country_code3, currency_code) = test.args;
+
+            let mut ip_info: IpInfoResponse = serde_json::from_str(IP_INFO_BODY).unwrap();
+            ip_info.country_code = country_code.to_owned();
+            ip_info.country_code3 = country_code3.to_owned();
+            ip_info.currency_code = currency_code.to_owned();
+            assert_codes(&ip_info, test);
+
+            let mut phone_validate: PhoneValidateResponse =
+                serde_json::from_str(PHONE_VALIDATE_BODY).unwrap();
+            phone_validate.country_code = country_code.to_owned();
+            phone_validate.country_code3 = country_code3.to_owned();
+            phone_validate.currency_code = currency_code.to_owned();
+            assert_codes(&phone_validate, test);
+        }
+    }
+}
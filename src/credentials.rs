@@ -0,0 +1,445 @@
+//! # Credentials module
+//! Load the [ApiAuth](../struct.ApiAuth.html) credentials from outside the code.
+//!
+//! [ApiAuth::from_env](../struct.ApiAuth.html#method.from_env) reads the credentials from [USER_ID_VAR](./constant.USER_ID_VAR.html) and [API_KEY_VAR](./constant.API_KEY_VAR.html),
+//! [ApiAuth::from_env_with](../struct.ApiAuth.html#method.from_env_with) from the `<prefix>_USER_ID` and `<prefix>_API_KEY` variables.
+//!
+//! [ApiAuth::from_file](../struct.ApiAuth.html#method.from_file) reads them from a JSON file, or from a TOML file holding `key = "value"` lines:
+//!
+//! ```toml
+//! user_id = "my-user"
+//! api_key = "my-api-key"
+//! ```
+//!
+//! With the `serde` feature, `ApiAuth` implements `Deserialize` from the same `user_id` and `api_key` fields, so it can be embedded in a configuration struct.
+//! Empty values are rejected and `ApiAuth` does not implement `Serialize`, so the credentials can't be written back into a dump of the configuration.
+//!
+//! With the `scrub-env` feature, [ApiAuth::from_env_scrubbed](../struct.ApiAuth.html#method.from_env_scrubbed) reads the credentials from
+//! [USER_ID_VAR](./constant.USER_ID_VAR.html) and [API_KEY_VAR](./constant.API_KEY_VAR.html), then removes both variables from the process environment,
+//! so they cannot be read back from `/proc/<pid>/environ`-like dumps of the process or by child processes.
+//! The values are moved into [Secret](https://docs.rs/secrecy/0.8.0/secrecy/struct.Secret.html) without any intermediate copy and zeroized on drop.
+//!
+//! Removing a variable is not synchronized with other threads: call it early, typically at the start of `main`,
+//! before any other thread may read or write the environment.
+
+use secrecy::{zeroize::Zeroize, Secret};
+use serde::Deserialize;
+use std::{env, path::Path};
+
+use crate::{
+    error::{ConfigError, Error},
+    ApiAuth,
+};
+
+/// Environment variable holding the neutrinoapi.com user id.
+pub const USER_ID_VAR: &str = "NEUTRINO_USER_ID";
+/// Environment variable holding the neutrinoapi.com api key.
+pub const API_KEY_VAR: &str = "NEUTRINO_API_KEY";
+
+/// Prefix of the environment variables read by [ApiAuth::from_env](../struct.ApiAuth.html#method.from_env).
+pub const DEFAULT_ENV_PREFIX: &str = "NEUTRINO";
+
+impl ApiAuth {
+    /// Read the credentials from [USER_ID_VAR](./credentials/constant.USER_ID_VAR.html) and [API_KEY_VAR](./credentials/constant.API_KEY_VAR.html).
+    ///
+    /// Fails with [ConfigError::MissingCredentials](./error/enum.ConfigError.html#variant.MissingCredentials) listing every variable which is not set, empty or not valid unicode.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_env_with(DEFAULT_ENV_PREFIX)
+    }
+
+    /// Same as [from_env](#method.from_env), reading the `<prefix>_USER_ID` and `<prefix>_API_KEY` variables.
+    pub fn from_env_with(prefix: &str) -> Result<Self, ConfigError> {
+        let user_id_var = format!("{}_USER_ID", prefix);
+        let api_key_var = format!("{}_API_KEY", prefix);
+        match (read_non_empty(&user_id_var), read_non_empty(&api_key_var)) {
+            (Some(user_id), Some(api_key)) => Ok(ApiAuth { user_id, api_key }),
+            (user_id, api_key) => {
+                let mut missing = Vec::new();
+                if user_id.is_none() {
+                    missing.push(user_id_var);
+                }
+                if api_key.is_none() {
+                    missing.push(api_key_var);
+                }
+                Err(ConfigError::MissingCredentials(missing))
+            }
+        }
+    }
+
+    /// Read the credentials from the `user_id` and `api_key` keys of a JSON or TOML file, see the [credentials](./credentials/index.html) module.
+    ///
+    /// Fails with [ConfigError::InvalidCredentialsFile](./error/enum.ConfigError.html#variant.InvalidCredentialsFile) when the file cannot be parsed,
+    /// or [ConfigError::MissingCredentials](./error/enum.ConfigError.html#variant.MissingCredentials) listing the keys which are missing or empty.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let mut contents = std::fs::read_to_string(path)?;
+        let parsed = if contents.trim_start().starts_with('{') {
+            serde_json::from_str::<CredentialsFile>(&contents).ok()
+        } else {
+            parse_toml(&contents)
+        };
+        contents.zeroize();
+
+        let file = parsed.ok_or_else(|| ConfigError::InvalidCredentialsFile(path.to_owned()))?;
+        match (non_empty(file.user_id), non_empty(file.api_key)) {
+            (Some(user_id), Some(api_key)) => Ok(ApiAuth { user_id, api_key }),
+            (user_id, api_key) => {
+                let mut missing = Vec::new();
+                if user_id.is_none() {
+                    missing.push("user_id".to_owned());
+                }
+                if api_key.is_none() {
+                    missing.push("api_key".to_owned());
+                }
+                Err(ConfigError::MissingCredentials(missing).into())
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CredentialsFile {
+    user_id: Option<String>,
+    api_key: Option<String>,
+}
+
+/// Parse the `key = "value"` lines of a TOML file, ignoring comments, blank lines and unknown keys.
+fn parse_toml(contents: &str) -> Option<CredentialsFile> {
+    let mut file = CredentialsFile {
+        user_id: None,
+        api_key: None,
+    };
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = line.split_once('=')?;
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .or_else(|| {
+                value
+                    .strip_prefix('\'')
+                    .and_then(|value| value.strip_suffix('\''))
+            })?;
+        match key.trim() {
+            "user_id" => file.user_id = Some(value.to_owned()),
+            "api_key" => file.api_key = Some(value.to_owned()),
+            _ => {}
+        }
+    }
+    Some(file)
+}
+
+/// Move a non empty `value` into a secret.
+fn non_empty(value: Option<String>) -> Option<Secret<String>> {
+    value.filter(|value| !value.is_empty()).map(Secret::new)
+}
+
+/// Read the environment variable `name` into a secret, when set to a non empty unicode value.
+fn read_non_empty(name: &str) -> Option<Secret<String>> {
+    non_empty(env::var_os(name).and_then(|value| value.into_string().ok()))
+}
+
+#[cfg(feature = "scrub-env")]
+impl ApiAuth {
+    /// Read the credentials from the environment, then remove them from it, see the [credentials](./credentials/index.html) module.
+    ///
+    /// Fails with [ConfigError::MissingEnvVar](./error/enum.ConfigError.html#variant.MissingEnvVar) when a variable is not set or not valid unicode,
+    /// in which case the environment is left untouched.
+    pub fn from_env_scrubbed() -> Result<Self, ConfigError> {
+        let user_id = read(USER_ID_VAR)?;
+        let api_key = read(API_KEY_VAR)?;
+        env::remove_var(USER_ID_VAR);
+        env::remove_var(API_KEY_VAR);
+        Ok(ApiAuth { user_id, api_key })
+    }
+}
+
+#[cfg(feature = "scrub-env")]
+fn read(name: &'static str) -> Result<Secret<String>, ConfigError> {
+    env::var_os(name)
+        .and_then(|value| value.into_string().ok())
+        .map(Secret::new)
+        .ok_or(ConfigError::MissingEnvVar(name))
+}
+
+/// Read the credentials from the `user_id` and `api_key` string fields, e.g. of a configuration loaded with figment or config-rs.
+///
+/// Empty values are rejected. `ApiAuth` deliberately does not implement `Serialize`, so the credentials can't leak into a dump of the configuration.
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for ApiAuth {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use secrecy::ExposeSecret;
+
+        #[derive(Deserialize)]
+        struct Fields {
+            user_id: Secret<String>,
+            api_key: Secret<String>,
+        }
+
+        let Fields { user_id, api_key } = Fields::deserialize(deserializer)?;
+        for (name, value) in [("user_id", &user_id), ("api_key", &api_key)] {
+            if value.expose_secret().is_empty() {
+                return Err(serde::de::Error::custom(format!("{} is empty", name)));
+            }
+        }
+        Ok(ApiAuth { user_id, api_key })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use secrecy::ExposeSecret;
+
+    #[test]
+    fn test_debug_is_redacted() {
+        let api_auth = ApiAuth::new("debug-user".to_owned(), "debug-key".to_owned());
+        let debug = format!("{:?}", api_auth);
+        assert!(!debug.contains("debug-user"), "{}", debug);
+        assert!(!debug.contains("debug-key"), "{}", debug);
+        assert!(debug.contains("REDACTED"), "{}", debug);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_deserialize() {
+        #[derive(Deserialize)]
+        struct Config {
+            neutrino: ApiAuth,
+        }
+
+        struct TestingData {
+            pub name: String,
+            pub args: &'static str,
+            pub expected: Result<(&'static str, &'static str), &'static str>,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Using both credentials".to_owned(),
+                args: r#"{"neutrino": {"user_id": "json-user", "api_key": "json-key"}}"#,
+                expected: Ok(("json-user", "json-key")),
+            },
+            TestingData {
+                name: "Using an empty api key".to_owned(),
+                args: r#"{"neutrino": {"user_id": "json-user", "api_key": ""}}"#,
+                expected: Err("api_key is empty"),
+            },
+            TestingData {
+                name: "Using a missing user id".to_owned(),
+                args: r#"{"neutrino": {"api_key": "json-key"}}"#,
+                expected: Err("missing field `user_id`"),
+            },
+        ];
+
+        for test in tests {
+            let result = serde_json::from_str::<Config>(test.args);
+            match test.expected {
+                Ok((user_id, api_key)) => {
+                    let api_auth = result.unwrap().neutrino;
+                    assert_eq!(api_auth.user_id.expose_secret(), user_id, "{}", test.name);
+                    assert_eq!(api_auth.api_key.expose_secret(), api_key, "{}", test.name);
+                }
+                Err(expected) => {
+                    let err = result.err().unwrap().to_string();
+                    assert!(err.starts_with(expected), "{}: {}", test.name, err);
+                }
+            }
+        }
+    }
+
+    /// The string map a TOML or environment based configuration loader hands to `Deserialize`.
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_deserialize_from_config_map() {
+        use serde::de::{value::Error, value::MapDeserializer};
+
+        let fields = [("user_id", "toml-user"), ("api_key", "toml-key")];
+        let api_auth =
+            ApiAuth::deserialize(MapDeserializer::<_, Error>::new(fields.into_iter())).unwrap();
+        assert_eq!(api_auth.user_id.expose_secret(), "toml-user");
+        assert_eq!(api_auth.api_key.expose_secret(), "toml-key");
+
+        let fields = [("user_id", ""), ("api_key", "toml-key")];
+        let result = ApiAuth::deserialize(MapDeserializer::<_, Error>::new(fields.into_iter()));
+        assert_eq!(result.err().unwrap().to_string(), "user_id is empty");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_not_serializable() {
+        use std::marker::PhantomData;
+
+        trait NotSerialize {
+            fn is_serialize(&self) -> bool {
+                false
+            }
+        }
+        struct Probe<T>(PhantomData<T>);
+        impl<T> NotSerialize for Probe<T> {}
+        impl<T: serde::Serialize> Probe<T> {
+            fn is_serialize(&self) -> bool {
+                true
+            }
+        }
+
+        assert!(Probe::<String>(PhantomData).is_serialize());
+        assert!(!Probe::<ApiAuth>(PhantomData).is_serialize());
+    }
+
+    #[test]
+    fn test_from_env_with() {
+        env::set_var("NEUTRAL_TEST_ENV_USER_ID", "env-user");
+        env::set_var("NEUTRAL_TEST_ENV_API_KEY", "env-key");
+        let api_auth = ApiAuth::from_env_with("NEUTRAL_TEST_ENV").unwrap();
+        assert_eq!(api_auth.user_id.expose_secret(), "env-user");
+        assert_eq!(api_auth.api_key.expose_secret(), "env-key");
+    }
+
+    #[test]
+    fn test_from_env_with_missing_vars() {
+        struct TestingData {
+            pub name: String,
+            pub args: (&'static str, Option<&'static str>, Option<&'static str>),
+            pub expected: Vec<&'static str>,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Without any variable".to_owned(),
+                args: ("NEUTRAL_TEST_NONE", None, None),
+                expected: vec!["NEUTRAL_TEST_NONE_USER_ID", "NEUTRAL_TEST_NONE_API_KEY"],
+            },
+            TestingData {
+                name: "Without the api key".to_owned(),
+                args: ("NEUTRAL_TEST_NO_KEY", Some("user"), None),
+                expected: vec!["NEUTRAL_TEST_NO_KEY_API_KEY"],
+            },
+            TestingData {
+                name: "With an empty user id".to_owned(),
+                args: ("NEUTRAL_TEST_EMPTY", Some(""), Some("key")),
+                expected: vec!["NEUTRAL_TEST_EMPTY_USER_ID"],
+            },
+        ];
+
+        for test in &tests {
+            let (prefix, user_id, api_key) = test.args;
+            if let Some(user_id) = user_id {
+                env::set_var(format!("{}_USER_ID", prefix), user_id);
+            }
+            if let Some(api_key) = api_key {
+                env::set_var(format!("{}_API_KEY", prefix), api_key);
+            }
+            match ApiAuth::from_env_with(prefix) {
+                Err(ConfigError::MissingCredentials(missing)) => {
+                    assert_eq!(missing, test.expected, "{}", test.name)
+                }
+                other => panic!("{}: unexpected result {:?}", test.name, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_file() {
+        struct TestingData {
+            pub name: String,
+            pub args: (&'static str, &'static str),
+            pub expected: Result<(), Vec<&'static str>>,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Using a JSON file".to_owned(),
+                args: (
+                    "credentials.json",
+                    r#"{"user_id": "file-user", "api_key": "file-key"}"#,
+                ),
+                expected: Ok(()),
+            },
+            TestingData {
+                name: "Using a TOML file".to_owned(),
+                args: (
+                    "credentials.toml",
+                    "# neutrinoapi.com\nuser_id = \"file-user\"\napi_key = 'file-key'\n",
+                ),
+                expected: Ok(()),
+            },
+            TestingData {
+                name: "Using a file with an empty api key".to_owned(),
+                args: ("empty.json", r#"{"user_id": "file-user", "api_key": ""}"#),
+                expected: Err(vec!["api_key"]),
+            },
+        ];
+
+        let dir = env::temp_dir().join(format!("neutral-credentials-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for test in &tests {
+            let (file_name, contents) = test.args;
+            let path = dir.join(file_name);
+            std::fs::write(&path, contents).unwrap();
+
+            match (ApiAuth::from_file(&path), &test.expected) {
+                (Ok(api_auth), Ok(())) => {
+                    assert_eq!(
+                        api_auth.user_id.expose_secret(),
+                        "file-user",
+                        "{}",
+                        test.name
+                    );
+                    assert_eq!(
+                        api_auth.api_key.expose_secret(),
+                        "file-key",
+                        "{}",
+                        test.name
+                    );
+                }
+                (Err(Error::Config(ConfigError::MissingCredentials(missing))), Err(expected)) => {
+                    assert_eq!(&missing, expected, "{}", test.name)
+                }
+                (other, _) => panic!("{}: unexpected result {:?}", test.name, other),
+            }
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(
+            ApiAuth::from_file(dir.join("missing.json")),
+            Err(Error::Io(_))
+        ));
+    }
+
+    #[cfg(feature = "scrub-env")]
+    #[tokio::test]
+    async fn test_from_env_scrubbed() {
+        env::set_var(USER_ID_VAR, "scrubbed-user");
+        env::set_var(API_KEY_VAR, "scrubbed-key");
+
+        let api_auth = ApiAuth::from_env_scrubbed().unwrap();
+        assert!(env::var_os(USER_ID_VAR).is_none());
+        assert!(env::var_os(API_KEY_VAR).is_none());
+        assert!(matches!(
+            ApiAuth::from_env_scrubbed(),
+            Err(ConfigError::MissingEnvVar(USER_ID_VAR))
+        ));
+
+        let _m = mockito::mock("GET", "/ip-info")
+            .match_query(mockito::Matcher::Regex("ip=203.0.113.70$".into()))
+            .match_header("user-id", "scrubbed-user")
+            .match_header("api-key", "scrubbed-key")
+            .with_status(200)
+            .with_body(crate::fixtures::IP_INFO_BODY)
+            .expect(1)
+            .create();
+
+        let neutral = crate::Neutral::try_new(&mockito::server_url(), api_auth).unwrap();
+        neutral
+            .ip_info()
+            .send("203.0.113.70".parse().unwrap())
+            .await
+            .unwrap();
+        _m.assert();
+    }
+}
@@ -0,0 +1,493 @@
+//! # Decode module
+//! Choose how response bodies are parsed.
+//!
+//! Every endpoint decodes its response with the [JsonDecoder](./trait.JsonDecoder.html) registered with
+//! [NeutralBuilder::json_decoder](../builder/struct.NeutralBuilder.html#method.json_decoder).
+//! Without one, bodies are parsed by serde_json.
+//!
+//! The body is parsed into a [serde_json::Value](https://docs.rs/serde_json/1/serde_json/enum.Value.html), which is then converted to the typed response,
+//! so the keys of a response written in another casing can be renamed without parsing it again.
+//! A custom decoder parses the owned body into this value.
+//! It receives the body as owned [Bytes](https://docs.rs/bytes/1/bytes/struct.Bytes.html), which a parser requiring a mutable buffer copies into its own.
+//!
+//! With the `simd-json` feature, [SimdJsonDecoder](./struct.SimdJsonDecoder.html) parses the bodies with simd-json:
+//!
+//! ```ignore
+//! let neutral = Neutral::builder("https://neutrinoapi.net", api_auth)
+//!     .json_decoder(Arc::new(SimdJsonDecoder))
+//!     .build()?;
+//! ```
+
+use std::{fmt, sync::Arc};
+
+use http::{header::CONTENT_TYPE, response::Parts};
+use hyper::body::Bytes;
+use serde::de::{DeserializeOwned, Error as _};
+
+use crate::{
+    lenient::{self, Lenient},
+    warning::Warning,
+    Error,
+};
+
+/// Maximum size in bytes of the body excerpt of an [Error::Decode](../error/enum.Error.html#variant.Decode).
+pub const BODY_SNIPPET_LIMIT: usize = 512;
+
+/// Parse a response body.
+pub trait JsonDecoder: Send + Sync {
+    /// Parse `body` into a json value.
+    fn decode(
+        &self,
+        body: Bytes,
+    ) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// A [JsonDecoder](./trait.JsonDecoder.html) backed by serde_json.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SerdeJsonDecoder;
+
+impl JsonDecoder for SerdeJsonDecoder {
+    fn decode(
+        &self,
+        body: Bytes,
+    ) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(serde_json::from_slice(&body)?)
+    }
+}
+
+/// A [JsonDecoder](./trait.JsonDecoder.html) backed by simd-json.
+#[cfg(feature = "simd-json")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SimdJsonDecoder;
+
+#[cfg(feature = "simd-json")]
+impl JsonDecoder for SimdJsonDecoder {
+    fn decode(
+        &self,
+        body: Bytes,
+    ) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
+        // simd-json parses in place.
+        let mut buffer = body.to_vec();
+        Ok(simd_json::serde::from_slice(&mut buffer)?)
+    }
+}
+
+/// The decoder registered on a client.
+#[derive(Clone, Default)]
+pub(crate) struct Decoder(pub(crate) Option<Arc<dyn JsonDecoder>>);
+
+impl fmt::Debug for Decoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Decoder").field(&self.0.is_some()).finish()
+    }
+}
+
+impl Decoder {
+    /// Decode `body` into a typed response, renaming its camelCase and kebab-case keys to snake_case when the strict decoding fails.
+    ///
+    /// The body is parsed once. A failure of a custom decoder is reported as a serde_json error. The error of the strict decoding is returned when renaming doesn't help.
+    pub(crate) fn decode<T: DeserializeOwned>(&self, body: &Bytes) -> Result<T, serde_json::Error> {
+        let mut value = self.decode_value(body)?;
+        let err = match T::deserialize(&value) {
+            Ok(response) => return Ok(response),
+            Err(err) => err,
+        };
+        if !lenient::snake_case_keys(&mut value) {
+            return Err(err);
+        }
+        serde_json::from_value(value).map_err(|_| err)
+    }
+
+    /// Parse `body` into a json value.
+    fn decode_value(&self, body: &Bytes) -> Result<serde_json::Value, serde_json::Error> {
+        match &self.0 {
+            None => serde_json::from_slice(body),
+            Some(decoder) => decoder
+                .decode(body.clone())
+                .map_err(|err| serde_json::Error::custom(err.to_string())),
+        }
+    }
+
+    /// Decode `body` into a typed response, accepting camelCase and kebab-case keys, its numeric fields as json strings, its textual ones as json numbers
+    /// and the values unknown to its enums, reported as warnings.
+    ///
+    /// The body is parsed once, the conversion only runs when the strict decoding fails. The error of the strict decoding is returned when the conversion doesn't help.
+    pub(crate) fn decode_lenient<T: Lenient>(
+        &self,
+        body: &Bytes,
+    ) -> Result<(T, Vec<Warning>), serde_json::Error> {
+        let mut value = self.decode_value(body)?;
+        let err = match T::deserialize(&value) {
+            Ok(response) => return Ok((response, Vec::new())),
+            Err(err) => err,
+        };
+        let warnings = match lenient::normalize::<T>(&mut value) {
+            Some(warnings) => warnings,
+            None => return Err(err),
+        };
+        let response = serde_json::from_value(value).map_err(|_| err)?;
+        Ok((response, warnings))
+    }
+}
+
+/// Build the error of a response `body` of `endpoint` which can't be decoded.
+pub(crate) fn decode_error(
+    endpoint: &'static str,
+    source: serde_json::Error,
+    body: &[u8],
+) -> Error {
+    Error::Decode {
+        endpoint,
+        source,
+        body_snippet: body_snippet(body),
+    }
+}
+
+/// Returns `body` when it may be the JSON body of a successful response, with `parts` the status and headers of the response.
+///
+/// An empty body, or a body whose content type is set to anything else than JSON, such as the HTML page of a gateway, is an [Error::UnexpectedResponse](../error/enum.Error.html#variant.UnexpectedResponse).
+/// A JSON body of the wrong shape is left to the decoding of the endpoint.
+pub(crate) fn check_json(parts: &Parts, body: Bytes) -> Result<Bytes, Error> {
+    let content_type = parts
+        .headers
+        .get(CONTENT_TYPE)
+        .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned());
+    let is_json = content_type.as_deref().is_none_or(|content_type| {
+        let essence = content_type.split(';').next().unwrap_or_default().trim();
+        essence.eq_ignore_ascii_case("application/json")
+            || essence.to_ascii_lowercase().ends_with("+json")
+    });
+    let is_empty = body.iter().all(u8::is_ascii_whitespace);
+    if is_json && !is_empty {
+        return Ok(body);
+    }
+    Err(Error::UnexpectedResponse {
+        status: parts.status,
+        content_type,
+        body_snippet: body_snippet(&body),
+    })
+}
+
+/// Returns the first [BODY_SNIPPET_LIMIT] bytes of `body` at most, as lossy utf-8 cut on a char boundary.
+fn body_snippet(body: &[u8]) -> String {
+    // A char starting within the limit ends within the next 3 bytes.
+    let head = &body[..body.len().min(BODY_SNIPPET_LIMIT + 3)];
+    let mut snippet = String::from_utf8_lossy(head).into_owned();
+    let mut end = snippet.len().min(BODY_SNIPPET_LIMIT);
+    while !snippet.is_char_boundary(end) {
+        end -= 1;
+    }
+    snippet.truncate(end);
+    snippet
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{fixtures::*, ApiAuth, Neutral};
+    use http::StatusCode;
+    use mockito::{mock, Matcher};
+    use neutral_types::{
+        hlr_lookup::HlrLookupResponse, ip_blocklist::IpBlocklistResponse, ip_info::IpInfoResponse,
+        ip_probe::IpProbeResponse, phone_validate::PhoneValidateResponse,
+    };
+    use std::{
+        fmt::Debug,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    /// Returns the default decoder and a decoder using each [JsonDecoder] of the crate.
+    fn decoders() -> Vec<(&'static str, Decoder)> {
+        vec![
+            ("default", Decoder::default()),
+            ("serde_json", Decoder(Some(Arc::new(SerdeJsonDecoder)))),
+            #[cfg(feature = "simd-json")]
+            ("simd-json", Decoder(Some(Arc::new(SimdJsonDecoder)))),
+        ]
+    }
+
+    fn assert_identical<T: Lenient + PartialEq + Debug>(fixture: &'static str) {
+        let body = Bytes::from_static(fixture.as_bytes());
+        let expected: T = Decoder::default().decode(&body).unwrap();
+        for (name, decoder) in decoders() {
+            assert_eq!(decoder.decode::<T>(&body).unwrap(), expected, "{}", name);
+            let (lenient, warnings) = decoder.decode_lenient::<T>(&body).unwrap();
+            assert_eq!(lenient, expected, "{}", name);
+            assert!(warnings.is_empty(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_decoders_agree_on_fixtures() {
+        assert_identical::<IpInfoResponse>(IP_INFO_BODY);
+        assert_identical::<IpProbeResponse>(IP_PROBE_BODY);
+        assert_identical::<IpBlocklistResponse>(IP_BLOCKLIST_BODY);
+        assert_identical::<PhoneValidateResponse>(PHONE_VALIDATE_BODY);
+        assert_identical::<HlrLookupResponse>(HLR_LOOKUP_BODY);
+    }
+
+    #[test]
+    fn test_decoders_agree_on_lenient_bodies() {
+        let mut body: serde_json::Value = serde_json::from_str(IP_PROBE_BODY).unwrap();
+        body["as_age"] = "8".into();
+        body["asn"] = 12345.into();
+        body["provider_type"] = "something-new".into();
+        let body = Bytes::from(body.to_string());
+
+        let (expected, _) = Decoder::default()
+            .decode_lenient::<IpProbeResponse>(&body)
+            .unwrap();
+        for (name, decoder) in decoders() {
+            assert!(
+                decoder.decode::<IpProbeResponse>(&body).is_err(),
+                "{}",
+                name
+            );
+            let (response, warnings) = decoder.decode_lenient::<IpProbeResponse>(&body).unwrap();
+            assert_eq!(response, expected, "{}", name);
+            assert_eq!(warnings.len(), 1, "{}", name);
+            let truncated = Bytes::from_static(b"{\"ip\": ");
+            assert!(
+                decoder.decode::<IpProbeResponse>(&truncated).is_err(),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[derive(Default)]
+    struct CountingDecoder(AtomicUsize);
+
+    impl JsonDecoder for CountingDecoder {
+        fn decode(
+            &self,
+            body: Bytes,
+        ) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            SerdeJsonDecoder.decode(body)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_json_decoder_is_used_by_endpoints() {
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.90$".into()))
+            .with_status(200)
+            .with_body(IP_INFO_BODY)
+            .create();
+
+        let decoder = Arc::new(CountingDecoder::default());
+        let neutral = Neutral::builder(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .json_decoder(decoder.clone())
+        .build()
+        .unwrap();
+
+        neutral
+            .ip_info()
+            .send("203.0.113.90".parse().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(decoder.0.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_body_snippet() {
+        struct TestingData {
+            pub name: String,
+            pub args: String,
+            pub expected: String,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Short body".to_owned(),
+                args: r#"{"ip": "203.0.113.1"}"#.to_owned(),
+                expected: r#"{"ip": "203.0.113.1"}"#.to_owned(),
+            },
+            TestingData {
+                name: "Long body".to_owned(),
+                args: "a".repeat(BODY_SNIPPET_LIMIT * 2),
+                expected: "a".repeat(BODY_SNIPPET_LIMIT),
+            },
+            TestingData {
+                name: "Char across the limit".to_owned(),
+                args: format!("{}é{}", "a".repeat(BODY_SNIPPET_LIMIT - 1), "b".repeat(8)),
+                expected: "a".repeat(BODY_SNIPPET_LIMIT - 1),
+            },
+            TestingData {
+                name: "Char ending at the limit".to_owned(),
+                args: format!("{}🎉{}", "a".repeat(BODY_SNIPPET_LIMIT - 4), "b".repeat(8)),
+                expected: format!("{}🎉", "a".repeat(BODY_SNIPPET_LIMIT - 4)),
+            },
+        ];
+
+        for test in &tests {
+            assert_eq!(
+                body_snippet(test.args.as_bytes()),
+                test.expected,
+                "{}",
+                test.name
+            );
+        }
+        assert_eq!(
+            body_snippet(b"{\"ip\": \xff}"),
+            "{\"ip\": \u{fffd}}",
+            "Invalid utf-8"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_decode_error_context() {
+        let truncated = &IP_INFO_BODY[..IP_INFO_BODY.find("\"longitude\"").unwrap()];
+        let ip_info_body = truncated.replace("128.0.0.1", "203.0.113.217");
+        let _ip_info = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.217$".into()))
+            .with_status(200)
+            .with_header("x-secret-header", "not in the error")
+            .with_body(&ip_info_body)
+            .create();
+        let _email_verify = mock("GET", "/email-verify")
+            .match_query(Matcher::Regex("email=decode%40example.com".into()))
+            .with_status(200)
+            .with_body(r#"{"valid": "maybe"}"#)
+            .create();
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        let err = neutral
+            .ip_info()
+            .send("203.0.113.217".parse().unwrap())
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::Decode { endpoint: "ip-info", ref body_snippet, .. } if *body_snippet == ip_info_body),
+            "{:?}",
+            err
+        );
+        let message = err.to_string();
+        assert!(message.contains("ip-info"), "{}", message);
+        assert!(message.contains("\"ip\": \"203.0.113.217\""), "{}", message);
+        assert!(!message.contains("x-secret-header"), "{}", message);
+
+        let err = neutral
+            .email_verify()
+            .send("decode@example.com".to_owned())
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                err,
+                Error::Decode {
+                    endpoint: "email-verify",
+                    ..
+                }
+            ),
+            "{:?}",
+            err
+        );
+        let message = err.to_string();
+        assert!(message.contains("email-verify"), "{}", message);
+        assert!(message.contains(r#"{"valid": "maybe"}"#), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn test_unexpected_responses() {
+        struct TestingData {
+            pub name: String,
+            pub args: (&'static str, Option<&'static str>, String),
+            pub expected: Result<(), (Option<&'static str>, &'static str)>,
+        }
+
+        let maintenance = "<html><body>Down for maintenance</body></html>";
+        let tests = vec![
+            TestingData {
+                name: "Empty body".to_owned(),
+                args: ("203.0.113.218", Some("application/json"), String::new()),
+                expected: Err((Some("application/json"), "")),
+            },
+            TestingData {
+                name: "Html maintenance page".to_owned(),
+                args: ("203.0.113.219", Some("text/html"), maintenance.to_owned()),
+                expected: Err((Some("text/html"), maintenance)),
+            },
+            TestingData {
+                name: "Json body with a charset".to_owned(),
+                args: (
+                    "203.0.113.221",
+                    Some("application/json; charset=utf-8"),
+                    IP_INFO_BODY.to_owned(),
+                ),
+                expected: Ok(()),
+            },
+            TestingData {
+                name: "Json body without content type".to_owned(),
+                args: ("203.0.113.222", None, IP_INFO_BODY.to_owned()),
+                expected: Ok(()),
+            },
+        ];
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        for test in &tests {
+            let (ip, content_type, body) = &test.args;
+            let mut m = mock("GET", "/ip-info")
+                .match_query(Matcher::Regex(format!("ip={}$", ip)))
+                .with_status(200)
+                .with_body(body);
+            if let Some(content_type) = content_type {
+                m = m.with_header("content-type", content_type);
+            }
+            let _m = m.create();
+
+            let result = neutral.ip_info().send(ip.parse().unwrap()).await;
+            match (&result, test.expected) {
+                (Ok(_), Ok(())) => {}
+                (
+                    Err(Error::UnexpectedResponse {
+                        status,
+                        content_type,
+                        body_snippet,
+                    }),
+                    Err((expected_content_type, expected_snippet)),
+                ) => {
+                    assert_eq!(*status, StatusCode::OK, "{}", test.name);
+                    assert_eq!(
+                        content_type.as_deref(),
+                        expected_content_type,
+                        "{}",
+                        test.name
+                    );
+                    assert_eq!(body_snippet, expected_snippet, "{}", test.name);
+                }
+                _ => panic!("{}: {:?}", test.name, result),
+            }
+        }
+
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.223$".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"ip": "203.0.113.223"}"#)
+            .create();
+        let result = neutral
+            .ip_info()
+            .send("203.0.113.223".parse().unwrap())
+            .await;
+        assert!(
+            matches!(result, Err(Error::Decode { .. })),
+            "Json of the wrong shape: {:?}",
+            result
+        );
+    }
+}
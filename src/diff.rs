@@ -0,0 +1,248 @@
+//! # Diff module
+//! Detect the changes between two responses of the same endpoint, e.g. to alert when the classification of an ip address changes between two probes.
+//!
+//! Responses are compared field by field. Lists are compared as sets: a new member is reported with only a `new` value and a removed member with only an `old` value.
+//! Volatile fields, such as timestamps, are skipped by the `diff_*` functions, see the `*_VOLATILE` constants.
+//! [diff_with](./fn.diff_with.html) compares any serializable value with a custom ignore-list.
+//!
+//! ```ignore
+//! for change in diff_ip_blocklist(&yesterday, &today) {
+//!     alerting.send(serde_json::to_string(&change)?);
+//! }
+//! ```
+
+use neutral_types::{
+    ip_blocklist::IpBlocklistResponse, ip_info::IpInfoResponse, ip_probe::IpProbeResponse,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Volatile fields of an [IpBlocklistResponse](https://docs.rs/neutral_types/0.2.0/neutral_types/ip_blocklist/struct.IpBlocklistResponse.html).
+pub const IP_BLOCKLIST_VOLATILE: &[&str] = &["last_seen"];
+/// Volatile fields of an [IpInfoResponse](https://docs.rs/neutral_types/0.2.0/neutral_types/ip_info/struct.IpInfoResponse.html).
+pub const IP_INFO_VOLATILE: &[&str] = &["timezone.date", "timezone.time"];
+/// Volatile fields of an [IpProbeResponse](https://docs.rs/neutral_types/0.2.0/neutral_types/ip_probe/struct.IpProbeResponse.html).
+pub const IP_PROBE_VOLATILE: &[&str] = &[];
+
+/// A change of a single field, or of the membership of a list.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldChange {
+    /// Path of the field, nested fields are separated by a dot, e.g. `timezone.offset`.
+    pub path: String,
+    /// Previous value, `None` for a new list member or field.
+    pub old: Option<Value>,
+    /// Current value, `None` for a removed list member or field.
+    pub new: Option<Value>,
+}
+
+/// Returns the changes between two ip blocklist responses, skipping [IP_BLOCKLIST_VOLATILE](./constant.IP_BLOCKLIST_VOLATILE.html).
+pub fn diff_ip_blocklist(old: &IpBlocklistResponse, new: &IpBlocklistResponse) -> Vec<FieldChange> {
+    diff_with(old, new, IP_BLOCKLIST_VOLATILE)
+}
+
+/// Returns the changes between two ip info responses, skipping [IP_INFO_VOLATILE](./constant.IP_INFO_VOLATILE.html).
+pub fn diff_ip_info(old: &IpInfoResponse, new: &IpInfoResponse) -> Vec<FieldChange> {
+    diff_with(old, new, IP_INFO_VOLATILE)
+}
+
+/// Returns the changes between two ip probe responses, skipping [IP_PROBE_VOLATILE](./constant.IP_PROBE_VOLATILE.html).
+pub fn diff_ip_probe(old: &IpProbeResponse, new: &IpProbeResponse) -> Vec<FieldChange> {
+    diff_with(old, new, IP_PROBE_VOLATILE)
+}
+
+/// Returns the changes between `old` and `new`, skipping the fields whose path is in `ignore`, or nested in one of them.
+pub fn diff_with<T: Serialize>(old: &T, new: &T, ignore: &[&str]) -> Vec<FieldChange> {
+    let (Ok(old), Ok(new)) = (serde_json::to_value(old), serde_json::to_value(new)) else {
+        return Vec::new();
+    };
+    let mut changes = Vec::new();
+    diff_values(String::new(), &old, &new, ignore, &mut changes);
+    changes
+}
+
+fn diff_values(
+    path: String,
+    old: &Value,
+    new: &Value,
+    ignore: &[&str],
+    changes: &mut Vec<FieldChange>,
+) {
+    if is_ignored(&path, ignore) || old == new {
+        return;
+    }
+
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, old_value) in old {
+                let path = join(&path, key);
+                match new.get(key) {
+                    Some(new_value) => diff_values(path, old_value, new_value, ignore, changes),
+                    None if !is_ignored(&path, ignore) => changes.push(FieldChange {
+                        path,
+                        old: Some(old_value.clone()),
+                        new: None,
+                    }),
+                    None => {}
+                }
+            }
+            for (key, new_value) in new {
+                let path = join(&path, key);
+                if !old.contains_key(key) && !is_ignored(&path, ignore) {
+                    changes.push(FieldChange {
+                        path,
+                        old: None,
+                        new: Some(new_value.clone()),
+                    });
+                }
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            for removed in old.iter().filter(|member| !new.contains(member)) {
+                changes.push(FieldChange {
+                    path: path.clone(),
+                    old: Some(removed.clone()),
+                    new: None,
+                });
+            }
+            for added in new.iter().filter(|member| !old.contains(member)) {
+                changes.push(FieldChange {
+                    path: path.clone(),
+                    old: None,
+                    new: Some(added.clone()),
+                });
+            }
+        }
+        _ => changes.push(FieldChange {
+            path,
+            old: Some(old.clone()),
+            new: Some(new.clone()),
+        }),
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn is_ignored(path: &str, ignore: &[&str]) -> bool {
+    ignore.iter().any(|ignored| {
+        path.strip_prefix(ignored)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::IP_BLOCKLIST_BODY;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_ip_blocklist() {
+        struct TestingData {
+            pub name: String,
+            pub update: fn(&mut IpBlocklistResponse),
+            pub expected: Vec<FieldChange>,
+        }
+
+        let change = |path: &str, old: Option<Value>, new: Option<Value>| FieldChange {
+            path: path.to_owned(),
+            old,
+            new,
+        };
+
+        let tests = vec![
+            TestingData {
+                name: "No change".to_owned(),
+                update: |_| {},
+                expected: vec![],
+            },
+            TestingData {
+                name: "Volatile field ignored".to_owned(),
+                update: |response| response.last_seen = 1660000000,
+                expected: vec![],
+            },
+            TestingData {
+                name: "Single flag flip".to_owned(),
+                update: |response| response.is_vpn = true,
+                expected: vec![change("is_vpn", Some(json!(false)), Some(json!(true)))],
+            },
+            TestingData {
+                name: "Newly listed".to_owned(),
+                update: |response| {
+                    response.is_listed = true;
+                    response.list_count = 1;
+                    response.blocklists = vec!["tor".to_owned()];
+                },
+                expected: vec![
+                    change("blocklists", None, Some(json!("tor"))),
+                    change("is_listed", Some(json!(false)), Some(json!(true))),
+                    change("list_count", Some(json!(0)), Some(json!(1))),
+                ],
+            },
+        ];
+
+        let old: IpBlocklistResponse = serde_json::from_str(IP_BLOCKLIST_BODY).unwrap();
+        for test in &tests {
+            let mut new = old.clone();
+            (test.update)(&mut new);
+            let mut changes = diff_ip_blocklist(&old, &new);
+            changes.sort_by(|a, b| a.path.cmp(&b.path));
+            assert_eq!(changes, test.expected, "{}", test.name);
+        }
+    }
+
+    #[test]
+    fn test_diff_list_membership() {
+        let mut old: IpBlocklistResponse = serde_json::from_str(IP_BLOCKLIST_BODY).unwrap();
+        old.blocklists = vec!["spam".to_owned(), "tor".to_owned()];
+        let mut new = old.clone();
+        new.blocklists = vec!["tor".to_owned(), "malware".to_owned()];
+
+        let changes = diff_ip_blocklist(&old, &new);
+        assert_eq!(
+            changes,
+            vec![
+                FieldChange {
+                    path: "blocklists".to_owned(),
+                    old: Some(json!("spam")),
+                    new: None,
+                },
+                FieldChange {
+                    path: "blocklists".to_owned(),
+                    old: None,
+                    new: Some(json!("malware")),
+                },
+            ]
+        );
+        assert_eq!(
+            serde_json::to_string(&changes[1]).unwrap(),
+            r#"{"path":"blocklists","old":null,"new":"malware"}"#
+        );
+    }
+
+    #[test]
+    fn test_diff_with_nested_ignore() {
+        let old = json!({"timezone": {"date": "2022-08-01", "offset": "+02:00"}, "datetime": 1});
+        let new = json!({"timezone": {"date": "2022-08-02", "offset": "+01:00"}, "datetime": 2});
+        assert_eq!(
+            diff_with(&old, &new, &["timezone.date", "date"]),
+            vec![
+                FieldChange {
+                    path: "datetime".to_owned(),
+                    old: Some(json!(1)),
+                    new: Some(json!(2)),
+                },
+                FieldChange {
+                    path: "timezone.offset".to_owned(),
+                    old: Some(json!("+02:00")),
+                    new: Some(json!("+01:00")),
+                },
+            ]
+        );
+    }
+}
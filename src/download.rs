@@ -0,0 +1,57 @@
+//! # Download module
+//! Receive the large files of the download endpoints, such as the [bin list](../bin_list_download/index.html), without buffering them in memory.
+//!
+//! A download is a [ByteStream](./struct.ByteStream.html) of chunks, as they arrive from neutrinoapi.com.
+//! [ByteStream::write_to](./struct.ByteStream.html#method.write_to) streams it to a file, except on wasm32 which has no file system.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::Stream;
+#[cfg(not(target_arch = "wasm32"))]
+use futures::StreamExt;
+use hyper::{body::Bytes, Body};
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::io::AsyncWriteExt;
+
+use crate::Error;
+
+/// The body of a download, as a stream of chunks.
+#[derive(Debug)]
+pub struct ByteStream {
+    body: Body,
+}
+
+impl ByteStream {
+    pub(crate) fn new(body: Body) -> Self {
+        ByteStream { body }
+    }
+
+    /// Write the whole download to the file at `path`, replacing any existing file, and returns the number of bytes written.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn write_to<P: AsRef<Path>>(mut self, path: P) -> Result<u64, Error> {
+        let mut file = tokio::fs::File::create(path).await?;
+        let mut written = 0;
+        while let Some(chunk) = self.next().await {
+            let chunk = chunk?;
+            file.write_all(&chunk).await?;
+            written += chunk.len() as u64;
+        }
+        file.flush().await?;
+        Ok(written)
+    }
+}
+
+impl Stream for ByteStream {
+    type Item = Result<Bytes, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.body)
+            .poll_next(cx)
+            .map(|chunk| chunk.map(|chunk| chunk.map_err(Error::from)))
+    }
+}
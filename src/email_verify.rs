@@ -0,0 +1,346 @@
+//! # Email verify module
+//! Quoted from [neutrinoapi.com](https://www.neutrinoapi.com/api/email-verify):
+//!
+//! SMTP based email address verification. Verify real users and filter out low-quality email addresses.
+//!
+//! This API will connect to the mail server of the email address and perform a live SMTP handshake, checking that the mailbox exists and accepts mail. The whole round trip can take several seconds.
+
+use hyper::{body::Bytes, Body, Request};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::{context::CallContext, meta::ResponseMeta, params, pipeline::Fetched, Error, Neutral};
+
+#[cfg(test)]
+use mockito;
+
+/// Outcome of the SMTP handshake with the mail server of the address.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SmtpStatus {
+    /// The mail server accepted the address.
+    Ok,
+    /// The domain has no mail server.
+    Absent,
+    /// The mail server rejected the address.
+    Invalid,
+    /// The domain of the address does not resolve.
+    Unresolvable,
+    /// The mail server could not be reached or answered with an error.
+    Error,
+    /// Any other status, e.g. when the mail server deferred the handshake.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Response of email verify neutrinoapi.com endpoint
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct EmailVerifyResponse {
+    #[serde(alias = "valid", alias = "is_valid")]
+    pub is_valid: bool,
+    #[serde(alias = "verified", alias = "is_verified")]
+    pub is_verified: bool,
+    pub email: String,
+    pub domain: String,
+    pub provider: String,
+    pub is_freemail: bool,
+    pub is_disposable: bool,
+    pub is_personal: bool,
+    pub is_catch_all: bool,
+    /// True when the mail server deferred the handshake, typically greylisting.
+    pub is_deferred: bool,
+    pub syntax_error: bool,
+    pub domain_error: bool,
+    pub typos_fixed: bool,
+    pub smtp_status: SmtpStatus,
+    pub smtp_response: String,
+}
+
+/// Parameters of an email verify call, e.g. to persist a pending call and send it later with [EmailVerify::send_params].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmailVerifyParams {
+    pub email: String,
+    /// See [EmailVerify::fix_typos].
+    #[serde(default)]
+    pub fix_typos: bool,
+}
+
+pub struct EmailVerify<'a> {
+    pub(crate) neutral: &'a Neutral,
+    pub(crate) context: CallContext,
+    pub(crate) fix_typos: bool,
+}
+
+impl<'a> EmailVerify<'a> {
+    /// Attach a [CallContext](../context/struct.CallContext.html) to the calls.
+    pub fn context(mut self, context: CallContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Bound each attempt of the call by `timeout` instead of the client default, see [CallContext::timeout](../context/struct.CallContext.html#structfield.timeout).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.context.timeout = Some(timeout);
+        self
+    }
+
+    /// Let neutrinoapi.com fix common typos of the address before verifying it, `false` by default.
+    pub fn fix_typos(mut self, fix_typos: bool) -> Self {
+        self.fix_typos = fix_typos;
+        self
+    }
+
+    /// Send an email verify request to neutrinoapi.com
+    pub async fn send(&self, email: String) -> Result<EmailVerifyResponse, Error> {
+        self.send_with_meta(email)
+            .await
+            .map(|(_, response)| response)
+    }
+
+    /// Same as [send](#method.send), with the address and options of `params`, which replace the options set on the call.
+    pub async fn send_params(
+        &self,
+        params: &EmailVerifyParams,
+    ) -> Result<EmailVerifyResponse, Error> {
+        let call = EmailVerify {
+            neutral: self.neutral,
+            context: self.context.clone(),
+            fix_typos: params.fix_typos,
+        };
+        call.send(params.email.clone()).await
+    }
+
+    /// Same as [send](#method.send), also returning the [ResponseMeta](../meta/struct.ResponseMeta.html) of the response.
+    pub async fn send_with_meta(
+        &self,
+        email: String,
+    ) -> Result<(ResponseMeta, EmailVerifyResponse), Error> {
+        let fetched = self.fetch(email).await?;
+        let response: EmailVerifyResponse = self.neutral.decode("email-verify", &fetched.body)?;
+        Ok((fetched.meta, response))
+    }
+
+    /// Same as [send](#method.send), returning the raw body of the response.
+    pub async fn send_raw(&self, email: String) -> Result<Bytes, Error> {
+        Ok(self.fetch(email).await?.body)
+    }
+
+    /// Same as [send](#method.send), decoding the response into any `T`, e.g. a struct holding fields unknown to [EmailVerifyResponse].
+    pub async fn send_as<T: DeserializeOwned>(&self, email: String) -> Result<T, Error> {
+        let fetched = self.fetch(email).await?;
+        self.neutral.decode("email-verify", &fetched.body)
+    }
+
+    /// Same as [send](#method.send), decoding the response into a [serde_json::Value].
+    pub async fn send_value(&self, email: String) -> Result<serde_json::Value, Error> {
+        self.send_as(email).await
+    }
+
+    /// Send the request, without decoding its response.
+    async fn fetch(&self, email: String) -> Result<Fetched, Error> {
+        let canonical_input = format!("{}:{}", self.fix_typos, email);
+        self.neutral
+            .execute(
+                "email-verify",
+                Some(&canonical_input),
+                &self.context,
+                || build_request(self.neutral, &email, self.fix_typos, &self.context),
+            )
+            .await
+    }
+}
+
+/// Build the email verify request of `email`, without sending it.
+pub(crate) fn build_request(
+    neutral: &Neutral,
+    email: &str,
+    fix_typos: bool,
+    context: &CallContext,
+) -> Result<Request<Body>, Error> {
+    let params = params()
+        .append_pair("email", email)
+        .append_pair("fix-typos", &fix_typos.to_string())
+        .finish();
+    neutral.params_request("/email-verify", params, context)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ApiAuth;
+    use http::Method;
+    use mockito::{mock, Matcher};
+
+    fn body(email: &str, verified: bool, deferred: bool, smtp_status: &str) -> String {
+        format!(
+            r#"
+            {{
+                "valid": true,
+                "verified": {},
+                "email": "{}",
+                "domain": "example.com",
+                "provider": "example.com",
+                "is_freemail": false,
+                "is_disposable": false,
+                "is_personal": true,
+                "is_catch_all": false,
+                "is_deferred": {},
+                "syntax_error": false,
+                "domain_error": false,
+                "typos_fixed": false,
+                "smtp_status": "{}",
+                "smtp_response": "250 2.1.5 Ok"
+            }}"#,
+            verified, email, deferred, smtp_status
+        )
+    }
+
+    #[test]
+    fn test_email_verify_build_request() {
+        struct TestingData {
+            pub name: String,
+            pub args: (&'static str, bool),
+            pub expected: &'static str,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Using a plain address".to_owned(),
+                args: ("jane@example.com", false),
+                expected: "/email-verify?email=jane%40example.com&fix-typos=false",
+            },
+            TestingData {
+                name: "Using a sub-addressed address and fixing typos".to_owned(),
+                args: ("jane+news@example.com", true),
+                expected: "/email-verify?email=jane%2Bnews%40example.com&fix-typos=true",
+            },
+            TestingData {
+                name: "Using an address trying to inject a param".to_owned(),
+                args: ("jane@example.com&fix-typos=true", false),
+                expected:
+                    "/email-verify?email=jane%40example.com%26fix-typos%3Dtrue&fix-typos=false",
+            },
+        ];
+
+        let neutral = Neutral::try_new(
+            "https://neutrinoapi.net",
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        for test in &tests {
+            let (email, fix_typos) = test.args;
+            let request =
+                build_request(&neutral, email, fix_typos, &CallContext::default()).unwrap();
+            assert_eq!(request.method(), Method::GET, "{}", test.name);
+            assert_eq!(
+                request.uri().path_and_query().unwrap().as_str(),
+                test.expected,
+                "{}",
+                test.name
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_email_verify() {
+        struct TestingData {
+            pub name: String,
+            pub email: &'static str,
+            pub expected: (bool, bool, SmtpStatus),
+        }
+
+        let _mocks = [
+            mock("GET", "/email-verify")
+                .match_query(Matcher::Regex("email=verified%40example.com".into()))
+                .with_status(200)
+                .with_body(body("verified@example.com", true, false, "ok"))
+                .create(),
+            mock("GET", "/email-verify")
+                .match_query(Matcher::Regex("email=unverified%40example.com".into()))
+                .with_status(200)
+                .with_body(body("unverified@example.com", false, false, "invalid"))
+                .create(),
+            mock("GET", "/email-verify")
+                .match_query(Matcher::Regex("email=greylisted%40example.com".into()))
+                .with_status(200)
+                .with_body(body("greylisted@example.com", false, true, "greylisted"))
+                .create(),
+        ];
+
+        let tests = vec![
+            TestingData {
+                name: "Verified address".to_owned(),
+                email: "verified@example.com",
+                expected: (true, false, SmtpStatus::Ok),
+            },
+            TestingData {
+                name: "Unverified address".to_owned(),
+                email: "unverified@example.com",
+                expected: (false, false, SmtpStatus::Invalid),
+            },
+            TestingData {
+                name: "Greylisted address".to_owned(),
+                email: "greylisted@example.com",
+                expected: (false, true, SmtpStatus::Unknown),
+            },
+        ];
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        for test in &tests {
+            let response = neutral
+                .email_verify()
+                .send(test.email.to_owned())
+                .await
+                .unwrap();
+            assert_eq!(response.email, test.email, "{}", test.name);
+            assert_eq!(
+                (
+                    response.is_verified,
+                    response.is_deferred,
+                    response.smtp_status
+                ),
+                test.expected,
+                "{}",
+                test.name
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_email_verify_params() {
+        let params = EmailVerifyParams {
+            email: "verified@example.com".to_owned(),
+            fix_typos: true,
+        };
+        let json = serde_json::to_string(&params).unwrap();
+        assert_eq!(
+            serde_json::from_str::<EmailVerifyParams>(&json).unwrap(),
+            params
+        );
+        assert!(
+            !serde_json::from_str::<EmailVerifyParams>(r#"{"email": "verified@example.com"}"#)
+                .unwrap()
+                .fix_typos
+        );
+
+        let (neutral, requests) =
+            crate::fixtures::recording(&body("verified@example.com", true, false, "ok"));
+        neutral.email_verify().send_params(&params).await.unwrap();
+        neutral
+            .email_verify()
+            .fix_typos(true)
+            .send(params.email.clone())
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0], requests[1]);
+    }
+}
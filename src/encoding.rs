@@ -0,0 +1,206 @@
+//! Decompress the response bodies neutrinoapi.com compressed, with the `gzip` feature.
+//!
+//! Every buffered request accepts gzip and deflate bodies, which are decompressed before their status is handled, so the rest of the crate only sees plain bodies.
+//! A body without `content-encoding`, or with the `identity` one, passes through untouched. Streamed requests ask for an uncompressed body.
+
+use std::io::Read;
+
+use flate2::read::{MultiGzDecoder, ZlibDecoder};
+use http::{header::CONTENT_ENCODING, response::Parts};
+use hyper::body::Bytes;
+
+use crate::Error;
+
+/// Value of the `accept-encoding` header of the buffered requests.
+pub(crate) const ACCEPT_ENCODING: &str = "gzip, deflate";
+
+/// Returns `body` decoded according to the `content-encoding` header of `parts`.
+///
+/// Fails with [Error::Decompress] when the body is not valid for its encoding, and with [Error::BodyTooLarge] as soon as the decompressed body exceeds `limit` bytes.
+pub(crate) fn decompress(parts: &Parts, body: Bytes, limit: usize) -> Result<Bytes, Error> {
+    let content_encoding = match parts.headers.get(CONTENT_ENCODING) {
+        Some(value) => String::from_utf8_lossy(value.as_bytes())
+            .trim()
+            .to_ascii_lowercase(),
+        None => return Ok(body),
+    };
+    let reader: Box<dyn Read> = match content_encoding.as_str() {
+        "gzip" | "x-gzip" => Box::new(MultiGzDecoder::new(&body[..])),
+        "deflate" => Box::new(ZlibDecoder::new(&body[..])),
+        _ => return Ok(body),
+    };
+
+    let mut decompressed = Vec::new();
+    let read = reader
+        .take(limit as u64 + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(|source| Error::Decompress {
+            content_encoding: content_encoding.clone(),
+            source,
+        })?;
+    if read > limit {
+        return Err(Error::BodyTooLarge {
+            limit,
+            received_at_least: read,
+        });
+    }
+    Ok(Bytes::from(decompressed))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{fixtures::IP_INFO_BODY, ApiAuth, Neutral};
+    use flate2::{
+        write::{GzEncoder, ZlibEncoder},
+        Compression,
+    };
+    use mockito::{mock, Matcher};
+    use std::io::Write;
+
+    fn gzip(body: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn deflate(body: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn parts(content_encoding: Option<&str>) -> Parts {
+        let mut builder = http::Response::builder();
+        if let Some(content_encoding) = content_encoding {
+            builder = builder.header(CONTENT_ENCODING, content_encoding);
+        }
+        builder.body(()).unwrap().into_parts().0
+    }
+
+    #[test]
+    fn test_decompress() {
+        struct TestingData {
+            pub name: String,
+            pub args: (Option<&'static str>, Vec<u8>),
+            pub expected: Option<&'static [u8]>,
+        }
+
+        let body = br#"{"ip": "203.0.113.232"}"#;
+        let tests = vec![
+            TestingData {
+                name: "No content encoding".to_owned(),
+                args: (None, body.to_vec()),
+                expected: Some(body),
+            },
+            TestingData {
+                name: "Identity".to_owned(),
+                args: (Some("identity"), body.to_vec()),
+                expected: Some(body),
+            },
+            TestingData {
+                name: "Gzip".to_owned(),
+                args: (Some("gzip"), gzip(body)),
+                expected: Some(body),
+            },
+            TestingData {
+                name: "Gzip in upper case".to_owned(),
+                args: (Some(" GZIP "), gzip(body)),
+                expected: Some(body),
+            },
+            TestingData {
+                name: "Deflate".to_owned(),
+                args: (Some("deflate"), deflate(body)),
+                expected: Some(body),
+            },
+            TestingData {
+                name: "Corrupted gzip".to_owned(),
+                args: (Some("gzip"), body.to_vec()),
+                expected: None,
+            },
+            TestingData {
+                name: "Truncated gzip".to_owned(),
+                args: (Some("gzip"), gzip(body)[..12].to_vec()),
+                expected: None,
+            },
+        ];
+
+        for test in tests {
+            let (content_encoding, body) = test.args;
+            let result = decompress(&parts(content_encoding), Bytes::from(body), 1024);
+            match (result, test.expected) {
+                (Ok(body), Some(expected)) => assert_eq!(&body[..], expected, "{}", test.name),
+                (Err(Error::Decompress { .. }), None) => {}
+                (other, _) => panic!("unexpected {:?}: {}", other, test.name),
+            }
+        }
+    }
+
+    #[test]
+    fn test_decompress_is_bounded() {
+        let body = Bytes::from(gzip(&[b' '; 4096]));
+        let result = decompress(&parts(Some("gzip")), body.clone(), 1024);
+        assert!(matches!(
+            result,
+            Err(Error::BodyTooLarge {
+                limit: 1024,
+                received_at_least: 1025
+            })
+        ));
+        assert_eq!(
+            decompress(&parts(Some("gzip")), body, 4096).unwrap().len(),
+            4096
+        );
+    }
+
+    #[tokio::test]
+    async fn test_gzip_response() {
+        let _ok = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.232$".into()))
+            .match_header("accept-encoding", ACCEPT_ENCODING)
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_header("content-encoding", "gzip")
+            .with_body(gzip(IP_INFO_BODY.as_bytes()))
+            .expect(1)
+            .create();
+        let _corrupted = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.233$".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_header("content-encoding", "gzip")
+            .with_body(&gzip(IP_INFO_BODY.as_bytes())[..64])
+            .expect(1)
+            .create();
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        let ip_info_response = neutral
+            .ip_info()
+            .send("203.0.113.232".parse().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(ip_info_response.country_code, "AC");
+
+        let result = neutral
+            .ip_info()
+            .send("203.0.113.233".parse().unwrap())
+            .await;
+        match result {
+            Err(err @ Error::Decompress { .. }) => {
+                assert!(
+                    err.to_string().starts_with("the gzip response body"),
+                    "{}",
+                    err
+                )
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        _ok.assert();
+        _corrupted.assert();
+    }
+}
@@ -0,0 +1,96 @@
+//! # Endpoint module
+//! Identify the neutrinoapi.com endpoints supported by this crate.
+
+use std::fmt;
+
+/// A neutrinoapi.com endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Endpoint {
+    BinListDownload,
+    BinLookup,
+    EmailVerify,
+    HlrLookup,
+    HtmlRender,
+    ImageWatermark,
+    IpBlocklist,
+    IpBlocklistDownload,
+    IpInfo,
+    IpProbe,
+    PhoneValidate,
+    PhoneVerify,
+    SmsMessage,
+    SmsVerify,
+    UaLookup,
+    VerifySecurityCode,
+}
+
+impl Endpoint {
+    /// Every endpoint supported by this crate.
+    pub const ALL: [Endpoint; 16] = [
+        Endpoint::BinListDownload,
+        Endpoint::BinLookup,
+        Endpoint::EmailVerify,
+        Endpoint::HlrLookup,
+        Endpoint::HtmlRender,
+        Endpoint::ImageWatermark,
+        Endpoint::IpBlocklist,
+        Endpoint::IpBlocklistDownload,
+        Endpoint::IpInfo,
+        Endpoint::IpProbe,
+        Endpoint::PhoneValidate,
+        Endpoint::PhoneVerify,
+        Endpoint::SmsMessage,
+        Endpoint::SmsVerify,
+        Endpoint::UaLookup,
+        Endpoint::VerifySecurityCode,
+    ];
+
+    /// Returns the name of the endpoint, as used in its path, e.g. `ip-info`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Endpoint::BinListDownload => "bin-list-download",
+            Endpoint::BinLookup => "bin-lookup",
+            Endpoint::EmailVerify => "email-verify",
+            Endpoint::HlrLookup => "hlr-lookup",
+            Endpoint::HtmlRender => "html-render",
+            Endpoint::ImageWatermark => "image-watermark",
+            Endpoint::IpBlocklist => "ip-blocklist",
+            Endpoint::IpBlocklistDownload => "ip-blocklist-download",
+            Endpoint::IpInfo => "ip-info",
+            Endpoint::IpProbe => "ip-probe",
+            Endpoint::PhoneValidate => "phone-validate",
+            Endpoint::PhoneVerify => "phone-verify",
+            Endpoint::SmsMessage => "sms-message",
+            Endpoint::SmsVerify => "sms-verify",
+            Endpoint::UaLookup => "ua-lookup",
+            Endpoint::VerifySecurityCode => "verify-security-code",
+        }
+    }
+
+    /// Returns the endpoint named `name`, if supported by this crate.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|endpoint| endpoint.name() == name)
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_names_round_trip() {
+        for endpoint in Endpoint::ALL {
+            assert_eq!(Endpoint::from_name(endpoint.name()), Some(endpoint));
+        }
+        assert_eq!(Endpoint::from_name("unknown"), None);
+    }
+}
@@ -0,0 +1,1070 @@
+//! # Contains error types
+//! Contains all different type of errors that could possibly happen.
+
+use crate::{context::CallContext, phone_number::PhoneNumberError};
+use http::{header::RETRY_AFTER, HeaderMap, StatusCode, Uri};
+use serde::Deserialize;
+use std::{
+    fmt,
+    net::IpAddr,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::time::error::Elapsed;
+
+/// Error returned once the deadline of a call is passed, see [Error::Timeout](enum.Error.html#variant.Timeout).
+#[cfg(target_arch = "wasm32")]
+pub use crate::time::Elapsed;
+
+/// Represent the documented `api-error` codes of neutrinoapi.com.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiErrorCode {
+    /// The user id is missing or invalid.
+    InvalidUserId,
+    /// The api key is missing or invalid.
+    InvalidApiKey,
+    /// A parameter of the request is missing or invalid.
+    BadRequest,
+    /// The JSON body of the request is not valid.
+    InvalidJson,
+    /// The endpoint is not enabled for the plan of the account.
+    FeatureNotEnabled,
+    /// The soft limit set on the account is reached.
+    SoftLimitReached,
+    /// The daily limit of the plan of the account is exceeded.
+    DailyLimitExceeded,
+    /// The account has not enough credit left.
+    InsufficientCredit,
+    /// Any other code.
+    Other(u16),
+}
+
+impl From<u16> for ApiErrorCode {
+    fn from(code: u16) -> Self {
+        match code {
+            1 => ApiErrorCode::InvalidUserId,
+            2 => ApiErrorCode::InvalidApiKey,
+            3 => ApiErrorCode::BadRequest,
+            4 => ApiErrorCode::InvalidJson,
+            5 => ApiErrorCode::FeatureNotEnabled,
+            6 => ApiErrorCode::SoftLimitReached,
+            7 => ApiErrorCode::DailyLimitExceeded,
+            14 => ApiErrorCode::InsufficientCredit,
+            code => ApiErrorCode::Other(code),
+        }
+    }
+}
+
+/// Represent a generic error from neutrinoapi.com.
+///
+/// Its `Display` holds the status, the endpoint and the `api-error` payload, the raw body is only returned by [body](#method.body).
+#[derive(Debug)]
+pub struct NeutrinoError {
+    pub status_code: StatusCode,
+    /// The endpoint of the failed call, when known.
+    pub endpoint: Option<String>,
+    /// Raw body of the response.
+    pub error: String,
+    /// The `api-error` code of the body, when it is a JSON error payload.
+    pub api_error_code: Option<u16>,
+    /// The `api-error-msg` message of the body, when it is a JSON error payload.
+    pub api_error_msg: Option<String>,
+    /// Context of the failed call.
+    pub context: Box<CallContext>,
+}
+
+impl NeutrinoError {
+    /// Build the error of a response, parsing `body` when it is a JSON error payload.
+    pub(crate) fn new(status_code: StatusCode, body: &[u8], context: CallContext) -> Self {
+        #[derive(Deserialize)]
+        struct ApiError {
+            #[serde(rename = "api-error")]
+            code: Option<u16>,
+            #[serde(rename = "api-error-msg")]
+            msg: Option<String>,
+        }
+
+        let (api_error_code, api_error_msg) = match serde_json::from_slice::<ApiError>(body) {
+            Ok(api_error) => (api_error.code, api_error.msg),
+            Err(_) => (None, None),
+        };
+        NeutrinoError {
+            status_code,
+            endpoint: None,
+            error: String::from_utf8_lossy(body).into_owned(),
+            api_error_code,
+            api_error_msg,
+            context: Box::new(context),
+        }
+    }
+
+    /// Returns the typed `api-error` code of the body, when it is a JSON error payload.
+    pub fn api_error(&self) -> Option<ApiErrorCode> {
+        self.api_error_code.map(ApiErrorCode::from)
+    }
+
+    /// Returns the raw body of the response, which can be large.
+    pub fn body(&self) -> &str {
+        &self.error
+    }
+}
+
+/// Represent an invalid configuration of the client.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The uri would send credentials in cleartext while https is required.
+    InsecureTransport(Uri),
+    /// The uri of the proxy has no scheme or no authority.
+    IncompleteUri(Uri),
+    /// The environment variable is not set or not valid unicode.
+    MissingEnvVar(&'static str),
+    /// The listed credentials are not set, empty or not valid unicode.
+    MissingCredentials(Vec<String>),
+    /// The credentials file is neither valid JSON nor valid TOML.
+    InvalidCredentialsFile(PathBuf),
+    /// The header holds credentials and cannot be set as a default header.
+    ReservedHeader(http::header::HeaderName),
+    /// The listed settings cannot change once the client is built.
+    NotRuntimeMutable(Vec<&'static str>),
+    /// No uri was given to a client trying several hosts.
+    NoUri,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::InsecureTransport(uri) => {
+                write!(f, "{} would send the credentials in cleartext", uri)
+            }
+            ConfigError::IncompleteUri(uri) => write!(f, "{} has no scheme or no authority", uri),
+            ConfigError::MissingEnvVar(name) => {
+                write!(f, "the environment variable {} is not set", name)
+            }
+            ConfigError::MissingCredentials(names) => {
+                write!(f, "missing credentials: {}", names.join(", "))
+            }
+            ConfigError::InvalidCredentialsFile(path) => {
+                write!(f, "{} is neither valid JSON nor valid TOML", path.display())
+            }
+            ConfigError::ReservedHeader(name) => {
+                write!(f, "the {} header holds credentials", name)
+            }
+            ConfigError::NotRuntimeMutable(settings) => write!(
+                f,
+                "{} cannot change once the client is built",
+                settings.join(", ")
+            ),
+            ConfigError::NoUri => write!(f, "no uri was given"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Represent a connection to neutrinoapi.com, or to the proxy, which could not be established.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum ConnectError {
+    /// The host name can't be resolved, see the [resolve](../resolve/index.html) module.
+    Resolve {
+        host: String,
+        source: Arc<dyn std::error::Error + Send + Sync>,
+    },
+    /// The connection was not established within the timeout set by [NeutralBuilder::connect_timeout](../builder/struct.NeutralBuilder.html#method.connect_timeout).
+    Timeout { timeout: Duration },
+}
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectError::Resolve { host, source } => {
+                write!(f, "cannot resolve {}: {}", host, source)
+            }
+            ConnectError::Timeout { timeout } => {
+                write!(f, "no connection established within {:?}", timeout)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConnectError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConnectError::Resolve { source, .. } => Some(source.as_ref()),
+            ConnectError::Timeout { .. } => None,
+        }
+    }
+}
+
+/// The error of a tower service, see [NeutralBuilder::service](../builder/struct.NeutralBuilder.html#method.service).
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Represent the to level error of the neutral crate.
+///
+/// Its `Display` never holds a whole response body, see [response_body](#method.response_body).
+/// The underlying error of a transport, decoding or configuration failure is its [source](#method.source).
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    Hyper(hyper::Error),
+    /// The connection to neutrinoapi.com could not be established, no request was sent.
+    ///
+    /// Other connection failures, e.g. a refused connection, are returned as [Error::Hyper](#variant.Hyper).
+    Connect(ConnectError),
+    /// The tower service sending the requests failed, with an error which is neither a hyper error nor a timeout.
+    ///
+    /// The errors of a reqwest client, see [Neutral::from_reqwest](../struct.Neutral.html#method.from_reqwest), are returned as this variant too.
+    Service(BoxError),
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    /// A response of `endpoint` can't be decoded into its typed response.
+    Decode {
+        endpoint: &'static str,
+        source: serde_json::Error,
+        /// The start of the body, at most [BODY_SNIPPET_LIMIT](../decode/constant.BODY_SNIPPET_LIMIT.html) bytes, without the headers of the response.
+        body_snippet: String,
+    },
+    /// A successful response has an empty body or a content type other than JSON, e.g. the HTML page of a gateway.
+    UnexpectedResponse {
+        status: StatusCode,
+        content_type: Option<String>,
+        /// The start of the body, at most [BODY_SNIPPET_LIMIT](../decode/constant.BODY_SNIPPET_LIMIT.html) bytes.
+        body_snippet: String,
+    },
+    /// A response body exceeds the limit set by [NeutralBuilder::max_response_size](../builder/struct.NeutralBuilder.html#method.max_response_size), it was abandoned.
+    BodyTooLarge {
+        limit: usize,
+        /// Number of bytes received or announced by the `content-length` header when the body was abandoned.
+        received_at_least: usize,
+    },
+    /// A response body compressed with `content_encoding` can't be decompressed, e.g. a truncated or corrupted gzip stream.
+    Decompress {
+        content_encoding: String,
+        source: std::io::Error,
+    },
+    Timeout(Elapsed),
+    Neutrino(NeutrinoError),
+    /// neutrinoapi.com rejected the provided credentials (HTTP 401 or 403).
+    Unauthorized(NeutrinoError),
+    /// The endpoint requires an add-on which is not enabled for the plan of the account.
+    FeatureNotEnabled {
+        endpoint: String,
+        error: NeutrinoError,
+    },
+    /// The account has not enough credit left.
+    InsufficientCredit(NeutrinoError),
+    /// The quota of the account is exceeded (HTTP 402, or 403 with a daily or soft limit `api-error` code).
+    QuotaExceeded(NeutrinoError),
+    /// neutrinoapi.com throttled the request (HTTP 429).
+    RateLimited {
+        /// The delay requested by the `Retry-After` header, given in seconds or as an HTTP date.
+        retry_after: Option<Duration>,
+        error: NeutrinoError,
+    },
+    /// neutrinoapi.com failed to handle the request (HTTP 5xx).
+    ServerError(NeutrinoError),
+    InvalidUri(http::uri::InvalidUri),
+    /// The base uri or a failover uri of the client can't prefix the uri of a request: it has no scheme, a scheme other than http or https, or no authority.
+    InvalidBaseUri {
+        uri: String,
+        reason: &'static str,
+    },
+    Http(http::Error),
+    Config(ConfigError),
+    /// The phone number was rejected before any request.
+    InvalidPhoneNumber(PhoneNumberError),
+    /// The ip address is not routable on the internet and bogons are rejected, no request was sent, see the [bogon](../bogon/index.html) module.
+    BogonAddress(IpAddr),
+    /// A free text input is larger than the limit of its endpoint, no request was sent.
+    InputTooLarge {
+        len: usize,
+        limit: usize,
+    },
+    /// A response still redirects after [NeutralBuilder::max_redirects](../builder/struct.NeutralBuilder.html#method.max_redirects) redirects.
+    TooManyRedirects {
+        max_redirects: usize,
+        /// The uri the last redirect points to.
+        location: String,
+    },
+    /// The circuit breaker of the endpoint is open, no request was sent.
+    CircuitOpen {
+        endpoint: String,
+    },
+    /// A line of the ip blocklist file cannot be parsed, see the [blocklist](../blocklist/index.html) module.
+    MalformedBlocklistLine {
+        /// Number of the line, starting at 1.
+        line: usize,
+        reason: String,
+    },
+    /// A background task was required but the configured spawner cannot spawn it.
+    SpawnUnavailable,
+    /// In replay mode, no fixture matches the request, identified by its method, path and sorted query, see the [cassette](../cassette/index.html) module.
+    #[cfg(feature = "record-replay")]
+    NoFixture {
+        request: String,
+    },
+    /// The cancellation token of the [CallContext](../context/struct.CallContext.html) of the call was cancelled.
+    Cancelled,
+}
+
+impl Error {
+    /// Returns true when sending the same request again may succeed, the predicate used by the retry stage of the [pipeline](../pipeline/index.html).
+    ///
+    /// Connection failures and resets, timeouts, rate limits and server errors are retryable.
+    /// Errors caused by the request itself or by the account, such as a feature not enabled for the plan or an insufficient credit, are permanent.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Hyper(err) => {
+                err.is_connect() || err.is_incomplete_message() || is_connection_reset(err)
+            }
+            Error::Connect(_)
+            | Error::Timeout(_)
+            | Error::RateLimited { .. }
+            | Error::ServerError(_) => true,
+            #[cfg(feature = "reqwest-backend")]
+            Error::Service(err) => crate::transport::is_reqwest_retryable(err),
+            #[cfg(target_arch = "wasm32")]
+            Error::Service(err) => err.is::<crate::transport::FetchError>(),
+            _ => false,
+        }
+    }
+
+    /// Returns true when neutrinoapi.com rejected the credentials, see [Error::Unauthorized](#variant.Unauthorized).
+    pub fn is_auth_error(&self) -> bool {
+        matches!(self, Error::Unauthorized(_))
+    }
+
+    /// Returns true when the account can't send more calls until its plan or its credit changes,
+    /// see [Error::QuotaExceeded](#variant.QuotaExceeded) and [Error::InsufficientCredit](#variant.InsufficientCredit).
+    ///
+    /// A rate limited call is not a quota error: it is [retryable](#method.is_retryable).
+    pub fn is_quota_error(&self) -> bool {
+        matches!(self, Error::QuotaExceeded(_) | Error::InsufficientCredit(_))
+    }
+
+    /// Returns the HTTP status of the response, when the call failed on a response.
+    pub fn status_code(&self) -> Option<StatusCode> {
+        match self {
+            Error::UnexpectedResponse { status, .. } => Some(*status),
+            _ => self.neutrino_error().map(|err| err.status_code),
+        }
+    }
+
+    /// Returns the `api-error` code of the response, when neutrinoapi.com answered with a JSON error payload.
+    pub fn api_error_code(&self) -> Option<u16> {
+        self.neutrino_error().and_then(|err| err.api_error_code)
+    }
+
+    /// Returns the raw body of the error response, when neutrinoapi.com answered with one.
+    pub fn response_body(&self) -> Option<&str> {
+        self.neutrino_error().map(NeutrinoError::body)
+    }
+
+    /// Returns the context of the failed call, when neutrinoapi.com answered with an error.
+    pub fn context(&self) -> Option<&CallContext> {
+        self.neutrino_error().map(|err| err.context.as_ref())
+    }
+
+    /// Returns the error response of neutrinoapi.com, when it answered with one.
+    fn neutrino_error(&self) -> Option<&NeutrinoError> {
+        match self {
+            Error::Neutrino(err)
+            | Error::Unauthorized(err)
+            | Error::FeatureNotEnabled { error: err, .. }
+            | Error::InsufficientCredit(err)
+            | Error::QuotaExceeded(err)
+            | Error::RateLimited { error: err, .. }
+            | Error::ServerError(err) => Some(err),
+            _ => None,
+        }
+    }
+
+    /// Build the error of a non successful response of `endpoint`, from its `headers` and `err`.
+    pub(crate) fn from_response(
+        endpoint: &str,
+        headers: &HeaderMap,
+        mut err: NeutrinoError,
+    ) -> Self {
+        err.endpoint = Some(endpoint.to_owned());
+        match err.api_error() {
+            Some(ApiErrorCode::FeatureNotEnabled) => Error::FeatureNotEnabled {
+                endpoint: endpoint.to_owned(),
+                error: err,
+            },
+            Some(ApiErrorCode::InsufficientCredit) => Error::InsufficientCredit(err),
+            _ if err.status_code == StatusCode::TOO_MANY_REQUESTS => Error::RateLimited {
+                retry_after: headers
+                    .get(RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| parse_retry_after(value, crate::time::now())),
+                error: err,
+            },
+            _ => err.into(),
+        }
+    }
+}
+
+impl fmt::Display for NeutrinoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "neutrinoapi.com answered {}", self.status_code)?;
+        if let Some(endpoint) = &self.endpoint {
+            write!(f, " to {}", endpoint)?;
+        }
+        match (self.api_error_code, &self.api_error_msg) {
+            (Some(code), Some(msg)) => write!(f, ", api-error {}: {}", code, msg),
+            (Some(code), None) => write!(f, ", api-error {}", code),
+            (None, _) => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for NeutrinoError {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Hyper(err) => write!(f, "transport error: {}", err),
+            Error::Connect(err) => write!(f, "cannot connect: {}", err),
+            Error::Service(err) => write!(f, "service error: {}", err),
+            Error::Io(err) => write!(f, "io error: {}", err),
+            Error::Json(err) => write!(f, "json error: {}", err),
+            Error::Decode {
+                endpoint,
+                source,
+                body_snippet,
+            } => write!(
+                f,
+                "cannot decode the response of {}: {}, body: {}",
+                endpoint, source, body_snippet
+            ),
+            Error::UnexpectedResponse {
+                status,
+                content_type,
+                body_snippet,
+            } => write!(
+                f,
+                "unexpected {} response with content type {}, body: {}",
+                status,
+                content_type.as_deref().unwrap_or("none"),
+                body_snippet
+            ),
+            Error::BodyTooLarge {
+                limit,
+                received_at_least,
+            } => write!(
+                f,
+                "the response body of at least {} bytes exceeds the limit of {} bytes",
+                received_at_least, limit
+            ),
+            Error::Decompress {
+                content_encoding,
+                source,
+            } => write!(
+                f,
+                "the {} response body can't be decompressed: {}",
+                content_encoding, source
+            ),
+            Error::Timeout(_) => write!(f, "the request timed out"),
+            Error::Neutrino(err) | Error::ServerError(err) => err.fmt(f),
+            Error::Unauthorized(err) => write!(f, "unauthorized: {}", err),
+            Error::FeatureNotEnabled { endpoint, error } => {
+                write!(f, "{} is not enabled for the plan: {}", endpoint, error)
+            }
+            Error::InsufficientCredit(err) => write!(f, "insufficient credit: {}", err),
+            Error::QuotaExceeded(err) => write!(f, "quota exceeded: {}", err),
+            Error::RateLimited { retry_after, error } => match retry_after {
+                Some(retry_after) => {
+                    write!(f, "rate limited for {:?}: {}", retry_after, error)
+                }
+                None => write!(f, "rate limited: {}", error),
+            },
+            Error::InvalidUri(err) => write!(f, "invalid uri: {}", err),
+            Error::InvalidBaseUri { uri, reason } => {
+                write!(f, "invalid base uri {:?}: {}", uri, reason)
+            }
+            Error::Http(err) => write!(f, "invalid request: {}", err),
+            Error::Config(err) => write!(f, "invalid configuration: {}", err),
+            Error::InvalidPhoneNumber(err) => write!(f, "invalid phone number: {}", err),
+            Error::BogonAddress(ip_addr) => write!(f, "{} is a bogon address", ip_addr),
+            Error::InputTooLarge { len, limit } => {
+                write!(
+                    f,
+                    "input of {} bytes exceeds the limit of {} bytes",
+                    len, limit
+                )
+            }
+            Error::TooManyRedirects {
+                max_redirects,
+                location,
+            } => write!(
+                f,
+                "still redirected to {} after {} redirects",
+                location, max_redirects
+            ),
+            Error::CircuitOpen { endpoint } => {
+                write!(f, "the circuit breaker of {} is open", endpoint)
+            }
+            Error::MalformedBlocklistLine { line, reason } => {
+                write!(f, "malformed blocklist line {}: {}", line, reason)
+            }
+            Error::SpawnUnavailable => write!(f, "no spawner can run the background task"),
+            #[cfg(feature = "record-replay")]
+            Error::NoFixture { request } => write!(f, "no fixture matches {}", request),
+            Error::Cancelled => write!(f, "the call was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Hyper(err) => Some(err),
+            Error::Connect(err) => Some(err),
+            Error::Service(err) => Some(err.as_ref()),
+            Error::Io(err) | Error::Decompress { source: err, .. } => Some(err),
+            Error::Json(err) | Error::Decode { source: err, .. } => Some(err),
+            Error::Timeout(err) => Some(err),
+            Error::InvalidUri(err) => Some(err),
+            Error::Http(err) => Some(err),
+            Error::Config(err) => Some(err),
+            Error::InvalidPhoneNumber(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Parse the `value` of a `Retry-After` header received at `now`, either a number of seconds or an HTTP date.
+///
+/// A date in the past is a zero delay.
+fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(now).unwrap_or_default())
+}
+
+/// Returns true when the connection was closed by neutrinoapi.com while the request was sent.
+pub(crate) fn is_connection_reset(err: &hyper::Error) -> bool {
+    use std::io::ErrorKind;
+
+    std::error::Error::source(err)
+        .and_then(|source| source.downcast_ref::<std::io::Error>())
+        .is_some_and(|err| {
+            matches!(
+                err.kind(),
+                ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted | ErrorKind::BrokenPipe
+            )
+        })
+}
+
+impl From<hyper::Error> for Error {
+    fn from(err: hyper::Error) -> Self {
+        // The connector reports a resolution failure or a connect timeout as the cause of a hyper error.
+        let connect = std::error::Error::source(&err)
+            .and_then(|source| source.downcast_ref::<ConnectError>())
+            .filter(|_| err.is_connect())
+            .cloned();
+        match connect {
+            Some(err) => Self::Connect(err),
+            None => Self::Hyper(err),
+        }
+    }
+}
+
+impl From<ConnectError> for Error {
+    fn from(err: ConnectError) -> Self {
+        Self::Connect(err)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<NeutrinoError> for Error {
+    fn from(err: NeutrinoError) -> Self {
+        let is_quota = matches!(
+            err.api_error(),
+            Some(ApiErrorCode::DailyLimitExceeded | ApiErrorCode::SoftLimitReached)
+        );
+        match err.status_code {
+            StatusCode::PAYMENT_REQUIRED => Self::QuotaExceeded(err),
+            StatusCode::FORBIDDEN if is_quota => Self::QuotaExceeded(err),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Self::Unauthorized(err),
+            StatusCode::TOO_MANY_REQUESTS => Self::RateLimited {
+                retry_after: None,
+                error: err,
+            },
+            status_code if status_code.is_server_error() => Self::ServerError(err),
+            _ => Self::Neutrino(err),
+        }
+    }
+}
+
+impl From<std::convert::Infallible> for Error {
+    fn from(err: std::convert::Infallible) -> Self {
+        match err {}
+    }
+}
+
+impl From<PhoneNumberError> for Error {
+    fn from(err: PhoneNumberError) -> Self {
+        Self::InvalidPhoneNumber(err)
+    }
+}
+
+impl From<ConfigError> for Error {
+    fn from(err: ConfigError) -> Self {
+        Self::Config(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Json(err)
+    }
+}
+
+impl From<Elapsed> for Error {
+    fn from(err: Elapsed) -> Self {
+        Self::Timeout(err)
+    }
+}
+
+impl From<http::uri::InvalidUri> for Error {
+    fn from(err: http::uri::InvalidUri) -> Self {
+        Self::InvalidUri(err)
+    }
+}
+
+impl From<http::Error> for Error {
+    fn from(err: http::Error) -> Self {
+        Self::Http(err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ApiAuth, Neutral};
+    use mockito::{mock, Matcher};
+
+    #[test]
+    fn test_neutrino_error_new() {
+        struct TestingData {
+            pub name: String,
+            pub args: &'static str,
+            pub expected: (Option<u16>, Option<&'static str>, Option<ApiErrorCode>),
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Using a JSON error payload".to_owned(),
+                args: r#"{"api-error": 7, "api-error-msg": "DAILY API LIMIT EXCEEDED"}"#,
+                expected: (
+                    Some(7),
+                    Some("DAILY API LIMIT EXCEEDED"),
+                    Some(ApiErrorCode::DailyLimitExceeded),
+                ),
+            },
+            TestingData {
+                name: "Using an undocumented code".to_owned(),
+                args: r#"{"api-error": 42, "api-error-msg": "UNKNOWN"}"#,
+                expected: (Some(42), Some("UNKNOWN"), Some(ApiErrorCode::Other(42))),
+            },
+            TestingData {
+                name: "Using a plain text body".to_owned(),
+                args: "bad gateway",
+                expected: (None, None, None),
+            },
+            TestingData {
+                name: "Using an empty body".to_owned(),
+                args: "",
+                expected: (None, None, None),
+            },
+        ];
+
+        for test in &tests {
+            let err = NeutrinoError::new(
+                StatusCode::BAD_REQUEST,
+                test.args.as_bytes(),
+                CallContext::default(),
+            );
+            let (code, msg, api_error) = test.expected;
+            assert_eq!(err.api_error_code, code, "{}", test.name);
+            assert_eq!(err.api_error_msg.as_deref(), msg, "{}", test.name);
+            assert_eq!(err.api_error(), api_error, "{}", test.name);
+            assert_eq!(err.error, test.args, "{}", test.name);
+        }
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        struct TestingData {
+            pub name: String,
+            pub args: &'static str,
+            pub expected: Option<Duration>,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Using delta-seconds".to_owned(),
+                args: "120",
+                expected: Some(Duration::from_secs(120)),
+            },
+            TestingData {
+                name: "Using an HTTP date".to_owned(),
+                args: "Sun, 06 Nov 1994 08:49:37 GMT",
+                expected: Some(Duration::from_secs(90)),
+            },
+            TestingData {
+                name: "Using an HTTP date in the past".to_owned(),
+                args: "Sun, 06 Nov 1994 08:46:00 GMT",
+                expected: Some(Duration::ZERO),
+            },
+            TestingData {
+                name: "Using a negative number".to_owned(),
+                args: "-5",
+                expected: None,
+            },
+            TestingData {
+                name: "Using garbage".to_owned(),
+                args: "soon",
+                expected: None,
+            },
+        ];
+
+        // Sun, 06 Nov 1994 08:48:07 GMT
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(784111687);
+        for test in &tests {
+            assert_eq!(
+                parse_retry_after(test.args, now),
+                test.expected,
+                "{}",
+                test.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_error_display() {
+        struct TestingData {
+            pub name: String,
+            pub args: Error,
+            pub expected: &'static str,
+        }
+
+        let large_body = format!(
+            r#"{{"api-error": 2, "api-error-msg": "INVALID API KEY", "padding": "{}"}}"#,
+            "x".repeat(4096)
+        );
+        let response = |status: StatusCode, body: &str| {
+            NeutrinoError::new(status, body.as_bytes(), CallContext::default())
+        };
+        let tests = vec![
+            TestingData {
+                name: "Using a rejected api key".to_owned(),
+                args: Error::from_response(
+                    "ip-info",
+                    &HeaderMap::new(),
+                    response(StatusCode::FORBIDDEN, &large_body),
+                ),
+                expected: "unauthorized: neutrinoapi.com answered 403 Forbidden to ip-info, api-error 2: INVALID API KEY",
+            },
+            TestingData {
+                name: "Using a plain text error".to_owned(),
+                args: Error::from_response(
+                    "hlr-lookup",
+                    &HeaderMap::new(),
+                    response(StatusCode::BAD_REQUEST, "bad request"),
+                ),
+                expected: "neutrinoapi.com answered 400 Bad Request to hlr-lookup",
+            },
+            TestingData {
+                name: "Using an error of an unknown endpoint".to_owned(),
+                args: response(StatusCode::BAD_GATEWAY, "").into(),
+                expected: "neutrinoapi.com answered 502 Bad Gateway",
+            },
+            TestingData {
+                name: "Using a connect timeout".to_owned(),
+                args: ConnectError::Timeout {
+                    timeout: Duration::from_secs(2),
+                }
+                .into(),
+                expected: "cannot connect: no connection established within 2s",
+            },
+            TestingData {
+                name: "Using an invalid phone number".to_owned(),
+                args: PhoneNumberError::TooLong.into(),
+                expected: "invalid phone number: the number has more than 15 digits",
+            },
+            TestingData {
+                name: "Using an invalid configuration".to_owned(),
+                args: Error::Config(ConfigError::MissingEnvVar("NEUTRINO_API_KEY")),
+                expected: "invalid configuration: the environment variable NEUTRINO_API_KEY is not set",
+            },
+        ];
+
+        for test in &tests {
+            assert_eq!(test.args.to_string(), test.expected, "{}", test.name);
+        }
+        assert_eq!(tests[0].args.response_body(), Some(large_body.as_str()));
+        assert_eq!(tests[3].args.response_body(), None);
+    }
+
+    #[tokio::test]
+    async fn test_error_source_chain() {
+        use std::error::Error as _;
+
+        let err: Error = serde_json::from_str::<u16>("\"x\"").unwrap_err().into();
+        let source = err.source().unwrap();
+        assert!(source.downcast_ref::<serde_json::Error>().is_some());
+
+        let err = Error::Decode {
+            endpoint: "ip-info",
+            source: serde_json::from_str::<u16>("[]").unwrap_err(),
+            body_snippet: "[]".to_owned(),
+        };
+        assert!(err
+            .source()
+            .unwrap()
+            .downcast_ref::<serde_json::Error>()
+            .is_some());
+
+        let err: Error = hyper::Client::new()
+            .get("http://127.0.0.1:1".parse().unwrap())
+            .await
+            .unwrap_err()
+            .into();
+        let mut chain = Vec::new();
+        let mut source = err.source();
+        while let Some(err) = source {
+            chain.push(err.to_string());
+            source = err.source();
+        }
+        assert!(err.source().unwrap().is::<hyper::Error>());
+        // The hyper error is caused by the io error of the connection.
+        assert!(chain.len() >= 2, "{:?}", chain);
+
+        let err: Error = PhoneNumberError::Empty.into();
+        assert!(err.source().unwrap().is::<PhoneNumberError>());
+    }
+
+    #[tokio::test]
+    async fn test_error_classification() {
+        struct TestingData {
+            pub name: String,
+            pub args: Error,
+            /// Retryable, auth error, quota error, status code and api error code.
+            pub expected: (bool, bool, bool, Option<u16>, Option<u16>),
+        }
+
+        let response = |status: u16, body: &str| {
+            NeutrinoError::new(
+                StatusCode::from_u16(status).unwrap(),
+                body.as_bytes(),
+                CallContext::default(),
+            )
+        };
+        let connect_error = hyper::Client::new()
+            .get("http://127.0.0.1:1".parse().unwrap())
+            .await
+            .unwrap_err();
+        let elapsed = tokio::time::timeout(Duration::ZERO, std::future::pending::<()>())
+            .await
+            .unwrap_err();
+
+        let tests = vec![
+            TestingData {
+                name: "Using a connect error".to_owned(),
+                args: Error::Hyper(connect_error),
+                expected: (true, false, false, None, None),
+            },
+            TestingData {
+                name: "Using a timeout".to_owned(),
+                args: Error::Timeout(elapsed),
+                expected: (true, false, false, None, None),
+            },
+            TestingData {
+                name: "Using a 429".to_owned(),
+                args: Error::RateLimited {
+                    retry_after: None,
+                    error: response(429, ""),
+                },
+                expected: (true, false, false, Some(429), None),
+            },
+            TestingData {
+                name: "Using a 503".to_owned(),
+                args: Error::ServerError(response(503, "service unavailable")),
+                expected: (true, false, false, Some(503), None),
+            },
+            TestingData {
+                name: "Using a 401".to_owned(),
+                args: Error::Unauthorized(response(401, r#"{"api-error": 2}"#)),
+                expected: (false, true, false, Some(401), Some(2)),
+            },
+            TestingData {
+                name: "Using a 403 with a daily limit code".to_owned(),
+                args: Error::QuotaExceeded(response(403, r#"{"api-error": 7}"#)),
+                expected: (false, false, true, Some(403), Some(7)),
+            },
+            TestingData {
+                name: "Using an insufficient credit".to_owned(),
+                args: Error::InsufficientCredit(response(400, r#"{"api-error": 14}"#)),
+                expected: (false, false, true, Some(400), Some(14)),
+            },
+            TestingData {
+                name: "Using a feature not enabled".to_owned(),
+                args: Error::FeatureNotEnabled {
+                    endpoint: "hlr-lookup".to_owned(),
+                    error: response(400, r#"{"api-error": 5}"#),
+                },
+                expected: (false, false, false, Some(400), Some(5)),
+            },
+            TestingData {
+                name: "Using a 400".to_owned(),
+                args: Error::Neutrino(response(400, r#"{"api-error": 3}"#)),
+                expected: (false, false, false, Some(400), Some(3)),
+            },
+            TestingData {
+                name: "Using an unexpected response".to_owned(),
+                args: Error::UnexpectedResponse {
+                    status: StatusCode::OK,
+                    content_type: Some("text/html".to_owned()),
+                    body_snippet: "<html>".to_owned(),
+                },
+                expected: (false, false, false, Some(200), None),
+            },
+            TestingData {
+                name: "Using an open circuit".to_owned(),
+                args: Error::CircuitOpen {
+                    endpoint: "ip-info".to_owned(),
+                },
+                expected: (false, false, false, None, None),
+            },
+            TestingData {
+                name: "Using a cancelled call".to_owned(),
+                args: Error::Cancelled,
+                expected: (false, false, false, None, None),
+            },
+        ];
+
+        for test in &tests {
+            let err = &test.args;
+            assert_eq!(
+                (
+                    err.is_retryable(),
+                    err.is_auth_error(),
+                    err.is_quota_error(),
+                    err.status_code().map(|status| status.as_u16()),
+                    err.api_error_code(),
+                ),
+                test.expected,
+                "{}",
+                test.name
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_error_from_status_code() {
+        struct TestingData {
+            pub name: String,
+            pub args: (&'static str, usize, Option<&'static str>, &'static str),
+            pub expected: fn(&Error) -> bool,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Using a 401".to_owned(),
+                args: ("203.0.113.170", 401, None, r#"{"api-error": 2}"#),
+                expected: |err| matches!(err, Error::Unauthorized(_)),
+            },
+            TestingData {
+                name: "Using a 403".to_owned(),
+                args: ("203.0.113.171", 403, None, r#"{"api-error": 1}"#),
+                expected: |err| matches!(err, Error::Unauthorized(_)),
+            },
+            TestingData {
+                name: "Using a 402".to_owned(),
+                args: ("203.0.113.172", 402, None, ""),
+                expected: |err| matches!(err, Error::QuotaExceeded(_)),
+            },
+            TestingData {
+                name: "Using a 403 with a daily limit code".to_owned(),
+                args: ("203.0.113.173", 403, None, r#"{"api-error": 7}"#),
+                expected: |err| matches!(err, Error::QuotaExceeded(_)),
+            },
+            TestingData {
+                name: "Using a 429 without Retry-After".to_owned(),
+                args: ("203.0.113.174", 429, None, ""),
+                expected: |err| {
+                    matches!(
+                        err,
+                        Error::RateLimited {
+                            retry_after: None,
+                            ..
+                        }
+                    )
+                },
+            },
+            TestingData {
+                name: "Using a 429 with Retry-After".to_owned(),
+                args: ("203.0.113.175", 429, Some("30"), ""),
+                expected: |err| matches!(err, Error::RateLimited { retry_after: Some(delay), .. } if *delay == Duration::from_secs(30)),
+            },
+            TestingData {
+                name: "Using a 429 with an HTTP date Retry-After".to_owned(),
+                args: (
+                    "203.0.113.178",
+                    429,
+                    Some("Fri, 01 Jan 2100 00:00:00 GMT"),
+                    "",
+                ),
+                expected: |err| matches!(err, Error::RateLimited { retry_after: Some(delay), .. } if *delay > Duration::from_secs(365 * 24 * 3600)),
+            },
+            TestingData {
+                name: "Using a 503".to_owned(),
+                args: ("203.0.113.176", 503, None, "service unavailable"),
+                expected: |err| matches!(err, Error::ServerError(_)),
+            },
+            TestingData {
+                name: "Using a 400".to_owned(),
+                args: ("203.0.113.177", 400, None, r#"{"api-error": 3}"#),
+                expected: |err| matches!(err, Error::Neutrino(_)),
+            },
+        ];
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        for test in &tests {
+            let (ip, status, retry_after, body) = test.args;
+            let mut mock = mock("GET", "/ip-info")
+                .match_query(Matcher::Regex(format!("ip={}$", ip)))
+                .with_status(status)
+                .with_body(body);
+            if let Some(retry_after) = retry_after {
+                mock = mock.with_header("retry-after", retry_after);
+            }
+            let _m = mock.create();
+
+            let err = neutral
+                .ip_info()
+                .send(ip.parse().unwrap())
+                .await
+                .unwrap_err();
+            assert!((test.expected)(&err), "{}: {:?}", test.name, err);
+        }
+    }
+}
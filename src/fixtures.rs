@@ -0,0 +1,226 @@
+//! Response bodies shared by the tests of this crate.
+
+pub(crate) const IP_INFO_BODY: &str = r#"
+{
+    "ip": "128.0.0.1",
+    "valid": true,
+    "is_v6": false,
+    "is_v4_mapped": false,
+    "is_bogon": false,
+    "country": "ACountry",
+    "country_code": "AC",
+    "country_code3": "ACO",
+    "continent_code": "EU",
+    "currency_code": "ABC",
+    "city": "Roubaix",
+    "region": "Hauts-de-ACountry",
+    "longitude": 1.00000,
+    "latitude": 1.00000,
+    "hostname": "",
+    "host_domain": "",
+    "timezone": {
+        "id": "Europe/Paris",
+        "name": "Central European Standard Time",
+        "abbr": "CET",
+        "date": "2021-11-24",
+        "time": "12:47:33.825588",
+        "offset": "+01:00"
+    }
+}"#;
+
+/// [IP_INFO_BODY] as sent without an `output-case` param, with kebab-case keys.
+pub(crate) const IP_INFO_KEBAB_BODY: &str = r#"
+{
+    "ip": "128.0.0.1",
+    "valid": true,
+    "is-v6": false,
+    "is-v4-mapped": false,
+    "is-bogon": false,
+    "country": "ACountry",
+    "country-code": "AC",
+    "country-code3": "ACO",
+    "continent-code": "EU",
+    "currency-code": "ABC",
+    "city": "Roubaix",
+    "region": "Hauts-de-ACountry",
+    "longitude": 1.00000,
+    "latitude": 1.00000,
+    "hostname": "",
+    "host-domain": "",
+    "timezone": {
+        "id": "Europe/Paris",
+        "name": "Central European Standard Time",
+        "abbr": "CET",
+        "date": "2021-11-24",
+        "time": "12:47:33.825588",
+        "offset": "+01:00"
+    }
+}"#;
+
+pub(crate) const IP_PROBE_BODY: &str = r#"
+{
+    "country": "ACountry",
+    "country_code": "AC",
+    "provider_domain": "networkoperator.com",
+    "city": "Roubaix",
+    "vpn_domain": "",
+    "is_vpn": false,
+    "as_cidr": "128.0.0.0/22",
+    "valid": true,
+    "provider_type": "isp",
+    "hostname": "",
+    "as_age": 8,
+    "continent_code": "EU",
+    "is_bogon": false,
+    "ip": "128.0.0.1",
+    "as_country_code": "AC",
+    "provider_description": "A network operator description",
+    "as_country_code3": "ACO",
+    "is_v4_mapped": false,
+    "is_isp": true,
+    "provider_website": "https://www.networkoperator.com/",
+    "as_description": "NETWORK-OPERATOR-AS,AC,Network Operator",
+    "is_hosting": false,
+    "as_domains": [
+        "networkoperator.com"
+    ],
+    "host_domain": "",
+    "is_proxy": false,
+    "currency_code": "ABC",
+    "region": "Hauts-de-ACountry",
+    "asn": "12345",
+    "country_code3": "ACO",
+    "is_v6": false
+}"#;
+
+pub(crate) const PHONE_VALIDATE_BODY: &str = r#"
+{
+    "valid": true,
+    "type": "mobile",
+    "international_calling_code": "33",
+    "international_number": "+12345678901",
+    "local_number": "01 23 45 67 89",
+    "location": "ACountry",
+    "country": "ACountry",
+    "country_code": "AC",
+    "country_code3": "ACO",
+    "currency_code": "ABC",
+    "is_mobile": true,
+    "prefix_network": "Phone operator"
+}"#;
+
+pub(crate) const HLR_LOOKUP_BODY: &str = r#"
+{
+    "country": "ACountry",
+    "country_code": "AC",
+    "country_code3": "ACO",
+    "currency_code": "ABC",
+    "current_network": "Phone operator",
+    "hlr_status": "ok",
+    "hlr_valid": true,
+    "imsi": "2081594584",
+    "international_calling_code": "33",
+    "international_number": "+12345678901",
+    "is_mobile": true,
+    "is_ported": false,
+    "is_roaming": false,
+    "local_number": "01 23 45 67 89",
+    "location": "ACountry",
+    "mcc": "208",
+    "mnc": "15",
+    "msc": "320433",
+    "msin": "",
+    "number_type": "mobile",
+    "number_valid": true,
+    "origin_network": "Phone operator",
+    "ported_network": "",
+    "roaming_country_code": ""
+}"#;
+
+/// [HLR_LOOKUP_BODY] as sent without an `output-case` param, with kebab-case keys.
+pub(crate) const HLR_LOOKUP_KEBAB_BODY: &str = r#"
+{
+    "country": "ACountry",
+    "country-code": "AC",
+    "country-code3": "ACO",
+    "currency-code": "ABC",
+    "current-network": "Phone operator",
+    "hlr-status": "ok",
+    "hlr-valid": true,
+    "imsi": "2081594584",
+    "international-calling-code": "33",
+    "international-number": "+12345678901",
+    "is-mobile": true,
+    "is-ported": false,
+    "is-roaming": false,
+    "local-number": "01 23 45 67 89",
+    "location": "ACountry",
+    "mcc": "208",
+    "mnc": "15",
+    "msc": "320433",
+    "msin": "",
+    "number-type": "mobile",
+    "number-valid": true,
+    "origin-network": "Phone operator",
+    "ported-network": "",
+    "roaming-country-code": ""
+}"#;
+
+pub(crate) const IP_BLOCKLIST_BODY: &str = r#"
+{
+    "ip": "128.0.0.1",
+    "is_listed": false,
+    "last_seen": 0,
+    "list_count": 0,
+    "blocklists": [],
+    "sensors": [],
+    "is_proxy": false,
+    "is_tor": false,
+    "is_vpn": false,
+    "is_malware": false,
+    "is_spyware": false,
+    "is_dshield": false,
+    "is_hijacked": false,
+    "is_spider": false,
+    "is_bot": false,
+    "is_spam_bot": false,
+    "is_exploit_bot": false
+}"#;
+
+/// A request received by a [recording] client.
+pub(crate) type Recorded = (http::Method, http::Uri, http::HeaderMap, hyper::body::Bytes);
+
+/// Returns a client answering every request with `body`, along with the requests it receives.
+pub(crate) fn recording(
+    body: &str,
+) -> (
+    crate::Neutral,
+    std::sync::Arc<std::sync::Mutex<Vec<Recorded>>>,
+) {
+    use hyper::{service::service_fn, Body, Request, Response};
+    use std::sync::{Arc, Mutex};
+
+    let body = hyper::body::Bytes::from(body.to_owned());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let recorded = requests.clone();
+    let service = service_fn(move |req: Request<Body>| {
+        let (recorded, body) = (recorded.clone(), body.clone());
+        async move {
+            let (parts, req_body) = req.into_parts();
+            let req_body = hyper::body::to_bytes(req_body).await?;
+            recorded
+                .lock()
+                .unwrap()
+                .push((parts.method, parts.uri, parts.headers, req_body));
+            Ok::<_, hyper::Error>(Response::new(Body::from(body)))
+        }
+    });
+    let neutral = crate::Neutral::builder(
+        "http://127.0.0.1",
+        crate::ApiAuth::new("User".to_string(), "test".to_string()),
+    )
+    .service(service)
+    .build()
+    .unwrap();
+    (neutral, requests)
+}
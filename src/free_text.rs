@@ -0,0 +1,186 @@
+//! # Free text module
+//! Prepare the large free text or html inputs of content endpoints before they are sent.
+//!
+//! neutrinoapi.com limits the size of such inputs, an oversized input fails server side with an opaque error.
+//! [prepare](./fn.prepare.html) checks the size locally, after normalizing line endings, and applies a [TextPolicy](./enum.TextPolicy.html):
+//!
+//! * [TextPolicy::Reject](./enum.TextPolicy.html#variant.Reject) fails with [Error::InputTooLarge](../error/enum.Error.html#variant.InputTooLarge) without any request,
+//! * [TextPolicy::Truncate](./enum.TextPolicy.html#variant.Truncate) cuts the input and reports a [Warning::Truncated](../warning/enum.Warning.html#variant.Truncated).
+//!
+//! Truncation is deterministic and UTF-8 safe: it never splits a code point and cuts at the last whitespace when there is one.
+
+use std::borrow::Cow;
+
+use crate::{warning::Warning, Error};
+
+/// Select what happens to an input larger than the limit of its endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextPolicy {
+    /// Fail without sending the input.
+    #[default]
+    Reject,
+    /// Keep at most the given number of bytes, never more than the limit of the endpoint.
+    Truncate(usize),
+}
+
+/// A normalized input, ready to be sent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreparedText<'a> {
+    /// The text to send.
+    pub text: Cow<'a, str>,
+    /// Set when the input was truncated.
+    pub warning: Option<Warning>,
+}
+
+/// Normalize `input` and apply `policy` when it is larger than `limit` bytes.
+pub fn prepare(input: &str, limit: usize, policy: TextPolicy) -> Result<PreparedText<'_>, Error> {
+    let text = normalize(input);
+    let max = match policy {
+        _ if text.len() <= limit => {
+            return Ok(PreparedText {
+                text,
+                warning: None,
+            })
+        }
+        TextPolicy::Reject => {
+            return Err(Error::InputTooLarge {
+                len: text.len(),
+                limit,
+            })
+        }
+        TextPolicy::Truncate(max) => max.min(limit),
+    };
+
+    let original_len = text.len();
+    let truncated = truncate(&text, max).to_owned();
+    let warning = Warning::Truncated {
+        original_len,
+        truncated_len: truncated.len(),
+    };
+    Ok(PreparedText {
+        text: Cow::Owned(truncated),
+        warning: Some(warning),
+    })
+}
+
+/// Replace `\r\n` line endings by `\n`.
+fn normalize(input: &str) -> Cow<'_, str> {
+    if input.contains("\r\n") {
+        Cow::Owned(input.replace("\r\n", "\n"))
+    } else {
+        Cow::Borrowed(input)
+    }
+}
+
+/// Returns the longest prefix of `text` of at most `max` bytes, ending before a whitespace when possible.
+fn truncate(text: &str, max: usize) -> &str {
+    let mut end = max.min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let prefix = &text[..end];
+
+    let ends_at_whitespace = text[end..].starts_with(char::is_whitespace);
+    if ends_at_whitespace {
+        return prefix.trim_end();
+    }
+    match prefix.rfind(char::is_whitespace) {
+        Some(space) if !prefix[..space].trim_end().is_empty() => prefix[..space].trim_end(),
+        _ => prefix,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_prepare() {
+        struct TestingData {
+            pub name: String,
+            pub args: (&'static str, usize, TextPolicy),
+            pub expected: Option<(&'static str, Option<Warning>)>,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Input within the limit".to_owned(),
+                args: ("hello world", 11, TextPolicy::Reject),
+                expected: Some(("hello world", None)),
+            },
+            TestingData {
+                name: "Line endings are normalized before measuring".to_owned(),
+                args: ("a\r\nb", 3, TextPolicy::Reject),
+                expected: Some(("a\nb", None)),
+            },
+            TestingData {
+                name: "Oversized input rejected".to_owned(),
+                args: ("hello world", 5, TextPolicy::Reject),
+                expected: None,
+            },
+            TestingData {
+                name: "Truncated at the last whitespace".to_owned(),
+                args: ("hello brave world", 13, TextPolicy::Truncate(100)),
+                expected: Some((
+                    "hello brave",
+                    Some(Warning::Truncated {
+                        original_len: 17,
+                        truncated_len: 11,
+                    }),
+                )),
+            },
+            TestingData {
+                name: "Truncated right before a whitespace".to_owned(),
+                args: ("hello world", 5, TextPolicy::Truncate(5)),
+                expected: Some((
+                    "hello",
+                    Some(Warning::Truncated {
+                        original_len: 11,
+                        truncated_len: 5,
+                    }),
+                )),
+            },
+            TestingData {
+                name: "Multi-byte character straddling the cut point".to_owned(),
+                // "é" takes 2 bytes, the cut at 4 bytes falls in the middle of the second one.
+                args: ("aéé", 4, TextPolicy::Truncate(100)),
+                expected: Some((
+                    "aé",
+                    Some(Warning::Truncated {
+                        original_len: 5,
+                        truncated_len: 3,
+                    }),
+                )),
+            },
+            TestingData {
+                name: "Multi-byte characters without whitespace".to_owned(),
+                // Each "日" takes 3 bytes.
+                args: ("日本語テキスト", 10, TextPolicy::Truncate(100)),
+                expected: Some((
+                    "日本語",
+                    Some(Warning::Truncated {
+                        original_len: 21,
+                        truncated_len: 9,
+                    }),
+                )),
+            },
+        ];
+
+        for test in &tests {
+            let (input, limit, policy) = test.args;
+            let prepared = prepare(input, limit, policy);
+            match &test.expected {
+                Some((text, warning)) => {
+                    let prepared = prepared.unwrap();
+                    assert_eq!(prepared.text, *text, "{}", test.name);
+                    assert_eq!(&prepared.warning, warning, "{}", test.name);
+                }
+                None => assert!(
+                    matches!(prepared, Err(Error::InputTooLarge { len: 11, limit: 5 })),
+                    "{}",
+                    test.name
+                ),
+            }
+        }
+    }
+}
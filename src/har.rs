@@ -0,0 +1,324 @@
+//! # Har module
+//! Record the requests sent to neutrinoapi.com in the [HAR 1.2](http://www.softwareishard.com/blog/har-12-spec/) format, to share reproducible traces with neutrinoapi.com support.
+//!
+//! The recorder is enabled with [NeutralBuilder::har](../builder/struct.NeutralBuilder.html#method.har) and requires the `har` feature.
+//! Credentials headers are redacted, bodies are truncated to `max_body_size` bytes and only the last `capacity` entries are kept.
+//!
+//! ```ignore
+//! let neutral = Neutral::builder("https://neutrinoapi.net", api_auth)
+//!     .har(HarConfig::default())
+//!     .build()?;
+//!
+//! neutral.ip_info().send(ip_addr).await?;
+//! std::fs::write("neutral.har", neutral.har_export())?;
+//! ```
+
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use http::{response::Parts, HeaderMap, Method, Uri};
+use hyper::body::Bytes;
+use serde_json::{json, Value};
+
+const REDACTED: &str = "[REDACTED]";
+const REDACTED_HEADERS: [&str; 2] = ["user-id", "api-key"];
+
+/// Configure the HAR recorder.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HarConfig {
+    /// Number of entries kept, older entries are dropped first.
+    pub capacity: usize,
+    /// Maximum number of bytes of a recorded body.
+    pub max_body_size: usize,
+}
+
+impl Default for HarConfig {
+    fn default() -> Self {
+        HarConfig {
+            capacity: 100,
+            max_body_size: 64 * 1024,
+        }
+    }
+}
+
+/// What is recorded of a request before it is sent.
+#[derive(Debug)]
+pub(crate) struct RequestSnapshot {
+    pub(crate) method: Method,
+    pub(crate) uri: Uri,
+    pub(crate) headers: HeaderMap,
+    pub(crate) started: SystemTime,
+}
+
+/// Keep the last entries of the traffic in memory.
+#[derive(Debug)]
+pub(crate) struct HarRecorder {
+    config: HarConfig,
+    entries: Mutex<VecDeque<Value>>,
+}
+
+impl HarRecorder {
+    pub(crate) fn new(config: HarConfig) -> Self {
+        HarRecorder {
+            config,
+            entries: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Record a request and its response, `response` is `None` when no response was received.
+    pub(crate) fn record(
+        &self,
+        request: &RequestSnapshot,
+        response: Option<(&Parts, &Bytes)>,
+        elapsed: Duration,
+    ) {
+        if self.config.capacity == 0 {
+            return;
+        }
+
+        let time = elapsed.as_secs_f64() * 1000.0;
+        let response = match response {
+            Some((parts, body)) => json!({
+                "status": parts.status.as_u16(),
+                "statusText": parts.status.canonical_reason().unwrap_or_default(),
+                "httpVersion": format!("{:?}", parts.version),
+                "cookies": [],
+                "headers": headers(&parts.headers),
+                "content": {
+                    "size": body.len(),
+                    "mimeType": parts
+                        .headers
+                        .get(http::header::CONTENT_TYPE)
+                        .and_then(|value| value.to_str().ok())
+                        .unwrap_or_default(),
+                    "text": self.truncate(body),
+                },
+                "redirectURL": "",
+                "headersSize": -1,
+                "bodySize": body.len(),
+            }),
+            None => json!({
+                "status": 0,
+                "statusText": "",
+                "httpVersion": "",
+                "cookies": [],
+                "headers": [],
+                "content": { "size": 0, "mimeType": "" },
+                "redirectURL": "",
+                "headersSize": -1,
+                "bodySize": -1,
+                "_error": "no response received",
+            }),
+        };
+
+        let query_string: Vec<Value> = request
+            .uri
+            .query()
+            .map(|query| {
+                form_urlencoded::parse(query.as_bytes())
+                    .map(|(name, value)| json!({ "name": name, "value": value }))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let entry = json!({
+            "startedDateTime": iso8601(request.started),
+            "time": time,
+            "request": {
+                "method": request.method.as_str(),
+                "url": request.uri.to_string(),
+                "httpVersion": "HTTP/1.1",
+                "cookies": [],
+                "headers": headers(&request.headers),
+                "queryString": query_string,
+                "headersSize": -1,
+                "bodySize": 0,
+            },
+            "response": response,
+            "cache": {},
+            "timings": { "send": 0, "wait": time, "receive": 0 },
+        });
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.config.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    /// Export the recorded entries as a HAR 1.2 document.
+    pub(crate) fn export(&self) -> String {
+        let entries: Vec<Value> = self.entries.lock().unwrap().iter().cloned().collect();
+        json!({
+            "log": {
+                "version": "1.2",
+                "creator": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+                "entries": entries,
+            }
+        })
+        .to_string()
+    }
+
+    fn truncate(&self, body: &Bytes) -> String {
+        let body = &body[..body.len().min(self.config.max_body_size)];
+        String::from_utf8_lossy(body).into_owned()
+    }
+}
+
+fn headers(headers: &HeaderMap) -> Vec<Value> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if REDACTED_HEADERS.contains(&name.as_str()) {
+                REDACTED.to_owned()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            };
+            json!({ "name": name.as_str(), "value": value })
+        })
+        .collect()
+}
+
+/// Format a time as an ISO 8601 UTC date time, e.g. `2021-11-24T12:47:33.825Z`.
+fn iso8601(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (days, seconds_of_day) = ((seconds / 86400) as i64, seconds % 86400);
+
+    // Convert days since epoch to a civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{fixtures::IP_INFO_BODY, ApiAuth, Neutral};
+    use mockito::{mock, Matcher};
+    use std::net::{IpAddr, Ipv4Addr};
+
+    #[test]
+    fn test_iso8601() {
+        let time = UNIX_EPOCH + Duration::from_millis(1637758053825);
+        assert_eq!(iso8601(time), "2021-11-24T12:47:33.825Z");
+        assert_eq!(iso8601(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+    }
+
+    #[tokio::test]
+    async fn test_har_export() {
+        let _ok = mock("GET", "/ip-info")
+            .match_query(Matcher::Any)
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(IP_INFO_BODY)
+            .create();
+
+        let _error = mock("GET", "/ip-blocklist")
+            .match_query(Matcher::Any)
+            .with_status(500)
+            .with_body("internal error")
+            .create();
+
+        let neutral = Neutral::builder(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "secret".to_string()),
+        )
+        .har(HarConfig {
+            capacity: 10,
+            max_body_size: 16,
+        })
+        .build()
+        .unwrap();
+
+        let ip_addr = IpAddr::V4(Ipv4Addr::new(128, 0, 0, 1));
+        assert!(neutral.ip_info().send(ip_addr).await.is_ok());
+        assert!(neutral.ip_blocklist().send(ip_addr).await.is_err());
+
+        let export = neutral.har_export();
+        assert!(!export.contains("secret"), "credentials are redacted");
+
+        let har: Value = serde_json::from_str(&export).unwrap();
+        assert_eq!(har["log"]["version"], "1.2");
+        assert_eq!(har["log"]["creator"]["name"], "neutral");
+
+        let entries = har["log"]["entries"].as_array().unwrap();
+        assert_eq!(entries.len(), 2);
+
+        let ok = &entries[0];
+        assert_eq!(ok["request"]["method"], "GET");
+        assert!(ok["request"]["url"].as_str().unwrap().contains("/ip-info?"));
+        assert!(ok["request"]["queryString"]
+            .as_array()
+            .unwrap()
+            .contains(&json!({ "name": "ip", "value": "128.0.0.1" })));
+        assert!(ok["request"]["headers"]
+            .as_array()
+            .unwrap()
+            .contains(&json!({ "name": "api-key", "value": REDACTED })));
+        assert_eq!(ok["response"]["status"], 200);
+        assert_eq!(ok["response"]["content"]["mimeType"], "application/json");
+        assert_eq!(ok["response"]["content"]["size"], IP_INFO_BODY.len());
+        assert_eq!(
+            ok["response"]["content"]["text"].as_str().unwrap(),
+            &IP_INFO_BODY[..16]
+        );
+        assert!(ok["startedDateTime"].as_str().unwrap().ends_with('Z'));
+        assert!(ok["time"].is_number());
+        assert!(ok["timings"]["wait"].is_number());
+
+        let error = &entries[1];
+        assert_eq!(error["response"]["status"], 500);
+        assert_eq!(error["response"]["statusText"], "Internal Server Error");
+        assert_eq!(error["response"]["content"]["text"], "internal error");
+    }
+
+    #[test]
+    fn test_har_capacity() {
+        let recorder = HarRecorder::new(HarConfig {
+            capacity: 2,
+            max_body_size: 16,
+        });
+        for path in ["/a", "/b", "/c"] {
+            let request = RequestSnapshot {
+                method: Method::GET,
+                uri: path.parse().unwrap(),
+                headers: HeaderMap::new(),
+                started: SystemTime::now(),
+            };
+            recorder.record(&request, None, Duration::from_millis(1));
+        }
+
+        let har: Value = serde_json::from_str(&recorder.export()).unwrap();
+        let urls: Vec<&str> = har["log"]["entries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["request"]["url"].as_str().unwrap())
+            .collect();
+        assert_eq!(urls, vec!["/b", "/c"]);
+    }
+}
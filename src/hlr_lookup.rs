@@ -5,42 +5,245 @@
 //!
 //! The home location register (HLR) is a central database that contains details of each mobile phone subscriber connected to the global mobile network. You can use this API to validate that a mobile number is live and registered on a mobile network in real-time. Find out the carrier name, ported number status and fetch up-to-date device status information.
 
-use crate::{Error, Neutral};
-use http::Method;
-use hyper::Body;
-use neutral_types::hlr_lookup::HlrLookupResponse;
+use std::time::Duration;
+
+use crate::{
+    context::CallContext,
+    country::CountryCode,
+    meta::ResponseMeta,
+    params,
+    phone_number::{IntoPhoneNumber, NumberClass, PhoneNumber, PhoneNumberError},
+    pipeline::Fetched,
+    warning::Warning,
+    Error, Neutral,
+};
+use futures::{Stream, StreamExt};
+use hyper::{body::Bytes, Body, Request};
+/// The response of the endpoint and its status, defined by neutral_types and re-exported so they can be named without depending on it.
+pub use neutral_types::hlr_lookup::{HlrLookupResponse, HlrStatus};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 #[cfg(test)]
 use mockito;
 
+/// Options of an hlr lookup call.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HlrLookupOptions {
+    /// ISO 3166-1 alpha-2 country code used to parse a national number, e.g. `FR`.
+    pub country_code: Option<String>,
+}
+
+impl HlrLookupOptions {
+    /// Returns the cache key of `phone_number` sent with these options.
+    fn canonical_input(&self, phone_number: &PhoneNumber) -> String {
+        match &self.country_code {
+            Some(country_code) => format!("{}:{}", phone_number, country_code),
+            None => phone_number.to_string(),
+        }
+    }
+}
+
+/// Parameters of an hlr lookup call, e.g. to persist a pending call and send it later with [HlrLookup::send_params].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HlrLookupParams {
+    pub number: String,
+    /// See [HlrLookupOptions::country_code].
+    #[serde(default)]
+    pub country_code: Option<String>,
+}
+
 pub struct HlrLookup<'a> {
     pub(crate) neutral: &'a Neutral,
+    pub(crate) context: CallContext,
 }
 
 impl<'a> HlrLookup<'a> {
+    /// Attach a [CallContext](../context/struct.CallContext.html) to the calls.
+    pub fn context(mut self, context: CallContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Bound each attempt of the call by `timeout` instead of the client default, see [CallContext::timeout](../context/struct.CallContext.html#structfield.timeout).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.context.timeout = Some(timeout);
+        self
+    }
+
     /// Send an hlr lookup request to neutrinoapi.com
-    pub async fn send(&self, phone_number: String) -> Result<HlrLookupResponse, Error> {
-        let path_and_query = format!(
-            "/hlr-lookup?output-case=snake&number={}",
-            phone_number.replace('+', "")
-        );
+    ///
+    /// Short codes are rejected with [PhoneNumberError::Unsupported](../phone_number/enum.PhoneNumberError.html#variant.Unsupported) since they are not registered on the mobile network.
+    /// An international number is sent with its leading `+`, a national number needs the `country_code` of [send_with](#method.send_with).
+    pub async fn send(
+        &self,
+        phone_number: impl IntoPhoneNumber,
+    ) -> Result<HlrLookupResponse, Error> {
+        self.send_with(phone_number, HlrLookupOptions::default())
+            .await
+    }
+
+    /// Same as [send](#method.send), with `options`.
+    pub async fn send_with(
+        &self,
+        phone_number: impl IntoPhoneNumber,
+        options: HlrLookupOptions,
+    ) -> Result<HlrLookupResponse, Error> {
+        let fetched = self.fetch(phone_number, &options).await?;
+        self.neutral.decode_lenient("hlr-lookup", &fetched.body)
+    }
+
+    /// Same as [send_with](#method.send_with), with the phone number and options of `params`.
+    pub async fn send_params(&self, params: &HlrLookupParams) -> Result<HlrLookupResponse, Error> {
+        let options = HlrLookupOptions {
+            country_code: params.country_code.clone(),
+        };
+        self.send_with(params.number.as_str(), options).await
+    }
+
+    /// Same as [send](#method.send), also returning the [ResponseMeta](../meta/struct.ResponseMeta.html) of the response.
+    pub async fn send_with_meta(
+        &self,
+        phone_number: impl IntoPhoneNumber,
+    ) -> Result<(ResponseMeta, HlrLookupResponse), Error> {
+        let fetched = self
+            .fetch(phone_number, &HlrLookupOptions::default())
+            .await?;
+        let response: HlrLookupResponse =
+            self.neutral.decode_lenient("hlr-lookup", &fetched.body)?;
+        Ok((fetched.meta, response))
+    }
+
+    /// Same as [send](#method.send), returning the raw body of the response.
+    pub async fn send_raw(&self, phone_number: impl IntoPhoneNumber) -> Result<Bytes, Error> {
+        Ok(self
+            .fetch(phone_number, &HlrLookupOptions::default())
+            .await?
+            .body)
+    }
+
+    /// Same as [send](#method.send), decoding the response into any `T`, e.g. a struct holding fields unknown to [HlrLookupResponse].
+    pub async fn send_as<T: DeserializeOwned>(
+        &self,
+        phone_number: impl IntoPhoneNumber,
+    ) -> Result<T, Error> {
+        let fetched = self
+            .fetch(phone_number, &HlrLookupOptions::default())
+            .await?;
+        self.neutral.decode("hlr-lookup", &fetched.body)
+    }
+
+    /// Same as [send](#method.send), decoding the response into a [serde_json::Value].
+    pub async fn send_value(
+        &self,
+        phone_number: impl IntoPhoneNumber,
+    ) -> Result<serde_json::Value, Error> {
+        self.send_as(phone_number).await
+    }
+
+    /// Send an hlr lookup request for each number of `numbers`, at most `concurrency` at the same time.
+    ///
+    /// Results are yielded as they complete, each with its number, and a failing request does not abort the others.
+    /// `numbers` is only polled when a request slot is free, so a large list never piles up in memory.
+    /// Every call goes through the rate limits, the circuit breaker and the retries of the client.
+    pub fn stream<S>(
+        &self,
+        numbers: S,
+        concurrency: usize,
+    ) -> impl Stream<Item = (String, Result<HlrLookupResponse, Error>)> + '_
+    where
+        S: Stream<Item = String> + 'a,
+    {
+        numbers
+            .map(move |number| async move {
+                let result = self.send(number.as_str()).await;
+                (number, result)
+            })
+            .buffer_unordered(concurrency.max(1))
+    }
+
+    /// Send the request, without decoding its response.
+    async fn fetch(
+        &self,
+        phone_number: impl IntoPhoneNumber,
+        options: &HlrLookupOptions,
+    ) -> Result<Fetched, Error> {
+        let phone_number = phone_number.into_phone_number()?;
+        if phone_number.class() == NumberClass::ShortCode {
+            return Err(PhoneNumberError::Unsupported(NumberClass::ShortCode).into());
+        }
+
+        let canonical_input = options.canonical_input(&phone_number);
+        self.neutral
+            .execute("hlr-lookup", Some(&canonical_input), &self.context, || {
+                build_request(self.neutral, &phone_number, options, &self.context)
+            })
+            .await
+    }
+}
+
+/// Build the hlr lookup request of `phone_number`, without sending it.
+pub(crate) fn build_request(
+    neutral: &Neutral,
+    phone_number: &PhoneNumber,
+    options: &HlrLookupOptions,
+    context: &CallContext,
+) -> Result<Request<Body>, Error> {
+    let mut params = params();
+    params.append_pair("number", &phone_number.to_string());
+    if let Some(country_code) = &options.country_code {
+        params.append_pair("country-code", country_code);
+    }
+    neutral.params_request("/hlr-lookup", params.finish(), context)
+}
+
+/// Describes the roaming status of a mobile device, computed from `is_roaming` and `roaming_country_code`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RoamingStatus {
+    /// The device is on its home network.
+    NotRoaming,
+    /// The device is roaming in the given country.
+    Roaming(CountryCode),
+    /// `is_roaming` and `roaming_country_code` contradict each other.
+    Inconsistent,
+}
 
-        let request = self
-            .neutral
-            .request_builder(path_and_query)?
-            .method(Method::GET)
-            .body(Body::empty())?;
+/// Typed views computed from an hlr lookup response.
+pub trait HlrLookupExt {
+    /// Returns the roaming status, validating that a roaming device comes with a country code.
+    fn roaming(&self) -> RoamingStatus;
 
-        let body = self.neutral.request(request).await?;
-        let response: HlrLookupResponse = serde_json::from_slice(&body)?;
-        Ok(response)
+    /// Returns the anomalies detected in the response.
+    fn warnings(&self) -> Vec<Warning>;
+}
+
+impl HlrLookupExt for HlrLookupResponse {
+    fn roaming(&self) -> RoamingStatus {
+        let country_code = CountryCode::parse(&self.roaming_country_code);
+        match (self.is_roaming, country_code) {
+            (false, None) if self.roaming_country_code.is_empty() => RoamingStatus::NotRoaming,
+            (true, Some(country_code)) => RoamingStatus::Roaming(country_code),
+            _ => RoamingStatus::Inconsistent,
+        }
+    }
+
+    fn warnings(&self) -> Vec<Warning> {
+        let mut warnings = Vec::new();
+        if self.roaming() == RoamingStatus::Inconsistent {
+            warnings.push(Warning::InconsistentRoaming {
+                is_roaming: self.is_roaming,
+                roaming_country_code: self.roaming_country_code.clone(),
+            });
+        }
+        warnings
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::endpoint::Endpoint;
     use crate::ApiAuth;
+    use http::Method;
     use mockito::{mock, Matcher};
     use neutral_types::{hlr_lookup::HlrStatus, PhoneInfoKind};
 
@@ -134,7 +337,7 @@ mod test {
         for test in &tests {
             let Args { phone_number } = &test.args;
             let hlr_lookup_result = neutral.hlr_lookup().send(phone_number.to_owned()).await;
-            let result = hlr_lookup_result.map(|hlr_lookup| hlr_lookup.clone());
+            let result = hlr_lookup_result;
             let expected = test.expected;
 
             assert_eq!(
@@ -145,4 +348,374 @@ mod test {
             )
         }
     }
+
+    #[test]
+    fn test_hlr_lookup_roaming_status() {
+        let response: HlrLookupResponse =
+            serde_json::from_str(crate::fixtures::HLR_LOOKUP_BODY).unwrap();
+
+        struct TestingData {
+            pub name: String,
+            pub is_roaming: bool,
+            pub roaming_country_code: String,
+            pub expected: RoamingStatus,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Not roaming without country code".to_owned(),
+                is_roaming: false,
+                roaming_country_code: "".to_owned(),
+                expected: RoamingStatus::NotRoaming,
+            },
+            TestingData {
+                name: "Roaming with a country code".to_owned(),
+                is_roaming: true,
+                roaming_country_code: "BE".to_owned(),
+                expected: RoamingStatus::Roaming(CountryCode::parse("BE").unwrap()),
+            },
+            TestingData {
+                name: "Roaming without country code".to_owned(),
+                is_roaming: true,
+                roaming_country_code: "".to_owned(),
+                expected: RoamingStatus::Inconsistent,
+            },
+            TestingData {
+                name: "Roaming with a malformed country code".to_owned(),
+                is_roaming: true,
+                roaming_country_code: "B3".to_owned(),
+                expected: RoamingStatus::Inconsistent,
+            },
+            TestingData {
+                name: "Not roaming with a country code".to_owned(),
+                is_roaming: false,
+                roaming_country_code: "BE".to_owned(),
+                expected: RoamingStatus::Inconsistent,
+            },
+        ];
+
+        for test in &tests {
+            let response = HlrLookupResponse {
+                is_roaming: test.is_roaming,
+                roaming_country_code: test.roaming_country_code.clone(),
+                ..response.clone()
+            };
+
+            assert_eq!(test.expected, response.roaming(), "{}", test.name);
+
+            let warnings = response.warnings();
+            if test.expected == RoamingStatus::Inconsistent {
+                assert_eq!(
+                    warnings,
+                    vec![Warning::InconsistentRoaming {
+                        is_roaming: test.is_roaming,
+                        roaming_country_code: test.roaming_country_code.clone(),
+                    }],
+                    "{}",
+                    test.name
+                );
+            } else {
+                assert!(warnings.is_empty(), "{}", test.name);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hlr_lookup_not_enabled_for_plan() {
+        let _not_enabled = mock("GET", "/hlr-lookup")
+            .match_query(Matcher::Regex("number=12345678901".into()))
+            .with_status(403)
+            .with_body(r#"{"api-error": 5, "api-error-msg": "ACCESS DENIED - UPGRADE REQUIRED"}"#)
+            .expect(1)
+            .create();
+
+        let _no_credit = mock("GET", "/hlr-lookup")
+            .match_query(Matcher::Regex("number=10987654321".into()))
+            .with_status(402)
+            .with_body(r#"{"api-error": 14, "api-error-msg": "INSUFFICIENT CREDIT"}"#)
+            .expect(1)
+            .create();
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        assert_eq!(neutral.supports(Endpoint::HlrLookup), None);
+
+        let result = neutral.hlr_lookup().send("12345678901".to_owned()).await;
+        match result {
+            Err(err @ Error::FeatureNotEnabled { .. }) => {
+                assert!(!err.is_retryable());
+                assert!(
+                    matches!(err, Error::FeatureNotEnabled { endpoint, .. } if endpoint == "hlr-lookup")
+                );
+            }
+            other => panic!("expected FeatureNotEnabled, got {:?}", other),
+        }
+        assert_eq!(neutral.supports(Endpoint::HlrLookup), Some(false));
+
+        let result = neutral.hlr_lookup().send("10987654321".to_owned()).await;
+        match result {
+            Err(err @ Error::InsufficientCredit(_)) => assert!(!err.is_retryable()),
+            other => panic!("expected InsufficientCredit, got {:?}", other),
+        }
+
+        _not_enabled.assert();
+        _no_credit.assert();
+    }
+
+    #[tokio::test]
+    async fn test_hlr_lookup_rejects_short_codes() {
+        let _m = mock("GET", "/hlr-lookup")
+            .match_query(Matcher::Regex("number=55555$".into()))
+            .expect(0)
+            .create();
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        let result = neutral.hlr_lookup().send("55555".to_owned()).await;
+        assert!(matches!(
+            result,
+            Err(Error::InvalidPhoneNumber(PhoneNumberError::Unsupported(
+                NumberClass::ShortCode
+            )))
+        ));
+        _m.assert();
+    }
+
+    #[test]
+    fn test_hlr_lookup_build_request() {
+        struct TestingData {
+            pub name: String,
+            pub args: (&'static str, Option<&'static str>),
+            pub expected: &'static str,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Using a phone number starting with +".to_owned(),
+                args: ("+12345678901", None),
+                expected: "/hlr-lookup?number=%2B12345678901",
+            },
+            TestingData {
+                name: "Using a phone number without + sign at start position".to_owned(),
+                args: ("12345678901", None),
+                expected: "/hlr-lookup?number=12345678901",
+            },
+            TestingData {
+                name: "Using a formatted phone number".to_owned(),
+                args: ("+1 (234) 567-89.01", None),
+                expected: "/hlr-lookup?number=%2B12345678901",
+            },
+            TestingData {
+                name: "Using a national number with a significant leading zero".to_owned(),
+                args: ("06 1234 5678", None),
+                expected: "/hlr-lookup?number=0612345678",
+            },
+            TestingData {
+                name: "Using a national number with a country code".to_owned(),
+                args: ("06 12 34 56 78", Some("FR")),
+                expected: "/hlr-lookup?number=0612345678&country-code=FR",
+            },
+        ];
+
+        let neutral = Neutral::try_new(
+            "https://neutrinoapi.net",
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        for test in &tests {
+            let (phone_number, country_code) = test.args;
+            let phone_number = PhoneNumber::parse(phone_number).unwrap();
+            let options = HlrLookupOptions {
+                country_code: country_code.map(str::to_owned),
+            };
+            let request =
+                build_request(&neutral, &phone_number, &options, &CallContext::default()).unwrap();
+            assert_eq!(request.method(), Method::GET, "{}", test.name);
+            assert_eq!(
+                request.uri().path_and_query().unwrap().as_str(),
+                test.expected,
+                "{}",
+                test.name
+            );
+            assert_eq!(request.headers()["user-id"], "User", "{}", test.name);
+            assert_eq!(request.headers()["api-key"], "test", "{}", test.name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hlr_lookup_build_request_with_params_in_body() {
+        let neutral = Neutral::builder(
+            "https://neutrinoapi.net",
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .params_in_body(true)
+        .build()
+        .unwrap();
+
+        let phone_number = PhoneNumber::parse("+12345678901").unwrap();
+        let request = build_request(
+            &neutral,
+            &phone_number,
+            &HlrLookupOptions::default(),
+            &CallContext::default(),
+        )
+        .unwrap();
+        assert_eq!(request.method(), Method::POST);
+        assert_eq!(
+            request.uri().path_and_query().unwrap().as_str(),
+            "/hlr-lookup"
+        );
+        assert_eq!(
+            request.headers()["content-type"],
+            "application/x-www-form-urlencoded"
+        );
+        let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+        assert_eq!(body, "number=%2B12345678901");
+    }
+
+    #[tokio::test]
+    async fn test_hlr_lookup_send_with_country_code() {
+        let _national = mock("GET", "/hlr-lookup")
+            .match_query(Matcher::Exact(
+                "number=0611223355&country-code=FR".to_owned(),
+            ))
+            .with_status(200)
+            .with_body(crate::fixtures::HLR_LOOKUP_BODY)
+            .expect(1)
+            .create();
+        let _international = mock("GET", "/hlr-lookup")
+            .match_query(Matcher::Exact("number=%2B33611223355".to_owned()))
+            .with_status(200)
+            .with_body(crate::fixtures::HLR_LOOKUP_BODY)
+            .expect(1)
+            .create();
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        let hlr_lookup = neutral.hlr_lookup();
+        let national = hlr_lookup
+            .send_with(
+                "06 11 22 33 55".to_owned(),
+                HlrLookupOptions {
+                    country_code: Some("FR".to_owned()),
+                },
+            )
+            .await;
+        assert!(national.is_ok(), "{:?}", national);
+        let international = hlr_lookup.send("+33 6 11 22 33 55".to_owned()).await;
+        assert!(international.is_ok(), "{:?}", international);
+        _national.assert();
+        _international.assert();
+    }
+
+    #[tokio::test]
+    async fn test_hlr_lookup_stream_bounds_in_flight_requests() {
+        use crate::fixtures::HLR_LOOKUP_BODY;
+        use hyper::{service::service_fn, Client, Response};
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        let _m = mock("GET", "/hlr-lookup")
+            .match_query(Matcher::Regex(r"number=%2B336200000\d\d$".into()))
+            .with_status(200)
+            .with_body(HLR_LOOKUP_BODY)
+            .expect(20)
+            .create();
+
+        // Forward the requests to mockito, counting the ones in flight.
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let service = {
+            let (in_flight, max_in_flight) = (in_flight.clone(), max_in_flight.clone());
+            let client = Client::new();
+            service_fn(move |req: Request<Body>| {
+                let (in_flight, max_in_flight) = (in_flight.clone(), max_in_flight.clone());
+                let response = client.request(req);
+                async move {
+                    let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_in_flight.fetch_max(current, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    let response: Result<Response<Body>, hyper::Error> = response.await;
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                    response
+                }
+            })
+        };
+        let neutral = Neutral::builder(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .service(service)
+        .build()
+        .unwrap();
+
+        let numbers: Vec<String> = (0..20).map(|i| format!("+336200000{:02}", i)).collect();
+        let results: Vec<_> = neutral
+            .hlr_lookup()
+            .stream(futures::stream::iter(numbers.clone()), 3)
+            .collect()
+            .await;
+
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 3);
+        assert_eq!(results.len(), 20);
+        let mut completed: Vec<String> = results
+            .into_iter()
+            .map(|(number, result)| {
+                assert!(result.is_ok(), "{}: {:?}", number, result);
+                number
+            })
+            .collect();
+        completed.sort();
+        assert_eq!(completed, numbers);
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_hlr_lookup_params() {
+        let params = HlrLookupParams {
+            number: "06 20 00 00 99".to_owned(),
+            country_code: Some("FR".to_owned()),
+        };
+        let json = serde_json::to_string(&params).unwrap();
+        assert_eq!(
+            serde_json::from_str::<HlrLookupParams>(&json).unwrap(),
+            params
+        );
+        assert_eq!(
+            serde_json::from_str::<HlrLookupParams>(r#"{"number": "+33620000099"}"#)
+                .unwrap()
+                .country_code,
+            None
+        );
+
+        let (neutral, requests) = crate::fixtures::recording(crate::fixtures::HLR_LOOKUP_BODY);
+        neutral.hlr_lookup().send_params(&params).await.unwrap();
+        let options = HlrLookupOptions {
+            country_code: Some("FR".to_owned()),
+        };
+        neutral
+            .hlr_lookup()
+            .send_with("06 20 00 00 99", options)
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0], requests[1]);
+    }
 }
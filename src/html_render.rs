@@ -0,0 +1,320 @@
+//! # Html render module
+//! Quoted from [neutrinoapi.com](https://www.neutrinoapi.com/api/html-render):
+//!
+//! Render HTML content to PDF, JPG or PNG.
+//!
+//! The content is posted as form data and the rendered file is returned untouched, with its content type.
+//!
+//! ```ignore
+//! let params = HtmlRenderParams::new(invoice_html)
+//!     .format(RenderFormat::Pdf)
+//!     .page_size("A4")
+//!     .margins(10.0, 10.0, 10.0, 10.0);
+//! neutral.html_render().render_to(&params, "invoice.pdf").await?;
+//! ```
+
+use http::{
+    header::{HeaderValue, CONTENT_TYPE},
+    Method,
+};
+use hyper::{body::Bytes, Body, Request};
+use serde::{Deserialize, Serialize};
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+use std::time::Duration;
+
+use crate::{context::CallContext, Error, Neutral};
+
+#[cfg(test)]
+use mockito;
+
+/// Format of the rendered file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RenderFormat {
+    #[default]
+    Pdf,
+    Png,
+    Jpg,
+}
+
+impl RenderFormat {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RenderFormat::Pdf => "PDF",
+            RenderFormat::Png => "PNG",
+            RenderFormat::Jpg => "JPG",
+        }
+    }
+}
+
+/// Parameters of an html render call, every setting left to `None` uses the neutrinoapi.com default.
+///
+/// The parameters can be serialized, e.g. to persist a pending call and send it later.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HtmlRenderParams {
+    content: String,
+    #[serde(default)]
+    format: RenderFormat,
+    #[serde(default)]
+    page_size: Option<String>,
+    #[serde(default)]
+    margins: Option<[f32; 4]>,
+    #[serde(default)]
+    landscape: bool,
+    #[serde(default)]
+    zoom: Option<f32>,
+    #[serde(default)]
+    timeout: Option<u32>,
+}
+
+impl HtmlRenderParams {
+    /// Render `content`, either an html document or the url of a page to render.
+    pub fn new<S: Into<String>>(content: S) -> Self {
+        HtmlRenderParams {
+            content: content.into(),
+            format: RenderFormat::default(),
+            page_size: None,
+            margins: None,
+            landscape: false,
+            zoom: None,
+            timeout: None,
+        }
+    }
+
+    /// Set the format of the rendered file, PDF by default.
+    pub fn format(mut self, format: RenderFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set the page size of a PDF, e.g. `A4` or `Letter`.
+    pub fn page_size<S: Into<String>>(mut self, page_size: S) -> Self {
+        self.page_size = Some(page_size.into());
+        self
+    }
+
+    /// Set the margins of a PDF, in millimeters.
+    pub fn margins(mut self, top: f32, right: f32, bottom: f32, left: f32) -> Self {
+        self.margins = Some([top, right, bottom, left]);
+        self
+    }
+
+    /// Render a PDF in landscape orientation, `false` by default.
+    pub fn landscape(mut self, landscape: bool) -> Self {
+        self.landscape = landscape;
+        self
+    }
+
+    /// Set the scaling of the content, `1.0` renders it at its original size.
+    pub fn zoom(mut self, zoom: f32) -> Self {
+        self.zoom = Some(zoom);
+        self
+    }
+
+    /// Set the time neutrinoapi.com waits for the content to load, in seconds.
+    pub fn timeout(mut self, timeout: u32) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Returns the form-encoded body of the call.
+    fn form(&self) -> String {
+        let mut form = form_urlencoded::Serializer::new(String::new());
+        form.append_pair("content", &self.content)
+            .append_pair("format", self.format.as_str());
+        if let Some(page_size) = &self.page_size {
+            form.append_pair("page-size", page_size);
+        }
+        if let Some([top, right, bottom, left]) = self.margins {
+            form.append_pair("margin-top", &top.to_string())
+                .append_pair("margin-right", &right.to_string())
+                .append_pair("margin-bottom", &bottom.to_string())
+                .append_pair("margin-left", &left.to_string());
+        }
+        if self.landscape {
+            form.append_pair("landscape", "true");
+        }
+        if let Some(zoom) = self.zoom {
+            form.append_pair("zoom", &zoom.to_string());
+        }
+        if let Some(timeout) = self.timeout {
+            form.append_pair("timeout", &timeout.to_string());
+        }
+        form.finish()
+    }
+}
+
+/// A rendered file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rendered {
+    /// The content type of the file, e.g. `application/pdf`.
+    pub content_type: Option<HeaderValue>,
+    pub body: Bytes,
+}
+
+pub struct HtmlRender<'a> {
+    pub(crate) neutral: &'a Neutral,
+    pub(crate) context: CallContext,
+}
+
+impl<'a> HtmlRender<'a> {
+    /// Attach a [CallContext](../context/struct.CallContext.html) to the calls.
+    pub fn context(mut self, context: CallContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Bound each attempt of the call by `timeout` instead of the client default, see [CallContext::timeout](../context/struct.CallContext.html#structfield.timeout).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.context.timeout = Some(timeout);
+        self
+    }
+
+    /// Send an html render request to neutrinoapi.com, returns the rendered file.
+    ///
+    /// The call is never cached nor retried, every render consumes credits.
+    pub async fn send(&self, params: &HtmlRenderParams) -> Result<Rendered, Error> {
+        let response = self
+            .neutral
+            .execute_stream("html-render", &self.context, || {
+                build_request(self.neutral, params, &self.context)
+            })
+            .await?;
+        let content_type = response.headers().get(CONTENT_TYPE).cloned();
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        Ok(Rendered { content_type, body })
+    }
+
+    /// Render to the file at `path`, replacing any existing file, and returns the content type of the file.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn render_to<P: AsRef<Path>>(
+        &self,
+        params: &HtmlRenderParams,
+        path: P,
+    ) -> Result<Option<HeaderValue>, Error> {
+        let rendered = self.send(params).await?;
+        tokio::fs::write(path, &rendered.body).await?;
+        Ok(rendered.content_type)
+    }
+}
+
+/// Build the html render request of `params`, without sending it.
+pub(crate) fn build_request(
+    neutral: &Neutral,
+    params: &HtmlRenderParams,
+    context: &CallContext,
+) -> Result<Request<Body>, Error> {
+    Ok(neutral
+        .request_builder("/html-render".to_owned(), context)?
+        .method(Method::POST)
+        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from(params.form()))?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ApiAuth;
+    use mockito::{mock, Matcher};
+
+    #[test]
+    fn test_html_render_params_serde() {
+        let params = HtmlRenderParams::new("<h1>Invoice</h1>")
+            .format(RenderFormat::Png)
+            .margins(10.0, 5.0, 10.0, 5.0)
+            .zoom(1.5);
+        let json = serde_json::to_string(&params).unwrap();
+        assert_eq!(
+            serde_json::from_str::<HtmlRenderParams>(&json).unwrap(),
+            params
+        );
+        assert_eq!(
+            serde_json::from_str::<HtmlRenderParams>(r#"{"content": "<h1>Invoice</h1>"}"#).unwrap(),
+            HtmlRenderParams::new("<h1>Invoice</h1>")
+        );
+    }
+
+    #[test]
+    fn test_html_render_params_form() {
+        struct TestingData {
+            pub name: String,
+            pub args: HtmlRenderParams,
+            pub expected: &'static str,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Defaults".to_owned(),
+                args: HtmlRenderParams::new("https://example.com/invoice?id=1&lang=fr"),
+                expected: "content=https%3A%2F%2Fexample.com%2Finvoice%3Fid%3D1%26lang%3Dfr&format=PDF",
+            },
+            TestingData {
+                name: "Every setting".to_owned(),
+                args: HtmlRenderParams::new("<h1>Invoice</h1>")
+                    .format(RenderFormat::Png)
+                    .page_size("A4")
+                    .margins(10.0, 5.5, 10.0, 5.5)
+                    .landscape(true)
+                    .zoom(0.8)
+                    .timeout(30),
+                expected: "content=%3Ch1%3EInvoice%3C%2Fh1%3E&format=PNG&page-size=A4&margin-top=10&margin-right=5.5&margin-bottom=10&margin-left=5.5&landscape=true&zoom=0.8&timeout=30",
+            },
+        ];
+
+        for test in &tests {
+            assert_eq!(test.args.form(), test.expected, "{}", test.name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_html_render_passes_binary_through() {
+        let html: String = std::iter::once("<html><body><table>".to_owned())
+            .chain((0..200).map(|i| format!("<tr><td>Item {}</td><td>{}.00 €</td></tr>", i, i)))
+            .chain(std::iter::once("</table></body></html>".to_owned()))
+            .collect();
+        assert!(html.len() > 4096);
+        let pdf: Vec<u8> = b"%PDF-1.7\n"
+            .iter()
+            .copied()
+            .chain((0..=255u8).cycle().take(8192))
+            .collect();
+
+        let _m = mock("POST", "/html-render")
+            .match_header("content-type", "application/x-www-form-urlencoded")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("content".into(), html.clone()),
+                Matcher::UrlEncoded("format".into(), "PDF".into()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/pdf")
+            .with_body(&pdf)
+            .expect(2)
+            .create();
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+        let params = HtmlRenderParams::new(html);
+
+        let rendered = neutral.html_render().send(&params).await.unwrap();
+        assert_eq!(
+            rendered.content_type,
+            Some(HeaderValue::from_static("application/pdf"))
+        );
+        assert_eq!(rendered.body, pdf);
+
+        let path =
+            std::env::temp_dir().join(format!("neutral-html-render-{}.pdf", std::process::id()));
+        neutral
+            .html_render()
+            .render_to(&params, &path)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), pdf);
+        std::fs::remove_file(&path).unwrap();
+        _m.assert();
+    }
+}
@@ -0,0 +1,382 @@
+//! # Image watermark module
+//! Quoted from [neutrinoapi.com](https://www.neutrinoapi.com/api/image-watermark):
+//!
+//! Watermark one image with another image.
+//!
+//! Both images are either urls or raw bytes. Urls only are posted as form data, uploaded bytes as `multipart/form-data`.
+//! The watermarked image is returned untouched, with its content type.
+//!
+//! ```ignore
+//! let params = WatermarkParams::new(
+//!     ImageSource::bytes(product_png, "image/png"),
+//!     ImageSource::url("https://example.com/logo.png"),
+//! )
+//! .position(WatermarkPosition::BottomRight)
+//! .opacity(40);
+//! let watermarked = neutral.image_watermark().send(&params).await?;
+//! ```
+
+use http::{header::CONTENT_TYPE, Method};
+use hyper::{body::Bytes, Body, Request};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::time::Duration;
+
+use crate::{context::CallContext, html_render::Rendered, multipart::Multipart, Error, Neutral};
+
+#[cfg(test)]
+use mockito;
+
+/// An image given to neutrinoapi.com.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageSource {
+    /// The url of the image.
+    Url(String),
+    /// The raw image, uploaded.
+    Bytes {
+        #[serde(with = "raw_bytes")]
+        data: Bytes,
+        filename: String,
+        content_type: String,
+    },
+}
+
+impl ImageSource {
+    /// An image neutrinoapi.com downloads from `url`.
+    pub fn url<S: Into<String>>(url: S) -> Self {
+        ImageSource::Url(url.into())
+    }
+
+    /// An uploaded image, `content_type` is e.g. `image/png`.
+    pub fn bytes<B: Into<Bytes>, S: Into<String>>(data: B, content_type: S) -> Self {
+        ImageSource::Bytes {
+            data: data.into(),
+            filename: "image".to_owned(),
+            content_type: content_type.into(),
+        }
+    }
+}
+
+/// (De)serialize the raw bytes of an uploaded image as a sequence of bytes.
+mod raw_bytes {
+    use super::*;
+
+    pub(super) fn serialize<S: Serializer>(data: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(data)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Bytes, D::Error> {
+        Vec::<u8>::deserialize(deserializer).map(Bytes::from)
+    }
+}
+
+/// Position of the watermark on the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WatermarkPosition {
+    #[default]
+    Center,
+    Top,
+    Bottom,
+    Left,
+    Right,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl WatermarkPosition {
+    fn as_str(&self) -> &'static str {
+        match self {
+            WatermarkPosition::Center => "center",
+            WatermarkPosition::Top => "top",
+            WatermarkPosition::Bottom => "bottom",
+            WatermarkPosition::Left => "left",
+            WatermarkPosition::Right => "right",
+            WatermarkPosition::TopLeft => "top-left",
+            WatermarkPosition::TopRight => "top-right",
+            WatermarkPosition::BottomLeft => "bottom-left",
+            WatermarkPosition::BottomRight => "bottom-right",
+        }
+    }
+}
+
+/// Format of the watermarked image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    #[default]
+    Png,
+    Jpg,
+}
+
+impl ImageFormat {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpg => "jpg",
+        }
+    }
+}
+
+/// Parameters of an image watermark call, every setting left to `None` uses the neutrinoapi.com default.
+///
+/// The parameters can be serialized, e.g. to persist a pending call and send it later.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WatermarkParams {
+    image: ImageSource,
+    watermark: ImageSource,
+    #[serde(default)]
+    position: Option<WatermarkPosition>,
+    #[serde(default)]
+    opacity: Option<u8>,
+    #[serde(default)]
+    resize: Option<(u32, u32)>,
+    #[serde(default)]
+    format: Option<ImageFormat>,
+}
+
+impl WatermarkParams {
+    /// Stamp `watermark` onto `image`.
+    pub fn new(image: ImageSource, watermark: ImageSource) -> Self {
+        WatermarkParams {
+            image,
+            watermark,
+            position: None,
+            opacity: None,
+            resize: None,
+            format: None,
+        }
+    }
+
+    /// Set the position of the watermark, centered by default.
+    pub fn position(mut self, position: WatermarkPosition) -> Self {
+        self.position = Some(position);
+        self
+    }
+
+    /// Set the opacity of the watermark, from 0 to 100.
+    pub fn opacity(mut self, opacity: u8) -> Self {
+        self.opacity = Some(opacity.min(100));
+        self
+    }
+
+    /// Resize the watermarked image to `width` x `height` pixels.
+    pub fn resize(mut self, width: u32, height: u32) -> Self {
+        self.resize = Some((width, height));
+        self
+    }
+
+    /// Set the format of the watermarked image.
+    pub fn format(mut self, format: ImageFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Returns the settings as form fields.
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = Vec::new();
+        if let Some(position) = self.position {
+            fields.push(("position", position.as_str().to_owned()));
+        }
+        if let Some(opacity) = self.opacity {
+            fields.push(("opacity", opacity.to_string()));
+        }
+        if let Some((width, height)) = self.resize {
+            fields.push(("width", width.to_string()));
+            fields.push(("height", height.to_string()));
+        }
+        if let Some(format) = self.format {
+            fields.push(("format", format.as_str().to_owned()));
+        }
+        fields
+    }
+
+    /// Returns the content type and the body of the call.
+    fn body(&self) -> (String, Vec<u8>) {
+        let images = [("image", &self.image), ("watermark", &self.watermark)];
+        if let (ImageSource::Url(image), ImageSource::Url(watermark)) =
+            (&self.image, &self.watermark)
+        {
+            let mut form = form_urlencoded::Serializer::new(String::new());
+            form.append_pair("image", image)
+                .append_pair("watermark", watermark);
+            for (name, value) in self.fields() {
+                form.append_pair(name, &value);
+            }
+            return (
+                "application/x-www-form-urlencoded".to_owned(),
+                form.finish().into_bytes(),
+            );
+        }
+
+        let mut multipart = Multipart::new();
+        for (name, source) in images {
+            match source {
+                ImageSource::Url(url) => multipart.text(name, url),
+                ImageSource::Bytes {
+                    data,
+                    filename,
+                    content_type,
+                } => multipart.file(name, filename, content_type, data),
+            };
+        }
+        for (name, value) in self.fields() {
+            multipart.text(name, &value);
+        }
+        multipart.finish()
+    }
+}
+
+pub struct ImageWatermark<'a> {
+    pub(crate) neutral: &'a Neutral,
+    pub(crate) context: CallContext,
+}
+
+impl<'a> ImageWatermark<'a> {
+    /// Attach a [CallContext](../context/struct.CallContext.html) to the calls.
+    pub fn context(mut self, context: CallContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Bound each attempt of the call by `timeout` instead of the client default, see [CallContext::timeout](../context/struct.CallContext.html#structfield.timeout).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.context.timeout = Some(timeout);
+        self
+    }
+
+    /// Send an image watermark request to neutrinoapi.com, returns the watermarked image.
+    ///
+    /// The call is never cached nor retried.
+    pub async fn send(&self, params: &WatermarkParams) -> Result<Rendered, Error> {
+        let response = self
+            .neutral
+            .execute_stream("image-watermark", &self.context, || {
+                build_request(self.neutral, params, &self.context)
+            })
+            .await?;
+        let content_type = response.headers().get(CONTENT_TYPE).cloned();
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        Ok(Rendered { content_type, body })
+    }
+}
+
+/// Build the image watermark request of `params`, without sending it.
+pub(crate) fn build_request(
+    neutral: &Neutral,
+    params: &WatermarkParams,
+    context: &CallContext,
+) -> Result<Request<Body>, Error> {
+    let (content_type, body) = params.body();
+    Ok(neutral
+        .request_builder("/image-watermark".to_owned(), context)?
+        .method(Method::POST)
+        .header(CONTENT_TYPE, content_type)
+        .body(Body::from(body))?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ApiAuth;
+    use mockito::{mock, Matcher};
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\nwatermarked";
+
+    #[test]
+    fn test_watermark_params_serde() {
+        let params = WatermarkParams::new(
+            ImageSource::bytes(PNG, "image/png"),
+            ImageSource::url("https://example.com/logo.png"),
+        )
+        .position(WatermarkPosition::BottomRight)
+        .opacity(40);
+        let json = serde_json::to_value(&params).unwrap();
+        assert_eq!(
+            json["watermark"],
+            serde_json::json!({"url": "https://example.com/logo.png"})
+        );
+        assert_eq!(json["position"], "bottom-right");
+        assert_eq!(
+            serde_json::from_value::<WatermarkParams>(json).unwrap(),
+            params
+        );
+    }
+
+    fn neutral() -> Neutral {
+        Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_image_watermark_urls() {
+        let _m = mock("POST", "/image-watermark")
+            .match_header("content-type", "application/x-www-form-urlencoded")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("image".into(), "https://example.com/product.png".into()),
+                Matcher::UrlEncoded("watermark".into(), "https://example.com/logo.png".into()),
+                Matcher::UrlEncoded("position".into(), "bottom-right".into()),
+                Matcher::UrlEncoded("opacity".into(), "40".into()),
+                Matcher::UrlEncoded("width".into(), "800".into()),
+                Matcher::UrlEncoded("height".into(), "600".into()),
+                Matcher::UrlEncoded("format".into(), "png".into()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_body(PNG)
+            .expect(1)
+            .create();
+
+        let params = WatermarkParams::new(
+            ImageSource::url("https://example.com/product.png"),
+            ImageSource::url("https://example.com/logo.png"),
+        )
+        .position(WatermarkPosition::BottomRight)
+        .opacity(40)
+        .resize(800, 600)
+        .format(ImageFormat::Png);
+        let watermarked = neutral().image_watermark().send(&params).await.unwrap();
+        assert_eq!(watermarked.body, PNG);
+        assert_eq!(watermarked.content_type.unwrap(), "image/png");
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_image_watermark_upload() {
+        let _m = mock("POST", "/image-watermark")
+            .match_header(
+                "content-type",
+                Matcher::Regex("^multipart/form-data; boundary=neutral-[0-9a-f]{16}$".into()),
+            )
+            .match_body(Matcher::AllOf(vec![
+                Matcher::Regex(
+                    "(?s)^--(neutral-[0-9a-f]{16})\r\nContent-Disposition: form-data; name=\"image\"; filename=\"image\"\r\nContent-Type: image/jpeg\r\n\r\nproduct-bytes\r\n".into(),
+                ),
+                Matcher::Regex(
+                    "(?s)\r\n--neutral-[0-9a-f]{16}\r\nContent-Disposition: form-data; name=\"watermark\"\r\n\r\nhttps://example.com/logo.png\r\n".into(),
+                ),
+                Matcher::Regex("(?s)name=\"opacity\"\r\n\r\n25\r\n--neutral-[0-9a-f]{16}--\r\n$".into()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "image/png")
+            .with_body(PNG)
+            .expect(1)
+            .create();
+
+        let params = WatermarkParams::new(
+            ImageSource::bytes(&b"product-bytes"[..], "image/jpeg"),
+            ImageSource::url("https://example.com/logo.png"),
+        )
+        .opacity(25);
+        let watermarked = neutral().image_watermark().send(&params).await.unwrap();
+        assert_eq!(watermarked.body, PNG);
+        _m.assert();
+    }
+}
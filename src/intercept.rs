@@ -0,0 +1,122 @@
+//! # Intercept module
+//! Edit every request just before it is sent, e.g. to add a correlation header or to audit the final uris.
+//!
+//! An interceptor registered with [NeutralBuilder::interceptor](../builder/struct.NeutralBuilder.html#method.interceptor) receives every attempt of a call,
+//! with its final uri and the credential headers attached. Interceptors run in registration order, before the request is signed,
+//! so a signature covers the headers they add.
+//!
+//! The `user-id` and `api-key` credential headers are reserved: whatever an interceptor does to them, they are restored before the request is sent.
+//!
+//! ```ignore
+//! let neutral = Neutral::builder("https://neutrinoapi.net", api_auth)
+//!     .interceptor(|req| {
+//!         if let Ok(value) = HeaderValue::from_str(&CORRELATION_ID.get()) {
+//!             req.headers_mut().insert("x-correlation-id", value);
+//!         }
+//!     })
+//!     .build()?;
+//! ```
+
+use std::{fmt, sync::Arc};
+
+use hyper::{Body, Request};
+
+/// Edit a request before it is sent.
+pub type InterceptFn = Arc<dyn Fn(&mut Request<Body>) + Send + Sync>;
+
+const CREDENTIAL_HEADERS: [&str; 2] = ["user-id", "api-key"];
+
+/// The interceptors registered on a client.
+#[derive(Clone, Default)]
+pub(crate) struct Interceptors(pub(crate) Vec<InterceptFn>);
+
+impl fmt::Debug for Interceptors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Interceptors").field(&self.0.len()).finish()
+    }
+}
+
+impl Interceptors {
+    /// Run every interceptor on `req` in registration order, then restore its credential headers.
+    pub(crate) fn apply(&self, req: &mut Request<Body>) {
+        if self.0.is_empty() {
+            return;
+        }
+
+        let credentials: Vec<_> = CREDENTIAL_HEADERS
+            .iter()
+            .map(|name| (*name, req.headers().get(*name).cloned()))
+            .collect();
+        for interceptor in &self.0 {
+            interceptor(req);
+        }
+        for (name, value) in credentials {
+            match value {
+                Some(value) => req.headers_mut().insert(name, value),
+                None => req.headers_mut().remove(name),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{fixtures::IP_INFO_BODY, ApiAuth, Neutral};
+    use http::HeaderValue;
+    use mockito::{mock, Matcher};
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn test_interceptors() {
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.246$".into()))
+            .match_header("x-correlation-id", "job-7")
+            .match_header("x-interceptors", "first,second")
+            .match_header("api-key", "test")
+            .with_status(200)
+            .with_body(IP_INFO_BODY)
+            .expect(1)
+            .create();
+
+        let uris = Arc::new(Mutex::new(Vec::new()));
+        let seen = uris.clone();
+        let neutral = Neutral::builder(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .interceptor(|req| {
+            let headers = req.headers_mut();
+            headers.insert("x-correlation-id", HeaderValue::from_static("job-7"));
+            headers.insert("x-interceptors", HeaderValue::from_static("first"));
+            // Credential headers are restored after the interceptors.
+            headers.insert("api-key", HeaderValue::from_static("stolen"));
+        })
+        .interceptor(move |req| {
+            assert_eq!(req.headers()["user-id"], "User");
+            seen.lock().unwrap().push(req.uri().to_string());
+            let order = format!(
+                "{},second",
+                req.headers()["x-interceptors"].to_str().unwrap()
+            );
+            req.headers_mut()
+                .insert("x-interceptors", HeaderValue::from_str(&order).unwrap());
+        })
+        .build()
+        .unwrap();
+
+        neutral
+            .ip_info()
+            .send("203.0.113.246".parse().unwrap())
+            .await
+            .unwrap();
+        _m.assert();
+        assert_eq!(
+            *uris.lock().unwrap(),
+            vec![format!(
+                "{}/ip-info?ip=203.0.113.246",
+                mockito::server_url()
+            )]
+        );
+    }
+}
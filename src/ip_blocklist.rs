@@ -22,44 +22,185 @@
 //! * Bots and botnets
 //! * Exploit scanners
 //! * Brute-force crackers
+//!
+//! The VPN lookup is a paid add-on of neutrinoapi.com, requested only with [IpBlocklistOptions::vpn_lookup](./struct.IpBlocklistOptions.html#structfield.vpn_lookup).
+//! Previous versions requested it on every call, use [send_with](./struct.IpBlocklist.html#method.send_with) to keep doing so.
 
-use crate::{Error, Neutral};
-use http::Method;
-use hyper::Body;
-use neutral_types::ip_blocklist::IpBlocklistResponse;
-use std::net::IpAddr;
+use crate::{
+    bogon, context::CallContext, meta::ResponseMeta, params, pipeline::Fetched,
+    report::ordered_concurrent, Error, Neutral,
+};
+use hyper::{body::Bytes, Body, Request};
+/// The response of the endpoint, defined by neutral_types and re-exported so it can be named without depending on it.
+pub use neutral_types::ip_blocklist::IpBlocklistResponse;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{net::IpAddr, time::Duration};
 
 #[cfg(test)]
 use mockito;
 
+/// Options of an ip blocklist call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct IpBlocklistOptions {
+    /// Also detect the addresses of public VPN servers, a paid add-on, `false` by default.
+    pub vpn_lookup: bool,
+}
+
+impl IpBlocklistOptions {
+    /// Returns the cache key of `ip_addr` sent with these options.
+    fn canonical_input(&self, ip_addr: IpAddr) -> String {
+        if self.vpn_lookup {
+            format!("{}:vpn-lookup", ip_addr)
+        } else {
+            ip_addr.to_string()
+        }
+    }
+}
+
+/// Parameters of an ip blocklist call, e.g. to persist a pending call and send it later with [IpBlocklist::send_params].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IpBlocklistParams {
+    pub ip: IpAddr,
+    /// See [IpBlocklistOptions::vpn_lookup].
+    #[serde(default)]
+    pub vpn_lookup: bool,
+}
+
 pub struct IpBlocklist<'a> {
     pub(crate) neutral: &'a Neutral,
+    pub(crate) context: CallContext,
 }
 
 impl<'a> IpBlocklist<'a> {
-    /// Send an ip blocklist request to neutrinoapi.com
+    /// Attach a [CallContext](../context/struct.CallContext.html) to the calls.
+    pub fn context(mut self, context: CallContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Bound each attempt of the call by `timeout` instead of the client default, see [CallContext::timeout](../context/struct.CallContext.html#structfield.timeout).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.context.timeout = Some(timeout);
+        self
+    }
+
+    /// Send an ip blocklist request to neutrinoapi.com, with the default [IpBlocklistOptions].
     pub async fn send(&self, ip_addr: IpAddr) -> Result<IpBlocklistResponse, Error> {
-        let path_and_query = format!(
-            "/ip-blocklist?output-case=snake&ip={}&vpn-lookup=true",
-            ip_addr.to_string()
-        );
+        self.send_with(ip_addr, IpBlocklistOptions::default()).await
+    }
 
-        let request = self
-            .neutral
-            .request_builder(path_and_query)?
-            .method(Method::GET)
-            .body(Body::empty())?;
+    /// Same as [send](#method.send), with `options`.
+    pub async fn send_with(
+        &self,
+        ip_addr: IpAddr,
+        options: IpBlocklistOptions,
+    ) -> Result<IpBlocklistResponse, Error> {
+        self.send_with_meta_and_options(ip_addr, options)
+            .await
+            .map(|(_, response)| response)
+    }
 
-        let body = self.neutral.request(request).await?;
-        let response: IpBlocklistResponse = serde_json::from_slice(&body)?;
-        Ok(response)
+    /// Same as [send_with](#method.send_with), with the ip address and options of `params`.
+    pub async fn send_params(
+        &self,
+        params: &IpBlocklistParams,
+    ) -> Result<IpBlocklistResponse, Error> {
+        let options = IpBlocklistOptions {
+            vpn_lookup: params.vpn_lookup,
+        };
+        self.send_with(params.ip, options).await
+    }
+
+    /// Send an ip blocklist request for each of `ip_addrs`, at most `concurrency` at the same time.
+    ///
+    /// The results are in the order of `ip_addrs`, a failing request does not abort the others.
+    pub async fn send_many(
+        &self,
+        ip_addrs: Vec<IpAddr>,
+        concurrency: usize,
+    ) -> Vec<(IpAddr, Result<IpBlocklistResponse, Error>)> {
+        ordered_concurrent(ip_addrs, concurrency, |ip_addr| self.send(ip_addr)).await
+    }
+
+    /// Same as [send](#method.send), also returning the [ResponseMeta](../meta/struct.ResponseMeta.html) of the response.
+    pub async fn send_with_meta(
+        &self,
+        ip_addr: IpAddr,
+    ) -> Result<(ResponseMeta, IpBlocklistResponse), Error> {
+        self.send_with_meta_and_options(ip_addr, IpBlocklistOptions::default())
+            .await
+    }
+
+    async fn send_with_meta_and_options(
+        &self,
+        ip_addr: IpAddr,
+        options: IpBlocklistOptions,
+    ) -> Result<(ResponseMeta, IpBlocklistResponse), Error> {
+        let canonical_input = options.canonical_input(ip_addr);
+        let fetched = self.fetch(ip_addr, options).await?;
+        let response: IpBlocklistResponse =
+            self.neutral.decode_lenient("ip-blocklist", &fetched.body)?;
+        if !fetched.meta.is_cached {
+            self.neutral
+                .cache_insert("ip-blocklist", &canonical_input, fetched.body);
+        }
+        Ok((fetched.meta, response))
+    }
+
+    /// Same as [send](#method.send), returning the raw body of the response.
+    pub async fn send_raw(&self, ip_addr: IpAddr) -> Result<Bytes, Error> {
+        Ok(self
+            .fetch(ip_addr, IpBlocklistOptions::default())
+            .await?
+            .body)
+    }
+
+    /// Same as [send](#method.send), decoding the response into any `T`, e.g. a struct holding fields unknown to [IpBlocklistResponse].
+    pub async fn send_as<T: DeserializeOwned>(&self, ip_addr: IpAddr) -> Result<T, Error> {
+        let fetched = self.fetch(ip_addr, IpBlocklistOptions::default()).await?;
+        self.neutral.decode("ip-blocklist", &fetched.body)
+    }
+
+    /// Same as [send](#method.send), decoding the response into a [serde_json::Value].
+    pub async fn send_value(&self, ip_addr: IpAddr) -> Result<serde_json::Value, Error> {
+        self.send_as(ip_addr).await
+    }
+
+    /// Send the request, without decoding its response.
+    async fn fetch(&self, ip_addr: IpAddr, options: IpBlocklistOptions) -> Result<Fetched, Error> {
+        bogon::check(self.neutral.reject_bogons, ip_addr)?;
+        let canonical_input = options.canonical_input(ip_addr);
+        self.neutral
+            .execute(
+                "ip-blocklist",
+                Some(&canonical_input),
+                &self.context,
+                || build_request(self.neutral, ip_addr, options, &self.context),
+            )
+            .await
     }
 }
 
+/// Build the ip blocklist request of `ip_addr`, without sending it.
+pub(crate) fn build_request(
+    neutral: &Neutral,
+    ip_addr: IpAddr,
+    options: IpBlocklistOptions,
+    context: &CallContext,
+) -> Result<Request<Body>, Error> {
+    let mut params = params();
+    params.append_pair("ip", &ip_addr.to_string());
+    if options.vpn_lookup {
+        params.append_pair("vpn-lookup", "true");
+    }
+    neutral.get_request("/ip-blocklist", params.finish(), context)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::ApiAuth;
+    use http::Method;
     use mockito::{mock, Matcher};
     use std::net::{IpAddr, Ipv4Addr};
 
@@ -140,7 +281,7 @@ mod test {
         for test in &tests {
             let Args { ip_addr } = test.args;
             let ip_blocklist_res = neutral.ip_blocklist().send(ip_addr).await;
-            let ip_blocklist_result = ip_blocklist_res.map(|ip_blocklist| ip_blocklist.clone());
+            let ip_blocklist_result = ip_blocklist_res;
             let expected = test.expected;
 
             assert_eq!(
@@ -151,4 +292,134 @@ mod test {
             )
         }
     }
+
+    #[test]
+    fn test_ip_blocklist_build_request() {
+        struct TestingData {
+            pub name: String,
+            pub args: (IpAddr, IpBlocklistOptions),
+            pub expected: &'static str,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Using an ipv4 address".to_owned(),
+                args: (
+                    IpAddr::V4(Ipv4Addr::new(128, 0, 0, 1)),
+                    IpBlocklistOptions::default(),
+                ),
+                expected: "/ip-blocklist?ip=128.0.0.1",
+            },
+            TestingData {
+                name: "Using an ipv6 address".to_owned(),
+                args: (
+                    "2001:db8::1".parse().unwrap(),
+                    IpBlocklistOptions::default(),
+                ),
+                expected: "/ip-blocklist?ip=2001%3Adb8%3A%3A1",
+            },
+            TestingData {
+                name: "Using the vpn lookup".to_owned(),
+                args: (
+                    IpAddr::V4(Ipv4Addr::new(128, 0, 0, 1)),
+                    IpBlocklistOptions { vpn_lookup: true },
+                ),
+                expected: "/ip-blocklist?ip=128.0.0.1&vpn-lookup=true",
+            },
+        ];
+
+        let neutral = Neutral::try_new(
+            "https://neutrinoapi.net",
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        for test in &tests {
+            let (ip_addr, options) = test.args;
+            let request =
+                build_request(&neutral, ip_addr, options, &CallContext::default()).unwrap();
+            assert_eq!(request.method(), Method::GET, "{}", test.name);
+            assert_eq!(
+                request.uri().path_and_query().unwrap().as_str(),
+                test.expected,
+                "{}",
+                test.name
+            );
+            assert_eq!(request.headers()["user-id"], "User", "{}", test.name);
+            assert_eq!(request.headers()["api-key"], "test", "{}", test.name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ip_blocklist_send_with_options() {
+        let _without = mock("GET", "/ip-blocklist")
+            .match_query(Matcher::Exact("ip=203.0.113.212".to_owned()))
+            .with_status(200)
+            .with_body(crate::fixtures::IP_BLOCKLIST_BODY)
+            .expect(1)
+            .create();
+        let _with = mock("GET", "/ip-blocklist")
+            .match_query(Matcher::Exact(
+                "ip=203.0.113.212&vpn-lookup=true".to_owned(),
+            ))
+            .with_status(200)
+            .with_body(crate::fixtures::IP_BLOCKLIST_BODY.replace(
+                r#""is_vpn": false,"#,
+                r#""is_vpn": true, "vpn_domain": "vpn.example.com","#,
+            ))
+            .expect(1)
+            .create();
+
+        let neutral = Neutral::builder(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .cache(crate::cache::CacheConfig::default())
+        .build()
+        .unwrap();
+
+        let ip_addr: IpAddr = "203.0.113.212".parse().unwrap();
+        let ip_blocklist = neutral.ip_blocklist();
+        let without = ip_blocklist.send(ip_addr).await.unwrap();
+        let with = ip_blocklist
+            .send_with(ip_addr, IpBlocklistOptions { vpn_lookup: true })
+            .await
+            .unwrap();
+        assert!(!without.is_vpn);
+        assert!(with.is_vpn);
+        // Both are cached under their own key.
+        ip_blocklist.send(ip_addr).await.unwrap();
+        ip_blocklist
+            .send_with(ip_addr, IpBlocklistOptions { vpn_lookup: true })
+            .await
+            .unwrap();
+        _without.assert();
+        _with.assert();
+    }
+
+    #[tokio::test]
+    async fn test_ip_blocklist_params() {
+        let params = IpBlocklistParams {
+            ip: "203.0.113.249".parse().unwrap(),
+            vpn_lookup: true,
+        };
+        let json = serde_json::to_string(&params).unwrap();
+        assert_eq!(
+            serde_json::from_str::<IpBlocklistParams>(&json).unwrap(),
+            params
+        );
+
+        let (neutral, requests) = crate::fixtures::recording(crate::fixtures::IP_BLOCKLIST_BODY);
+        neutral.ip_blocklist().send_params(&params).await.unwrap();
+        let options = IpBlocklistOptions { vpn_lookup: true };
+        neutral
+            .ip_blocklist()
+            .send_with(params.ip, options)
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0], requests[1]);
+    }
 }
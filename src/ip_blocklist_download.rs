@@ -0,0 +1,331 @@
+//! # Ip blocklist download module
+//! Quoted from [neutrinoapi.com](https://www.neutrinoapi.com/api/ip-blocklist-download):
+//!
+//! This API is a direct feed to our IP blocklist data.
+//!
+//! The file is large: it is streamed as a [ByteStream](../download/struct.ByteStream.html) and never buffered in memory.
+//!
+//! ```ignore
+//! let written = neutral
+//!     .ip_blocklist_download()
+//!     .format(BlocklistFormat::Txt)
+//!     .cidr(true)
+//!     .download_to("blocklist.txt")
+//!     .await?;
+//! ```
+//!
+//! [entries](./struct.IpBlocklistDownload.html#method.entries) parses the CSV file into typed entries as it arrives, see the [blocklist](../blocklist/index.html) module.
+
+use hyper::{Body, Request};
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+use std::time::Duration;
+
+use crate::{blocklist::EntryStream, context::CallContext, download::ByteStream, Error, Neutral};
+
+#[cfg(test)]
+use mockito;
+
+/// Format of the downloaded file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlocklistFormat {
+    #[default]
+    Csv,
+    Json,
+    /// One ip address or range per line.
+    Txt,
+}
+
+impl BlocklistFormat {
+    fn as_str(&self) -> &'static str {
+        match self {
+            BlocklistFormat::Csv => "csv",
+            BlocklistFormat::Json => "json",
+            BlocklistFormat::Txt => "txt",
+        }
+    }
+}
+
+/// Options of an ip blocklist download.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct DownloadOptions {
+    format: BlocklistFormat,
+    include_vpn: bool,
+    cidr: bool,
+    ip6: bool,
+}
+
+pub struct IpBlocklistDownload<'a> {
+    pub(crate) neutral: &'a Neutral,
+    pub(crate) context: CallContext,
+    options: DownloadOptions,
+}
+
+impl<'a> IpBlocklistDownload<'a> {
+    pub(crate) fn new(neutral: &'a Neutral) -> Self {
+        IpBlocklistDownload {
+            neutral,
+            context: CallContext::default(),
+            options: DownloadOptions::default(),
+        }
+    }
+
+    /// Attach a [CallContext](../context/struct.CallContext.html) to the calls.
+    pub fn context(mut self, context: CallContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Bound each attempt of the call by `timeout` instead of the client default, see [CallContext::timeout](../context/struct.CallContext.html#structfield.timeout).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.context.timeout = Some(timeout);
+        self
+    }
+
+    /// Set the format of the file, CSV by default.
+    pub fn format(mut self, format: BlocklistFormat) -> Self {
+        self.options.format = format;
+        self
+    }
+
+    /// Include the ip addresses of VPN servers, `false` by default.
+    pub fn include_vpn(mut self, include_vpn: bool) -> Self {
+        self.options.include_vpn = include_vpn;
+        self
+    }
+
+    /// Group the ip addresses in CIDR ranges, `false` by default.
+    pub fn cidr(mut self, cidr: bool) -> Self {
+        self.options.cidr = cidr;
+        self
+    }
+
+    /// Download the IPv6 blocklist instead of the IPv4 one, `false` by default.
+    pub fn ip6(mut self, ip6: bool) -> Self {
+        self.options.ip6 = ip6;
+        self
+    }
+
+    /// Send an ip blocklist download request to neutrinoapi.com, returns the file as a stream of chunks.
+    pub async fn send(&self) -> Result<ByteStream, Error> {
+        let response = self
+            .neutral
+            .execute_stream("ip-blocklist-download", &self.context, || {
+                build_request(self.neutral, &self.options, &self.context)
+            })
+            .await?;
+        Ok(ByteStream::new(response.into_body()))
+    }
+
+    /// Download the file in the CSV format, whatever the configured format, returns its parsed entries as a stream.
+    pub async fn entries(&self) -> Result<EntryStream, Error> {
+        let options = DownloadOptions {
+            format: BlocklistFormat::Csv,
+            ..self.options
+        };
+        let response = self
+            .neutral
+            .execute_stream("ip-blocklist-download", &self.context, || {
+                build_request(self.neutral, &options, &self.context)
+            })
+            .await?;
+        Ok(EntryStream::new(ByteStream::new(response.into_body())))
+    }
+
+    /// Download the file to `path`, replacing any existing file, and returns the number of bytes written.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn download_to<P: AsRef<Path>>(&self, path: P) -> Result<u64, Error> {
+        self.send().await?.write_to(path).await
+    }
+}
+
+/// Build the ip blocklist download request, without sending it.
+fn build_request(
+    neutral: &Neutral,
+    options: &DownloadOptions,
+    context: &CallContext,
+) -> Result<Request<Body>, Error> {
+    let params = form_urlencoded::Serializer::new(String::new())
+        .append_pair("format", options.format.as_str())
+        .append_pair("include-vpn", &options.include_vpn.to_string())
+        .append_pair("cidr", &options.cidr.to_string())
+        .append_pair("ip6", &options.ip6.to_string())
+        .finish();
+    neutral.get_request("/ip-blocklist-download", params, context)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ApiAuth;
+    use futures::StreamExt;
+    use mockito::{mock, Matcher};
+
+    fn neutral() -> Neutral {
+        Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_ip_blocklist_download_build_request() {
+        struct TestingData {
+            pub name: String,
+            pub args: DownloadOptions,
+            pub expected: &'static str,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Defaults".to_owned(),
+                args: DownloadOptions::default(),
+                expected:
+                    "/ip-blocklist-download?format=csv&include-vpn=false&cidr=false&ip6=false",
+            },
+            TestingData {
+                name: "Every option".to_owned(),
+                args: DownloadOptions {
+                    format: BlocklistFormat::Txt,
+                    include_vpn: true,
+                    cidr: true,
+                    ip6: true,
+                },
+                expected: "/ip-blocklist-download?format=txt&include-vpn=true&cidr=true&ip6=true",
+            },
+        ];
+
+        let neutral = Neutral::try_new(
+            "https://neutrinoapi.net",
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        for test in &tests {
+            let request = build_request(&neutral, &test.args, &CallContext::default()).unwrap();
+            assert_eq!(
+                request.uri().path_and_query().unwrap().as_str(),
+                test.expected,
+                "{}",
+                test.name
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ip_blocklist_download_to() {
+        let line = |i: u32| format!("198.51.{}.{}/32\n", i / 256, i % 256);
+        let _m = mock("GET", "/ip-blocklist-download")
+            .match_query(Matcher::UrlEncoded("format".into(), "txt".into()))
+            .with_status(200)
+            .with_body_from_fn(move |w| {
+                for i in 0..2000 {
+                    w.write_all(line(i).as_bytes())?;
+                }
+                Ok(())
+            })
+            .expect(1)
+            .create();
+        let expected: String = (0..2000).map(line).collect();
+
+        let path =
+            std::env::temp_dir().join(format!("neutral-ip-blocklist-{}.txt", std::process::id()));
+        let written = neutral()
+            .ip_blocklist_download()
+            .format(BlocklistFormat::Txt)
+            .download_to(&path)
+            .await
+            .unwrap();
+        assert_eq!(written, expected.len() as u64);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), expected);
+        std::fs::remove_file(&path).unwrap();
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_ip_blocklist_download_forbidden() {
+        let _m = mock("GET", "/ip-blocklist-download")
+            .match_query(Matcher::UrlEncoded("format".into(), "json".into()))
+            .with_status(403)
+            .with_body(r#"{"api-error": 2, "api-error-msg": "INVALID API KEY"}"#)
+            .create();
+
+        let path = std::env::temp_dir().join(format!(
+            "neutral-ip-blocklist-forbidden-{}.json",
+            std::process::id()
+        ));
+        let result = neutral()
+            .ip_blocklist_download()
+            .format(BlocklistFormat::Json)
+            .download_to(&path)
+            .await;
+        assert!(matches!(result, Err(Error::Unauthorized(_))));
+        assert!(!path.exists(), "no file is created for a failed download");
+    }
+
+    #[tokio::test]
+    async fn test_ip_blocklist_download_entries() {
+        let lines = vec![
+            "ip,is-proxy,is-tor,is-vpn,is-bot,last-seen\n".to_owned(),
+            "192.0.2.0/24,true,false,false,1,1650000000\n".to_owned(),
+            "192.0.2.300/32,false,true,false,0,1650000001\n".to_owned(),
+            "2001:db8::/32,false,false,true,0,\r\n".to_owned(),
+            "198.51.100.7/32,false,maybe,false,0,1650000002\n".to_owned(),
+            "198.51.100.8/32,false,false,false,0,1650000003".to_owned(),
+        ];
+        let _m = mock("GET", "/ip-blocklist-download")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("format".into(), "csv".into()),
+                Matcher::UrlEncoded("cidr".into(), "true".into()),
+            ]))
+            .with_status(200)
+            .with_body_from_fn(move |w| {
+                // Split the lines across chunks.
+                for line in &lines {
+                    let (head, tail) = line.split_at(line.len() / 2);
+                    w.write_all(head.as_bytes())?;
+                    w.flush()?;
+                    w.write_all(tail.as_bytes())?;
+                    w.flush()?;
+                }
+                Ok(())
+            })
+            .expect(1)
+            .create();
+
+        let entries: Vec<_> = neutral()
+            .ip_blocklist_download()
+            .format(BlocklistFormat::Txt)
+            .cidr(true)
+            .entries()
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        _m.assert();
+
+        assert_eq!(entries.len(), 5);
+        let first = entries[0].as_ref().unwrap();
+        assert_eq!(first.network.to_string(), "192.0.2.0/24");
+        assert!(first.categories.is_proxy && first.categories.is_bot);
+        assert!(!first.categories.is_tor);
+        assert_eq!(first.last_seen, Some(1650000000));
+        assert!(matches!(
+            entries[1],
+            Err(Error::MalformedBlocklistLine { line: 3, .. })
+        ));
+        let third = entries[2].as_ref().unwrap();
+        assert_eq!(third.network.to_string(), "2001:db8::/32");
+        assert!(third.categories.is_vpn);
+        assert_eq!(third.last_seen, None);
+        assert!(matches!(
+            entries[3],
+            Err(Error::MalformedBlocklistLine { line: 5, .. })
+        ));
+        assert_eq!(
+            entries[4].as_ref().unwrap().network.to_string(),
+            "198.51.100.8/32"
+        );
+    }
+}
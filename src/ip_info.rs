@@ -15,41 +15,181 @@
 //! * Fraud analysis
 //! * Traffic analysis
 //! * Access controls
+//!
+//! The reverse DNS lookup filling `hostname` and `host_domain` is requested only with [IpInfoOptions::reverse_lookup](./struct.IpInfoOptions.html#structfield.reverse_lookup).
 
-use http::Method;
-use hyper::Body;
-use neutral_types::ip_info::IpInfoResponse;
-use std::net::IpAddr;
+use hyper::{body::Bytes, Body, Request};
+/// The response of the endpoint, defined by neutral_types and re-exported so it can be named without depending on it.
+pub use neutral_types::ip_info::IpInfoResponse;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{net::IpAddr, time::Duration};
 
-use crate::{Error, Neutral};
+use crate::{
+    bogon, context::CallContext, meta::ResponseMeta, params, pipeline::Fetched,
+    report::ordered_concurrent, Error, Neutral,
+};
 
 #[cfg(test)]
 use mockito;
 
+/// Options of an ip info call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct IpInfoOptions {
+    /// Do a reverse DNS (PTR) lookup filling `hostname` and `host_domain`, `false` by default.
+    pub reverse_lookup: bool,
+}
+
+impl IpInfoOptions {
+    /// Returns the cache key of `ip_addr` sent with these options.
+    fn canonical_input(&self, ip_addr: IpAddr) -> String {
+        if self.reverse_lookup {
+            format!("{}:reverse-lookup", ip_addr)
+        } else {
+            ip_addr.to_string()
+        }
+    }
+}
+
+/// Parameters of an ip info call, e.g. to persist a pending call and send it later with [IpInfo::send_params].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IpInfoParams {
+    pub ip: IpAddr,
+    /// See [IpInfoOptions::reverse_lookup].
+    #[serde(default)]
+    pub reverse_lookup: bool,
+}
+
 pub struct IpInfo<'a> {
     pub(crate) neutral: &'a Neutral,
+    pub(crate) context: CallContext,
 }
 
 impl<'a> IpInfo<'a> {
-    /// Send an ip info request to neutrinoapi.com
+    /// Attach a [CallContext](../context/struct.CallContext.html) to the calls.
+    pub fn context(mut self, context: CallContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Bound each attempt of the call by `timeout` instead of the client default, see [CallContext::timeout](../context/struct.CallContext.html#structfield.timeout).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.context.timeout = Some(timeout);
+        self
+    }
+
+    /// Send an ip info request to neutrinoapi.com, with the default [IpInfoOptions].
     pub async fn send(&self, ip_addr: IpAddr) -> Result<IpInfoResponse, Error> {
-        let path_and_query = format!("/ip-info?output-case=snake&ip={}", ip_addr.to_string());
-        let request = self
-            .neutral
-            .request_builder(path_and_query)?
-            .method(Method::GET)
-            .body(Body::empty())?;
+        self.send_with(ip_addr, IpInfoOptions::default()).await
+    }
+
+    /// Same as [send](#method.send), with `options`.
+    pub async fn send_with(
+        &self,
+        ip_addr: IpAddr,
+        options: IpInfoOptions,
+    ) -> Result<IpInfoResponse, Error> {
+        self.send_with_meta_and_options(ip_addr, options)
+            .await
+            .map(|(_, response)| response)
+    }
+
+    /// Same as [send_with](#method.send_with), with the ip address and options of `params`.
+    pub async fn send_params(&self, params: &IpInfoParams) -> Result<IpInfoResponse, Error> {
+        let options = IpInfoOptions {
+            reverse_lookup: params.reverse_lookup,
+        };
+        self.send_with(params.ip, options).await
+    }
+
+    /// Send an ip info request for each of `ip_addrs`, at most `concurrency` at the same time.
+    ///
+    /// The results are in the order of `ip_addrs`, a failing request does not abort the others.
+    pub async fn send_many(
+        &self,
+        ip_addrs: Vec<IpAddr>,
+        concurrency: usize,
+    ) -> Vec<(IpAddr, Result<IpInfoResponse, Error>)> {
+        ordered_concurrent(ip_addrs, concurrency, |ip_addr| self.send(ip_addr)).await
+    }
+
+    /// Same as [send](#method.send), also returning the [ResponseMeta](../meta/struct.ResponseMeta.html) of the response.
+    pub async fn send_with_meta(
+        &self,
+        ip_addr: IpAddr,
+    ) -> Result<(ResponseMeta, IpInfoResponse), Error> {
+        self.send_with_meta_and_options(ip_addr, IpInfoOptions::default())
+            .await
+    }
+
+    async fn send_with_meta_and_options(
+        &self,
+        ip_addr: IpAddr,
+        options: IpInfoOptions,
+    ) -> Result<(ResponseMeta, IpInfoResponse), Error> {
+        let canonical_input = options.canonical_input(ip_addr);
+        let fetched = self.fetch(ip_addr, options).await?;
+        let response: IpInfoResponse = self.neutral.decode_lenient("ip-info", &fetched.body)?;
+        if fetched.meta.is_cached {
+            return Ok((fetched.meta, response));
+        }
+        if response.is_valid {
+            self.neutral
+                .cache_insert("ip-info", &canonical_input, fetched.body);
+        } else {
+            self.neutral
+                .negative_cache_insert("ip-info", &canonical_input, fetched.body);
+        }
+        Ok((fetched.meta, response))
+    }
+
+    /// Same as [send](#method.send), returning the raw body of the response.
+    pub async fn send_raw(&self, ip_addr: IpAddr) -> Result<Bytes, Error> {
+        Ok(self.fetch(ip_addr, IpInfoOptions::default()).await?.body)
+    }
+
+    /// Same as [send](#method.send), decoding the response into any `T`, e.g. a struct holding fields unknown to [IpInfoResponse].
+    pub async fn send_as<T: DeserializeOwned>(&self, ip_addr: IpAddr) -> Result<T, Error> {
+        let fetched = self.fetch(ip_addr, IpInfoOptions::default()).await?;
+        self.neutral.decode("ip-info", &fetched.body)
+    }
+
+    /// Same as [send](#method.send), decoding the response into a [serde_json::Value].
+    pub async fn send_value(&self, ip_addr: IpAddr) -> Result<serde_json::Value, Error> {
+        self.send_as(ip_addr).await
+    }
+
+    /// Send the request, without decoding its response.
+    async fn fetch(&self, ip_addr: IpAddr, options: IpInfoOptions) -> Result<Fetched, Error> {
+        bogon::check(self.neutral.reject_bogons, ip_addr)?;
+        let canonical_input = options.canonical_input(ip_addr);
+        self.neutral
+            .execute("ip-info", Some(&canonical_input), &self.context, || {
+                build_request(self.neutral, ip_addr, options, &self.context)
+            })
+            .await
+    }
+}
 
-        let body = self.neutral.request(request).await?;
-        let response: IpInfoResponse = serde_json::from_slice(&body)?;
-        Ok(response)
+/// Build the ip info request of `ip_addr`, without sending it.
+pub(crate) fn build_request(
+    neutral: &Neutral,
+    ip_addr: IpAddr,
+    options: IpInfoOptions,
+    context: &CallContext,
+) -> Result<Request<Body>, Error> {
+    let mut params = params();
+    params.append_pair("ip", &ip_addr.to_string());
+    if options.reverse_lookup {
+        params.append_pair("reverse-lookup", "true");
     }
+    neutral.get_request("/ip-info", params.finish(), context)
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::ApiAuth;
+    use crate::{fixtures, meta::RateLimit, ApiAuth};
+    use http::Method;
     use mockito::{mock, Matcher};
     use neutral_types::NeutrinoTimeZoneResponse;
     use std::net::{IpAddr, Ipv4Addr};
@@ -138,7 +278,7 @@ mod test {
         }];
 
         let neutral = Neutral::try_new(
-            "http://localhost:1234",
+            &mockito::server_url(),
             ApiAuth::new("User".to_string(), "test".to_string()),
         )
         .unwrap();
@@ -146,7 +286,7 @@ mod test {
         for test in &tests {
             let Args { ip_addr } = test.args;
             let ip_info_res = neutral.ip_info().send(ip_addr).await;
-            let ip_info_result = ip_info_res.map(|ip_info| ip_info.clone());
+            let ip_info_result = ip_info_res;
             let expected = test.expected;
 
             assert_eq!(
@@ -157,4 +297,296 @@ mod test {
             )
         }
     }
+
+    #[test]
+    fn test_ip_info_build_request() {
+        struct TestingData {
+            pub name: String,
+            pub args: (IpAddr, IpInfoOptions),
+            pub expected: &'static str,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Using an ipv4 address".to_owned(),
+                args: (
+                    IpAddr::V4(Ipv4Addr::new(128, 0, 0, 1)),
+                    IpInfoOptions::default(),
+                ),
+                expected: "/ip-info?ip=128.0.0.1",
+            },
+            TestingData {
+                name: "Using an ipv6 address".to_owned(),
+                args: ("2001:db8::1".parse().unwrap(), IpInfoOptions::default()),
+                expected: "/ip-info?ip=2001%3Adb8%3A%3A1",
+            },
+            TestingData {
+                name: "Using the reverse lookup".to_owned(),
+                args: (
+                    IpAddr::V4(Ipv4Addr::new(128, 0, 0, 1)),
+                    IpInfoOptions {
+                        reverse_lookup: true,
+                    },
+                ),
+                expected: "/ip-info?ip=128.0.0.1&reverse-lookup=true",
+            },
+        ];
+
+        let neutral = Neutral::try_new(
+            "https://neutrinoapi.net",
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        for test in &tests {
+            let (ip_addr, options) = test.args;
+            let request =
+                build_request(&neutral, ip_addr, options, &CallContext::default()).unwrap();
+            assert_eq!(request.method(), Method::GET, "{}", test.name);
+            assert_eq!(
+                request.uri().path_and_query().unwrap().as_str(),
+                test.expected,
+                "{}",
+                test.name
+            );
+            assert_eq!(request.headers()["user-id"], "User", "{}", test.name);
+            assert_eq!(request.headers()["api-key"], "test", "{}", test.name);
+            assert!(
+                !request.headers().contains_key("x-request-id"),
+                "{}",
+                test.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_ip_info_build_request_with_context() {
+        let neutral = Neutral::try_new(
+            "https://neutrinoapi.net",
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        let context = CallContext::new().with_correlation_id("4f6c1b2e");
+        let request = build_request(
+            &neutral,
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpInfoOptions::default(),
+            &context,
+        )
+        .unwrap();
+        assert_eq!(request.headers()["x-request-id"], "4f6c1b2e");
+        assert_eq!(request.extensions().get::<CallContext>(), Some(&context));
+    }
+
+    #[tokio::test]
+    async fn test_ip_info_send_with_meta() {
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.140$".into()))
+            .with_status(200)
+            .with_header("x-ratelimit-limit", "1000")
+            .with_header("x-ratelimit-remaining", "42")
+            .with_header("x-ratelimit-reset", "60")
+            .with_body(fixtures::IP_INFO_BODY)
+            .create();
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        let (meta, response) = neutral
+            .ip_info()
+            .send_with_meta("203.0.113.140".parse().unwrap())
+            .await
+            .unwrap();
+        assert!(response.is_valid);
+        assert_eq!(meta.status, http::StatusCode::OK);
+        assert!(!meta.is_cached);
+        assert_eq!(
+            meta.rate_limit,
+            RateLimit {
+                limit: Some(1000),
+                remaining: Some(42),
+                reset: Some(Duration::from_secs(60)),
+            }
+        );
+        assert_eq!(meta.headers["x-ratelimit-remaining"], "42");
+    }
+
+    #[tokio::test]
+    async fn test_ip_info_send_with_meta_without_rate_limit_headers() {
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.141$".into()))
+            .with_status(200)
+            .with_body(fixtures::IP_INFO_BODY)
+            .create();
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        let (meta, _) = neutral
+            .ip_info()
+            .send_with_meta("203.0.113.141".parse().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(meta.status, http::StatusCode::OK);
+        assert_eq!(meta.rate_limit, RateLimit::default());
+        assert!(!meta.headers.contains_key("x-ratelimit-remaining"));
+    }
+
+    #[tokio::test]
+    async fn test_ip_info_send_many() {
+        let _ok = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.19[13]$".into()))
+            .with_status(200)
+            .with_body(fixtures::IP_INFO_BODY)
+            .expect(2)
+            .create();
+        let _failing = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.192$".into()))
+            .with_status(500)
+            .with_body("internal error")
+            .expect(1)
+            .create();
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        let ip_addrs: Vec<IpAddr> = ["203.0.113.191", "203.0.113.192", "203.0.113.193"]
+            .iter()
+            .map(|ip| ip.parse().unwrap())
+            .collect();
+        let results = neutral.ip_info().send_many(ip_addrs.clone(), 2).await;
+
+        assert_eq!(
+            results
+                .iter()
+                .map(|(ip_addr, _)| *ip_addr)
+                .collect::<Vec<_>>(),
+            ip_addrs
+        );
+        assert!(results[0].1.is_ok());
+        assert!(matches!(results[1].1, Err(Error::ServerError(_))));
+        assert!(results[2].1.is_ok());
+        _ok.assert();
+        _failing.assert();
+    }
+
+    #[tokio::test]
+    async fn test_ip_info_send_with_reverse_lookup() {
+        let _without = mock("GET", "/ip-info")
+            .match_query(Matcher::Exact("ip=203.0.113.213".to_owned()))
+            .with_status(200)
+            .with_body(fixtures::IP_INFO_BODY)
+            .expect(1)
+            .create();
+        let _with = mock("GET", "/ip-info")
+            .match_query(Matcher::Exact(
+                "ip=203.0.113.213&reverse-lookup=true".to_owned(),
+            ))
+            .with_status(200)
+            .with_body(
+                fixtures::IP_INFO_BODY
+                    .replace(r#""hostname": """#, r#""hostname": "host-213.example.com""#)
+                    .replace(r#""host_domain": """#, r#""host_domain": "example.com""#),
+            )
+            .expect(1)
+            .create();
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        let ip_addr: IpAddr = "203.0.113.213".parse().unwrap();
+        let without = neutral.ip_info().send(ip_addr).await.unwrap();
+        assert_eq!(without.hostname, "");
+        let with = neutral
+            .ip_info()
+            .send_with(
+                ip_addr,
+                IpInfoOptions {
+                    reverse_lookup: true,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(with.hostname, "host-213.example.com");
+        assert_eq!(with.host_domain, "example.com");
+        _without.assert();
+        _with.assert();
+    }
+
+    #[tokio::test]
+    async fn test_ip_info_with_camel_case_keys() {
+        let snake: IpInfoResponse = serde_json::from_str(fixtures::IP_INFO_BODY).unwrap();
+        let camel = fixtures::IP_INFO_BODY
+            .replace("\"country_code\"", "\"countryCode\"")
+            .replace("\"country_code3\"", "\"countryCode3\"")
+            .replace("\"currency_code\"", "\"currencyCode\"")
+            .replace("\"is_v6\"", "\"isV6\"")
+            .replace("\"is_v4_mapped\"", "\"isV4Mapped\"");
+        assert_ne!(camel, fixtures::IP_INFO_BODY);
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Exact("ip=203.0.113.241".into()))
+            .with_status(200)
+            .with_body(camel)
+            .expect(1)
+            .create();
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+        let response = neutral
+            .ip_info()
+            .send("203.0.113.241".parse().unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response, snake);
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_ip_info_params() {
+        let params = IpInfoParams {
+            ip: "203.0.113.247".parse().unwrap(),
+            reverse_lookup: true,
+        };
+        let json = serde_json::to_string(&params).unwrap();
+        assert_eq!(serde_json::from_str::<IpInfoParams>(&json).unwrap(), params);
+        assert_eq!(
+            serde_json::from_str::<IpInfoParams>(r#"{"ip": "203.0.113.247"}"#).unwrap(),
+            IpInfoParams {
+                reverse_lookup: false,
+                ..params
+            }
+        );
+
+        let (neutral, requests) = fixtures::recording(fixtures::IP_INFO_BODY);
+        neutral.ip_info().send_params(&params).await.unwrap();
+        let options = IpInfoOptions {
+            reverse_lookup: true,
+        };
+        neutral
+            .ip_info()
+            .send_with(params.ip, options)
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0], requests[1]);
+    }
 }
@@ -3,44 +3,233 @@
 //! Execute a realtime network probe against an IPv4 or IPv6 address.
 //!
 //! This API will run a series of live network scans and service probes to extract useful details about the host provider.
+//!
+//! The [IpProbeTyped](./trait.IpProbeTyped.html) extension trait exposes the autonomous system of a response as typed values,
+//! `None` when empty or malformed:
+//!
+//! ```ignore
+//! use neutral::ip_probe::IpProbeTyped;
+//!
+//! let ip_probe_response = neutral.ip_probe().send(ip_addr).await?;
+//! if let (Some(asn), Some(network)) = (ip_probe_response.as_number(), ip_probe_response.as_network()) {
+//!     println!("AS{} announces {}", asn, network);
+//! }
+//! ```
 
-use http::Method;
-use hyper::Body;
-use neutral_types::ip_probe::IpProbeResponse;
-use std::net::IpAddr;
+use hyper::{body::Bytes, Body, Request};
+/// The response of the endpoint, defined by neutral_types and re-exported so it can be named without depending on it.
+pub use neutral_types::ip_probe::IpProbeResponse;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{net::IpAddr, time::Duration};
 
-use crate::{Error, Neutral};
+use crate::{
+    blocklist::IpNetwork, bogon, context::CallContext, meta::ResponseMeta, params,
+    pipeline::Fetched, report::ordered_concurrent, Error, Neutral,
+};
 
 #[cfg(test)]
 use mockito;
 
+/// Parameters of an ip probe call, e.g. to persist a pending call and send it later with [IpProbe::send_params].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IpProbeParams {
+    pub ip: IpAddr,
+}
+
 pub struct IpProbe<'a> {
     pub(crate) neutral: &'a Neutral,
+    pub(crate) context: CallContext,
 }
 
 impl<'a> IpProbe<'a> {
+    /// Attach a [CallContext](../context/struct.CallContext.html) to the calls.
+    pub fn context(mut self, context: CallContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Bound each attempt of the call by `timeout` instead of the client default, see [CallContext::timeout](../context/struct.CallContext.html#structfield.timeout).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.context.timeout = Some(timeout);
+        self
+    }
+
     /// Send an ip probe request to neutrinoapi.com
     pub async fn send(&self, ip_addr: IpAddr) -> Result<IpProbeResponse, Error> {
-        let path_and_query = format!("/ip-probe?output-case=snake&ip={}", ip_addr.to_string());
-        let request = self
-            .neutral
-            .request_builder(path_and_query)?
-            .method(Method::GET)
-            .body(Body::empty())?;
+        self.send_with_meta(ip_addr)
+            .await
+            .map(|(_, response)| response)
+    }
+
+    /// Same as [send](#method.send), with the ip address of `params`.
+    pub async fn send_params(&self, params: &IpProbeParams) -> Result<IpProbeResponse, Error> {
+        self.send(params.ip).await
+    }
+
+    /// Send an ip probe request for each of `ip_addrs`, at most `concurrency` at the same time.
+    ///
+    /// The results are in the order of `ip_addrs`, a failing request does not abort the others.
+    pub async fn send_many(
+        &self,
+        ip_addrs: Vec<IpAddr>,
+        concurrency: usize,
+    ) -> Vec<(IpAddr, Result<IpProbeResponse, Error>)> {
+        ordered_concurrent(ip_addrs, concurrency, |ip_addr| self.send(ip_addr)).await
+    }
+
+    /// Same as [send](#method.send), also returning the [ResponseMeta](../meta/struct.ResponseMeta.html) of the response.
+    pub async fn send_with_meta(
+        &self,
+        ip_addr: IpAddr,
+    ) -> Result<(ResponseMeta, IpProbeResponse), Error> {
+        let canonical_input = ip_addr.to_string();
+        let fetched = self.fetch(ip_addr).await?;
+        let response: IpProbeResponse = self.neutral.decode_lenient("ip-probe", &fetched.body)?;
+        if response.is_valid && !fetched.meta.is_cached {
+            self.neutral
+                .cache_insert("ip-probe", &canonical_input, fetched.body);
+        }
+        Ok((fetched.meta, response))
+    }
+
+    /// Same as [send](#method.send), returning the raw body of the response.
+    pub async fn send_raw(&self, ip_addr: IpAddr) -> Result<Bytes, Error> {
+        Ok(self.fetch(ip_addr).await?.body)
+    }
+
+    /// Same as [send](#method.send), decoding the response into any `T`, e.g. a struct holding fields unknown to [IpProbeResponse].
+    pub async fn send_as<T: DeserializeOwned>(&self, ip_addr: IpAddr) -> Result<T, Error> {
+        let fetched = self.fetch(ip_addr).await?;
+        self.neutral.decode("ip-probe", &fetched.body)
+    }
+
+    /// Same as [send](#method.send), decoding the response into a [serde_json::Value].
+    pub async fn send_value(&self, ip_addr: IpAddr) -> Result<serde_json::Value, Error> {
+        self.send_as(ip_addr).await
+    }
+
+    /// Send the request, without decoding its response.
+    async fn fetch(&self, ip_addr: IpAddr) -> Result<Fetched, Error> {
+        bogon::check(self.neutral.reject_bogons, ip_addr)?;
+        let canonical_input = ip_addr.to_string();
+        self.neutral
+            .execute("ip-probe", Some(&canonical_input), &self.context, || {
+                build_request(self.neutral, ip_addr, &self.context)
+            })
+            .await
+    }
+}
+
+/// Build the ip probe request of `ip_addr`, without sending it.
+pub(crate) fn build_request(
+    neutral: &Neutral,
+    ip_addr: IpAddr,
+    context: &CallContext,
+) -> Result<Request<Body>, Error> {
+    let params = params().append_pair("ip", &ip_addr.to_string()).finish();
+    neutral.get_request("/ip-probe", params, context)
+}
+
+/// Typed autonomous system fields of an ip probe response, `None` when empty or malformed.
+pub trait IpProbeTyped {
+    /// Returns the number of the autonomous system, parsed from `asn` with or without its `AS` prefix.
+    fn as_number(&self) -> Option<u32>;
+
+    /// Returns the range of addresses announced by the autonomous system, parsed from `as_cidr`.
+    fn as_network(&self) -> Option<IpNetwork>;
+
+    /// Returns the age of the autonomous system in years, `None` when `as_age` is 0, i.e. unknown.
+    fn as_age_years(&self) -> Option<u32>;
+}
+
+impl IpProbeTyped for IpProbeResponse {
+    fn as_number(&self) -> Option<u32> {
+        let asn = self.asn.trim();
+        let digits = match asn.get(..2) {
+            Some(prefix) if prefix.eq_ignore_ascii_case("as") => &asn[2..],
+            _ => asn,
+        };
+        // AS 0 is reserved, it stands for no autonomous system.
+        digits.parse().ok().filter(|asn| *asn != 0)
+    }
+
+    fn as_network(&self) -> Option<IpNetwork> {
+        let as_cidr = self.as_cidr.trim();
+        if !as_cidr.contains('/') {
+            return None;
+        }
+        as_cidr.parse().ok()
+    }
 
-        let body = self.neutral.request(request).await?;
-        let response: IpProbeResponse = serde_json::from_slice(&body)?;
-        Ok(response)
+    fn as_age_years(&self) -> Option<u32> {
+        u32::try_from(self.as_age)
+            .ok()
+            .filter(|as_age| *as_age != 0)
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::ApiAuth;
+    use crate::{metrics::MetricsSink, warning::Warning, ApiAuth};
+    use http::Method;
     use mockito::{mock, Matcher};
     use neutral_types::NeutrinoProviderKind;
-    use std::net::{IpAddr, Ipv4Addr};
+    use std::{
+        net::{IpAddr, Ipv4Addr},
+        sync::{Arc, Mutex},
+    };
+
+    #[tokio::test]
+    async fn test_ip_probe_with_unknown_provider_type() {
+        #[derive(Default)]
+        struct Warnings(Mutex<Vec<(String, Warning)>>);
+
+        impl MetricsSink for Warnings {
+            fn on_warning(&self, endpoint: &str, warning: &Warning) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push((endpoint.to_owned(), warning.clone()));
+            }
+        }
+
+        let mut body: serde_json::Value =
+            serde_json::from_str(crate::fixtures::IP_PROBE_BODY).unwrap();
+        body["ip"] = "203.0.113.216".into();
+        body["provider_type"] = "something-new".into();
+        let _m = mock("GET", "/ip-probe")
+            .match_query(Matcher::Regex("ip=203.0.113.216$".into()))
+            .with_status(200)
+            .with_body(body.to_string())
+            .create();
+
+        let warnings = Arc::new(Warnings::default());
+        let neutral = Neutral::builder(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .metrics(warnings.clone())
+        .build()
+        .unwrap();
+
+        let response = neutral
+            .ip_probe()
+            .send("203.0.113.216".parse().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.provider_type, NeutrinoProviderKind::Unknown);
+        assert_eq!(
+            *warnings.0.lock().unwrap(),
+            vec![(
+                "ip-probe".to_owned(),
+                Warning::UnknownVariant {
+                    field: "provider_type".to_owned(),
+                    value: "something-new".to_owned(),
+                }
+            )]
+        );
+    }
 
     #[tokio::test]
     async fn test_ip_probe_with_good_ip() {
@@ -147,7 +336,7 @@ mod test {
         for test in &tests {
             let Args { ip_addr } = test.args;
             let ip_probe_res = neutral.ip_probe().send(ip_addr).await;
-            let ip_probe_result = ip_probe_res.map(|ip_probe| ip_probe.clone());
+            let ip_probe_result = ip_probe_res;
             let expected = test.expected;
 
             assert_eq!(
@@ -158,4 +347,189 @@ mod test {
             )
         }
     }
+
+    #[test]
+    fn test_ip_probe_build_request() {
+        struct TestingData {
+            pub name: String,
+            pub args: IpAddr,
+            pub expected: &'static str,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Using an ipv4 address".to_owned(),
+                args: IpAddr::V4(Ipv4Addr::new(128, 0, 0, 1)),
+                expected: "/ip-probe?ip=128.0.0.1",
+            },
+            TestingData {
+                name: "Using an ipv6 address".to_owned(),
+                args: "2001:db8::1".parse().unwrap(),
+                expected: "/ip-probe?ip=2001%3Adb8%3A%3A1",
+            },
+        ];
+
+        let neutral = Neutral::try_new(
+            "https://neutrinoapi.net",
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        for test in &tests {
+            let request = build_request(&neutral, test.args, &CallContext::default()).unwrap();
+            assert_eq!(request.method(), Method::GET, "{}", test.name);
+            assert_eq!(
+                request.uri().path_and_query().unwrap().as_str(),
+                test.expected,
+                "{}",
+                test.name
+            );
+            assert_eq!(request.headers()["user-id"], "User", "{}", test.name);
+            assert_eq!(request.headers()["api-key"], "test", "{}", test.name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ip_probe_params() {
+        let params = IpProbeParams {
+            ip: "203.0.113.248".parse().unwrap(),
+        };
+        let json = serde_json::to_string(&params).unwrap();
+        assert_eq!(json, r#"{"ip":"203.0.113.248"}"#);
+        assert_eq!(
+            serde_json::from_str::<IpProbeParams>(&json).unwrap(),
+            params
+        );
+
+        let (neutral, requests) = crate::fixtures::recording(crate::fixtures::IP_PROBE_BODY);
+        neutral.ip_probe().send_params(&params).await.unwrap();
+        neutral.ip_probe().send(params.ip).await.unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0], requests[1]);
+    }
+
+    #[test]
+    fn test_ip_probe_typed() {
+        struct Args {
+            pub asn: &'static str,
+            pub as_cidr: &'static str,
+            pub as_age: i64,
+        }
+
+        struct TestingData {
+            pub name: String,
+            pub args: Args,
+            pub expected: (Option<u32>, Option<&'static str>, Option<u32>),
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Using an IPv4 range".to_owned(),
+                args: Args {
+                    asn: "12345",
+                    as_cidr: "128.0.0.0/22",
+                    as_age: 8,
+                },
+                expected: (Some(12345), Some("128.0.0.0/22"), Some(8)),
+            },
+            TestingData {
+                name: "Using an IPv6 range".to_owned(),
+                args: Args {
+                    asn: "AS64496",
+                    as_cidr: "2001:db8::/32",
+                    as_age: 21,
+                },
+                expected: (Some(64496), Some("2001:db8::/32"), Some(21)),
+            },
+            TestingData {
+                name: "Using empty fields".to_owned(),
+                args: Args {
+                    asn: "",
+                    as_cidr: "",
+                    as_age: 0,
+                },
+                expected: (None, None, None),
+            },
+            TestingData {
+                name: "Using garbage".to_owned(),
+                args: Args {
+                    asn: "AS-unknown",
+                    as_cidr: "128.0.0.0/33",
+                    as_age: -1,
+                },
+                expected: (None, None, None),
+            },
+            TestingData {
+                name: "Using garbage with a valid address".to_owned(),
+                args: Args {
+                    asn: "0",
+                    as_cidr: "128.0.0.1",
+                    as_age: 0,
+                },
+                expected: (None, None, None),
+            },
+            TestingData {
+                name: "Using an asn overflowing 32 bits".to_owned(),
+                args: Args {
+                    asn: "4294967296",
+                    as_cidr: "not a cidr/24",
+                    as_age: 0,
+                },
+                expected: (None, None, None),
+            },
+        ];
+
+        let fixture: IpProbeResponse =
+            serde_json::from_str(crate::fixtures::IP_PROBE_BODY).unwrap();
+        for test in tests {
+            let response = IpProbeResponse {
+                asn: test.args.asn.to_owned(),
+                as_cidr: test.args.as_cidr.to_owned(),
+                as_age: test.args.as_age,
+                ..fixture.clone()
+            };
+            assert_eq!(
+                (
+                    response.as_number(),
+                    response.as_network(),
+                    response.as_age_years()
+                ),
+                (
+                    test.expected.0,
+                    test.expected.1.map(|network| network.parse().unwrap()),
+                    test.expected.2
+                ),
+                "{}",
+                test.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_ip_probe_without_autonomous_system() {
+        let mut body: serde_json::Value =
+            serde_json::from_str(crate::fixtures::IP_PROBE_BODY).unwrap();
+        let object = body.as_object_mut().unwrap();
+        object.remove("as_age");
+        object.insert("asn".to_owned(), serde_json::Value::Null);
+        object.insert("as_cidr".to_owned(), serde_json::Value::Null);
+
+        let (response, warnings) = crate::decode::Decoder::default()
+            .decode_lenient::<IpProbeResponse>(&Bytes::from(body.to_string()))
+            .unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(
+            (
+                response.as_age,
+                response.asn.as_str(),
+                response.as_cidr.as_str()
+            ),
+            (0, "", "")
+        );
+        assert_eq!(response.as_number(), None);
+        assert_eq!(response.as_network(), None);
+        assert_eq!(response.as_age_years(), None);
+    }
 }
@@ -0,0 +1,556 @@
+//! # Lenient module
+//! Parse fields encoded either as json numbers or as json strings.
+//!
+//! neutrinoapi.com sometimes sends a numeric field, such as `as_age`, `last_seen` or `longitude`, as a string,
+//! and a textual one holding digits, such as `asn` or `mcc`, as a number.
+//! [string_or_number](./fn.string_or_number.html) accepts both encodings, e.g. on the types given to `send_as`:
+//!
+//! ```ignore
+//! #[derive(Deserialize)]
+//! struct AsAge {
+//!     #[serde(deserialize_with = "neutral::lenient::string_or_number")]
+//!     as_age: i64,
+//! }
+//! ```
+//!
+//! The responses of neutral_types can't carry this attribute, so their affected fields are converted to the expected encoding
+//! when the strict parsing of a response fails. The public field types don't change and a well encoded response is parsed as before.
+//! The `as_age`, `asn` and `as_cidr` fields of an ip probe response, null or missing for an address without an autonomous system,
+//! are read as `0` and empty texts, see [IpProbeTyped](../ip_probe/trait.IpProbeTyped.html).
+//!
+//! The requests don't ask for a key casing, so neutrinoapi.com answers with its default kebab-case keys, e.g. `country-code3`,
+//! or with camelCase ones, e.g. `countryCode3`.
+//! They are renamed to the snake_case names of the responses, e.g. `country_code3`, when the strict parsing fails, for every typed response
+//! including the types given to `send_as`. [send_value](../ip_info/struct.IpInfo.html#method.send_value) and `send_raw` return the keys as received.
+//!
+//! Likewise, the enums of neutral_types, such as [NeutrinoProviderKind](https://docs.rs/neutral_types/0.2.0/neutral_types/enum.NeutrinoProviderKind.html),
+//! can't get a catch-all variant: a value added by neutrinoapi.com is read as their `Unknown` variant
+//! and reported to the [MetricsSink](../metrics/trait.MetricsSink.html#method.on_warning) of the client
+//! as a [Warning::UnknownVariant](../warning/enum.Warning.html#variant.UnknownVariant).
+
+use std::{fmt::Display, str::FromStr};
+
+use neutral_types::{
+    hlr_lookup::{HlrLookupResponse, HlrStatus},
+    ip_blocklist::IpBlocklistResponse,
+    ip_info::IpInfoResponse,
+    ip_probe::IpProbeResponse,
+    phone_validate::PhoneValidateResponse,
+    NeutrinoProviderKind, PhoneInfoKind,
+};
+use serde::{
+    de::{DeserializeOwned, Error as _},
+    Deserialize, Deserializer,
+};
+use serde_json::{Number, Value};
+
+use crate::warning::Warning;
+
+/// Deserialize a `T` from a json string or a json number, parsing its text with `T::from_str`.
+pub fn string_or_number<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        String(String),
+        Number(Number),
+    }
+
+    let text = match Raw::deserialize(deserializer)? {
+        Raw::String(text) => text,
+        Raw::Number(number) => number.to_string(),
+    };
+    text.trim().parse().map_err(D::Error::custom)
+}
+
+/// A json number parsed from its text.
+struct JsonNumber(Number);
+
+impl FromStr for JsonNumber {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s).map(JsonNumber)
+    }
+}
+
+/// A field holding an enum without a catch-all variant.
+pub(crate) struct EnumField {
+    name: &'static str,
+    is_known: fn(&Value) -> bool,
+    /// The value of the variant standing for the unknown values.
+    fallback: &'static str,
+}
+
+impl EnumField {
+    const fn new<E: DeserializeOwned>(name: &'static str, fallback: &'static str) -> Self {
+        EnumField {
+            name,
+            is_known: is_known::<E>,
+            fallback,
+        }
+    }
+}
+
+fn is_known<E: DeserializeOwned>(value: &Value) -> bool {
+    E::deserialize(value).is_ok()
+}
+
+/// A response whose fields may arrive with the other encoding, or with a value unknown to their enum.
+pub(crate) trait Lenient: DeserializeOwned {
+    /// Fields holding a number.
+    const NUMBERS: &'static [&'static str];
+    /// Fields holding a text.
+    const STRINGS: &'static [&'static str];
+    /// Fields holding an enum.
+    const ENUMS: &'static [EnumField];
+    /// Fields which may be null or missing, with the value standing for their absence.
+    const ABSENT: &'static [(&'static str, Absent)] = &[];
+}
+
+/// The value of a null or missing field.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Absent {
+    Zero,
+    Empty,
+}
+
+impl Absent {
+    fn value(self) -> Value {
+        match self {
+            Absent::Zero => Value::from(0),
+            Absent::Empty => Value::from(""),
+        }
+    }
+}
+
+impl Lenient for IpInfoResponse {
+    const NUMBERS: &'static [&'static str] = &["longitude", "latitude"];
+    const STRINGS: &'static [&'static str] = &[];
+    const ENUMS: &'static [EnumField] = &[];
+}
+
+impl Lenient for IpProbeResponse {
+    const NUMBERS: &'static [&'static str] = &["as_age"];
+    const STRINGS: &'static [&'static str] = &["asn"];
+    const ENUMS: &'static [EnumField] = &[EnumField::new::<NeutrinoProviderKind>(
+        "provider_type",
+        "unknown",
+    )];
+    const ABSENT: &'static [(&'static str, Absent)] = &[
+        ("as_age", Absent::Zero),
+        ("asn", Absent::Empty),
+        ("as_cidr", Absent::Empty),
+    ];
+}
+
+impl Lenient for IpBlocklistResponse {
+    const NUMBERS: &'static [&'static str] = &["last_seen", "list_count"];
+    const STRINGS: &'static [&'static str] = &[];
+    const ENUMS: &'static [EnumField] = &[];
+}
+
+impl Lenient for PhoneValidateResponse {
+    const NUMBERS: &'static [&'static str] = &[];
+    const STRINGS: &'static [&'static str] = &[];
+    const ENUMS: &'static [EnumField] = &[
+        EnumField::new::<PhoneInfoKind>("type", "unknown"),
+        EnumField::new::<PhoneInfoKind>("kind", "unknown"),
+    ];
+}
+
+impl Lenient for HlrLookupResponse {
+    const NUMBERS: &'static [&'static str] = &[];
+    const STRINGS: &'static [&'static str] = &[
+        "imsi",
+        "mcc",
+        "mnc",
+        "msin",
+        "msc",
+        "international_calling_code",
+    ];
+    const ENUMS: &'static [EnumField] = &[
+        EnumField::new::<HlrStatus>("hlr_status", "unknown"),
+        EnumField::new::<PhoneInfoKind>("number_type", "unknown"),
+    ];
+}
+
+/// Returns the snake_case name of the camelCase or kebab-case `key`, e.g. `country_code3` for `countryCode3` or `country-code3`,
+/// or `None` when it is already in snake_case.
+fn snake_case(key: &str) -> Option<String> {
+    if !key.chars().any(|c| c.is_ascii_uppercase() || c == '-') {
+        return None;
+    }
+    let mut snake = String::with_capacity(key.len() + 4);
+    for c in key.chars() {
+        if c == '-' {
+            snake.push('_');
+        } else if c.is_ascii_uppercase() {
+            if !snake.is_empty() && !snake.ends_with('_') {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    Some(snake)
+}
+
+/// Rename the camelCase and kebab-case keys of `value` and of its nested objects to snake_case.
+///
+/// A key whose snake_case name is already present is kept as is. Returns true when a key was renamed.
+pub(crate) fn snake_case_keys(value: &mut Value) -> bool {
+    match value {
+        Value::Object(object) => {
+            let mut is_changed = false;
+            let renamed: Vec<(String, String)> = object
+                .keys()
+                .filter_map(|key| Some((key.clone(), snake_case(key)?)))
+                .filter(|(_, snake)| !object.contains_key(snake))
+                .collect();
+            for (key, snake) in renamed {
+                if let Some(field) = object.remove(&key) {
+                    object.insert(snake, field);
+                    is_changed = true;
+                }
+            }
+            for field in object.values_mut() {
+                is_changed |= snake_case_keys(field);
+            }
+            is_changed
+        }
+        Value::Array(items) => items
+            .iter_mut()
+            .fold(false, |is_changed, item| snake_case_keys(item) | is_changed),
+        _ => false,
+    }
+}
+
+/// Rename the camelCase and kebab-case keys of `value`, convert its fields encoded with the other encoding than the one expected by `T`,
+/// fill in its null or missing optional fields, and replace the unknown values of its enums by their fallback.
+///
+/// Returns `None` when no field was renamed or converted, the warnings of the replaced enum values otherwise.
+pub(crate) fn normalize<T: Lenient>(value: &mut Value) -> Option<Vec<Warning>> {
+    let mut is_changed = snake_case_keys(value);
+    let object = value.as_object_mut()?;
+    for field in T::NUMBERS {
+        if let Some(field @ Value::String(_)) = object.get_mut(*field) {
+            if let Ok(JsonNumber(number)) = string_or_number(field.take()) {
+                *field = Value::Number(number);
+                is_changed = true;
+            }
+        }
+    }
+    for field in T::STRINGS {
+        if let Some(field @ Value::Number(_)) = object.get_mut(*field) {
+            if let Ok(text) = string_or_number::<_, String>(field.take()) {
+                *field = Value::String(text);
+                is_changed = true;
+            }
+        }
+    }
+    for (field, absent) in T::ABSENT {
+        match object.get_mut(*field) {
+            Some(Value::Null) | None => {
+                object.insert((*field).to_owned(), absent.value());
+                is_changed = true;
+            }
+            Some(_) => {}
+        }
+    }
+    let mut warnings = Vec::new();
+    for field in T::ENUMS {
+        if let Some(value @ Value::String(_)) = object.get_mut(field.name) {
+            if !(field.is_known)(value) {
+                let value = std::mem::replace(value, Value::String(field.fallback.to_owned()));
+                warnings.push(Warning::UnknownVariant {
+                    field: field.name.to_owned(),
+                    value: value.as_str().unwrap_or_default().to_owned(),
+                });
+            }
+        }
+    }
+    if is_changed || !warnings.is_empty() {
+        Some(warnings)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{decode::Decoder, fixtures::*};
+    use hyper::body::Bytes;
+    use std::fmt::Debug;
+
+    /// Returns `fixture` with `field` encoded with the other encoding.
+    fn swap_encoding(fixture: &str, field: &str) -> Bytes {
+        let mut value: Value = serde_json::from_str(fixture).unwrap();
+        let field = value.get_mut(field).unwrap();
+        *field = match field.take() {
+            Value::Number(number) => Value::String(number.to_string()),
+            Value::String(text) => Value::Number(serde_json::from_str(&text).unwrap()),
+            other => panic!("{} is neither a number nor a string", other),
+        };
+        Bytes::from(serde_json::to_vec(&value).unwrap())
+    }
+
+    fn assert_identical<T: Lenient + PartialEq + Debug>(fixture: &'static str) {
+        let decoder = Decoder::default();
+        let (expected, _) = decoder
+            .decode_lenient::<T>(&Bytes::from_static(fixture.as_bytes()))
+            .unwrap();
+        let value: Value = serde_json::from_str(fixture).unwrap();
+        for field in T::NUMBERS.iter().chain(T::STRINGS) {
+            if value[*field] == "" {
+                continue;
+            }
+            let body = swap_encoding(fixture, field);
+            assert!(decoder.decode::<T>(&body).is_err(), "{}", field);
+            let (parsed, warnings) = decoder.decode_lenient::<T>(&body).unwrap();
+            assert_eq!(parsed, expected, "{}", field);
+            assert!(warnings.is_empty(), "{}", field);
+        }
+    }
+
+    #[test]
+    fn test_lenient_responses() {
+        assert_identical::<IpInfoResponse>(IP_INFO_BODY);
+        assert_identical::<IpProbeResponse>(IP_PROBE_BODY);
+        assert_identical::<IpBlocklistResponse>(IP_BLOCKLIST_BODY);
+        assert_identical::<HlrLookupResponse>(HLR_LOOKUP_BODY);
+    }
+
+    #[test]
+    fn test_unknown_variants() {
+        struct TestingData {
+            pub name: String,
+            pub args: (&'static str, &'static str),
+            pub expected: Warning,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Provider type of an ip probe response".to_owned(),
+                args: (IP_PROBE_BODY, "provider_type"),
+                expected: Warning::UnknownVariant {
+                    field: "provider_type".to_owned(),
+                    value: "something-new".to_owned(),
+                },
+            },
+            TestingData {
+                name: "Hlr status of an hlr lookup response".to_owned(),
+                args: (HLR_LOOKUP_BODY, "hlr_status"),
+                expected: Warning::UnknownVariant {
+                    field: "hlr_status".to_owned(),
+                    value: "something-new".to_owned(),
+                },
+            },
+            TestingData {
+                name: "Number type of an hlr lookup response".to_owned(),
+                args: (HLR_LOOKUP_BODY, "number_type"),
+                expected: Warning::UnknownVariant {
+                    field: "number_type".to_owned(),
+                    value: "something-new".to_owned(),
+                },
+            },
+            TestingData {
+                name: "Type of a phone validate response".to_owned(),
+                args: (PHONE_VALIDATE_BODY, "type"),
+                expected: Warning::UnknownVariant {
+                    field: "type".to_owned(),
+                    value: "something-new".to_owned(),
+                },
+            },
+        ];
+
+        let decoder = Decoder::default();
+        for test in &tests {
+            let (fixture, field) = test.args;
+            let mut value: Value = serde_json::from_str(fixture).unwrap();
+            value[field] = Value::String("something-new".to_owned());
+            let body = Bytes::from(serde_json::to_vec(&value).unwrap());
+            value[field] = Value::String("unknown".to_owned());
+            let unknown = Bytes::from(serde_json::to_vec(&value).unwrap());
+
+            let warnings = match fixture {
+                IP_PROBE_BODY => {
+                    let (response, warnings) =
+                        decoder.decode_lenient::<IpProbeResponse>(&body).unwrap();
+                    assert_eq!(response.provider_type, NeutrinoProviderKind::Unknown);
+                    assert_eq!(
+                        response,
+                        decoder.decode::<IpProbeResponse>(&unknown).unwrap(),
+                        "{}",
+                        test.name
+                    );
+                    warnings
+                }
+                HLR_LOOKUP_BODY => {
+                    let (response, warnings) =
+                        decoder.decode_lenient::<HlrLookupResponse>(&body).unwrap();
+                    assert_eq!(
+                        response,
+                        decoder.decode::<HlrLookupResponse>(&unknown).unwrap(),
+                        "{}",
+                        test.name
+                    );
+                    warnings
+                }
+                _ => {
+                    let (response, warnings) = decoder
+                        .decode_lenient::<PhoneValidateResponse>(&body)
+                        .unwrap();
+                    assert_eq!(response.kind, PhoneInfoKind::Unknown, "{}", test.name);
+                    warnings
+                }
+            };
+            assert_eq!(warnings, vec![test.expected.clone()], "{}", test.name);
+        }
+    }
+
+    #[test]
+    fn test_string_or_number() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Fields {
+            #[serde(deserialize_with = "string_or_number")]
+            as_age: i64,
+            #[serde(deserialize_with = "string_or_number")]
+            longitude: f64,
+            #[serde(deserialize_with = "string_or_number")]
+            asn: String,
+        }
+
+        let expected = Fields {
+            as_age: 8,
+            longitude: 2.35,
+            asn: "12345".to_owned(),
+        };
+        for body in [
+            r#"{"as_age": 8, "longitude": 2.35, "asn": "12345"}"#,
+            r#"{"as_age": "8", "longitude": "2.35", "asn": 12345}"#,
+            r#"{"as_age": " 8 ", "longitude": 2.35, "asn": 12345}"#,
+        ] {
+            let fields: Fields = serde_json::from_str(body).unwrap();
+            assert_eq!(fields, expected, "{}", body);
+        }
+        assert!(serde_json::from_str::<Fields>(
+            r#"{"as_age": "eight", "longitude": 2.35, "asn": "12345"}"#
+        )
+        .is_err());
+    }
+
+    /// Returns `fixture` with its keys, and the keys of its nested objects, written in camelCase.
+    fn camel_case(fixture: &str) -> Bytes {
+        fn camel_case_keys(value: Value) -> Value {
+            match value {
+                Value::Object(object) => Value::Object(
+                    object
+                        .into_iter()
+                        .map(|(key, field)| {
+                            let mut words = key.split('_');
+                            let mut camel = words.next().unwrap_or_default().to_owned();
+                            for word in words {
+                                let mut chars = word.chars();
+                                camel.extend(chars.next().map(|c| c.to_ascii_uppercase()));
+                                camel.push_str(chars.as_str());
+                            }
+                            (camel, camel_case_keys(field))
+                        })
+                        .collect(),
+                ),
+                Value::Array(items) => {
+                    Value::Array(items.into_iter().map(camel_case_keys).collect())
+                }
+                other => other,
+            }
+        }
+
+        let value = camel_case_keys(serde_json::from_str(fixture).unwrap());
+        Bytes::from(serde_json::to_vec(&value).unwrap())
+    }
+
+    #[test]
+    fn test_snake_case() {
+        assert_eq!(snake_case("countryCode3").as_deref(), Some("country_code3"));
+        assert_eq!(snake_case("isV4Mapped").as_deref(), Some("is_v4_mapped"));
+        assert_eq!(snake_case("numberValid").as_deref(), Some("number_valid"));
+        assert_eq!(
+            snake_case("country-code3").as_deref(),
+            Some("country_code3")
+        );
+        assert_eq!(snake_case("is-v4-mapped").as_deref(), Some("is_v4_mapped"));
+        assert_eq!(snake_case("api-Error").as_deref(), Some("api_error"));
+        assert_eq!(snake_case("country_code"), None);
+        assert_eq!(snake_case("ip"), None);
+    }
+
+    #[test]
+    fn test_camel_case_responses() {
+        fn assert_same<T: Lenient + PartialEq + Debug>(fixture: &'static str) {
+            let decoder = Decoder::default();
+            let body = camel_case(fixture);
+            assert!(body
+                .windows(b"Code3".len())
+                .any(|window| window == b"Code3"));
+
+            let snake: T = serde_json::from_str(fixture).unwrap();
+            let (camel, warnings) = decoder.decode_lenient::<T>(&body).unwrap();
+            assert_eq!(camel, snake);
+            assert!(warnings.is_empty());
+            assert_eq!(decoder.decode::<T>(&body).unwrap(), snake);
+        }
+
+        assert_same::<IpInfoResponse>(IP_INFO_BODY);
+        assert_same::<HlrLookupResponse>(HLR_LOOKUP_BODY);
+    }
+
+    #[test]
+    fn test_kebab_case_responses() {
+        fn assert_same<T: Lenient + PartialEq + Debug>(kebab: &'static str, snake: &'static str) {
+            let decoder = Decoder::default();
+            let body = Bytes::from_static(kebab.as_bytes());
+            assert!(serde_json::from_str::<T>(kebab).is_err());
+
+            let snake: T = serde_json::from_str(snake).unwrap();
+            let (kebab, warnings) = decoder.decode_lenient::<T>(&body).unwrap();
+            assert_eq!(kebab, snake);
+            assert!(warnings.is_empty());
+            assert_eq!(decoder.decode::<T>(&body).unwrap(), snake);
+        }
+
+        assert_same::<IpInfoResponse>(IP_INFO_KEBAB_BODY, IP_INFO_BODY);
+        assert_same::<HlrLookupResponse>(HLR_LOOKUP_KEBAB_BODY, HLR_LOOKUP_BODY);
+
+        let response: crate::sms_verify::SmsVerifyResponse = Decoder::default()
+            .decode(&Bytes::from_static(
+                br#"{"security-code": "123456", "number-valid": true, "sent": true}"#,
+            ))
+            .unwrap();
+        assert_eq!(response.security_code, "123456");
+        assert!(response.is_number_valid);
+        assert!(response.is_sent);
+    }
+
+    #[test]
+    fn test_snake_case_keys_keeps_present_keys() {
+        let mut value: Value = serde_json::from_str(
+            r#"{"countryCode": "FR", "country_code": "BE", "nested": [{"isV6": true, "is-bogon": false}]}"#,
+        )
+        .unwrap();
+        assert!(snake_case_keys(&mut value));
+        assert_eq!(value["country_code"], "BE");
+        assert_eq!(value["countryCode"], "FR");
+        assert_eq!(value["nested"][0]["is_v6"], true);
+        assert_eq!(value["nested"][0]["is_bogon"], false);
+        assert!(!snake_case_keys(
+            &mut serde_json::json!({"ip": "203.0.113.1"})
+        ));
+    }
+}
@@ -17,29 +17,167 @@
 //! let ip_info_response = neutral.ip_info().send(ip_addr).await.unwrap();
 //! ```
 
+//! # Reusable and one-shot calls
+//! Calls to idempotent endpoints (GET lookups such as ip info or phone validate) send with `&self`, so a configured call is reusable:
+//!
+//! ```no_run
+//! # use neutral::{ApiAuth, Neutral};
+//! # async fn run(neutral: Neutral) -> Result<(), neutral::error::Error> {
+//! let ip_info = neutral.ip_info();
+//! let first = ip_info.send("128.0.0.1".parse().unwrap()).await?;
+//! let second = ip_info.send("128.0.0.2".parse().unwrap()).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Calls to mutating endpoints, which send a message or consume a body meant to be sent once, send with `self` and are not `Clone`.
+//! Reusing such a call after `send`, or cloning it to send it twice, is rejected at compile time, see the [sms_verify](./sms_verify/index.html),
+//! [phone_verify](./phone_verify/index.html) and [sms_message](./sms_message/index.html) modules.
+
+//! # Platform support
+//! On native targets, the client runs on the tokio runtime and sends its requests with a hyper client over TCP.
+//!
+//! On `wasm32-unknown-unknown`, e.g. in a browser extension or a Cloudflare Worker, build with `default-features = false` and the `wasm` feature:
+//! the requests are sent with the `fetch` of the JavaScript host, its timers bound the calls and delay the retries,
+//! and the background tasks are spawned on its event loop. The endpoints and their responses are the same.
+//! What the host handles is cfg'd out, so using it fails to compile: the hyper [client](./builder/struct.NeutralBuilder.html#method.client),
+//! the proxy, the resolver, the connect timeout and the HTTP protocol of the connections, [keep_warm](./struct.Neutral.html#method.keep_warm),
+//! the file system helpers and the `blocking` client. A [service](./builder/struct.NeutralBuilder.html#method.service) can still replace fetch.
+//!
+//! ```toml
+//! [target.'cfg(target_arch = "wasm32")'.dependencies]
+//! neutral = { version = "0.2", default-features = false, features = ["wasm"] }
+//! ```
+
+use audit::{AuditEvent, AuditSink, AuditSinks};
+use batch::Batch;
+use bin_list_download::BinListDownload;
+use bin_lookup::BinLookup;
+use cache::{cache_key, RecordError, SecondTier, TtlCache};
+use context::CallContext;
+use decode::Decoder;
+use email_verify::EmailVerify;
+use endpoint::Endpoint;
+use error::{Error, NeutrinoError};
 use hlr_lookup::HlrLookup;
+use html_render::HtmlRender;
 use http::{
+    header::{ACCEPT_ENCODING, CONTENT_TYPE},
     uri::{Authority, Scheme},
-    StatusCode, Uri,
+    Method, StatusCode, Uri,
 };
-use neutral_types::error::{Error, NeutrinoError};
+use image_watermark::ImageWatermark;
 
-use hyper::{body::Bytes, client::HttpConnector, Body, Client, Request};
-use hyper_tls::HttpsConnector;
+use hyper::{body::Bytes, Body, Request};
+#[cfg(not(target_arch = "wasm32"))]
+use hyper::{client::connect::Connect, Client};
 use ip_blocklist::IpBlocklist;
+use ip_blocklist_download::IpBlocklistDownload;
 use ip_info::IpInfo;
 use ip_probe::IpProbe;
+use meta::ResponseMeta;
+use metrics::{endpoint_name, MetricsSink, MetricsSinks};
+use multi::Multi;
 use phone_validate::PhoneValidate;
+use phone_verify::PhoneVerify;
+use pipeline::{CircuitBreaker, RetryConfig, Singleflight};
+use runtime::{Runtime, RuntimeConfig};
 use secrecy::{ExposeSecret, Secret};
+use signing::Signing;
+use sms_message::SmsMessage;
+use sms_verify::SmsVerify;
+use spawn::{SpawnHandle, Spawner};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    future::Future,
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex, RwLock},
+};
+use time::Instant;
+use transport::Transport;
+use ua_lookup::UaLookup;
+use verify_security_code::VerifySecurityCode;
+use warning::Warning;
+
+pub use builder::{NeutralBuilder, VerificationReport};
+/// The types shared by several responses, defined by neutral_types and re-exported so they can be named without depending on it.
+pub use neutral_types::{
+    NeutrinoProviderKind, NeutrinoSensor, NeutrinoTimeZoneResponse, PhoneInfoKind,
+};
 
+pub mod api;
+pub mod audit;
+pub mod batch;
+pub mod bin_list_download;
+pub mod bin_lookup;
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+pub mod blocking;
+pub mod blocklist;
+pub mod bogon;
+pub mod builder;
+pub mod cache;
+#[cfg(feature = "record-replay")]
+pub mod cassette;
+pub mod context;
+pub mod country;
+pub mod credentials;
+pub mod decode;
+pub mod diff;
+pub mod download;
+pub mod email_verify;
+#[cfg(feature = "gzip")]
+mod encoding;
+pub mod endpoint;
+pub mod error;
+#[cfg(test)]
+pub(crate) mod fixtures;
+pub mod free_text;
+#[cfg(feature = "har")]
+pub mod har;
 pub mod hlr_lookup;
+pub mod html_render;
+pub mod image_watermark;
+pub mod intercept;
 pub mod ip_blocklist;
+pub mod ip_blocklist_download;
 pub mod ip_info;
 pub mod ip_probe;
+pub mod lenient;
+pub mod local_blocklist;
+pub mod meta;
+pub mod metrics;
+pub mod multi;
+mod multipart;
+pub mod optional_text;
+pub mod phone_number;
 pub mod phone_validate;
-
-#[cfg(test)]
-use mockito;
+pub mod phone_verify;
+pub mod pipeline;
+pub mod prelude;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod proxy;
+mod redirect;
+pub mod region;
+pub mod report;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod resolve;
+pub mod runtime;
+pub mod signing;
+pub mod sms_message;
+pub mod sms_verify;
+pub mod spawn;
+#[cfg(feature = "test-util")]
+pub mod testing;
+mod time;
+pub mod tls;
+mod transport;
+#[cfg(feature = "chrono")]
+pub mod typed_time;
+pub mod ua_lookup;
+pub mod usage;
+pub mod verify_security_code;
+pub mod warmer;
+pub mod warning;
 
 /// Provide authorization credentials for neutrinoapi.com
 #[derive(Debug, Clone)]
@@ -56,101 +194,811 @@ impl ApiAuth {
             api_key: Secret::new(api_key),
         }
     }
+
+    /// Returns a hash of the credentials, telling them apart in the cache and singleflight keys without exposing them.
+    ///
+    /// It is stable between processes built by the same compiler, so the records of a [CacheStore](./cache/trait.CacheStore.html) can be shared.
+    pub(crate) fn identity(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.user_id.expose_secret().hash(&mut hasher);
+        self.api_key.expose_secret().hash(&mut hasher);
+        hasher.finish()
+    }
 }
 
 /// A client to consume features provided by neutrinoapi.com
 #[derive(Debug, Clone)]
 pub struct Neutral {
-    pub(crate) uri: Uri,
+    pub(crate) runtime: Arc<RwLock<Arc<Runtime>>>,
     pub(crate) auth: ApiAuth,
-    pub(crate) client: Client<HttpsConnector<HttpConnector>>,
+    pub(crate) client: Transport,
+    pub(crate) cache: Option<Arc<TtlCache>>,
+    pub(crate) negative_cache: Option<Arc<TtlCache>>,
+    pub(crate) cache_store: Option<SecondTier>,
+    pub(crate) metrics: MetricsSinks,
+    pub(crate) metric_labels: Arc<[String]>,
+    pub(crate) audit: AuditSinks,
+    pub(crate) signing: Signing,
+    pub(crate) interceptors: intercept::Interceptors,
+    pub(crate) decoder: Decoder,
+    pub(crate) spawner: Spawner,
+    pub(crate) singleflight: Arc<Singleflight>,
+    pub(crate) breaker: Option<Arc<CircuitBreaker>>,
+    pub(crate) retry: Option<RetryConfig>,
+    pub(crate) timeout: Option<std::time::Duration>,
+    pub(crate) max_response_size: usize,
+    pub(crate) max_redirects: usize,
+    /// The permits of the requests in flight, see [NeutralBuilder::max_in_flight](./builder/struct.NeutralBuilder.html#method.max_in_flight).
+    pub(crate) in_flight: Option<Arc<tokio::sync::Semaphore>>,
+    pub(crate) params_in_body: bool,
+    pub(crate) reject_bogons: bool,
+    pub(crate) default_headers: Arc<http::HeaderMap>,
+    #[cfg(test)]
+    pub(crate) probe: pipeline::Probe,
+    pub(crate) capabilities: Arc<Mutex<HashMap<String, bool>>>,
+    pub(crate) usage: Arc<usage::UsageTracker>,
+    #[cfg(feature = "har")]
+    pub(crate) har: Option<Arc<har::HarRecorder>>,
 }
 
 impl<'a> Neutral {
     /// Create a new Neutral instance. Needs some credentials to be authorized.
     /// Provide your neutrinoapi.com userid and apikey with an instance of `ApiAuth` as argument.
+    ///
+    /// Returns [Error::InvalidBaseUri](./error/enum.Error.html#variant.InvalidBaseUri) when `uri` has no http or https scheme or no authority.
     pub fn try_new(uri: &str, auth: ApiAuth) -> Result<Self, Error> {
-        let mut https = HttpsConnector::new();
+        NeutralBuilder::new(uri, auth).build()
+    }
 
-        #[cfg(test)]
-        let uri = &mockito::server_url();
+    /// Create a new Neutral instance sending its requests with `client`, see [NeutralBuilder::client](./builder/struct.NeutralBuilder.html#method.client).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn try_new_with_client<C>(
+        uri: &str,
+        auth: ApiAuth,
+        client: Client<C>,
+    ) -> Result<Self, Error>
+    where
+        C: Connect + Clone + Send + Sync + 'static,
+    {
+        NeutralBuilder::new(uri, auth).client(client).build()
+    }
 
-        let uri = uri.parse::<Uri>()?;
+    /// Create a new Neutral instance sending its requests with the tower `service`, see [NeutralBuilder::service](./builder/struct.NeutralBuilder.html#method.service).
+    pub fn try_new_with_service<S>(uri: &str, auth: ApiAuth, service: S) -> Result<Self, Error>
+    where
+        S: hyper::service::Service<Request<Body>, Response = hyper::Response<Body>>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        S::Future: Send,
+        S::Error: Into<error::BoxError>,
+    {
+        NeutralBuilder::new(uri, auth).service(service).build()
+    }
 
-        https.https_only(uri.scheme() == Some(&Scheme::HTTPS));
-        Ok(Self {
-            uri: uri,
-            auth: auth,
-            client: Client::builder().build::<_, hyper::Body>(https),
-        })
+    /// Create a new Neutral instance sending its requests with the reqwest `client`, see [NeutralBuilder::reqwest](./builder/struct.NeutralBuilder.html#method.reqwest).
+    #[cfg(feature = "reqwest-backend")]
+    pub fn from_reqwest(uri: &str, auth: ApiAuth, client: reqwest::Client) -> Result<Self, Error> {
+        NeutralBuilder::new(uri, auth).reqwest(client).build()
+    }
+
+    /// Create a new Neutral instance trying the hosts of `uris` in order, e.g. the uris of several [regions](./region/enum.Region.html).
+    ///
+    /// The first uri is the base uri, the others are failover uris, see the [region](./region/index.html) module.
+    /// Returns [ConfigError::NoUri](./error/enum.ConfigError.html#variant.NoUri) when `uris` is empty.
+    pub fn try_new_multi(uris: Vec<Uri>, auth: ApiAuth) -> Result<Self, Error> {
+        let mut uris = uris.into_iter();
+        let uri = uris.next().ok_or(error::ConfigError::NoUri)?;
+        uris.fold(
+            NeutralBuilder::new(&uri.to_string(), auth),
+            |builder, uri| builder.failover(&uri.to_string()),
+        )
+        .build()
+    }
+
+    /// Returns a client sending `auth` as credentials, e.g. the credentials of a tenant, and sharing everything else with this one.
+    ///
+    /// Both clients share the connection pool, the configuration, the caches, the rate limits and the circuit breaker.
+    /// Only the `user-id` and `api-key` headers of their requests differ: calls of different credentials never share a request
+    /// nor a cached response, since the cache keys include a hash of the credentials.
+    pub fn with_auth(&self, auth: ApiAuth) -> Self {
+        Neutral {
+            auth,
+            ..self.clone()
+        }
+    }
+
+    /// Returns a [NeutralBuilder](./builder/struct.NeutralBuilder.html) to configure how the client is initialized.
+    pub fn builder(uri: &str, auth: ApiAuth) -> NeutralBuilder {
+        NeutralBuilder::new(uri, auth)
     }
 
     /// Returns the URI scheme.
-    pub fn scheme(&self) -> Option<&Scheme> {
-        self.uri.scheme()
+    pub fn scheme(&self) -> Option<Scheme> {
+        self.runtime().config.uri.scheme().cloned()
     }
 
     /// Returns the URI authority.
-    pub fn authority(&self) -> Option<&Authority> {
-        self.uri.authority()
+    pub fn authority(&self) -> Option<Authority> {
+        self.runtime().config.uri.authority().cloned()
     }
 
-    pub(crate) fn uri_builder(&self) -> http::uri::Builder {
-        Uri::builder()
-            .authority(self.authority().unwrap().as_str())
-            .scheme(self.scheme().unwrap().as_str())
+    /// Change the configuration of the client and of all its clones, see the [runtime](./runtime/index.html) module.
+    ///
+    /// Returns [ConfigError::NotRuntimeMutable](./error/enum.ConfigError.html#variant.NotRuntimeMutable) listing the fields which cannot change at runtime,
+    /// in which case the configuration is left untouched.
+    pub fn reconfigure<F>(&self, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut RuntimeConfig),
+    {
+        let mut runtime = self.runtime.write().unwrap();
+        let mut config = runtime.config.clone();
+        f(&mut config);
+        *runtime = Arc::new(runtime.update(config)?);
+        Ok(())
+    }
+
+    /// Returns the current configuration snapshot.
+    pub(crate) fn runtime(&self) -> Arc<Runtime> {
+        self.runtime.read().unwrap().clone()
+    }
+
+    /// Spawn `future` in the background using the configured [Spawner](./spawn/enum.Spawner.html).
+    ///
+    /// Returns [Error::SpawnUnavailable](./error/enum.Error.html#variant.SpawnUnavailable) when the spawner cannot spawn it.
+    pub fn spawn<F>(&self, future: F) -> Result<SpawnHandle<F::Output>, Error>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.spawner.spawn(future)
     }
 
     pub(crate) fn request_builder(
         &self,
         path_and_query: String,
+        context: &CallContext,
     ) -> Result<http::request::Builder, Error> {
-        let uri = self.uri_builder().path_and_query(path_and_query).build()?;
-        let request_builder = Request::builder()
+        let uri = endpoint_uri(&self.runtime().config.uri, &path_and_query)?;
+        let mut request_builder = Request::builder()
             .uri(uri)
+            .extension(EndpointPath(path_and_query))
             .header("user-id", self.auth.user_id.expose_secret())
             .header("api-key", self.auth.api_key.expose_secret());
-        Ok(request_builder)
+        #[cfg(feature = "gzip")]
+        {
+            request_builder = request_builder.header(ACCEPT_ENCODING, encoding::ACCEPT_ENCODING);
+        }
+        if let Some(request_id) = context.request_id() {
+            request_builder = request_builder.header("x-request-id", request_id);
+        }
+        if let Some(headers) = request_builder.headers_mut() {
+            for (name, value) in self.default_headers.iter() {
+                headers.append(name, value.clone());
+            }
+        }
+        Ok(request_builder.extension(context.clone()))
+    }
+
+    /// Build a request to `path` carrying the form encoded `params`.
+    ///
+    /// The params are sent in a POST form body with [NeutralBuilder::params_in_body](./builder/struct.NeutralBuilder.html#method.params_in_body), in the query string of a GET otherwise.
+    pub(crate) fn params_request(
+        &self,
+        path: &str,
+        params: String,
+        context: &CallContext,
+    ) -> Result<Request<Body>, Error> {
+        if self.params_in_body {
+            return Ok(self
+                .request_builder(path.to_owned(), context)?
+                .method(Method::POST)
+                .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .body(Body::from(params))?);
+        }
+        self.get_request(path, params, context)
+    }
+
+    /// Build a GET request to `path` carrying the form encoded `params` in its query string.
+    pub(crate) fn get_request(
+        &self,
+        path: &str,
+        params: String,
+        context: &CallContext,
+    ) -> Result<Request<Body>, Error> {
+        Ok(self
+            .request_builder(format!("{}?{}", path, params), context)?
+            .method(Method::GET)
+            .body(Body::empty())?)
     }
 
-    pub(crate) async fn request(&self, req: Request<Body>) -> Result<Bytes, Error> {
-        let http_resp = self.client.request(req).await?;
-        match http_resp.status() {
-            StatusCode::OK => {
-                let body = hyper::body::to_bytes(http_resp.into_body()).await?;
-                Ok(body)
+    /// Decode a response body of `endpoint` with the configured [JsonDecoder](./decode/trait.JsonDecoder.html).
+    pub(crate) fn decode<T: serde::de::DeserializeOwned>(
+        &self,
+        endpoint: &'static str,
+        body: &Bytes,
+    ) -> Result<T, Error> {
+        self.decoder
+            .decode(body)
+            .map_err(|err| decode::decode_error(endpoint, err, body))
+    }
+
+    /// Decode the response `body` of `endpoint` leniently, see [Decoder::decode_lenient], reporting its unknown enum values.
+    pub(crate) fn decode_lenient<T: lenient::Lenient>(
+        &self,
+        endpoint: &'static str,
+        body: &Bytes,
+    ) -> Result<T, Error> {
+        let (response, warnings) = self
+            .decoder
+            .decode_lenient(body)
+            .map_err(|err| decode::decode_error(endpoint, err, body))?;
+        for warning in &warnings {
+            self.metrics.on_warning(endpoint, warning);
+        }
+        Ok(response)
+    }
+
+    /// Returns the body of a previous response to the same input, if still cached.
+    pub(crate) fn cache_get(&self, endpoint: &str, canonical_input: &str) -> Option<Bytes> {
+        let key = cache_key(endpoint, self.auth.identity(), canonical_input);
+        let body = [&self.cache, &self.negative_cache]
+            .into_iter()
+            .flatten()
+            .find_map(|cache| cache.get(&key))
+            .or_else(|| match self.cache_store.as_ref()?.get(&key)? {
+                Ok(body) => Some(body),
+                Err(RecordError::Incompatible(version)) => {
+                    self.metrics
+                        .on_warning(endpoint, &Warning::IncompatibleCacheRecord { version });
+                    None
+                }
+                Err(RecordError::Unusable) => None,
+            });
+        if body.is_some() {
+            self.metrics.on_cache_hit(endpoint);
+        }
+        body
+    }
+
+    /// Returns true when a response to the same input is still cached.
+    pub(crate) fn cache_contains(&self, endpoint: &str, canonical_input: &str) -> bool {
+        let key = cache_key(endpoint, self.auth.identity(), canonical_input);
+        [&self.cache, &self.negative_cache]
+            .into_iter()
+            .flatten()
+            .any(|cache| cache.get(&key).is_some())
+    }
+
+    /// Remember the body of a successful response.
+    pub(crate) fn cache_insert(&self, endpoint: &str, canonical_input: &str, body: Bytes) {
+        let key = cache_key(endpoint, self.auth.identity(), canonical_input);
+        if let Some(cache_store) = &self.cache_store {
+            cache_store.put(&key, &body);
+        }
+        if let Some(cache) = &self.cache {
+            cache.insert(key, body);
+        }
+    }
+
+    /// Remember the body of a response for an input judged invalid.
+    pub(crate) fn negative_cache_insert(&self, endpoint: &str, canonical_input: &str, body: Bytes) {
+        if let Some(cache) = &self.negative_cache {
+            cache.insert(
+                cache_key(endpoint, self.auth.identity(), canonical_input),
+                body,
+            );
+        }
+    }
+
+    pub(crate) async fn request(&self, req: Request<Body>) -> Result<(ResponseMeta, Bytes), Error> {
+        let host = req.uri().authority().cloned();
+        let (head, body) = self.dispatch(req).await?;
+        let meta = ResponseMeta::new(&head.parts, host);
+        let body = within(head.deadline, read_body(body, self.max_response_size)).await;
+        #[cfg(feature = "gzip")]
+        let body =
+            body.and_then(|body| encoding::decompress(&head.parts, body, self.max_response_size));
+        match body {
+            Ok(body) => self.finish(head, body, true).map(|body| (meta, body)),
+            Err(err) => Err(self.abandon(head, err)),
+        }
+    }
+
+    /// Send `req` without buffering the body of a successful response.
+    ///
+    /// The timeout only bounds the head of a successful response, its body is read at the pace of the caller. The body of an error response is buffered to build the error. The HAR entry of a streamed response has an empty content.
+    pub(crate) async fn request_stream(
+        &self,
+        mut req: Request<Body>,
+    ) -> Result<http::Response<Body>, Error> {
+        req.headers_mut().remove(ACCEPT_ENCODING);
+        let (head, body) = self.dispatch(req).await?;
+        if head.parts.status == StatusCode::OK {
+            let headers = head.parts.headers.clone();
+            self.finish(head, Bytes::new(), false)?;
+            let mut response = http::Response::new(body);
+            *response.headers_mut() = headers;
+            return Ok(response);
+        }
+        match within(head.deadline, hyper::body::to_bytes(body)).await {
+            Ok(body) => self
+                .finish(head, body, false)
+                .map(|_| http::Response::new(Body::empty())),
+            Err(err) => Err(self.abandon(head, err)),
+        }
+    }
+
+    /// Send `req`, returning the head of the response and its body, not received yet.
+    async fn dispatch(&self, mut req: Request<Body>) -> Result<(ResponseHead, Body), Error> {
+        self.interceptors.apply(&mut req);
+        let req = self.signing.apply(req).await?;
+        let endpoint = endpoint_name(req.uri().path()).to_owned();
+        let context = req
+            .extensions()
+            .get::<CallContext>()
+            .cloned()
+            .unwrap_or_default();
+        let deadline = context
+            .timeout
+            .or(self.timeout)
+            .map(|timeout| Instant::now() + timeout);
+        let permit = match &self.in_flight {
+            Some(in_flight) => {
+                let acquire = in_flight.clone().acquire_owned();
+                // The semaphore is never closed.
+                let acquire = async { acquire.await.map_err(|_| Error::Cancelled) };
+                Some(within(deadline, acquire).await?)
             }
-            _ => {
-                let status_code = http_resp.status();
-                let body = hyper::body::to_bytes(http_resp.into_body()).await?;
-                let error = String::from_utf8_lossy(&body).into_owned();
-                Err(Error::Neutrino(NeutrinoError { status_code, error }))
+            None => None,
+        };
+        #[cfg(feature = "har")]
+        let snapshot = self.har.as_ref().map(|_| har::RequestSnapshot {
+            method: req.method().clone(),
+            uri: req.uri().clone(),
+            headers: req.headers().clone(),
+            started: time::now(),
+        });
+
+        let start = Instant::now();
+        let http_resp = match within(deadline, self.send(req)).await {
+            Ok(http_resp) => http_resp,
+            Err(err) => {
+                self.observe(&endpoint, None, start.elapsed(), &context);
+                self.metrics.on_error(&endpoint, &err);
+                self.usage.record(None, true);
+                #[cfg(feature = "har")]
+                if let (Some(har), Some(snapshot)) = (&self.har, &snapshot) {
+                    har.record(snapshot, None, start.elapsed());
+                }
+                return Err(err);
             }
+        };
+
+        let (parts, body) = http_resp.into_parts();
+        let head = ResponseHead {
+            endpoint,
+            context,
+            start,
+            deadline,
+            parts,
+            _permit: permit,
+            #[cfg(feature = "har")]
+            snapshot,
+        };
+        Ok((head, body))
+    }
+
+    /// Send `req` with the transport, following up to [NeutralBuilder::max_redirects](./builder/struct.NeutralBuilder.html#method.max_redirects) redirects.
+    fn send(&self, req: Request<Body>) -> transport::ResponseFuture {
+        if self.max_redirects == 0 {
+            return self.client.request(req);
+        }
+        let (client, max_redirects) = (self.client.clone(), self.max_redirects);
+        let require_https = self.runtime().config.require_https;
+        let signature_header = self.signing.signer.as_ref().map(|(name, _)| name.clone());
+        Box::pin(async move {
+            redirect::follow(&client, req, max_redirects, require_https, signature_header).await
+        })
+    }
+
+    /// Report a response whose body was received, returning the body of a successful response.
+    ///
+    /// When `expects_json`, a successful response must have a JSON body, see [decode::check_json].
+    fn finish(&self, head: ResponseHead, body: Bytes, expects_json: bool) -> Result<Bytes, Error> {
+        let ResponseHead {
+            endpoint,
+            context,
+            start,
+            parts,
+            #[cfg(feature = "har")]
+            snapshot,
+            ..
+        } = head;
+
+        self.observe(&endpoint, Some(parts.status), start.elapsed(), &context);
+        if parts.status == StatusCode::TOO_MANY_REQUESTS {
+            self.metrics.on_rate_limited(&endpoint);
+        }
+        #[cfg(feature = "har")]
+        if let (Some(har), Some(snapshot)) = (&self.har, &snapshot) {
+            har.record(snapshot, Some((&parts, &body)), start.elapsed());
+        }
+
+        let result = match parts.status {
+            StatusCode::OK if expects_json => decode::check_json(&parts, body),
+            StatusCode::OK => Ok(body),
+            status_code => Err(Error::from_response(
+                &endpoint,
+                &parts.headers,
+                NeutrinoError::new(status_code, &body, context),
+            )),
+        };
+
+        match &result {
+            Ok(_) => self.learn_capability(&endpoint, true),
+            Err(Error::FeatureNotEnabled { .. }) => self.learn_capability(&endpoint, false),
+            Err(_) => {}
+        }
+        if let Err(err) = &result {
+            self.metrics.on_error(&endpoint, err);
         }
+        self.usage.record(Some(&parts.headers), result.is_err());
+        result
+    }
+
+    /// Report a response whose body could not be received, returning `err`.
+    fn abandon(&self, head: ResponseHead, err: Error) -> Error {
+        self.observe(&head.endpoint, None, head.start.elapsed(), &head.context);
+        self.metrics.on_error(&head.endpoint, &err);
+        self.usage.record(Some(&head.parts.headers), true);
+        #[cfg(feature = "har")]
+        if let (Some(har), Some(snapshot)) = (&self.har, &head.snapshot) {
+            har.record(snapshot, None, head.start.elapsed());
+        }
+        err
+    }
+
+    /// Report a completed request to the metrics and audit sinks.
+    fn observe(
+        &self,
+        endpoint: &str,
+        status: Option<StatusCode>,
+        elapsed: std::time::Duration,
+        context: &CallContext,
+    ) {
+        self.metrics.on_request_with_labels(
+            endpoint,
+            status,
+            elapsed,
+            &context.labels(&self.metric_labels),
+        );
+        self.audit.on_call(&AuditEvent {
+            endpoint,
+            status,
+            elapsed,
+            context,
+        });
+    }
+
+    fn learn_capability(&self, endpoint: &str, is_supported: bool) {
+        self.capabilities
+            .lock()
+            .unwrap()
+            .insert(endpoint.to_owned(), is_supported);
+    }
+
+    /// Returns whether the plan of the account supports `endpoint`, as learned from previous responses.
+    ///
+    /// Returns `None` until a response of this endpoint was received.
+    pub fn supports(&self, endpoint: Endpoint) -> Option<bool> {
+        self.capabilities
+            .lock()
+            .unwrap()
+            .get(endpoint.name())
+            .copied()
+    }
+
+    /// Returns a snapshot of the requests sent by this client and its clones, see the [usage](./usage/index.html) module.
+    pub fn usage(&self) -> usage::Usage {
+        self.usage.snapshot()
+    }
+
+    /// Export the recorded traffic as a HAR 1.2 document, see the [har](./har/index.html) module.
+    ///
+    /// Returns a document without entries when the recorder is not enabled.
+    #[cfg(feature = "har")]
+    pub fn har_export(&self) -> String {
+        match &self.har {
+            Some(har) => har.export(),
+            None => har::HarRecorder::new(har::HarConfig::default()).export(),
+        }
+    }
+
+    /// Send a cheap authenticated request to check that neutrinoapi.com accepts the credentials.
+    ///
+    /// The request is a phone validate call of an invalid number, sent through the retries, timeouts and circuit breaker of the client, but never cached.
+    /// Returns [Error::Unauthorized](./error/enum.Error.html#variant.Unauthorized) when the credentials are rejected,
+    /// any other error means they could not be checked, e.g. [Error::Hyper](./error/enum.Error.html#variant.Hyper) when neutrinoapi.com is unreachable.
+    ///
+    /// ```ignore
+    /// match neutral.verify_credentials().await {
+    ///     Ok(()) => println!("credentials accepted"),
+    ///     Err(err) if err.is_auth_error() => println!("credentials rejected"),
+    ///     Err(err) => println!("credentials not checked: {}", err),
+    /// }
+    /// ```
+    pub async fn verify_credentials(&self) -> Result<(), Error> {
+        let context = CallContext::default();
+        self.execute("phone-validate", None, &context, || {
+            let params = params().append_pair("number", "0").finish();
+            self.get_request("/phone-validate", params, &context)
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Open a connection to the base uri ahead of the first call, so it doesn't pay for the name resolution and the TCP and TLS handshakes.
+    ///
+    /// Sends a `HEAD` request to the base uri without the credentials, so no credit is consumed, and ignores its status.
+    /// Like the calls, it carries the [default headers](./builder/struct.NeutralBuilder.html#method.default_header), goes through the interceptors and the signing,
+    /// and counts towards [max_in_flight](./builder/struct.NeutralBuilder.html#method.max_in_flight).
+    /// The connection is then kept in the pool of the client, until it stays idle longer than the pool allows, see [keep_warm](#method.keep_warm).
+    /// It is bounded by the [timeout](./builder/struct.NeutralBuilder.html#method.timeout) of the client and can be called repeatedly and concurrently:
+    /// an error is returned, e.g. when neutrinoapi.com is unreachable, but has no effect on the following calls.
+    pub async fn warm_up(&self) -> Result<(), Error> {
+        let mut req = self
+            .request_builder("/".to_owned(), &CallContext::default())?
+            .method(Method::HEAD)
+            .body(Body::empty())?;
+        req.headers_mut().remove("user-id");
+        req.headers_mut().remove("api-key");
+        let (head, body) = self.dispatch(req).await?;
+        // The connection goes back to the pool once the body is received.
+        within(head.deadline, hyper::body::to_bytes(body)).await?;
+        Ok(())
+    }
+
+    /// [Warm up](#method.warm_up) a connection every `interval` in the background, until `cancellation` is cancelled.
+    ///
+    /// Choose an `interval` shorter than the idle timeout of the connection pool, 90 seconds by default, to always have a connection ready.
+    /// A failed warm-up is ignored and tried again at the next interval. The returned handle resolves once the refresh stops.
+    /// Returns [Error::SpawnUnavailable](./error/enum.Error.html#variant.SpawnUnavailable) when the configured spawner cannot spawn the refresh.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn keep_warm(
+        &self,
+        interval: std::time::Duration,
+        cancellation: context::CancellationToken,
+    ) -> Result<SpawnHandle<()>, Error> {
+        let neutral = self.clone();
+        self.spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    _ = cancellation.cancelled() => return,
+                    _ = ticks.tick() => {
+                        let _ = neutral.warm_up().await;
+                    }
+                }
+            }
+        })
     }
 
     /// Returns an instance of PhoneValidate
     pub fn phone_validate(&'a self) -> PhoneValidate<'a> {
-        PhoneValidate { neutral: self }
+        PhoneValidate {
+            neutral: self,
+            context: CallContext::default(),
+            hints: Default::default(),
+        }
+    }
+
+    /// Returns an instance of PhoneVerify
+    pub fn phone_verify(&'a self) -> PhoneVerify<'a> {
+        PhoneVerify {
+            neutral: self,
+            context: CallContext::default(),
+        }
+    }
+
+    /// Returns an instance of SmsMessage
+    pub fn sms_message(&'a self) -> SmsMessage<'a> {
+        SmsMessage {
+            neutral: self,
+            context: CallContext::default(),
+        }
+    }
+
+    /// Returns an instance of SmsVerify
+    pub fn sms_verify(&'a self) -> SmsVerify<'a> {
+        SmsVerify {
+            neutral: self,
+            context: CallContext::default(),
+        }
+    }
+
+    /// Returns an instance of VerifySecurityCode
+    pub fn verify_security_code(&'a self) -> VerifySecurityCode<'a> {
+        VerifySecurityCode {
+            neutral: self,
+            context: CallContext::default(),
+        }
     }
 
     /// Returns an instance of IpInfo
     pub fn ip_info(&'a self) -> IpInfo<'a> {
-        IpInfo { neutral: self }
+        IpInfo {
+            neutral: self,
+            context: CallContext::default(),
+        }
     }
 
     /// Returns an instance of IpBlocklist
     pub fn ip_blocklist(&'a self) -> IpBlocklist<'a> {
-        IpBlocklist { neutral: self }
+        IpBlocklist {
+            neutral: self,
+            context: CallContext::default(),
+        }
+    }
+
+    /// Returns an instance of IpBlocklistDownload
+    pub fn ip_blocklist_download(&'a self) -> IpBlocklistDownload<'a> {
+        IpBlocklistDownload::new(self)
     }
 
     /// Returns an instance of IpProbe
     pub fn ip_probe(&'a self) -> IpProbe<'a> {
-        IpProbe { neutral: self }
+        IpProbe {
+            neutral: self,
+            context: CallContext::default(),
+        }
     }
 
     /// Returns an instance of HlrLookup
     pub fn hlr_lookup(&'a self) -> HlrLookup<'a> {
-        HlrLookup { neutral: self }
+        HlrLookup {
+            neutral: self,
+            context: CallContext::default(),
+        }
+    }
+
+    /// Returns an instance of EmailVerify
+    pub fn email_verify(&'a self) -> EmailVerify<'a> {
+        EmailVerify {
+            neutral: self,
+            context: CallContext::default(),
+            fix_typos: false,
+        }
     }
+
+    /// Returns an instance of BinLookup
+    pub fn bin_lookup(&'a self) -> BinLookup<'a> {
+        BinLookup {
+            neutral: self,
+            context: CallContext::default(),
+        }
+    }
+
+    /// Returns an instance of BinListDownload
+    pub fn bin_list_download(&'a self) -> BinListDownload<'a> {
+        BinListDownload {
+            neutral: self,
+            context: CallContext::default(),
+            include_iso3: false,
+            include_8digit: false,
+        }
+    }
+
+    /// Returns an instance of HtmlRender
+    pub fn html_render(&'a self) -> HtmlRender<'a> {
+        HtmlRender {
+            neutral: self,
+            context: CallContext::default(),
+        }
+    }
+
+    /// Returns an instance of ImageWatermark
+    pub fn image_watermark(&'a self) -> ImageWatermark<'a> {
+        ImageWatermark {
+            neutral: self,
+            context: CallContext::default(),
+        }
+    }
+
+    /// Returns an instance of UaLookup
+    pub fn ua_lookup(&'a self) -> UaLookup<'a> {
+        UaLookup::new(self)
+    }
+
+    /// Returns an instance of Batch
+    pub fn batch(&'a self) -> Batch<'a> {
+        Batch::new(self)
+    }
+
+    /// Returns an instance of Multi
+    pub fn multi(&'a self) -> Multi<'a> {
+        Multi::new(self)
+    }
+}
+
+/// A response whose head was received.
+struct ResponseHead {
+    endpoint: String,
+    context: CallContext,
+    start: Instant,
+    /// The response, body included, must be received before this instant.
+    deadline: Option<Instant>,
+    parts: http::response::Parts,
+    /// Keeps the request in flight until the response is reported.
+    _permit: Option<tokio::sync::OwnedSemaphorePermit>,
+    #[cfg(feature = "har")]
+    snapshot: Option<har::RequestSnapshot>,
+}
+
+/// Await `future`, failing with [Error::Timeout](./error/enum.Error.html#variant.Timeout) once `deadline` is passed.
+async fn within<F, T, E>(deadline: Option<Instant>, future: F) -> Result<T, Error>
+where
+    F: Future<Output = Result<T, E>>,
+    Error: From<E>,
+{
+    match deadline {
+        Some(deadline) => Ok(time::timeout_at(deadline, future).await??),
+        None => Ok(future.await?),
+    }
+}
+
+/// Receive `body`, failing with [Error::BodyTooLarge] as soon as it exceeds `limit` bytes.
+async fn read_body(mut body: Body, limit: usize) -> Result<Bytes, Error> {
+    use hyper::body::HttpBody;
+
+    let announced = body.size_hint().lower();
+    if announced > limit as u64 {
+        return Err(Error::BodyTooLarge {
+            limit,
+            received_at_least: usize::try_from(announced).unwrap_or(usize::MAX),
+        });
+    }
+    let mut received = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if received.len() + chunk.len() > limit {
+            return Err(Error::BodyTooLarge {
+                limit,
+                received_at_least: received.len() + chunk.len(),
+            });
+        }
+        received.extend_from_slice(&chunk);
+    }
+    Ok(Bytes::from(received))
+}
+
+/// Returns an encoder of the params of a request.
+///
+/// Every value is percent-encoded, so none can inject another param.
+pub(crate) fn params() -> form_urlencoded::Serializer<'static, String> {
+    form_urlencoded::Serializer::new(String::new())
+}
+
+/// The path and query of a request relative to the base uri, kept so the request can target another base.
+#[derive(Debug, Clone)]
+pub(crate) struct EndpointPath(pub(crate) String);
+
+/// Returns the uri of `path_and_query` below `base`.
+///
+/// The path of `base` is a prefix, e.g. `https://gateway.internal/neutrino/` and `/ip-info` give `https://gateway.internal/neutrino/ip-info`.
+pub(crate) fn endpoint_uri(base: &Uri, path_and_query: &str) -> Result<Uri, Error> {
+    builder::check_base_uri(base)?;
+    let prefix = base.path().trim_end_matches('/');
+    let separator = if path_and_query.starts_with('/') {
+        ""
+    } else {
+        "/"
+    };
+    Ok(Uri::builder()
+        .authority(base.authority().map_or("", |authority| authority.as_str()))
+        .scheme(base.scheme_str().unwrap_or_default())
+        .path_and_query(format!("{}{}{}", prefix, separator, path_and_query))
+        .build()?)
 }
@@ -0,0 +1,666 @@
+//! # Local blocklist module
+//! Look ip addresses up in a local copy of the [ip blocklist download](../ip_blocklist_download/index.html), without a call per address.
+//!
+//! A [LocalBlocklist](./struct.LocalBlocklist.html) is filled from the CIDR ranges of the download, streamed from neutrinoapi.com or read from a CSV file on disk.
+//! The ranges are kept in one sorted table per prefix length, so a lookup is a binary search per prefix length present in the list,
+//! and an ip address inside nested ranges matches the most specific one.
+//!
+//! [LocalBlocklist::refresh](./struct.LocalBlocklist.html#method.refresh) downloads a new copy and swaps it in once it is complete:
+//! lookups go on with the previous copy meanwhile, and a failed download keeps it.
+//!
+//! ```ignore
+//! let blocklist = LocalBlocklist::download(&neutral, LocalBlocklistOptions { ip6: true, ..Default::default() }).await?;
+//! if let Some(entry) = blocklist.contains(ip_addr) {
+//!     println!("{} is listed, proxy: {}", entry.network, entry.categories.is_proxy);
+//! }
+//!
+//! // Later, e.g. every day.
+//! blocklist.refresh(&neutral).await?;
+//! ```
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+use std::{
+    net::IpAddr,
+    sync::{Arc, RwLock},
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use futures::Stream;
+use futures::StreamExt;
+#[cfg(not(target_arch = "wasm32"))]
+use hyper::{body::Bytes, Body};
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::io::AsyncReadExt;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::download::ByteStream;
+use crate::{
+    blocklist::{EntryStream, IpBlocklistEntry},
+    Error, Neutral,
+};
+
+/// Size of the chunks read from a blocklist file.
+#[cfg(not(target_arch = "wasm32"))]
+const FILE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Options of the lists downloaded by [LocalBlocklist::refresh](./struct.LocalBlocklist.html#method.refresh).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LocalBlocklistOptions {
+    /// Include the ranges of public VPN providers.
+    pub include_vpn: bool,
+    /// Also download the IPv6 list, otherwise IPv6 addresses are never listed.
+    pub ip6: bool,
+}
+
+/// The outcome of filling a blocklist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LoadReport {
+    /// Number of ranges loaded.
+    pub entries: usize,
+    /// Number of malformed lines skipped.
+    pub malformed: usize,
+}
+
+/// A local copy of the ip blocklist, refreshed in place.
+#[derive(Debug, Default)]
+pub struct LocalBlocklist {
+    options: LocalBlocklistOptions,
+    current: RwLock<Arc<BlocklistSnapshot>>,
+}
+
+impl LocalBlocklist {
+    /// Returns an empty blocklist, filled by [LocalBlocklist::refresh](#method.refresh) with the lists selected by `options`.
+    pub fn new(options: LocalBlocklistOptions) -> Self {
+        LocalBlocklist {
+            options,
+            current: RwLock::default(),
+        }
+    }
+
+    /// Returns a blocklist filled with the lists selected by `options`.
+    pub async fn download(
+        neutral: &Neutral,
+        options: LocalBlocklistOptions,
+    ) -> Result<Self, Error> {
+        let blocklist = LocalBlocklist::new(options);
+        blocklist.refresh(neutral).await?;
+        Ok(blocklist)
+    }
+
+    /// Returns a blocklist filled from the CSV file at `path`, as written by
+    /// [IpBlocklistDownload::download_to](../ip_blocklist_download/struct.IpBlocklistDownload.html#method.download_to) with the CSV format.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let blocklist = LocalBlocklist::default();
+        blocklist.load_file(path).await?;
+        Ok(blocklist)
+    }
+
+    /// Download the lists selected by the options of the blocklist, then replace its ranges with them.
+    ///
+    /// The current ranges are kept when a download fails.
+    pub async fn refresh(&self, neutral: &Neutral) -> Result<LoadReport, Error> {
+        let mut builder = SnapshotBuilder::default();
+        let download = neutral
+            .ip_blocklist_download()
+            .include_vpn(self.options.include_vpn)
+            .cidr(true);
+        builder.extend(download.entries().await?).await?;
+        if self.options.ip6 {
+            builder.extend(download.ip6(true).entries().await?).await?;
+        }
+        Ok(self.swap(builder))
+    }
+
+    /// Replace the ranges of the blocklist with the ones of the CSV file at `path`.
+    ///
+    /// The current ranges are kept when the file can't be read.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn load_file<P: AsRef<Path>>(&self, path: P) -> Result<LoadReport, Error> {
+        let mut builder = SnapshotBuilder::default();
+        builder.extend(file_entries(path.as_ref()).await?).await?;
+        Ok(self.swap(builder))
+    }
+
+    /// Returns the most specific range containing `ip_addr`, `None` when it is not listed.
+    pub fn contains(&self, ip_addr: IpAddr) -> Option<IpBlocklistEntry> {
+        self.snapshot().contains(ip_addr).copied()
+    }
+
+    /// Returns the current ranges, unaffected by later refreshes.
+    ///
+    /// Prefer it to [LocalBlocklist::contains](#method.contains) to look many addresses up in a row.
+    pub fn snapshot(&self) -> Arc<BlocklistSnapshot> {
+        self.current.read().unwrap().clone()
+    }
+
+    fn swap(&self, builder: SnapshotBuilder) -> LoadReport {
+        let malformed = builder.malformed;
+        let snapshot = Arc::new(builder.build());
+        let report = LoadReport {
+            entries: snapshot.len(),
+            malformed,
+        };
+        *self.current.write().unwrap() = snapshot;
+        report
+    }
+}
+
+/// The ranges of a blocklist at a point in time.
+#[derive(Debug, Default)]
+pub struct BlocklistSnapshot {
+    entries: Vec<IpBlocklistEntry>,
+    v4: PrefixTables<u32>,
+    v6: PrefixTables<u128>,
+}
+
+impl BlocklistSnapshot {
+    /// Returns the ranges of `entries`; of several entries with the same range, the first one is kept.
+    pub fn from_entries<I: IntoIterator<Item = IpBlocklistEntry>>(entries: I) -> Self {
+        let mut builder = SnapshotBuilder::default();
+        builder.entries.extend(entries);
+        builder.build()
+    }
+
+    /// Returns the most specific range containing `ip_addr`, `None` when it is not listed.
+    pub fn contains(&self, ip_addr: IpAddr) -> Option<&IpBlocklistEntry> {
+        let index = match ip_addr {
+            IpAddr::V4(ip_addr) => self.v4.lookup(u32::from(ip_addr)),
+            IpAddr::V6(ip_addr) => self.v6.lookup(u128::from(ip_addr)),
+        }?;
+        self.entries.get(index as usize)
+    }
+
+    /// Returns the number of ranges.
+    pub fn len(&self) -> usize {
+        self.v4.len() + self.v6.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the approximate number of bytes allocated for the ranges.
+    pub fn heap_size(&self) -> usize {
+        self.entries.capacity() * std::mem::size_of::<IpBlocklistEntry>()
+            + self.v4.heap_size()
+            + self.v6.heap_size()
+    }
+}
+
+/// The bits of an address family.
+trait Bits: Copy + Ord {
+    const BITS: u8;
+
+    /// Returns the first address of the range of `prefix_len` bits containing `self`.
+    fn network(self, prefix_len: u8) -> Self;
+}
+
+impl Bits for u32 {
+    const BITS: u8 = 32;
+
+    fn network(self, prefix_len: u8) -> Self {
+        self & u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0)
+    }
+}
+
+impl Bits for u128 {
+    const BITS: u8 = 128;
+
+    fn network(self, prefix_len: u8) -> Self {
+        self & u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0)
+    }
+}
+
+/// The ranges of an address family, one table per prefix length.
+#[derive(Debug)]
+struct PrefixTables<K> {
+    /// Tables by decreasing prefix length, so the first match is the most specific.
+    tables: Vec<PrefixTable<K>>,
+}
+
+impl<K> Default for PrefixTables<K> {
+    fn default() -> Self {
+        PrefixTables { tables: Vec::new() }
+    }
+}
+
+/// The ranges of a prefix length, sorted by first address.
+#[derive(Debug)]
+struct PrefixTable<K> {
+    prefix_len: u8,
+    /// The first address of each range and the index of its entry.
+    networks: Vec<(K, u32)>,
+}
+
+impl<K: Bits> PrefixTables<K> {
+    /// Returns the tables of `networks`, the first address and prefix length of each entry by index.
+    fn new(networks: impl Iterator<Item = (K, u8, u32)>) -> Self {
+        let mut by_len: Vec<Vec<(K, u32)>> = (0..=K::BITS).map(|_| Vec::new()).collect();
+        for (addr, prefix_len, index) in networks {
+            let prefix_len = prefix_len.min(K::BITS);
+            by_len[prefix_len as usize].push((addr.network(prefix_len), index));
+        }
+        let tables = by_len
+            .into_iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, networks)| !networks.is_empty())
+            .map(|(prefix_len, mut networks)| {
+                // A stable sort keeps the first of the entries of a range.
+                networks.sort_by_key(|(addr, _)| *addr);
+                networks.dedup_by_key(|(addr, _)| *addr);
+                networks.shrink_to_fit();
+                PrefixTable {
+                    prefix_len: prefix_len as u8,
+                    networks,
+                }
+            })
+            .collect();
+        PrefixTables { tables }
+    }
+
+    fn lookup(&self, ip_addr: K) -> Option<u32> {
+        self.tables.iter().find_map(|table| {
+            let network = ip_addr.network(table.prefix_len);
+            table
+                .networks
+                .binary_search_by_key(&network, |(addr, _)| *addr)
+                .ok()
+                .map(|position| table.networks[position].1)
+        })
+    }
+
+    fn len(&self) -> usize {
+        self.tables.iter().map(|table| table.networks.len()).sum()
+    }
+
+    fn heap_size(&self) -> usize {
+        self.tables.capacity() * std::mem::size_of::<PrefixTable<K>>()
+            + self
+                .tables
+                .iter()
+                .map(|table| table.networks.capacity() * std::mem::size_of::<(K, u32)>())
+                .sum::<usize>()
+    }
+}
+
+/// Collect the entries of a blocklist before indexing them.
+#[derive(Debug, Default)]
+struct SnapshotBuilder {
+    entries: Vec<IpBlocklistEntry>,
+    malformed: usize,
+}
+
+impl SnapshotBuilder {
+    /// Collect the entries of `stream`, skipping its malformed lines.
+    async fn extend(&mut self, mut stream: EntryStream) -> Result<(), Error> {
+        while let Some(entry) = stream.next().await {
+            match entry {
+                Ok(entry) => self.entries.push(entry),
+                Err(Error::MalformedBlocklistLine { .. }) => self.malformed += 1,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    fn build(self) -> BlocklistSnapshot {
+        let entries = self.entries;
+        let indexed = || entries.iter().zip(0u32..);
+        let v4 = PrefixTables::new(indexed().filter_map(
+            |(entry, index)| match entry.network.addr {
+                IpAddr::V4(addr) => Some((u32::from(addr), entry.network.prefix_len, index)),
+                IpAddr::V6(_) => None,
+            },
+        ));
+        let v6 = PrefixTables::new(indexed().filter_map(
+            |(entry, index)| match entry.network.addr {
+                IpAddr::V6(addr) => Some((u128::from(addr), entry.network.prefix_len, index)),
+                IpAddr::V4(_) => None,
+            },
+        ));
+        let mut entries = entries;
+        entries.shrink_to_fit();
+        BlocklistSnapshot { entries, v4, v6 }
+    }
+}
+
+/// Returns the entries of the CSV file at `path`, read by chunks.
+#[cfg(not(target_arch = "wasm32"))]
+async fn file_entries(path: &Path) -> Result<EntryStream, Error> {
+    let file = tokio::fs::File::open(path).await?;
+    Ok(EntryStream::new(ByteStream::new(Body::wrap_stream(
+        file_chunks(file),
+    ))))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn file_chunks(file: tokio::fs::File) -> impl Stream<Item = std::io::Result<Bytes>> + Send {
+    futures::stream::try_unfold(file, |mut file| async move {
+        let mut chunk = Vec::with_capacity(FILE_CHUNK_SIZE);
+        let read = (&mut file)
+            .take(FILE_CHUNK_SIZE as u64)
+            .read_to_end(&mut chunk)
+            .await?;
+        Ok((read > 0).then(|| (Bytes::from(chunk), file)))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{blocklist::BlocklistCategories, ApiAuth};
+    use mockito::{mock, Matcher};
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    /// An entry of `network`, told apart from the others by its `last_seen`.
+    fn entry(network: &str, last_seen: u64) -> IpBlocklistEntry {
+        IpBlocklistEntry {
+            network: network.parse().unwrap(),
+            categories: BlocklistCategories::default(),
+            last_seen: Some(last_seen),
+        }
+    }
+
+    #[test]
+    fn test_cidr_boundaries() {
+        let snapshot = BlocklistSnapshot::from_entries(vec![
+            entry("192.0.2.0/24", 1),
+            entry("198.51.100.0/24", 2),
+            entry("198.51.100.7/32", 3),
+            // Host bits are ignored.
+            entry("203.0.113.77/28", 4),
+            entry("2001:db8::/32", 5),
+            entry("2001:db8:1::/48", 6),
+            entry("2001:db8:ffff::1/128", 7),
+        ]);
+        assert_eq!(snapshot.len(), 7);
+
+        struct TestingData {
+            pub name: String,
+            pub args: &'static str,
+            pub expected: Option<u64>,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Using the first address of a range".to_owned(),
+                args: "192.0.2.0",
+                expected: Some(1),
+            },
+            TestingData {
+                name: "Using the last address of a range".to_owned(),
+                args: "192.0.2.255",
+                expected: Some(1),
+            },
+            TestingData {
+                name: "Using the address before a range".to_owned(),
+                args: "192.0.1.255",
+                expected: None,
+            },
+            TestingData {
+                name: "Using the address after a range".to_owned(),
+                args: "192.0.3.0",
+                expected: None,
+            },
+            TestingData {
+                name: "Using a single address range".to_owned(),
+                args: "198.51.100.7",
+                expected: Some(3),
+            },
+            TestingData {
+                name: "Using the neighbours of a single address range".to_owned(),
+                args: "198.51.100.8",
+                expected: Some(2),
+            },
+            TestingData {
+                name: "Using the first address of a range with host bits".to_owned(),
+                args: "203.0.113.64",
+                expected: Some(4),
+            },
+            TestingData {
+                name: "Using the last address of a range with host bits".to_owned(),
+                args: "203.0.113.79",
+                expected: Some(4),
+            },
+            TestingData {
+                name: "Using the address after a range with host bits".to_owned(),
+                args: "203.0.113.80",
+                expected: None,
+            },
+            TestingData {
+                name: "Using the first IPv6 address of a range".to_owned(),
+                args: "2001:db8::",
+                expected: Some(5),
+            },
+            TestingData {
+                name: "Using the last IPv6 address of a range".to_owned(),
+                args: "2001:db8:ffff:ffff:ffff:ffff:ffff:ffff",
+                expected: Some(5),
+            },
+            TestingData {
+                name: "Using the IPv6 address before a range".to_owned(),
+                args: "2001:db7:ffff:ffff:ffff:ffff:ffff:ffff",
+                expected: None,
+            },
+            TestingData {
+                name: "Using the IPv6 address after a range".to_owned(),
+                args: "2001:db9::",
+                expected: None,
+            },
+            TestingData {
+                name: "Using the last IPv6 address of a nested range".to_owned(),
+                args: "2001:db8:1:ffff:ffff:ffff:ffff:ffff",
+                expected: Some(6),
+            },
+            TestingData {
+                name: "Using the IPv6 address after a nested range".to_owned(),
+                args: "2001:db8:2::",
+                expected: Some(5),
+            },
+            TestingData {
+                name: "Using a single IPv6 address range".to_owned(),
+                args: "2001:db8:ffff::1",
+                expected: Some(7),
+            },
+            TestingData {
+                name: "Using the IPv4 address of the bits of an IPv6 range".to_owned(),
+                args: "32.1.13.184",
+                expected: None,
+            },
+        ];
+
+        for test in tests {
+            assert_eq!(
+                snapshot
+                    .contains(test.args.parse().unwrap())
+                    .and_then(|entry| entry.last_seen),
+                test.expected,
+                "{}",
+                test.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_whole_address_space() {
+        let snapshot =
+            BlocklistSnapshot::from_entries(vec![entry("0.0.0.0/0", 1), entry("::/0", 2)]);
+        for (ip_addr, expected) in [
+            (IpAddr::V4(Ipv4Addr::UNSPECIFIED), 1),
+            (IpAddr::V4(Ipv4Addr::BROADCAST), 1),
+            (IpAddr::V6(Ipv6Addr::UNSPECIFIED), 2),
+            (IpAddr::V6(Ipv6Addr::from(u128::MAX)), 2),
+        ] {
+            assert_eq!(
+                snapshot.contains(ip_addr).unwrap().last_seen,
+                Some(expected)
+            );
+        }
+    }
+
+    #[test]
+    fn test_duplicate_ranges() {
+        let snapshot = BlocklistSnapshot::from_entries(vec![
+            entry("192.0.2.0/24", 1),
+            entry("192.0.2.9/24", 2),
+        ]);
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(
+            snapshot
+                .contains("192.0.2.9".parse().unwrap())
+                .unwrap()
+                .last_seen,
+            Some(1)
+        );
+        assert!(BlocklistSnapshot::default().is_empty());
+    }
+
+    #[test]
+    fn test_lookup_large_list() {
+        // The IPv4 list holds a few hundred thousand ranges, mostly single addresses, see benches/local_blocklist.rs for the timings.
+        let mut entries: Vec<_> = (0..200_000u32)
+            .map(|i| IpBlocklistEntry {
+                network: crate::blocklist::IpNetwork {
+                    addr: IpAddr::V4(Ipv4Addr::from(i.wrapping_mul(2_654_435_761))),
+                    prefix_len: if i % 10 == 0 { 24 } else { 32 },
+                },
+                categories: BlocklistCategories::default(),
+                last_seen: None,
+            })
+            .collect();
+        entries.extend((0..20_000u128).map(|i| IpBlocklistEntry {
+            network: crate::blocklist::IpNetwork {
+                addr: IpAddr::V6(Ipv6Addr::from((0x2001_0db8u128 << 96) | (i << 64))),
+                prefix_len: 64,
+            },
+            categories: BlocklistCategories::default(),
+            last_seen: None,
+        }));
+
+        let snapshot = BlocklistSnapshot::from_entries(entries.clone());
+        assert!(snapshot.len() <= entries.len());
+        assert!(
+            snapshot.heap_size() / entries.len() <= 96,
+            "{} bytes per range",
+            snapshot.heap_size() / entries.len()
+        );
+        for entry in &entries {
+            let listed = snapshot.contains(entry.network.addr).unwrap();
+            assert!(listed.network.contains(entry.network.addr), "{:?}", entry);
+        }
+        assert_eq!(snapshot.contains("2001:db9::1".parse().unwrap()), None);
+    }
+
+    const BLOCKLIST_V4: &str = "cidr,is-proxy,is-tor,last-seen\n\
+        192.0.2.0/24,true,false,1650000000\n\
+        198.51.100.7/32,false,maybe,1650000001\n\
+        198.51.100.8/32,false,true,\n";
+
+    #[tokio::test]
+    async fn test_load_file() {
+        let path = std::env::temp_dir().join(format!(
+            "neutral-local-blocklist-{}.csv",
+            std::process::id()
+        ));
+        // Lines longer than a chunk are split across reads.
+        let padding = " ".repeat(FILE_CHUNK_SIZE);
+        std::fs::write(
+            &path,
+            format!("{}203.0.113.1/32,false,false,{}\n", BLOCKLIST_V4, padding),
+        )
+        .unwrap();
+
+        let blocklist = LocalBlocklist::from_file(&path).await.unwrap();
+        let listed = blocklist.contains("192.0.2.255".parse().unwrap()).unwrap();
+        assert!(listed.categories.is_proxy);
+        assert_eq!(listed.last_seen, Some(1650000000));
+        assert!(
+            blocklist
+                .contains("198.51.100.8".parse().unwrap())
+                .unwrap()
+                .categories
+                .is_tor
+        );
+        assert_eq!(blocklist.contains("198.51.100.7".parse().unwrap()), None);
+        assert!(blocklist.contains("203.0.113.1".parse().unwrap()).is_some());
+        assert_eq!(blocklist.snapshot().len(), 3);
+
+        // A file which can't be read keeps the ranges.
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            blocklist.load_file(&path).await,
+            Err(Error::Io(_))
+        ));
+        assert_eq!(blocklist.snapshot().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_refresh() {
+        let query = |ip6: &str| {
+            Matcher::AllOf(vec![
+                Matcher::UrlEncoded("format".into(), "csv".into()),
+                Matcher::UrlEncoded("include-vpn".into(), "true".into()),
+                Matcher::UrlEncoded("cidr".into(), "true".into()),
+                Matcher::UrlEncoded("ip6".into(), ip6.into()),
+            ])
+        };
+        let _v4 = mock("GET", "/local-blocklist/ip-blocklist-download")
+            .match_query(query("false"))
+            .with_status(200)
+            .with_body(BLOCKLIST_V4)
+            .expect(1)
+            .create();
+        let _v6 = mock("GET", "/local-blocklist/ip-blocklist-download")
+            .match_query(query("true"))
+            .with_status(200)
+            .with_body("cidr,is-vpn\n2001:db8::/32,true\n")
+            .expect(1)
+            .create();
+
+        let neutral = Neutral::try_new(
+            &format!("{}/local-blocklist", mockito::server_url()),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+        let blocklist = LocalBlocklist::new(LocalBlocklistOptions {
+            include_vpn: true,
+            ip6: true,
+        });
+        let before = blocklist.snapshot();
+        assert_eq!(
+            blocklist.refresh(&neutral).await.unwrap(),
+            LoadReport {
+                entries: 3,
+                malformed: 1,
+            }
+        );
+        _v4.assert();
+        _v6.assert();
+
+        // A snapshot is not affected by a refresh.
+        assert!(before.is_empty());
+        assert!(
+            blocklist
+                .contains("2001:db8:ffff::1".parse().unwrap())
+                .unwrap()
+                .categories
+                .is_vpn
+        );
+        assert!(blocklist.contains("192.0.2.1".parse().unwrap()).is_some());
+
+        // A failed download keeps the ranges.
+        let _forbidden = mock("GET", "/local-blocklist/ip-blocklist-download")
+            .match_query(query("false"))
+            .with_status(403)
+            .with_body(r#"{"api-error": 2, "api-error-msg": "INVALID API KEY"}"#)
+            .create();
+        assert!(matches!(
+            blocklist.refresh(&neutral).await,
+            Err(Error::Unauthorized(_))
+        ));
+        assert_eq!(blocklist.snapshot().len(), 3);
+    }
+}
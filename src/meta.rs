@@ -0,0 +1,109 @@
+//! # Meta module
+//! Describe the response a call was answered with, beyond its decoded body.
+//!
+//! Every endpoint returning a JSON response provides a `send_with_meta` method, which returns a [ResponseMeta](./struct.ResponseMeta.html) along with the response.
+//! Read [RateLimit::remaining](./struct.RateLimit.html#structfield.remaining) to slow down before neutrinoapi.com answers with a 429.
+//!
+//! A response served from a cache was not sent by neutrinoapi.com, so its meta has neither header nor host.
+
+use std::time::Duration;
+
+use http::{response::Parts, uri::Authority, HeaderMap, StatusCode};
+
+/// The rate limit reported by neutrinoapi.com, each field is `None` when its header is absent or malformed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RateLimit {
+    /// Number of requests allowed in the current window, from the `x-ratelimit-limit` header.
+    pub limit: Option<u64>,
+    /// Number of requests left in the current window, from the `x-ratelimit-remaining` header.
+    pub remaining: Option<u64>,
+    /// Time left before the window resets, from the `x-ratelimit-reset` header in seconds.
+    pub reset: Option<Duration>,
+}
+
+impl RateLimit {
+    /// Parse the rate limit headers of `headers`.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        RateLimit {
+            limit: parse_header(headers, "x-ratelimit-limit"),
+            remaining: parse_header(headers, "x-ratelimit-remaining"),
+            reset: parse_header(headers, "x-ratelimit-reset").map(Duration::from_secs),
+        }
+    }
+}
+
+/// The status and headers of a response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResponseMeta {
+    /// Status of the response.
+    pub status: StatusCode,
+    /// Rate limit parsed from the headers.
+    pub rate_limit: RateLimit,
+    /// Every header of the response.
+    pub headers: HeaderMap,
+    /// True when the response was served from a cache, without any request to neutrinoapi.com.
+    pub is_cached: bool,
+    /// Host which served the response, the base uri or one of the failover uris, see the [region](../region/index.html) module.
+    pub host: Option<Authority>,
+}
+
+impl ResponseMeta {
+    pub(crate) fn new(parts: &Parts, host: Option<Authority>) -> Self {
+        ResponseMeta {
+            status: parts.status,
+            rate_limit: RateLimit::from_headers(&parts.headers),
+            headers: parts.headers.clone(),
+            is_cached: false,
+            host,
+        }
+    }
+
+    /// Returns the meta of a response served from a cache.
+    pub(crate) fn cached() -> Self {
+        ResponseMeta {
+            status: StatusCode::OK,
+            rate_limit: RateLimit::default(),
+            headers: HeaderMap::new(),
+            is_cached: true,
+            host: None,
+        }
+    }
+}
+
+fn parse_header(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use http::HeaderValue;
+
+    #[test]
+    fn test_rate_limit_from_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-limit", HeaderValue::from_static("1000"));
+        headers.insert("x-ratelimit-remaining", HeaderValue::from_static(" 42 "));
+        headers.insert("x-ratelimit-reset", HeaderValue::from_static("60"));
+        assert_eq!(
+            RateLimit::from_headers(&headers),
+            RateLimit {
+                limit: Some(1000),
+                remaining: Some(42),
+                reset: Some(Duration::from_secs(60)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_rate_limit_from_missing_or_malformed_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-remaining", HeaderValue::from_static("many"));
+        headers.insert("x-ratelimit-reset", HeaderValue::from_static("-1"));
+        assert_eq!(RateLimit::from_headers(&headers), RateLimit::default());
+        assert_eq!(
+            RateLimit::from_headers(&HeaderMap::new()),
+            RateLimit::default()
+        );
+    }
+}
@@ -0,0 +1,214 @@
+//! # Metrics module
+//! Observe the traffic between [Neutral](../struct.Neutral.html) and neutrinoapi.com.
+//!
+//! Implement [MetricsSink](./trait.MetricsSink.html) to export metrics to the backend of your choice and register it with [NeutralBuilder::metrics](../builder/struct.NeutralBuilder.html#method.metrics).
+//! With the `prometheus` feature, [PrometheusSink](./struct.PrometheusSink.html) is a ready to use sink rendering the Prometheus exposition format.
+
+use std::{fmt, sync::Arc, time::Duration};
+
+use http::StatusCode;
+
+use crate::{warning::Warning, Error};
+
+#[cfg(feature = "prometheus")]
+mod prometheus;
+
+#[cfg(feature = "prometheus")]
+pub use self::prometheus::PrometheusSink;
+
+/// Receive events about the requests sent to neutrinoapi.com.
+///
+/// Every method has a no-op default implementation, so a sink only implements the events it is interested in.
+pub trait MetricsSink: Send + Sync {
+    /// Called once a request completed. `status` is `None` when no response was received.
+    fn on_request(&self, _endpoint: &str, _status: Option<StatusCode>, _elapsed: Duration) {}
+
+    /// Called once a request completed, with the labels taken from its [CallContext](../context/struct.CallContext.html).
+    ///
+    /// Only the keys allowed with [NeutralBuilder::metric_labels](../builder/struct.NeutralBuilder.html#method.metric_labels) are present.
+    /// Defaults to [on_request](#method.on_request), ignoring the labels.
+    fn on_request_with_labels(
+        &self,
+        endpoint: &str,
+        status: Option<StatusCode>,
+        elapsed: Duration,
+        _labels: &[(&str, &str)],
+    ) {
+        self.on_request(endpoint, status, elapsed);
+    }
+
+    /// Called once a request failed, after [on_request](#method.on_request), with the error returned for it.
+    ///
+    /// Covers the failures to send the request or to receive its response, and the error responses of neutrinoapi.com.
+    fn on_error(&self, _endpoint: &str, _error: &Error) {}
+
+    /// Called before a request is sent again.
+    fn on_retry(&self, _endpoint: &str) {}
+
+    /// Called before a request is sent to the next host, `host` being the one which failed, see the [region](../region/index.html) module.
+    fn on_failover(&self, _endpoint: &str, _host: &str) {}
+
+    /// Called when a response is served from a cache.
+    fn on_cache_hit(&self, _endpoint: &str) {}
+
+    /// Called when neutrinoapi.com answered with a rate limit error.
+    fn on_rate_limited(&self, _endpoint: &str) {}
+
+    /// Called when a non fatal anomaly is detected while serving a call.
+    fn on_warning(&self, _endpoint: &str, _warning: &Warning) {}
+}
+
+/// The sinks registered on a client.
+#[derive(Clone, Default)]
+pub(crate) struct MetricsSinks(pub(crate) Vec<Arc<dyn MetricsSink>>);
+
+impl fmt::Debug for MetricsSinks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MetricsSinks").field(&self.0.len()).finish()
+    }
+}
+
+impl MetricsSink for MetricsSinks {
+    fn on_request(&self, endpoint: &str, status: Option<StatusCode>, elapsed: Duration) {
+        for sink in &self.0 {
+            sink.on_request(endpoint, status, elapsed);
+        }
+    }
+
+    fn on_request_with_labels(
+        &self,
+        endpoint: &str,
+        status: Option<StatusCode>,
+        elapsed: Duration,
+        labels: &[(&str, &str)],
+    ) {
+        for sink in &self.0 {
+            sink.on_request_with_labels(endpoint, status, elapsed, labels);
+        }
+    }
+
+    fn on_error(&self, endpoint: &str, error: &Error) {
+        for sink in &self.0 {
+            sink.on_error(endpoint, error);
+        }
+    }
+
+    fn on_retry(&self, endpoint: &str) {
+        for sink in &self.0 {
+            sink.on_retry(endpoint);
+        }
+    }
+
+    fn on_failover(&self, endpoint: &str, host: &str) {
+        for sink in &self.0 {
+            sink.on_failover(endpoint, host);
+        }
+    }
+
+    fn on_cache_hit(&self, endpoint: &str) {
+        for sink in &self.0 {
+            sink.on_cache_hit(endpoint);
+        }
+    }
+
+    fn on_rate_limited(&self, endpoint: &str) {
+        for sink in &self.0 {
+            sink.on_rate_limited(endpoint);
+        }
+    }
+
+    fn on_warning(&self, endpoint: &str, warning: &Warning) {
+        for sink in &self.0 {
+            sink.on_warning(endpoint, warning);
+        }
+    }
+}
+
+/// Returns the name of the endpoint targeted by a request path, e.g. `ip-info` for `/ip-info`.
+pub(crate) fn endpoint_name(path: &str) -> &str {
+    path.trim_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ApiAuth, Neutral};
+    use mockito::{mock, Matcher};
+    use std::sync::Mutex;
+
+    /// Count the outcomes of the requests, per endpoint.
+    #[derive(Default)]
+    struct CountingSink {
+        responses: Mutex<Vec<(String, Option<StatusCode>)>>,
+        errors: Mutex<Vec<String>>,
+    }
+
+    impl MetricsSink for CountingSink {
+        fn on_request(&self, endpoint: &str, status: Option<StatusCode>, _elapsed: Duration) {
+            self.responses
+                .lock()
+                .unwrap()
+                .push((endpoint.to_owned(), status));
+        }
+
+        fn on_error(&self, endpoint: &str, _error: &Error) {
+            self.errors.lock().unwrap().push(endpoint.to_owned());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_on_request_and_on_error() {
+        let _ok = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.200$".into()))
+            .with_status(200)
+            .with_body(crate::fixtures::IP_INFO_BODY)
+            .create();
+        let _failing = mock("GET", "/ip-probe")
+            .match_query(Matcher::Regex("ip=203.0.113.201$".into()))
+            .with_status(500)
+            .with_body("internal error")
+            .create();
+
+        let sink = Arc::new(CountingSink::default());
+        let neutral = Neutral::builder(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .metrics(sink.clone())
+        .build()
+        .unwrap();
+
+        assert!(neutral
+            .ip_info()
+            .send("203.0.113.200".parse().unwrap())
+            .await
+            .is_ok());
+        assert!(neutral
+            .ip_probe()
+            .send("203.0.113.201".parse().unwrap())
+            .await
+            .is_err());
+
+        assert_eq!(
+            *sink.responses.lock().unwrap(),
+            vec![
+                ("ip-info".to_owned(), Some(StatusCode::OK)),
+                (
+                    "ip-probe".to_owned(),
+                    Some(StatusCode::INTERNAL_SERVER_ERROR)
+                ),
+            ]
+        );
+        assert_eq!(*sink.errors.lock().unwrap(), vec!["ip-probe".to_owned()]);
+    }
+
+    #[test]
+    fn test_endpoint_name() {
+        assert_eq!(endpoint_name("/ip-info"), "ip-info");
+        assert_eq!(endpoint_name("/phone-validate/"), "phone-validate");
+        assert_eq!(endpoint_name("/"), "");
+    }
+}
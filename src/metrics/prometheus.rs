@@ -0,0 +1,296 @@
+//! A [MetricsSink](../trait.MetricsSink.html) rendering the Prometheus text exposition format.
+
+use std::{collections::BTreeMap, fmt::Write, sync::Mutex, time::Duration};
+
+use http::StatusCode;
+
+use super::MetricsSink;
+
+const DURATION_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Debug, Default)]
+struct Histogram {
+    buckets: [u64; DURATION_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    requests: BTreeMap<(String, String, &'static str), u64>,
+    durations: BTreeMap<String, Histogram>,
+    retries: BTreeMap<String, u64>,
+    cache_hits: BTreeMap<String, u64>,
+    rate_limited: BTreeMap<String, u64>,
+}
+
+/// Maintain counters and histograms about neutrinoapi.com traffic, to serve them from your own `/metrics` handler.
+///
+/// ```ignore
+/// let sink = Arc::new(PrometheusSink::new());
+/// let neutral = Neutral::builder("https://neutrinoapi.net", api_auth)
+///     .metrics(sink.clone())
+///     .build()?;
+///
+/// // In your http handler
+/// let body = sink.render();
+/// ```
+#[derive(Debug, Default)]
+pub struct PrometheusSink {
+    state: Mutex<State>,
+}
+
+impl PrometheusSink {
+    /// Create a sink without any recorded event.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Render every series in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let state = self.state.lock().unwrap();
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "neutral_requests_total",
+            "counter",
+            "Requests sent to neutrinoapi.com.",
+        );
+        for ((endpoint, labels, status), value) in &state.requests {
+            let _ = writeln!(
+                out,
+                "neutral_requests_total{{endpoint=\"{}\"{},status=\"{}\"}} {}",
+                escape(endpoint),
+                labels,
+                status,
+                value
+            );
+        }
+
+        header(
+            &mut out,
+            "neutral_request_duration_seconds",
+            "histogram",
+            "Duration of the requests sent to neutrinoapi.com.",
+        );
+        for (endpoint, histogram) in &state.durations {
+            let endpoint = escape(endpoint);
+            for (le, value) in DURATION_BUCKETS.iter().zip(histogram.buckets.iter()) {
+                let _ = writeln!(
+                    out,
+                    "neutral_request_duration_seconds_bucket{{endpoint=\"{}\",le=\"{}\"}} {}",
+                    endpoint, le, value
+                );
+            }
+            let _ = writeln!(
+                out,
+                "neutral_request_duration_seconds_bucket{{endpoint=\"{}\",le=\"+Inf\"}} {}",
+                endpoint, histogram.count
+            );
+            let _ = writeln!(
+                out,
+                "neutral_request_duration_seconds_sum{{endpoint=\"{}\"}} {}",
+                endpoint, histogram.sum
+            );
+            let _ = writeln!(
+                out,
+                "neutral_request_duration_seconds_count{{endpoint=\"{}\"}} {}",
+                endpoint, histogram.count
+            );
+        }
+
+        counter(
+            &mut out,
+            "neutral_retries_total",
+            "Requests sent again after a failure.",
+            &state.retries,
+        );
+        counter(
+            &mut out,
+            "neutral_cache_hits_total",
+            "Responses served from a cache.",
+            &state.cache_hits,
+        );
+        counter(
+            &mut out,
+            "neutral_rate_limited_total",
+            "Requests rejected by the neutrinoapi.com rate limit.",
+            &state.rate_limited,
+        );
+
+        out
+    }
+}
+
+impl MetricsSink for PrometheusSink {
+    fn on_request(&self, endpoint: &str, status: Option<StatusCode>, elapsed: Duration) {
+        self.on_request_with_labels(endpoint, status, elapsed, &[]);
+    }
+
+    fn on_request_with_labels(
+        &self,
+        endpoint: &str,
+        status: Option<StatusCode>,
+        elapsed: Duration,
+        labels: &[(&str, &str)],
+    ) {
+        let labels: String = labels
+            .iter()
+            .map(|(key, value)| format!(",{}=\"{}\"", key, escape(value)))
+            .collect();
+        let mut state = self.state.lock().unwrap();
+        *state
+            .requests
+            .entry((endpoint.to_owned(), labels, status_class(status)))
+            .or_default() += 1;
+
+        let seconds = elapsed.as_secs_f64();
+        let histogram = state.durations.entry(endpoint.to_owned()).or_default();
+        for (le, bucket) in DURATION_BUCKETS.iter().zip(histogram.buckets.iter_mut()) {
+            if seconds <= *le {
+                *bucket += 1;
+            }
+        }
+        histogram.sum += seconds;
+        histogram.count += 1;
+    }
+
+    fn on_retry(&self, endpoint: &str) {
+        let mut state = self.state.lock().unwrap();
+        *state.retries.entry(endpoint.to_owned()).or_default() += 1;
+    }
+
+    fn on_cache_hit(&self, endpoint: &str) {
+        let mut state = self.state.lock().unwrap();
+        *state.cache_hits.entry(endpoint.to_owned()).or_default() += 1;
+    }
+
+    fn on_rate_limited(&self, endpoint: &str) {
+        let mut state = self.state.lock().unwrap();
+        *state.rate_limited.entry(endpoint.to_owned()).or_default() += 1;
+    }
+}
+
+fn status_class(status: Option<StatusCode>) -> &'static str {
+    match status.map(|status| status.as_u16() / 100) {
+        Some(1) => "1xx",
+        Some(2) => "2xx",
+        Some(3) => "3xx",
+        Some(4) => "4xx",
+        Some(5) => "5xx",
+        _ => "error",
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn counter(out: &mut String, name: &str, help: &str, values: &BTreeMap<String, u64>) {
+    header(out, name, "counter", help);
+    for (endpoint, value) in values {
+        let _ = writeln!(
+            out,
+            "{}{{endpoint=\"{}\"}} {}",
+            name,
+            escape(endpoint),
+            value
+        );
+    }
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{cache::NegativeCacheConfig, ApiAuth, Neutral};
+    use mockito::{mock, Matcher};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_prometheus_sink_render() {
+        let body_resp = r#"
+            {
+                "valid":false,
+                "type":"unknown",
+                "international_calling_code":"",
+                "international_number":"",
+                "local_number":"",
+                "location":"",
+                "country":"",
+                "country_code":"",
+                "country_code3":"",
+                "currency_code":"",
+                "is_mobile":false,
+                "prefix_network":""
+            }"#;
+
+        let _ok = mock("GET", "/phone-validate")
+            .match_query(Matcher::Regex("number=1234".into()))
+            .with_status(200)
+            .with_body(body_resp)
+            .create();
+
+        let _limited = mock("GET", "/hlr-lookup")
+            .match_query(Matcher::Any)
+            .with_status(429)
+            .with_body("rate limit exceeded")
+            .create();
+
+        let sink = Arc::new(PrometheusSink::new());
+        let neutral = Neutral::builder(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .negative_cache(NegativeCacheConfig::default())
+        .metrics(sink.clone())
+        .build()
+        .unwrap();
+
+        assert!(neutral
+            .phone_validate()
+            .send("1234".to_owned())
+            .await
+            .is_ok());
+        assert!(neutral
+            .phone_validate()
+            .send("1234".to_owned())
+            .await
+            .is_ok());
+        assert!(neutral
+            .hlr_lookup()
+            .send("12345678".to_owned())
+            .await
+            .is_err());
+
+        let rendered = sink.render();
+        for expected in [
+            "# TYPE neutral_requests_total counter",
+            "neutral_requests_total{endpoint=\"phone-validate\",status=\"2xx\"} 1",
+            "neutral_requests_total{endpoint=\"hlr-lookup\",status=\"4xx\"} 1",
+            "# TYPE neutral_request_duration_seconds histogram",
+            "neutral_request_duration_seconds_bucket{endpoint=\"phone-validate\",le=\"+Inf\"} 1",
+            "neutral_request_duration_seconds_count{endpoint=\"hlr-lookup\"} 1",
+            "neutral_cache_hits_total{endpoint=\"phone-validate\"} 1",
+            "neutral_rate_limited_total{endpoint=\"hlr-lookup\"} 1",
+            "# TYPE neutral_retries_total counter",
+        ] {
+            assert!(rendered.contains(expected), "missing {}", expected);
+        }
+    }
+
+    #[test]
+    fn test_label_escaping() {
+        assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
@@ -0,0 +1,293 @@
+//! # Multi module
+//! Compose several neutrinoapi.com calls, possibly built dynamically, and send them concurrently.
+//!
+//! Each added call returns a typed [Handle](./struct.Handle.html) which retrieves its own result after [send](./struct.Multi.html#method.send), without any downcasting on the caller side.
+//! A failing call never prevents the other ones from completing.
+//!
+//! ```ignore
+//! let mut multi = neutral.multi();
+//! let handles: Vec<_> = ips.into_iter().map(|ip| multi.ip_info(ip)).collect();
+//! let phone = multi.phone_validate("+12345678901".to_owned());
+//! let results = multi.send().await;
+//!
+//! for handle in &handles {
+//!     let ip_info: &SectionResult<IpInfoResponse> = results.get(handle);
+//! }
+//! ```
+
+use std::{any::Any, future::Future, marker::PhantomData, net::IpAddr, pin::Pin};
+
+use futures::future::join_all;
+use neutral_types::{
+    hlr_lookup::HlrLookupResponse, ip_blocklist::IpBlocklistResponse, ip_info::IpInfoResponse,
+    ip_probe::IpProbeResponse, phone_validate::PhoneValidateResponse,
+};
+
+use crate::{Error, Neutral};
+
+/// Result of a single call of a [Multi](./struct.Multi.html).
+pub type SectionResult<T> = Result<T, Error>;
+
+type ErasedResult = Box<dyn Any + Send>;
+type ErasedFuture<'a> = Pin<Box<dyn Future<Output = (bool, ErasedResult)> + Send + 'a>>;
+
+/// Identify a call added to a [Multi](./struct.Multi.html) and the type of its response.
+#[derive(Debug)]
+pub struct Handle<T> {
+    index: usize,
+    kind: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+/// Type erased description of a call, mainly useful for logging.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SectionSummary {
+    /// Name of the neutrinoapi.com endpoint.
+    pub endpoint: &'static str,
+    /// Input of the call, as sent to neutrinoapi.com.
+    pub input: String,
+    /// Whether the call succeeded.
+    pub is_ok: bool,
+}
+
+struct Section<'a> {
+    endpoint: &'static str,
+    input: String,
+    future: ErasedFuture<'a>,
+}
+
+struct SectionOutput {
+    endpoint: &'static str,
+    input: String,
+    is_ok: bool,
+    result: ErasedResult,
+}
+
+/// Collect calls to send them concurrently.
+pub struct Multi<'a> {
+    pub(crate) neutral: &'a Neutral,
+    sections: Vec<Section<'a>>,
+}
+
+impl<'a> Multi<'a> {
+    pub(crate) fn new(neutral: &'a Neutral) -> Self {
+        Multi {
+            neutral,
+            sections: Vec::new(),
+        }
+    }
+
+    fn push<T, F>(&mut self, endpoint: &'static str, input: String, future: F) -> Handle<T>
+    where
+        T: Send + 'static,
+        F: Future<Output = SectionResult<T>> + Send + 'a,
+    {
+        let index = self.sections.len();
+        self.sections.push(Section {
+            endpoint,
+            input,
+            future: Box::pin(async move {
+                let result = future.await;
+                (result.is_ok(), Box::new(result) as ErasedResult)
+            }),
+        });
+        Handle {
+            index,
+            kind: PhantomData,
+        }
+    }
+
+    /// Add an ip info call.
+    pub fn ip_info(&mut self, ip_addr: IpAddr) -> Handle<IpInfoResponse> {
+        let neutral = self.neutral;
+        self.push("ip-info", ip_addr.to_string(), async move {
+            neutral.ip_info().send(ip_addr).await
+        })
+    }
+
+    /// Add an ip blocklist call.
+    pub fn ip_blocklist(&mut self, ip_addr: IpAddr) -> Handle<IpBlocklistResponse> {
+        let neutral = self.neutral;
+        self.push("ip-blocklist", ip_addr.to_string(), async move {
+            neutral.ip_blocklist().send(ip_addr).await
+        })
+    }
+
+    /// Add an ip probe call.
+    pub fn ip_probe(&mut self, ip_addr: IpAddr) -> Handle<IpProbeResponse> {
+        let neutral = self.neutral;
+        self.push("ip-probe", ip_addr.to_string(), async move {
+            neutral.ip_probe().send(ip_addr).await
+        })
+    }
+
+    /// Add a phone validate call.
+    pub fn phone_validate(&mut self, phone_number: String) -> Handle<PhoneValidateResponse> {
+        let neutral = self.neutral;
+        self.push("phone-validate", phone_number.clone(), async move {
+            neutral.phone_validate().send(phone_number).await
+        })
+    }
+
+    /// Add an hlr lookup call.
+    pub fn hlr_lookup(&mut self, phone_number: String) -> Handle<HlrLookupResponse> {
+        let neutral = self.neutral;
+        self.push("hlr-lookup", phone_number.clone(), async move {
+            neutral.hlr_lookup().send(phone_number).await
+        })
+    }
+
+    /// Send all the calls concurrently and wait for every one of them to complete.
+    pub async fn send(self) -> MultiResults {
+        let (descriptions, futures): (Vec<_>, Vec<_>) = self
+            .sections
+            .into_iter()
+            .map(|section| ((section.endpoint, section.input), section.future))
+            .unzip();
+
+        let sections = descriptions
+            .into_iter()
+            .zip(join_all(futures).await)
+            .map(|((endpoint, input), (is_ok, result))| SectionOutput {
+                endpoint,
+                input,
+                is_ok,
+                result,
+            })
+            .collect();
+
+        MultiResults { sections }
+    }
+}
+
+/// Results of a [Multi](./struct.Multi.html), where some calls may have failed.
+pub struct MultiResults {
+    sections: Vec<SectionOutput>,
+}
+
+impl MultiResults {
+    /// Returns the result of the call identified by `handle`.
+    pub fn get<T: 'static>(&self, handle: &Handle<T>) -> &SectionResult<T> {
+        self.sections[handle.index]
+            .result
+            .downcast_ref::<SectionResult<T>>()
+            .expect("handle from another multi")
+    }
+
+    /// Iterate over a summary of every call, in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = SectionSummary> + '_ {
+        self.sections.iter().map(|section| SectionSummary {
+            endpoint: section.endpoint,
+            input: section.input.clone(),
+            is_ok: section.is_ok,
+        })
+    }
+
+    /// Returns the summaries of the failed calls only.
+    pub fn failures(&self) -> impl Iterator<Item = SectionSummary> + '_ {
+        self.iter().filter(|summary| !summary.is_ok)
+    }
+
+    /// Returns the number of calls.
+    pub fn len(&self) -> usize {
+        self.sections.len()
+    }
+
+    /// Returns true when no call was added.
+    pub fn is_empty(&self) -> bool {
+        self.sections.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ApiAuth;
+    use mockito::{mock, Matcher};
+    use std::net::Ipv4Addr;
+
+    fn ip_info_body(ip_addr: IpAddr) -> String {
+        format!(
+            r#"
+            {{
+                "ip": "{}",
+                "valid": true,
+                "is_v6": false,
+                "is_v4_mapped": false,
+                "is_bogon": false,
+                "country": "ACountry",
+                "country_code": "AC",
+                "country_code3": "ACO",
+                "continent_code": "EU",
+                "currency_code": "ABC",
+                "city": "Roubaix",
+                "region": "Hauts-de-ACountry",
+                "longitude": 1.00000,
+                "latitude": 1.00000,
+                "hostname": "",
+                "host_domain": "",
+                "timezone": {{}}
+            }}"#,
+            ip_addr
+        )
+    }
+
+    #[tokio::test]
+    async fn test_multi_built_in_a_loop() {
+        let ips: Vec<IpAddr> = (1..=10)
+            .map(|i| IpAddr::V4(Ipv4Addr::new(128, 0, 0, i)))
+            .collect();
+
+        let _ip_mocks: Vec<_> = ips
+            .iter()
+            .map(|ip_addr| {
+                mock("GET", "/ip-info")
+                    .match_query(Matcher::Regex(format!("ip={}$", ip_addr)))
+                    .with_status(200)
+                    .with_body(ip_info_body(*ip_addr))
+                    .create()
+            })
+            .collect();
+
+        let _phone_mock = mock("GET", "/phone-validate")
+            .match_query(Matcher::Regex("number=%2B12345678901".into()))
+            .with_status(500)
+            .with_body("internal error")
+            .create();
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        let mut multi = neutral.multi();
+        let handles: Vec<_> = ips.iter().map(|ip_addr| multi.ip_info(*ip_addr)).collect();
+        let phone = multi.phone_validate("+12345678901".to_owned());
+        let results = multi.send().await;
+
+        assert_eq!(results.len(), 11);
+        for (ip_addr, handle) in ips.iter().zip(&handles) {
+            let ip_info = results.get(handle).as_ref().unwrap();
+            assert_eq!(&ip_info.ip, ip_addr);
+        }
+        assert!(results.get(&phone).is_err());
+
+        let failures: Vec<_> = results.failures().collect();
+        assert_eq!(
+            failures,
+            vec![SectionSummary {
+                endpoint: "phone-validate",
+                input: "+12345678901".to_owned(),
+                is_ok: false,
+            }]
+        );
+    }
+}
@@ -0,0 +1,109 @@
+//! Build `multipart/form-data` request bodies, to upload files.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+/// A `multipart/form-data` body being built.
+#[derive(Debug)]
+pub(crate) struct Multipart {
+    boundary: String,
+    body: Vec<u8>,
+}
+
+impl Multipart {
+    /// Start a body with a random boundary.
+    pub(crate) fn new() -> Self {
+        let hasher = RandomState::new().build_hasher();
+        Self::with_boundary(format!("neutral-{:016x}", hasher.finish()))
+    }
+
+    pub(crate) fn with_boundary(boundary: String) -> Self {
+        Multipart {
+            boundary,
+            body: Vec::new(),
+        }
+    }
+
+    /// Add a text field.
+    pub(crate) fn text(&mut self, name: &str, value: &str) -> &mut Self {
+        self.part_header(name, None);
+        self.body.extend_from_slice(value.as_bytes());
+        self.body.extend_from_slice(b"\r\n");
+        self
+    }
+
+    /// Add a file field.
+    pub(crate) fn file(
+        &mut self,
+        name: &str,
+        filename: &str,
+        content_type: &str,
+        data: &[u8],
+    ) -> &mut Self {
+        self.part_header(name, Some((filename, content_type)));
+        self.body.extend_from_slice(data);
+        self.body.extend_from_slice(b"\r\n");
+        self
+    }
+
+    /// Returns the content type of the body, with its boundary, and the body.
+    pub(crate) fn finish(mut self) -> (String, Vec<u8>) {
+        self.body
+            .extend_from_slice(format!("--{}--\r\n", self.boundary).as_bytes());
+        (
+            format!("multipart/form-data; boundary={}", self.boundary),
+            self.body,
+        )
+    }
+
+    fn part_header(&mut self, name: &str, file: Option<(&str, &str)>) {
+        let mut header = format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"{}\"",
+            self.boundary,
+            escape(name)
+        );
+        if let Some((filename, content_type)) = file {
+            header.push_str(&format!(
+                "; filename=\"{}\"\r\nContent-Type: {}",
+                escape(filename),
+                content_type
+            ));
+        }
+        header.push_str("\r\n\r\n");
+        self.body.extend_from_slice(header.as_bytes());
+    }
+}
+
+/// Escape a quoted parameter of a `Content-Disposition` header.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace(['\r', '\n'], " ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_multipart() {
+        let mut multipart = Multipart::with_boundary("b0undary".to_owned());
+        multipart
+            .text("opacity", "50")
+            .file("image", "logo \"v2\".png", "image/png", b"\x89PNG");
+        let (content_type, body) = multipart.finish();
+
+        assert_eq!(content_type, "multipart/form-data; boundary=b0undary");
+        assert_eq!(
+            body,
+            b"--b0undary\r\nContent-Disposition: form-data; name=\"opacity\"\r\n\r\n50\r\n\
+              --b0undary\r\nContent-Disposition: form-data; name=\"image\"; filename=\"logo \\\"v2\\\".png\"\r\nContent-Type: image/png\r\n\r\n\x89PNG\r\n\
+              --b0undary--\r\n"
+                .to_vec()
+        );
+        assert_ne!(Multipart::new().boundary, Multipart::new().boundary);
+    }
+}
@@ -0,0 +1,165 @@
+//! # Optional text module
+//! neutrinoapi.com uses empty strings where a value is unknown or not applicable.
+//!
+//! The extension traits of this module expose these text fields as `Option<&str>`, mapping `""` to `None`, without changing the response types.
+//!
+//! ```ignore
+//! use neutral::optional_text::IpProbeOptionalText;
+//!
+//! let ip_probe_response = neutral.ip_probe().send(ip_addr).await?;
+//! if let Some(vpn_domain) = ip_probe_response.vpn_domain_opt() {
+//!     println!("VPN provided by {}", vpn_domain);
+//! }
+//! ```
+
+use neutral_types::{
+    hlr_lookup::HlrLookupResponse, ip_info::IpInfoResponse, ip_probe::IpProbeResponse,
+    phone_validate::PhoneValidateResponse,
+};
+
+/// Map an empty text to `None`.
+pub trait OptionalText {
+    /// Returns `None` if the text is empty, the text otherwise.
+    fn optional_text(&self) -> Option<&str>;
+}
+
+impl OptionalText for str {
+    fn optional_text(&self) -> Option<&str> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self)
+        }
+    }
+}
+
+impl OptionalText for String {
+    fn optional_text(&self) -> Option<&str> {
+        self.as_str().optional_text()
+    }
+}
+
+/// Declare an extension trait exposing text fields of a response as `Option<&str>`, and its test.
+macro_rules! optional_text_ext {
+    (
+        $(#[$meta:meta])*
+        $ext:ident for $response:ty, test $test:ident with $fixture:expr,
+        { $($field:ident => $method:ident),* $(,)? }
+    ) => {
+        $(#[$meta])*
+        pub trait $ext {
+            $(
+                #[doc = concat!("Returns `", stringify!($field), "`, or `None` when it is empty.")]
+                fn $method(&self) -> Option<&str>;
+            )*
+        }
+
+        impl $ext for $response {
+            $(
+                fn $method(&self) -> Option<&str> {
+                    self.$field.optional_text()
+                }
+            )*
+        }
+
+        #[cfg(test)]
+        #[test]
+        fn $test() {
+            let response: $response = serde_json::from_str($fixture).unwrap();
+            $(
+                let mut empty = response.clone();
+                empty.$field = String::new();
+                assert_eq!(empty.$method(), None, "{} is empty", stringify!($field));
+
+                let mut filled = response.clone();
+                filled.$field = "value".to_owned();
+                assert_eq!(filled.$method(), Some("value"), "{} is filled", stringify!($field));
+            )*
+        }
+    };
+}
+
+optional_text_ext! {
+    /// Optional text fields of an ip info response.
+    IpInfoOptionalText for IpInfoResponse,
+    test test_ip_info_optional_text with crate::fixtures::IP_INFO_BODY,
+    {
+        country => country_opt,
+        country_code => country_code_opt,
+        country_code3 => country_code3_opt,
+        continent_code => continent_code_opt,
+        currency_code => currency_code_opt,
+        city => city_opt,
+        region => region_opt,
+        hostname => hostname_opt,
+        host_domain => host_domain_opt,
+    }
+}
+
+optional_text_ext! {
+    /// Optional text fields of an ip probe response.
+    IpProbeOptionalText for IpProbeResponse,
+    test test_ip_probe_optional_text with crate::fixtures::IP_PROBE_BODY,
+    {
+        country => country_opt,
+        country_code => country_code_opt,
+        country_code3 => country_code3_opt,
+        continent_code => continent_code_opt,
+        currency_code => currency_code_opt,
+        city => city_opt,
+        region => region_opt,
+        hostname => hostname_opt,
+        host_domain => host_domain_opt,
+        provider_domain => provider_domain_opt,
+        provider_description => provider_description_opt,
+        provider_website => provider_website_opt,
+        vpn_domain => vpn_domain_opt,
+        asn => asn_opt,
+        as_cidr => as_cidr_opt,
+        as_country_code => as_country_code_opt,
+        as_country_code3 => as_country_code3_opt,
+        as_description => as_description_opt,
+    }
+}
+
+optional_text_ext! {
+    /// Optional text fields of an hlr lookup response.
+    HlrLookupOptionalText for HlrLookupResponse,
+    test test_hlr_lookup_optional_text with crate::fixtures::HLR_LOOKUP_BODY,
+    {
+        imsi => imsi_opt,
+        mcc => mcc_opt,
+        mnc => mnc_opt,
+        msin => msin_opt,
+        msc => msc_opt,
+        current_network => current_network_opt,
+        origin_network => origin_network_opt,
+        ported_network => ported_network_opt,
+        location => location_opt,
+        country => country_opt,
+        country_code => country_code_opt,
+        country_code3 => country_code3_opt,
+        currency_code => currency_code_opt,
+        roaming_country_code => roaming_country_code_opt,
+        international_calling_code => international_calling_code_opt,
+        international_number => international_number_opt,
+        local_number => local_number_opt,
+    }
+}
+
+optional_text_ext! {
+    /// Optional text fields of a phone validate response.
+    PhoneValidateOptionalText for PhoneValidateResponse,
+    test test_phone_validate_optional_text with crate::fixtures::PHONE_VALIDATE_BODY,
+    {
+        international_calling_code => international_calling_code_opt,
+        international_number => international_number_opt,
+        local_number => local_number_opt,
+        location => location_opt,
+        country => country_opt,
+        country_code => country_code_opt,
+        country_code3 => country_code3_opt,
+        currency_code => currency_code_opt,
+        prefix_network => prefix_network_opt,
+    }
+}
@@ -0,0 +1,247 @@
+//! # Phone number module
+//! Phone numbers kept as their exact digit string.
+//!
+//! A phone number is never parsed to an integer: leading zeros are significant in many national formats (e.g. `06` for Rome) and short codes are not numbers at all.
+//! The digits are sent to neutrinoapi.com exactly as parsed.
+//!
+//! The phone number endpoints accept a [PhoneNumber] or a string, parsed before any request so an invalid number is never billed:
+//!
+//! ```ignore
+//! let number = PhoneNumber::try_from("+33 6 12 34 56 78")?;
+//! let response = neutral.phone_validate().send(number).await?;
+//! let response = neutral.phone_validate().send("+33 6 12 34 56 78").await?;
+//! ```
+
+use std::{fmt, str::FromStr};
+
+use crate::Error;
+
+/// Shortest number which is not a short code.
+const MIN_SUBSCRIBER_DIGITS: usize = 7;
+/// Shortest number in the international format, country calling code included.
+const MIN_INTERNATIONAL_DIGITS: usize = 6;
+/// Longest number allowed by E.164.
+const MAX_DIGITS: usize = 15;
+
+/// Describes the kind of a phone number, as far as it can be told from its digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NumberClass {
+    /// A short code, e.g. a 5-digit SMS number, only reachable from within its country.
+    ShortCode,
+    /// A number in a national format, e.g. `06 1234 5678`.
+    National,
+    /// A number in the international format, starting with `+`.
+    International,
+}
+
+/// Describes why a phone number was rejected before any request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PhoneNumberError {
+    /// The number has no digit.
+    Empty,
+    /// The number contains a character which is neither a digit nor a separator.
+    InvalidCharacter(char),
+    /// The number has more than 15 digits.
+    TooLong,
+    /// The number is in the international format with less than 6 digits.
+    TooShort,
+    /// The endpoint cannot look up numbers of this class.
+    Unsupported(NumberClass),
+}
+
+impl fmt::Display for PhoneNumberError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PhoneNumberError::Empty => write!(f, "the number has no digit"),
+            PhoneNumberError::InvalidCharacter(c) => {
+                write!(f, "the number contains the invalid character {:?}", c)
+            }
+            PhoneNumberError::TooLong => write!(f, "the number has more than 15 digits"),
+            PhoneNumberError::TooShort => {
+                write!(f, "the international number has less than 6 digits")
+            }
+            PhoneNumberError::Unsupported(class) => {
+                write!(f, "the endpoint cannot look up a {:?} number", class)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PhoneNumberError {}
+
+/// A phone number, with separators removed and digits preserved exactly.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PhoneNumber {
+    digits: String,
+    is_international: bool,
+}
+
+impl PhoneNumber {
+    /// Parse a phone number, ignoring spaces, dashes, dots and parentheses.
+    ///
+    /// A leading `+` marks an international number, followed by 6 to 15 digits.
+    pub fn parse(input: &str) -> Result<Self, PhoneNumberError> {
+        let input = input.trim();
+        let (is_international, rest) = match input.strip_prefix('+') {
+            Some(rest) => (true, rest),
+            None => (false, input),
+        };
+
+        let mut digits = String::with_capacity(rest.len());
+        for c in rest.chars() {
+            match c {
+                '0'..='9' => digits.push(c),
+                ' ' | '-' | '.' | '(' | ')' => {}
+                c => return Err(PhoneNumberError::InvalidCharacter(c)),
+            }
+        }
+
+        if digits.is_empty() {
+            return Err(PhoneNumberError::Empty);
+        }
+        if digits.len() > MAX_DIGITS {
+            return Err(PhoneNumberError::TooLong);
+        }
+        if is_international && digits.len() < MIN_INTERNATIONAL_DIGITS {
+            return Err(PhoneNumberError::TooShort);
+        }
+        Ok(PhoneNumber {
+            digits,
+            is_international,
+        })
+    }
+
+    /// Returns the digits, without the leading `+` of an international number.
+    pub fn digits(&self) -> &str {
+        &self.digits
+    }
+
+    /// Returns the class of the number.
+    pub fn class(&self) -> NumberClass {
+        if self.is_international {
+            NumberClass::International
+        } else if self.digits.len() < MIN_SUBSCRIBER_DIGITS {
+            NumberClass::ShortCode
+        } else {
+            NumberClass::National
+        }
+    }
+}
+
+impl fmt::Display for PhoneNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_international {
+            f.write_str("+")?;
+        }
+        f.write_str(&self.digits)
+    }
+}
+
+impl FromStr for PhoneNumber {
+    type Err = PhoneNumberError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PhoneNumber::parse(s)
+    }
+}
+
+impl TryFrom<&str> for PhoneNumber {
+    type Error = PhoneNumberError;
+
+    fn try_from(input: &str) -> Result<Self, Self::Error> {
+        PhoneNumber::parse(input)
+    }
+}
+
+impl TryFrom<String> for PhoneNumber {
+    type Error = PhoneNumberError;
+
+    fn try_from(input: String) -> Result<Self, Self::Error> {
+        PhoneNumber::parse(&input)
+    }
+}
+
+/// An input of the phone number endpoints: a [PhoneNumber], or a string parsed with [PhoneNumber::parse].
+pub trait IntoPhoneNumber {
+    fn into_phone_number(self) -> Result<PhoneNumber, Error>;
+}
+
+impl<T> IntoPhoneNumber for T
+where
+    T: TryInto<PhoneNumber>,
+    T::Error: Into<Error>,
+{
+    fn into_phone_number(self) -> Result<PhoneNumber, Error> {
+        self.try_into().map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_phone_number_parse() {
+        struct TestingData {
+            name: &'static str,
+            input: &'static str,
+            expected: Result<(&'static str, NumberClass), PhoneNumberError>,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "5-digit short code",
+                input: "12345",
+                expected: Ok(("12345", NumberClass::ShortCode)),
+            },
+            TestingData {
+                name: "Italian number with a significant leading zero",
+                input: "06 1234 5678",
+                expected: Ok(("0612345678", NumberClass::National)),
+            },
+            TestingData {
+                name: "Italian number in international format",
+                input: "+39 06-1234-5678",
+                expected: Ok(("390612345678", NumberClass::International)),
+            },
+            TestingData {
+                name: "Only separators",
+                input: " ( ) ",
+                expected: Err(PhoneNumberError::Empty),
+            },
+            TestingData {
+                name: "Letters",
+                input: "06 12AB",
+                expected: Err(PhoneNumberError::InvalidCharacter('A')),
+            },
+            TestingData {
+                name: "International number with too few digits",
+                input: "+33 12",
+                expected: Err(PhoneNumberError::TooShort),
+            },
+            TestingData {
+                name: "More than 15 digits",
+                input: "+1234567890123456",
+                expected: Err(PhoneNumberError::TooLong),
+            },
+        ];
+
+        for test in &tests {
+            let parsed = PhoneNumber::parse(test.input);
+            let parsed = parsed
+                .as_ref()
+                .map(|number| (number.digits(), number.class()))
+                .map_err(Clone::clone);
+            assert_eq!(parsed, test.expected, "{}", test.name);
+        }
+    }
+
+    #[test]
+    fn test_phone_number_display() {
+        assert_eq!(
+            PhoneNumber::parse("+39 06 1234 5678").unwrap().to_string(),
+            "+390612345678"
+        );
+        assert_eq!(PhoneNumber::parse("00123").unwrap().to_string(), "00123");
+    }
+}
@@ -4,46 +4,210 @@
 //! Parse, validate and get location information about a phone number.
 //!
 //! Use this API to validate local and international phone numbers in any country. You can determine the location of the number and also reformat the number into local and international dialing formats.
+//!
+//! A number in a national format is only valid with a hint of its country, its [country_code](./struct.PhoneValidate.html#method.country_code)
+//! or the [ip](./struct.PhoneValidate.html#method.ip) address of the user:
+//!
+//! ```ignore
+//! let response = neutral.phone_validate().country_code("FR").send("01 23 45 67 89".to_owned()).await?;
+//! ```
 
-use http::Method;
-use hyper::Body;
-use neutral_types::phone_validate::PhoneValidateResponse;
+use hyper::{body::Bytes, Body, Request};
+/// The response of the endpoint, defined by neutral_types and re-exported so it can be named without depending on it.
+pub use neutral_types::phone_validate::PhoneValidateResponse;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{net::IpAddr, time::Duration};
 
-use crate::{Error, Neutral};
+use crate::{
+    context::CallContext,
+    meta::ResponseMeta,
+    params,
+    phone_number::{IntoPhoneNumber, PhoneNumber},
+    pipeline::Fetched,
+    Error, Neutral,
+};
 
 #[cfg(test)]
 use mockito;
 
+/// Parameters of a phone validate call, e.g. to persist a pending call and send it later with [PhoneValidate::send_params].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhoneValidateParams {
+    pub number: String,
+    /// See [PhoneValidate::country_code].
+    #[serde(default)]
+    pub country_code: Option<String>,
+    /// See [PhoneValidate::ip].
+    #[serde(default)]
+    pub ip: Option<IpAddr>,
+}
+
 pub struct PhoneValidate<'a> {
     pub(crate) neutral: &'a Neutral,
+    pub(crate) context: CallContext,
+    pub(crate) hints: CountryHints,
+}
+
+/// The hints of the country of a national number.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct CountryHints {
+    pub(crate) country_code: Option<String>,
+    pub(crate) ip_addr: Option<IpAddr>,
+}
+
+impl CountryHints {
+    /// Returns the cache key of `phone_number` sent with these hints.
+    fn canonical_input(&self, phone_number: &PhoneNumber) -> String {
+        if *self == CountryHints::default() {
+            return phone_number.to_string();
+        }
+        format!(
+            "{}:{}:{}",
+            phone_number,
+            self.country_code.as_deref().unwrap_or_default(),
+            self.ip_addr
+                .map(|ip_addr| ip_addr.to_string())
+                .unwrap_or_default()
+        )
+    }
 }
 
 impl<'a> PhoneValidate<'a> {
+    /// Attach a [CallContext](../context/struct.CallContext.html) to the calls.
+    pub fn context(mut self, context: CallContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Bound each attempt of the call by `timeout` instead of the client default, see [CallContext::timeout](../context/struct.CallContext.html#structfield.timeout).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.context.timeout = Some(timeout);
+        self
+    }
+
+    /// Parse national numbers as numbers of `country_code`, an ISO 3166-1 alpha-2 country code, e.g. `FR`.
+    pub fn country_code(mut self, country_code: impl Into<String>) -> Self {
+        self.hints.country_code = Some(country_code.into());
+        self
+    }
+
+    /// Parse national numbers as numbers of the country of `ip_addr`, e.g. the address of the user.
+    ///
+    /// A [country_code](#method.country_code) takes precedence over the country of `ip_addr`.
+    pub fn ip(mut self, ip_addr: IpAddr) -> Self {
+        self.hints.ip_addr = Some(ip_addr);
+        self
+    }
+
     /// Send an phone validate request to neutrinoapi.com
-    pub async fn send(&self, phone_number: String) -> Result<PhoneValidateResponse, Error> {
-        let path_and_query = format!(
-            "/phone-validate?output-case=snake&number={}",
-            phone_number.replace('+', "")
-        );
+    ///
+    /// An invalid `phone_number` is rejected with [Error::InvalidPhoneNumber](../error/enum.Error.html#variant.InvalidPhoneNumber) before any request.
+    pub async fn send(
+        &self,
+        phone_number: impl IntoPhoneNumber,
+    ) -> Result<PhoneValidateResponse, Error> {
+        self.send_with_meta(phone_number)
+            .await
+            .map(|(_, response)| response)
+    }
+
+    /// Same as [send](#method.send), with the phone number and country hints of `params`, which replace the hints set on the call.
+    pub async fn send_params(
+        &self,
+        params: &PhoneValidateParams,
+    ) -> Result<PhoneValidateResponse, Error> {
+        let call = PhoneValidate {
+            neutral: self.neutral,
+            context: self.context.clone(),
+            hints: CountryHints {
+                country_code: params.country_code.clone(),
+                ip_addr: params.ip,
+            },
+        };
+        call.send(params.number.as_str()).await
+    }
 
-        let request = self
+    /// Same as [send](#method.send), also returning the [ResponseMeta](../meta/struct.ResponseMeta.html) of the response.
+    pub async fn send_with_meta(
+        &self,
+        phone_number: impl IntoPhoneNumber,
+    ) -> Result<(ResponseMeta, PhoneValidateResponse), Error> {
+        let phone_number = phone_number.into_phone_number()?;
+        let canonical_input = self.hints.canonical_input(&phone_number);
+        let fetched = self.fetch(&phone_number).await?;
+        let response: PhoneValidateResponse = self
             .neutral
-            .request_builder(path_and_query)?
-            .method(Method::GET)
-            .body(Body::empty())?;
+            .decode_lenient("phone-validate", &fetched.body)?;
+        if !response.is_valid && !fetched.meta.is_cached {
+            self.neutral
+                .negative_cache_insert("phone-validate", &canonical_input, fetched.body);
+        }
+        Ok((fetched.meta, response))
+    }
+
+    /// Same as [send](#method.send), returning the raw body of the response.
+    pub async fn send_raw(&self, phone_number: impl IntoPhoneNumber) -> Result<Bytes, Error> {
+        Ok(self.fetch(&phone_number.into_phone_number()?).await?.body)
+    }
+
+    /// Same as [send](#method.send), decoding the response into any `T`, e.g. a struct holding fields unknown to [PhoneValidateResponse].
+    pub async fn send_as<T: DeserializeOwned>(
+        &self,
+        phone_number: impl IntoPhoneNumber,
+    ) -> Result<T, Error> {
+        let fetched = self.fetch(&phone_number.into_phone_number()?).await?;
+        self.neutral.decode("phone-validate", &fetched.body)
+    }
+
+    /// Same as [send](#method.send), decoding the response into a [serde_json::Value].
+    pub async fn send_value(
+        &self,
+        phone_number: impl IntoPhoneNumber,
+    ) -> Result<serde_json::Value, Error> {
+        self.send_as(phone_number).await
+    }
 
-        let body = self.neutral.request(request).await?;
-        let response: PhoneValidateResponse = serde_json::from_slice(&body)?;
-        Ok(response)
+    /// Send the request, without decoding its response.
+    async fn fetch(&self, phone_number: &PhoneNumber) -> Result<Fetched, Error> {
+        let canonical_input = self.hints.canonical_input(phone_number);
+        self.neutral
+            .execute(
+                "phone-validate",
+                Some(&canonical_input),
+                &self.context,
+                || build_request(self.neutral, phone_number, &self.hints, &self.context),
+            )
+            .await
+    }
+}
+
+/// Build the phone validate request of `phone_number`, without sending it.
+pub(crate) fn build_request(
+    neutral: &Neutral,
+    phone_number: &PhoneNumber,
+    hints: &CountryHints,
+    context: &CallContext,
+) -> Result<Request<Body>, Error> {
+    let mut params = params();
+    params.append_pair("number", &phone_number.to_string());
+    if let Some(country_code) = &hints.country_code {
+        params.append_pair("country-code", country_code);
+    }
+    if let Some(ip_addr) = hints.ip_addr {
+        params.append_pair("ip", &ip_addr.to_string());
     }
+    neutral.params_request("/phone-validate", params.finish(), context)
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::ApiAuth;
+    use crate::cache::NegativeCacheConfig;
+    use crate::{phone_number::PhoneNumberError, ApiAuth};
+    use http::Method;
     use mockito::{mock, Matcher};
     use neutral_types::PhoneInfoKind;
+    use std::time::Duration;
 
     #[tokio::test]
     async fn test_phone_validate_with_good_phone_number() {
@@ -64,8 +228,7 @@ mod test {
             }"#;
 
         let _m = mock("GET", "/phone-validate")
-            .match_query(Matcher::Regex("number=12345678901".into()))
-            .match_query(Matcher::Regex("number=+12345678901".into()))
+            .match_query(Matcher::Regex("number=%2B12345678901".into()))
             .with_status(200)
             .with_body(body_resp)
             .create();
@@ -95,22 +258,13 @@ mod test {
             prefix_network: "Phone operator".to_owned(),
         };
 
-        let tests = vec![
-            TestingData {
-                name: "Using a phone number starting with +".to_owned(),
-                args: Args {
-                    phone_number: "+12345678901".to_owned(),
-                },
-                expected: &expected_response,
-            },
-            TestingData {
-                name: "Using a phone number without + sign at start position".to_owned(),
-                args: Args {
-                    phone_number: "12345678901".to_owned(),
-                },
-                expected: &expected_response,
+        let tests = vec![TestingData {
+            name: "Using a phone number starting with +".to_owned(),
+            args: Args {
+                phone_number: "+12345678901".to_owned(),
             },
-        ];
+            expected: &expected_response,
+        }];
 
         let neutral = Neutral::try_new(
             &mockito::server_url(),
@@ -122,7 +276,7 @@ mod test {
             let Args { phone_number } = &test.args;
             let validate_phone_result =
                 neutral.phone_validate().send(phone_number.to_owned()).await;
-            let phone_info_result = validate_phone_result.map(|phone_info| phone_info.clone());
+            let phone_info_result = validate_phone_result;
             let expected = test.expected;
 
             assert_eq!(
@@ -133,4 +287,328 @@ mod test {
             )
         }
     }
+
+    #[tokio::test]
+    async fn test_phone_validate_negative_cache() {
+        let body_resp = r#"
+            {
+                "valid":false,
+                "type":"unknown",
+                "international_calling_code":"",
+                "international_number":"",
+                "local_number":"",
+                "location":"",
+                "country":"",
+                "country_code":"",
+                "country_code3":"",
+                "currency_code":"",
+                "is_mobile":false,
+                "prefix_network":""
+            }"#;
+
+        let _m = mock("GET", "/phone-validate")
+            .match_query(Matcher::Regex("number=%2B1234567".into()))
+            .with_status(200)
+            .with_body(body_resp)
+            .expect(2)
+            .create();
+
+        let neutral = Neutral::builder(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .negative_cache(NegativeCacheConfig {
+            ttl: Duration::from_millis(200),
+            capacity: 16,
+        })
+        .build()
+        .unwrap();
+
+        let first = neutral.phone_validate().send("+1234567".to_owned()).await;
+        assert!(!first.unwrap().is_valid);
+
+        let second = neutral.phone_validate().send("+1 234 567".to_owned()).await;
+        assert!(
+            !second.unwrap().is_valid,
+            "Answered by the negative cache using the canonicalized number"
+        );
+
+        tokio::time::sleep(Duration::from_millis(250)).await;
+
+        let third = neutral.phone_validate().send("+1-234-567".to_owned()).await;
+        assert!(!third.unwrap().is_valid);
+
+        _m.assert();
+    }
+
+    #[test]
+    fn test_phone_validate_build_request() {
+        struct TestingData {
+            pub name: String,
+            pub args: &'static str,
+            pub expected: &'static str,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Using a phone number starting with +".to_owned(),
+                args: "+12345678901",
+                expected: "/phone-validate?number=%2B12345678901",
+            },
+            TestingData {
+                name: "Using a phone number without + sign at start position".to_owned(),
+                args: "12345678901",
+                expected: "/phone-validate?number=12345678901",
+            },
+            TestingData {
+                name: "Using a formatted phone number".to_owned(),
+                args: "+1 (234) 567-89.01",
+                expected: "/phone-validate?number=%2B12345678901",
+            },
+            TestingData {
+                name: "Using a spaced international number".to_owned(),
+                args: "+33 1 23 45 67 89",
+                expected: "/phone-validate?number=%2B33123456789",
+            },
+            TestingData {
+                name: "Using a national number with a significant leading zero".to_owned(),
+                args: "06 1234 5678",
+                expected: "/phone-validate?number=0612345678",
+            },
+            TestingData {
+                name: "Using a short code with leading zeros".to_owned(),
+                args: "00555",
+                expected: "/phone-validate?number=00555",
+            },
+        ];
+
+        let neutral = Neutral::try_new(
+            "https://neutrinoapi.net",
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        for test in &tests {
+            let phone_number = PhoneNumber::parse(test.args).unwrap();
+            let request = build_request(
+                &neutral,
+                &phone_number,
+                &CountryHints::default(),
+                &CallContext::default(),
+            )
+            .unwrap();
+            assert_eq!(request.method(), Method::GET, "{}", test.name);
+            assert_eq!(
+                request.uri().path_and_query().unwrap().as_str(),
+                test.expected,
+                "{}",
+                test.name
+            );
+            assert_eq!(request.headers()["user-id"], "User", "{}", test.name);
+            assert_eq!(request.headers()["api-key"], "test", "{}", test.name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_phone_validate_country_hints() {
+        struct TestingData {
+            pub name: String,
+            pub args: (Option<&'static str>, Option<&'static str>),
+            pub expected: &'static str,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Using a country code".to_owned(),
+                args: (Some("FR"), None),
+                expected: "number=0123456789&country-code=FR",
+            },
+            TestingData {
+                name: "Using an ip address".to_owned(),
+                args: (None, Some("203.0.113.214")),
+                expected: "number=0123456789&ip=203.0.113.214",
+            },
+            TestingData {
+                name: "Using both hints".to_owned(),
+                args: (Some("FR"), Some("203.0.113.214")),
+                expected: "number=0123456789&country-code=FR&ip=203.0.113.214",
+            },
+        ];
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        for test in &tests {
+            let _m = mock("GET", "/phone-validate")
+                .match_query(Matcher::Exact(test.expected.to_owned()))
+                .with_status(200)
+                .with_body(crate::fixtures::PHONE_VALIDATE_BODY)
+                .expect(1)
+                .create();
+
+            let (country_code, ip_addr) = test.args;
+            let mut phone_validate = neutral.phone_validate();
+            if let Some(country_code) = country_code {
+                phone_validate = phone_validate.country_code(country_code);
+            }
+            if let Some(ip_addr) = ip_addr {
+                phone_validate = phone_validate.ip(ip_addr.parse().unwrap());
+            }
+            let response = phone_validate.send("01 23 45 67 89".to_owned()).await;
+            assert!(response.is_ok(), "{}: {:?}", test.name, response);
+            _m.assert();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_phone_validate_typed_number() {
+        let _m = mock("GET", "/phone-validate")
+            .match_query(Matcher::Exact("number=%2B33611223366".to_owned()))
+            .with_status(200)
+            .with_body(crate::fixtures::PHONE_VALIDATE_BODY)
+            .expect(2)
+            .create();
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        let phone_number = PhoneNumber::try_from("+33 (6) 11-22-33-66").unwrap();
+        assert!(neutral.phone_validate().send(phone_number).await.is_ok());
+        assert!(neutral
+            .phone_validate()
+            .send("+33.6.11.22.33.66")
+            .await
+            .is_ok());
+        _m.assert();
+
+        // Nothing listens on this port: an attempted request would fail with a transport error.
+        let unreachable = Neutral::try_new(
+            "http://127.0.0.1:1",
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+        for (input, expected) in [
+            ("", PhoneNumberError::Empty),
+            ("+33 12", PhoneNumberError::TooShort),
+            ("06 12AB", PhoneNumberError::InvalidCharacter('A')),
+        ] {
+            let result = unreachable.phone_validate().send(input).await;
+            assert!(
+                matches!(result, Err(Error::InvalidPhoneNumber(ref err)) if *err == expected),
+                "{:?}: {:?}",
+                input,
+                result
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_phone_validate_params_in_body() {
+        let _m = mock("POST", "/phone-validate")
+            .match_query(Matcher::Missing)
+            .match_header("content-type", "application/x-www-form-urlencoded")
+            .match_body(Matcher::UrlEncoded("number".into(), "+33698765432".into()))
+            .with_status(200)
+            .with_body(crate::fixtures::PHONE_VALIDATE_BODY)
+            .expect(1)
+            .create();
+
+        let neutral = Neutral::builder(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .params_in_body(true)
+        .build()
+        .unwrap();
+
+        let response = neutral
+            .phone_validate()
+            .send("+33698765432".to_owned())
+            .await
+            .unwrap();
+        assert!(response.is_valid);
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_phone_validate_untyped_responses() {
+        let body = crate::fixtures::PHONE_VALIDATE_BODY
+            .replace(r#""type": "mobile""#, r#""type": "satellite""#);
+        let _m = mock("GET", "/phone-validate")
+            .match_query(Matcher::Regex("number=%2B33698765434".into()))
+            .with_status(200)
+            .with_body(&body)
+            .expect(3)
+            .create();
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+        let phone_validate = neutral.phone_validate();
+
+        let typed = phone_validate
+            .send("+33698765434".to_owned())
+            .await
+            .unwrap();
+        assert_eq!(
+            typed.kind,
+            PhoneInfoKind::Unknown,
+            "Read as the unknown kind"
+        );
+
+        let value = phone_validate
+            .send_value("+33698765434".to_owned())
+            .await
+            .unwrap();
+        assert_eq!(value["type"], "satellite");
+        assert_eq!(value["is_mobile"], true);
+
+        let raw = phone_validate
+            .send_raw("+33698765434".to_owned())
+            .await
+            .unwrap();
+        assert_eq!(raw, body.as_bytes());
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_phone_validate_params() {
+        let params = PhoneValidateParams {
+            number: "06 11 22 33 99".to_owned(),
+            country_code: Some("FR".to_owned()),
+            ip: Some("203.0.113.250".parse().unwrap()),
+        };
+        let json = serde_json::to_string(&params).unwrap();
+        assert_eq!(
+            serde_json::from_str::<PhoneValidateParams>(&json).unwrap(),
+            params
+        );
+
+        let (neutral, requests) = crate::fixtures::recording(crate::fixtures::PHONE_VALIDATE_BODY);
+        neutral
+            .phone_validate()
+            .country_code("DE")
+            .send_params(&params)
+            .await
+            .unwrap();
+        neutral
+            .phone_validate()
+            .country_code("FR")
+            .ip("203.0.113.250".parse().unwrap())
+            .send("06 11 22 33 99")
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0], requests[1]);
+    }
 }
@@ -0,0 +1,369 @@
+//! # Phone verify module
+//! Quoted from [neutrinoapi.com](https://www.neutrinoapi.com/api/phone-verify):
+//!
+//! Make an automated call to any valid phone number and playback a unique security code.
+//!
+//! A PhoneVerify call is one-shot: [send](./struct.PhoneVerify.html#method.send) consumes it since every request places a new call.
+//! Reusing or cloning it does not compile:
+//!
+//! ```compile_fail
+//! # use neutral::{phone_verify::PhoneVerifyOptions, Neutral};
+//! # async fn run(neutral: Neutral) {
+//! let phone_verify = neutral.phone_verify();
+//! let first = phone_verify.send("+33612345678".to_owned(), PhoneVerifyOptions::default()).await;
+//! let second = phone_verify.send("+33612345678".to_owned(), PhoneVerifyOptions::default()).await;
+//! # }
+//! ```
+//!
+//! ```compile_fail
+//! # use neutral::{phone_verify::PhoneVerifyOptions, Neutral};
+//! # async fn run(neutral: Neutral) {
+//! let phone_verify = neutral.phone_verify();
+//! let first = phone_verify.clone().send("+33612345678".to_owned(), PhoneVerifyOptions::default()).await;
+//! # }
+//! ```
+//!
+//! Check the code typed by the user with [VerifySecurityCode](../verify_security_code/struct.VerifySecurityCode.html), using the `security_code` of the response.
+
+use hyper::{body::Bytes, Body, Request};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::{
+    context::CallContext,
+    meta::ResponseMeta,
+    params,
+    phone_number::{NumberClass, PhoneNumber, PhoneNumberError},
+    pipeline::Fetched,
+    Error, Neutral,
+};
+
+#[cfg(test)]
+use mockito;
+
+/// Options of a phone verify call, every option left to `None` uses the neutrinoapi.com default.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PhoneVerifyOptions {
+    /// Number of digits of the generated security code, between 4 and 12.
+    pub code_length: Option<u8>,
+    /// Pass your own security code instead of a generated one.
+    pub security_code: Option<String>,
+    /// Language of the voice playing the code, e.g. `en`.
+    pub language_code: Option<String>,
+    /// Delay between each digit of the code, in milliseconds.
+    pub playback_delay: Option<u32>,
+    /// Maximum number of calls to the same number within `limit_ttl`.
+    pub limit: Option<u32>,
+    /// Period of the `limit`, in hours.
+    pub limit_ttl: Option<u32>,
+}
+
+/// Response of phone verify neutrinoapi.com endpoint
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PhoneVerifyResponse {
+    /// The security code played to the user.
+    pub security_code: String,
+    #[serde(alias = "number_valid")]
+    pub is_number_valid: bool,
+    /// True when the call is being placed.
+    #[serde(alias = "calling")]
+    pub is_calling: bool,
+    /// International calling code of the number, empty when not provided.
+    #[serde(default)]
+    pub calling_code: String,
+}
+
+/// Parameters of a phone verify call, e.g. to persist a pending call and send it later with [PhoneVerify::send_params].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PhoneVerifyParams {
+    pub number: String,
+    #[serde(flatten)]
+    pub options: PhoneVerifyOptions,
+}
+
+pub struct PhoneVerify<'a> {
+    pub(crate) neutral: &'a Neutral,
+    pub(crate) context: CallContext,
+}
+
+impl<'a> PhoneVerify<'a> {
+    /// Attach a [CallContext](../context/struct.CallContext.html) to the call.
+    pub fn context(mut self, context: CallContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Bound each attempt of the call by `timeout` instead of the client default, see [CallContext::timeout](../context/struct.CallContext.html#structfield.timeout).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.context.timeout = Some(timeout);
+        self
+    }
+
+    /// Send a phone verify request to neutrinoapi.com, placing a call to `phone_number`.
+    ///
+    /// Short codes are rejected with [PhoneNumberError::Unsupported](../phone_number/enum.PhoneNumberError.html#variant.Unsupported).
+    pub async fn send(
+        self,
+        phone_number: String,
+        options: PhoneVerifyOptions,
+    ) -> Result<PhoneVerifyResponse, Error> {
+        self.send_with_meta(phone_number, options)
+            .await
+            .map(|(_, response)| response)
+    }
+
+    /// Same as [send](#method.send), with the phone number and options of `params`.
+    pub async fn send_params(
+        self,
+        params: &PhoneVerifyParams,
+    ) -> Result<PhoneVerifyResponse, Error> {
+        self.send(params.number.clone(), params.options.clone())
+            .await
+    }
+
+    /// Same as [send](#method.send), also returning the [ResponseMeta](../meta/struct.ResponseMeta.html) of the response.
+    pub async fn send_with_meta(
+        self,
+        phone_number: String,
+        options: PhoneVerifyOptions,
+    ) -> Result<(ResponseMeta, PhoneVerifyResponse), Error> {
+        let fetched = self.fetch(phone_number, options).await?;
+        let response: PhoneVerifyResponse = self.neutral.decode("phone-verify", &fetched.body)?;
+        Ok((fetched.meta, response))
+    }
+
+    /// Same as [send](#method.send), returning the raw body of the response.
+    pub async fn send_raw(
+        self,
+        phone_number: String,
+        options: PhoneVerifyOptions,
+    ) -> Result<Bytes, Error> {
+        Ok(self.fetch(phone_number, options).await?.body)
+    }
+
+    /// Same as [send](#method.send), decoding the response into any `T`, e.g. a struct holding fields unknown to [PhoneVerifyResponse].
+    pub async fn send_as<T: DeserializeOwned>(
+        self,
+        phone_number: String,
+        options: PhoneVerifyOptions,
+    ) -> Result<T, Error> {
+        let fetched = self.fetch(phone_number, options).await?;
+        self.neutral.decode("phone-verify", &fetched.body)
+    }
+
+    /// Same as [send](#method.send), decoding the response into a [serde_json::Value].
+    pub async fn send_value(
+        self,
+        phone_number: String,
+        options: PhoneVerifyOptions,
+    ) -> Result<serde_json::Value, Error> {
+        self.send_as(phone_number, options).await
+    }
+
+    /// Send the request, without decoding its response.
+    async fn fetch(
+        &self,
+        phone_number: String,
+        options: PhoneVerifyOptions,
+    ) -> Result<Fetched, Error> {
+        let phone_number = PhoneNumber::parse(&phone_number)?;
+        if phone_number.class() == NumberClass::ShortCode {
+            return Err(PhoneNumberError::Unsupported(NumberClass::ShortCode).into());
+        }
+
+        self.neutral
+            .execute("phone-verify", None, &self.context, || {
+                build_request(self.neutral, &phone_number, &options, &self.context)
+            })
+            .await
+    }
+}
+
+/// Build the phone verify request of `phone_number`, without sending it.
+pub(crate) fn build_request(
+    neutral: &Neutral,
+    phone_number: &PhoneNumber,
+    options: &PhoneVerifyOptions,
+    context: &CallContext,
+) -> Result<Request<Body>, Error> {
+    let mut query = params();
+    query.append_pair("number", &phone_number.to_string());
+    if let Some(code_length) = options.code_length {
+        query.append_pair("code-length", &code_length.to_string());
+    }
+    if let Some(security_code) = &options.security_code {
+        query.append_pair("security-code", security_code);
+    }
+    if let Some(language_code) = &options.language_code {
+        query.append_pair("language-code", language_code);
+    }
+    if let Some(playback_delay) = options.playback_delay {
+        query.append_pair("playback-delay", &playback_delay.to_string());
+    }
+    if let Some(limit) = options.limit {
+        query.append_pair("limit", &limit.to_string());
+    }
+    if let Some(limit_ttl) = options.limit_ttl {
+        query.append_pair("limit-ttl", &limit_ttl.to_string());
+    }
+
+    neutral.params_request("/phone-verify", query.finish(), context)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{error::ApiErrorCode, ApiAuth};
+    use http::Method;
+    use mockito::{mock, Matcher};
+
+    #[test]
+    fn test_phone_verify_build_request() {
+        struct TestingData {
+            pub name: String,
+            pub args: PhoneVerifyOptions,
+            pub expected: &'static str,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Without options".to_owned(),
+                args: PhoneVerifyOptions::default(),
+                expected: "/phone-verify?number=%2B33612345678",
+            },
+            TestingData {
+                name: "With every option".to_owned(),
+                args: PhoneVerifyOptions {
+                    code_length: Some(6),
+                    security_code: Some("12 34".to_owned()),
+                    language_code: Some("fr".to_owned()),
+                    playback_delay: Some(800),
+                    limit: Some(3),
+                    limit_ttl: Some(1),
+                },
+                expected: "/phone-verify?number=%2B33612345678&code-length=6&security-code=12+34&language-code=fr&playback-delay=800&limit=3&limit-ttl=1",
+            },
+        ];
+
+        let neutral = Neutral::try_new(
+            "https://neutrinoapi.net",
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+        let phone_number = PhoneNumber::parse("+33 6 12 34 56 78").unwrap();
+
+        for test in &tests {
+            let request =
+                build_request(&neutral, &phone_number, &test.args, &CallContext::default())
+                    .unwrap();
+            assert_eq!(request.method(), Method::GET, "{}", test.name);
+            assert_eq!(
+                request.uri().path_and_query().unwrap().as_str(),
+                test.expected,
+                "{}",
+                test.name
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_phone_verify() {
+        let _calling = mock("GET", "/phone-verify")
+            .match_query(Matcher::Regex("number=%2B33612345678&code-length=6".into()))
+            .with_status(200)
+            .with_body(r#"{"security_code": "482913", "number_valid": true, "calling": true}"#)
+            .expect(1)
+            .create();
+
+        let _invalid = mock("GET", "/phone-verify")
+            .match_query(Matcher::Regex("number=1234567$".into()))
+            .with_status(400)
+            .with_body(r#"{"api-error": 3, "api-error-msg": "INVALID NUMBER"}"#)
+            .expect(1)
+            .create();
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        let response = neutral
+            .phone_verify()
+            .send(
+                "+33612345678".to_owned(),
+                PhoneVerifyOptions {
+                    code_length: Some(6),
+                    ..PhoneVerifyOptions::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            response,
+            PhoneVerifyResponse {
+                security_code: "482913".to_owned(),
+                is_number_valid: true,
+                is_calling: true,
+                calling_code: "".to_owned(),
+            }
+        );
+
+        let result = neutral
+            .phone_verify()
+            .send("1234567".to_owned(), PhoneVerifyOptions::default())
+            .await;
+        match result {
+            Err(Error::Neutrino(err)) => {
+                assert_eq!(err.status_code, http::StatusCode::BAD_REQUEST);
+                assert_eq!(err.api_error(), Some(ApiErrorCode::BadRequest));
+                assert!(err.error.contains("INVALID NUMBER"));
+            }
+            other => panic!("expected a neutrino error, got {:?}", other),
+        }
+
+        _calling.assert();
+        _invalid.assert();
+    }
+
+    #[tokio::test]
+    async fn test_phone_verify_params() {
+        let params = PhoneVerifyParams {
+            number: "+33611223399".to_owned(),
+            options: PhoneVerifyOptions {
+                code_length: Some(6),
+                language_code: Some("fr".to_owned()),
+                limit: Some(3),
+                ..Default::default()
+            },
+        };
+        let json = serde_json::to_value(&params).unwrap();
+        assert_eq!(json["number"], "+33611223399");
+        assert_eq!(json["code_length"], 6);
+        assert_eq!(
+            serde_json::from_value::<PhoneVerifyParams>(json).unwrap(),
+            params
+        );
+        assert_eq!(
+            serde_json::from_str::<PhoneVerifyParams>(r#"{"number": "+33611223399"}"#)
+                .unwrap()
+                .options,
+            PhoneVerifyOptions::default()
+        );
+
+        let (neutral, requests) = crate::fixtures::recording(
+            r#"{"security_code": "482913", "number_valid": true, "calling": true}"#,
+        );
+        neutral.phone_verify().send_params(&params).await.unwrap();
+        neutral
+            .phone_verify()
+            .send(params.number.clone(), params.options.clone())
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0], requests[1]);
+    }
+}
@@ -0,0 +1,1199 @@
+//! # Pipeline module
+//! Describe the stages every neutrinoapi.com call goes through, and how to configure them.
+//!
+//! The stages always run in this order:
+//!
+//! 1. **cache**: a cached response is returned right away, see the [cache](../cache/index.html) module.
+//! 2. **singleflight**: concurrent calls of the same identity with the same endpoint and input share a single request.
+//!    If that request fails, every waiting call goes on with its own request.
+//! 3. **limiter**: wait for a token of the rate limit of the identity, then of the [RateLimitConfig](./struct.RateLimitConfig.html) global rate limit.
+//!    A call costs one token, whatever the number of attempts.
+//!    Identities are the user ids of the credentials, only kept hashed; their buckets are created on first use and dropped once idle.
+//! 4. **breaker**: fail fast with [Error::CircuitOpen](../error/enum.Error.html#variant.CircuitOpen) while the [CircuitBreakerConfig](./struct.CircuitBreakerConfig.html) circuit of the endpoint is open.
+//! 5. **retry**: send the request again after an exponential backoff on a [retryable](../error/enum.Error.html#method.is_retryable) error, such as a 429 or 5xx response or a reset connection, see [RetryConfig](./struct.RetryConfig.html).
+//! 6. **transport**: send the request to neutrinoapi.com.
+//!
+//! Hence cache hits and shared requests consume no rate limit token, and a rate limited call never blocks the calls of other inputs.
+//! The breaker counts the outcome of a call once its retries are exhausted, unless configured with [BreakerCounting::PerAttempt](./enum.BreakerCounting.html#variant.PerAttempt).
+//!
+//! The singleflight stage is always enabled, the other stages are configured on [NeutralBuilder](../builder/struct.NeutralBuilder.html).
+//! Calls which are not idempotent, such as placing a verification call, skip the cache and singleflight stages and are never retried.
+//!
+//! A call whose [CallContext](../context/struct.CallContext.html#method.with_cancellation) is cancelled leaves the pipeline at once with [Error::Cancelled](../error/enum.Error.html#variant.Cancelled), whatever its stage.
+//! Its request in flight is dropped, the calls sharing it go on with their own request, and the circuit breaker doesn't count it.
+
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    future::Future,
+    hash::BuildHasher,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use http::Uri;
+use hyper::{body::Bytes, Body, Request};
+use tokio::sync::watch;
+
+use secrecy::ExposeSecret;
+
+use crate::{
+    cache::cache_key,
+    context::CallContext,
+    endpoint_uri,
+    meta::ResponseMeta,
+    metrics::MetricsSink,
+    runtime::RuntimeConfig,
+    time::{self, Instant},
+    EndpointPath, Error, Neutral,
+};
+
+/// Configure a token bucket rate limit shared by every call of a client.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitConfig {
+    /// Number of calls allowed per `per` period, also the size of a burst.
+    pub permits: u32,
+    /// Period over which `permits` are refilled.
+    pub per: Duration,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            permits: 10,
+            per: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Select which outcomes a circuit breaker counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BreakerCounting {
+    /// Count the outcome of a call once its retries are exhausted.
+    #[default]
+    PerCall,
+    /// Count the outcome of every attempt.
+    PerAttempt,
+}
+
+/// Configure a circuit breaker per endpoint.
+///
+/// Only [retryable](../error/enum.Error.html#method.is_retryable) errors are failures: an application error such as a 400 proves neutrinoapi.com is up and closes the circuit like a success.
+/// Once open for `open_for`, the circuit lets a single trial call through, closing again when it succeeds. The circuits are shared by the clones of a client.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CircuitBreakerConfig {
+    /// Number of consecutive failures opening the circuit.
+    pub failure_threshold: u32,
+    /// How long the circuit stays open before a trial call is allowed.
+    pub open_for: Duration,
+    /// Which outcomes are counted.
+    pub counting: BreakerCounting,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        CircuitBreakerConfig {
+            failure_threshold: 5,
+            open_for: Duration::from_secs(30),
+            counting: BreakerCounting::default(),
+        }
+    }
+}
+
+/// Configure how calls failing with a retryable error are sent again.
+///
+/// The delay before a retry starts at `backoff`, doubles after every attempt and never exceeds `max_backoff`.
+/// With `jitter`, every delay is drawn at random between half and all of it, so clients failing together do not retry together.
+/// A rate limited call waits for the delay of its `Retry-After` header instead, never longer than `max_retry_after`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryConfig {
+    /// Maximum number of attempts after the first one.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled after every attempt.
+    pub backoff: Duration,
+    /// Upper bound of the delay between two attempts.
+    pub max_backoff: Duration,
+    /// Randomize the delay between two attempts.
+    pub jitter: bool,
+    /// Upper bound of the delay requested by the `Retry-After` header of a rate limited response.
+    pub max_retry_after: Duration,
+}
+
+impl RetryConfig {
+    /// Returns the delay before the retry following the attempt number `attempt`, starting from 0.
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        let delay = self
+            .backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff);
+        if !self.jitter {
+            return delay;
+        }
+        let random = RandomState::new().hash_one(attempt);
+        delay / 2 + delay.mul_f64((random % 1024) as f64 / 2048.0)
+    }
+
+    /// Returns the delay before retrying the attempt number `attempt` failing with `err`.
+    pub(crate) fn delay_after(&self, attempt: u32, err: &Error) -> Duration {
+        match err {
+            Error::RateLimited {
+                retry_after: Some(retry_after),
+                ..
+            } => (*retry_after).min(self.max_retry_after),
+            _ => self.delay(attempt),
+        }
+    }
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            max_retries: 2,
+            backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            jitter: true,
+            max_retry_after: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Identify a stage of the pipeline.
+#[cfg(test)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Stage {
+    Cache,
+    Singleflight,
+    Limiter,
+    Breaker,
+    Retry,
+    Transport,
+}
+
+/// Record the stages entered by every call, keyed by input.
+#[cfg(test)]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Probe(Arc<Mutex<Vec<(Stage, String)>>>);
+
+#[cfg(test)]
+impl Probe {
+    fn enter(&self, stage: Stage, input: &str) {
+        self.0.lock().unwrap().push((stage, input.to_owned()));
+    }
+
+    pub(crate) fn events(&self) -> Vec<(Stage, String)> {
+        self.0.lock().unwrap().clone()
+    }
+
+    pub(crate) fn count(&self, stage: Stage, input: &str) -> usize {
+        self.events()
+            .iter()
+            .filter(|(entered, entered_input)| *entered == stage && entered_input == input)
+            .count()
+    }
+}
+
+/// The body of a response, and its meta.
+pub(crate) struct Fetched {
+    pub(crate) body: Bytes,
+    pub(crate) meta: ResponseMeta,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// A token bucket.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    capacity: f64,
+    per_token: Duration,
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(config: RateLimitConfig) -> Self {
+        let permits = config.permits.max(1);
+        RateLimiter {
+            capacity: f64::from(permits),
+            per_token: config.per / permits,
+            bucket: Mutex::new(Bucket {
+                tokens: f64::from(permits),
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Take a token, waiting for the bucket to refill when empty. The lock is never held while waiting.
+    pub(crate) async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+                let now = Instant::now();
+                let refilled = now.duration_since(bucket.refilled_at).as_secs_f64()
+                    / self.per_token.as_secs_f64().max(f64::EPSILON);
+                bucket.tokens = (bucket.tokens + refilled).min(self.capacity);
+                bucket.refilled_at = now;
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return;
+                }
+                self.per_token.mul_f64(1.0 - bucket.tokens)
+            };
+            time::sleep(wait).await;
+        }
+    }
+}
+
+/// A token bucket per identity, keyed by a hash of the identity.
+#[derive(Debug)]
+pub(crate) struct IdentityRateLimiter {
+    config: RateLimitConfig,
+    hasher: RandomState,
+    buckets: Mutex<HashMap<u64, (Arc<RateLimiter>, Instant)>>,
+}
+
+impl IdentityRateLimiter {
+    pub(crate) fn new(config: RateLimitConfig) -> Self {
+        IdentityRateLimiter {
+            config,
+            hasher: RandomState::new(),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take a token from the bucket of `identity`.
+    ///
+    /// A bucket idle for a whole period is full again, so it is dropped and created anew on the next call.
+    pub(crate) async fn acquire(&self, identity: &str) {
+        let key = self.hasher.hash_one(identity);
+        let bucket = {
+            let mut buckets = self.buckets.lock().unwrap();
+            let now = Instant::now();
+            buckets.retain(|_, (_, last_used)| now.duration_since(*last_used) < self.config.per);
+            let (bucket, last_used) = buckets
+                .entry(key)
+                .or_insert_with(|| (Arc::new(RateLimiter::new(self.config)), now));
+            *last_used = now;
+            bucket.clone()
+        };
+        bucket.acquire().await;
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.buckets.lock().unwrap().len()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Circuit {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen { until: Instant },
+}
+
+/// A circuit breaker per endpoint.
+#[derive(Debug)]
+pub(crate) struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    circuits: Mutex<HashMap<String, Circuit>>,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(config: CircuitBreakerConfig) -> Self {
+        CircuitBreaker {
+            config,
+            circuits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns true when a call to `endpoint` may be sent, letting a single trial call through once the circuit was open long enough.
+    fn allow(&self, endpoint: &str) -> bool {
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits
+            .entry(endpoint.to_owned())
+            .or_insert(Circuit::Closed { failures: 0 });
+        let now = Instant::now();
+        match *circuit {
+            Circuit::Closed { .. } => true,
+            Circuit::Open { until } | Circuit::HalfOpen { until } if now >= until => {
+                *circuit = Circuit::HalfOpen {
+                    until: now + self.config.open_for,
+                };
+                true
+            }
+            Circuit::Open { .. } | Circuit::HalfOpen { .. } => false,
+        }
+    }
+
+    /// Count the outcome of a call or an attempt. Only retryable errors are failures, any other outcome proves neutrinoapi.com is reachable.
+    fn record<T>(&self, endpoint: &str, result: &Result<T, Error>) {
+        let is_failure = matches!(result, Err(err) if err.is_retryable());
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits
+            .entry(endpoint.to_owned())
+            .or_insert(Circuit::Closed { failures: 0 });
+        *circuit = match (*circuit, is_failure) {
+            (_, false) => Circuit::Closed { failures: 0 },
+            (Circuit::Closed { failures }, true)
+                if failures + 1 < self.config.failure_threshold =>
+            {
+                Circuit::Closed {
+                    failures: failures + 1,
+                }
+            }
+            (_, true) => Circuit::Open {
+                until: Instant::now() + self.config.open_for,
+            },
+        };
+    }
+}
+
+/// The response of the leader of a flight, `None` until it succeeded.
+type Shared = Option<(ResponseMeta, Bytes)>;
+
+/// Share a single request between concurrent calls of the same identity with the same key.
+#[derive(Debug, Default)]
+pub(crate) struct Singleflight {
+    /// Flights keyed by their cache key, which holds a hash of the identity.
+    flights: Mutex<HashMap<String, watch::Receiver<Shared>>>,
+}
+
+enum Flight<'a> {
+    Leader(watch::Sender<Shared>, FlightGuard<'a>),
+    Follower(watch::Receiver<Shared>),
+}
+
+/// Remove the flight of a leader once it completed or was dropped.
+struct FlightGuard<'a> {
+    singleflight: &'a Singleflight,
+    key: String,
+}
+
+impl Drop for FlightGuard<'_> {
+    fn drop(&mut self) {
+        self.singleflight.flights.lock().unwrap().remove(&self.key);
+    }
+}
+
+impl Singleflight {
+    fn join(&self, key: String) -> Flight<'_> {
+        let mut flights = self.flights.lock().unwrap();
+        if let Some(receiver) = flights.get(&key) {
+            return Flight::Follower(receiver.clone());
+        }
+        let (sender, receiver) = watch::channel(None);
+        flights.insert(key.clone(), receiver);
+        Flight::Leader(
+            sender,
+            FlightGuard {
+                singleflight: self,
+                key,
+            },
+        )
+    }
+}
+
+impl Neutral {
+    /// Run a call through the pipeline described in the [pipeline](./pipeline/index.html) module.
+    ///
+    /// `canonical_input` identifies idempotent calls for the cache and singleflight stages, `None` skips them.
+    /// `build` is called for every attempt since a request body cannot be sent twice.
+    /// The call resolves with [Error::Cancelled] as soon as the cancellation token of `context` is cancelled, whatever its stage.
+    pub(crate) async fn execute<F>(
+        &self,
+        endpoint: &str,
+        canonical_input: Option<&str>,
+        context: &CallContext,
+        build: F,
+    ) -> Result<Fetched, Error>
+    where
+        F: Fn() -> Result<Request<Body>, Error>,
+    {
+        cancellable(context, self.run(endpoint, canonical_input, build)).await
+    }
+
+    async fn run<F>(
+        &self,
+        endpoint: &str,
+        canonical_input: Option<&str>,
+        build: F,
+    ) -> Result<Fetched, Error>
+    where
+        F: Fn() -> Result<Request<Body>, Error>,
+    {
+        let input = canonical_input.unwrap_or_default();
+        let runtime = self.runtime();
+
+        let flight = match canonical_input {
+            Some(canonical_input) => {
+                #[cfg(test)]
+                self.probe.enter(Stage::Cache, input);
+                if let Some(body) = self.cache_get(endpoint, canonical_input) {
+                    return Ok(Fetched {
+                        body,
+                        meta: ResponseMeta::cached(),
+                    });
+                }
+
+                #[cfg(test)]
+                self.probe.enter(Stage::Singleflight, input);
+                match self.singleflight.join(cache_key(
+                    endpoint,
+                    self.auth.identity(),
+                    canonical_input,
+                )) {
+                    Flight::Leader(sender, guard) => Some((sender, guard)),
+                    Flight::Follower(mut receiver) => {
+                        let _ = receiver.changed().await;
+                        let shared = receiver.borrow().clone();
+                        if let Some((meta, body)) = shared {
+                            return Ok(Fetched { body, meta });
+                        }
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+
+        #[cfg(test)]
+        self.probe.enter(Stage::Limiter, input);
+        if let Some(limiter) = &runtime.identity_limiter {
+            limiter.acquire(self.auth.user_id.expose_secret()).await;
+        }
+        if let Some(limiter) = &runtime.limiter {
+            limiter.acquire().await;
+        }
+
+        #[cfg(test)]
+        self.probe.enter(Stage::Breaker, input);
+        if let Some(breaker) = &self.breaker {
+            if !breaker.allow(endpoint) {
+                return Err(Error::CircuitOpen {
+                    endpoint: endpoint.to_owned(),
+                });
+            }
+        }
+
+        #[cfg(test)]
+        self.probe.enter(Stage::Retry, input);
+        let result = self
+            .retry(
+                endpoint,
+                input,
+                canonical_input.is_some(),
+                &runtime.config,
+                &build,
+            )
+            .await;
+        if let Some(breaker) = &self.breaker {
+            if breaker.config.counting == BreakerCounting::PerCall {
+                breaker.record(endpoint, &result);
+            }
+        }
+
+        if let (Some((sender, _guard)), Ok(response)) = (flight, &result) {
+            let _ = sender.send(Some(response.clone()));
+        }
+        result.map(|(meta, body)| Fetched { body, meta })
+    }
+
+    /// Send the request built by `build` through the rate limits and the circuit breaker, without buffering the body of its response.
+    ///
+    /// A streamed call is neither cached, shared nor retried. Its cancellation stops applying once the head of its response is received.
+    pub(crate) async fn execute_stream<F>(
+        &self,
+        endpoint: &str,
+        context: &CallContext,
+        build: F,
+    ) -> Result<http::Response<Body>, Error>
+    where
+        F: FnOnce() -> Result<Request<Body>, Error>,
+    {
+        cancellable(context, self.run_stream(endpoint, build)).await
+    }
+
+    async fn run_stream<F>(&self, endpoint: &str, build: F) -> Result<http::Response<Body>, Error>
+    where
+        F: FnOnce() -> Result<Request<Body>, Error>,
+    {
+        let runtime = self.runtime();
+        if let Some(limiter) = &runtime.identity_limiter {
+            limiter.acquire(self.auth.user_id.expose_secret()).await;
+        }
+        if let Some(limiter) = &runtime.limiter {
+            limiter.acquire().await;
+        }
+        if let Some(breaker) = &self.breaker {
+            if !breaker.allow(endpoint) {
+                return Err(Error::CircuitOpen {
+                    endpoint: endpoint.to_owned(),
+                });
+            }
+        }
+
+        let result = self
+            .request_stream(rebase(build()?, &runtime.config.uri)?)
+            .await;
+        if let Some(breaker) = &self.breaker {
+            breaker.record(endpoint, &result);
+        }
+        result
+    }
+
+    async fn retry<F>(
+        &self,
+        endpoint: &str,
+        input: &str,
+        is_idempotent: bool,
+        config: &RuntimeConfig,
+        build: &F,
+    ) -> Result<(ResponseMeta, Bytes), Error>
+    where
+        F: Fn() -> Result<Request<Body>, Error>,
+    {
+        let mut attempt = 0;
+        loop {
+            let result = self
+                .failover(endpoint, input, is_idempotent, config, build)
+                .await;
+            match (&result, &self.retry) {
+                (Err(err), Some(retry))
+                    if is_idempotent && err.is_retryable() && attempt < retry.max_retries =>
+                {
+                    self.metrics.on_retry(endpoint);
+                    time::sleep(retry.delay_after(attempt, err)).await;
+                    attempt += 1;
+                }
+                _ => return result,
+            }
+        }
+    }
+
+    /// Send the request to the base uri, then to the failover uris in order while it fails over, see the [region](./region/index.html) module.
+    async fn failover<F>(
+        &self,
+        endpoint: &str,
+        _input: &str,
+        is_idempotent: bool,
+        config: &RuntimeConfig,
+        build: &F,
+    ) -> Result<(ResponseMeta, Bytes), Error>
+    where
+        F: Fn() -> Result<Request<Body>, Error>,
+    {
+        let mut index = 0;
+        loop {
+            let base = match index {
+                0 => &config.uri,
+                index => &config.failover[index - 1],
+            };
+            #[cfg(test)]
+            self.probe.enter(Stage::Transport, _input);
+            let result = self.request(rebase(build()?, base)?).await;
+            if let Some(breaker) = &self.breaker {
+                if breaker.config.counting == BreakerCounting::PerAttempt {
+                    breaker.record(endpoint, &result);
+                }
+            }
+
+            match &result {
+                Err(err) if index < config.failover.len() && fails_over(err, is_idempotent) => {
+                    let host = base.authority().map(|authority| authority.as_str());
+                    self.metrics.on_failover(endpoint, host.unwrap_or_default());
+                    index += 1;
+                }
+                _ => return result,
+            }
+        }
+    }
+}
+
+/// Await `future`, failing with [Error::Cancelled] as soon as the cancellation token of `context` is cancelled.
+///
+/// `future` is dropped on cancellation, along with its request in flight and its singleflight and rate limiter waits.
+async fn cancellable<F, T>(context: &CallContext, future: F) -> Result<T, Error>
+where
+    F: Future<Output = Result<T, Error>>,
+{
+    match &context.cancellation {
+        Some(token) => tokio::select! {
+            biased;
+            _ = token.cancelled() => Err(Error::Cancelled),
+            result = future => result,
+        },
+        None => future.await,
+    }
+}
+
+/// Returns true when a request failing with `err` is sent to the next host: a request which failed to connect was never received,
+/// a server error is only sent again when the call is idempotent.
+fn fails_over(err: &Error, is_idempotent: bool) -> bool {
+    match err {
+        Error::Hyper(err) => err.is_connect(),
+        Error::Connect(_) => true,
+        #[cfg(feature = "reqwest-backend")]
+        Error::Service(err) => crate::transport::is_reqwest_connect(err),
+        Error::ServerError(_) => is_idempotent,
+        _ => false,
+    }
+}
+
+/// Target `base` with `req`, so every attempt of a call uses the configuration snapshot the call started with.
+fn rebase(mut req: Request<Body>, base: &Uri) -> Result<Request<Body>, Error> {
+    let uri = match req.extensions().get::<EndpointPath>() {
+        Some(EndpointPath(path_and_query)) => endpoint_uri(base, path_and_query)?,
+        None => return Ok(req),
+    };
+    if *req.uri() != uri {
+        *req.uri_mut() = uri;
+    }
+    Ok(req)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        cache::CacheConfig,
+        context::{CallContext, CancellationToken},
+        error::NeutrinoError,
+        fixtures, ApiAuth,
+    };
+    use mockito::{mock, Matcher};
+    use std::net::IpAddr;
+
+    fn neutral(builder: impl FnOnce(crate::NeutralBuilder) -> crate::NeutralBuilder) -> Neutral {
+        builder(Neutral::builder(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        ))
+        .build()
+        .unwrap()
+    }
+
+    fn ip(ip_addr: &str) -> IpAddr {
+        ip_addr.parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_cache_hit_consumes_no_token() {
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.1$".into()))
+            .with_status(200)
+            .with_body(fixtures::IP_INFO_BODY)
+            .expect(1)
+            .create();
+
+        let neutral = neutral(|builder| {
+            builder
+                .cache(CacheConfig::default())
+                .rate_limit(RateLimitConfig {
+                    permits: 1,
+                    per: Duration::from_secs(60),
+                })
+        });
+
+        neutral.ip_info().send(ip("203.0.113.1")).await.unwrap();
+        let cached = tokio::time::timeout(
+            Duration::from_secs(1),
+            neutral.ip_info().send(ip("203.0.113.1")),
+        )
+        .await;
+        assert!(matches!(cached, Ok(Ok(_))), "cache hit waited for a token");
+
+        let limited = tokio::time::timeout(
+            Duration::from_millis(100),
+            neutral.ip_info().send(ip("203.0.113.2")),
+        )
+        .await;
+        assert!(limited.is_err(), "the only token was consumed twice");
+
+        assert_eq!(neutral.probe.count(Stage::Cache, "203.0.113.1"), 2);
+        assert_eq!(neutral.probe.count(Stage::Limiter, "203.0.113.1"), 1);
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_cache_hit_meta_has_no_header() {
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.142$".into()))
+            .with_status(200)
+            .with_header("x-ratelimit-remaining", "7")
+            .with_body(fixtures::IP_INFO_BODY)
+            .expect(1)
+            .create();
+
+        let neutral = neutral(|builder| builder.cache(CacheConfig::default()));
+
+        let ip_info = neutral.ip_info();
+        let (fetched, _) = ip_info.send_with_meta(ip("203.0.113.142")).await.unwrap();
+        assert_eq!(fetched.rate_limit.remaining, Some(7));
+        assert!(!fetched.is_cached);
+
+        let (cached, _) = ip_info.send_with_meta(ip("203.0.113.142")).await.unwrap();
+        assert_eq!(cached, ResponseMeta::cached());
+        _m.assert();
+    }
+
+    fn retrying(max_retries: u32) -> Neutral {
+        neutral(|builder| {
+            builder.retry(RetryConfig {
+                max_retries,
+                backoff: Duration::from_millis(1),
+                max_backoff: Duration::from_millis(5),
+                jitter: true,
+                max_retry_after: Duration::from_millis(20),
+            })
+        })
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let config = RetryConfig {
+            max_retries: 5,
+            backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(300),
+            jitter: false,
+            max_retry_after: Duration::from_secs(10),
+        };
+        let delays: Vec<_> = (0..4).map(|attempt| config.delay(attempt)).collect();
+        assert_eq!(
+            delays,
+            [100, 200, 300, 300].map(Duration::from_millis).to_vec()
+        );
+
+        let config = RetryConfig {
+            jitter: true,
+            ..config
+        };
+        for attempt in 0..4 {
+            let delay = config.delay(attempt);
+            assert!(delay >= delays[attempt as usize] / 2 && delay <= delays[attempt as usize]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_honors_a_capped_retry_after() {
+        let _limited = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.220$".into()))
+            .with_status(429)
+            .with_header("retry-after", "86400")
+            .expect(1)
+            .create();
+        let _ok = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.220$".into()))
+            .with_status(200)
+            .with_body(fixtures::IP_INFO_BODY)
+            .expect(1)
+            .create();
+
+        let started = std::time::Instant::now();
+        let result = retrying(1).ip_info().send(ip("203.0.113.220")).await;
+        assert!(result.is_ok(), "{:?}", result);
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(20) && elapsed < Duration::from_secs(5));
+        _limited.assert();
+        _ok.assert();
+
+        let config = RetryConfig::default();
+        let limited = |retry_after| Error::RateLimited {
+            retry_after,
+            error: NeutrinoError::new(
+                http::StatusCode::TOO_MANY_REQUESTS,
+                b"",
+                CallContext::default(),
+            ),
+        };
+        assert_eq!(
+            config.delay_after(0, &limited(Some(Duration::from_secs(3)))),
+            Duration::from_secs(3)
+        );
+        assert!(config.delay_after(0, &limited(None)) <= config.backoff);
+    }
+
+    #[tokio::test]
+    async fn test_retry_until_success() {
+        let _unavailable = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.130$".into()))
+            .with_status(503)
+            .with_body("service unavailable")
+            .expect(2)
+            .create();
+        let _ok = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.130$".into()))
+            .with_status(200)
+            .with_body(fixtures::IP_INFO_BODY)
+            .expect(1)
+            .create();
+
+        let result = retrying(2).ip_info().send(ip("203.0.113.130")).await;
+        assert!(result.is_ok());
+        _unavailable.assert();
+        _ok.assert();
+    }
+
+    #[tokio::test]
+    async fn test_retry_exhausted_returns_the_last_error() {
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.131$".into()))
+            .with_status(502)
+            .with_body("bad gateway")
+            .expect(3)
+            .create();
+
+        let result = retrying(2).ip_info().send(ip("203.0.113.131")).await;
+        match result {
+            Err(Error::ServerError(err)) => {
+                assert_eq!(err.status_code, http::StatusCode::BAD_GATEWAY);
+                assert_eq!(err.error, "bad gateway");
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_when_not_idempotent() {
+        let _m = mock("POST", "/sms-message")
+            .match_body(Matcher::UrlEncoded("number".into(), "+33698765431".into()))
+            .with_status(503)
+            .with_body("service unavailable")
+            .expect(1)
+            .create();
+
+        let result = retrying(2)
+            .sms_message()
+            .send("+33698765431".to_owned(), "hello".to_owned(), None)
+            .await;
+        assert!(matches!(result, Err(Error::ServerError(_))));
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_breaker_opens_after_retries_are_exhausted() {
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.10$".into()))
+            .with_status(500)
+            .with_body("internal error")
+            .expect(6)
+            .create();
+
+        let neutral = neutral(|builder| {
+            builder
+                .retry(RetryConfig {
+                    max_retries: 2,
+                    backoff: Duration::from_millis(1),
+                    ..RetryConfig::default()
+                })
+                .circuit_breaker(CircuitBreakerConfig {
+                    failure_threshold: 2,
+                    open_for: Duration::from_secs(60),
+                    counting: BreakerCounting::PerCall,
+                })
+        });
+
+        for _ in 0..2 {
+            let result = neutral.ip_info().send(ip("203.0.113.10")).await;
+            assert!(matches!(result, Err(Error::ServerError(_))));
+        }
+        let result = neutral.ip_info().send(ip("203.0.113.10")).await;
+        assert!(matches!(result, Err(Error::CircuitOpen { endpoint }) if endpoint == "ip-info"));
+
+        assert_eq!(neutral.probe.count(Stage::Transport, "203.0.113.10"), 6);
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_breaker_lifecycle() {
+        let open_for = Duration::from_millis(100);
+        let neutral = neutral(|builder| {
+            builder.circuit_breaker(CircuitBreakerConfig {
+                failure_threshold: 2,
+                open_for,
+                counting: BreakerCounting::PerCall,
+            })
+        });
+        let clone = neutral.clone();
+        let failing = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.238$".into()))
+            .with_status(503)
+            .with_body("service unavailable")
+            .expect(3)
+            .create();
+
+        // Closed, until the failures reach the threshold.
+        for _ in 0..2 {
+            let result = neutral.ip_info().send(ip("203.0.113.238")).await;
+            assert!(matches!(result, Err(Error::ServerError(_))));
+        }
+        // Open, for every clone.
+        let result = clone.ip_info().send(ip("203.0.113.238")).await;
+        assert!(matches!(result, Err(Error::CircuitOpen { .. })));
+
+        // Half open, a failing trial call opens the circuit again.
+        tokio::time::sleep(open_for).await;
+        let result = neutral.ip_info().send(ip("203.0.113.238")).await;
+        assert!(matches!(result, Err(Error::ServerError(_))));
+        let result = neutral.ip_info().send(ip("203.0.113.238")).await;
+        assert!(matches!(result, Err(Error::CircuitOpen { .. })));
+        failing.assert();
+        drop(failing);
+
+        // Half open, a single trial call is let through and its success closes the circuit.
+        let _ok = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.238$".into()))
+            .with_status(200)
+            .with_body(fixtures::IP_INFO_BODY)
+            .expect(3)
+            .create();
+        tokio::time::sleep(open_for).await;
+        let ip_info = clone.ip_info();
+        let (trial, concurrent) = tokio::join!(
+            ip_info.send(ip("203.0.113.238")),
+            ip_info.send(ip("203.0.113.239")),
+        );
+        assert!(trial.is_ok(), "{:?}", trial);
+        assert!(matches!(concurrent, Err(Error::CircuitOpen { .. })));
+        for _ in 0..2 {
+            let result = neutral.ip_info().send(ip("203.0.113.238")).await;
+            assert!(result.is_ok(), "{:?}", result);
+        }
+
+        assert_eq!(neutral.probe.count(Stage::Transport, "203.0.113.238"), 6);
+        _ok.assert();
+    }
+
+    #[tokio::test]
+    async fn test_breaker_ignores_client_errors() {
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.240$".into()))
+            .with_status(400)
+            .with_body(r#"{"api-error": 3, "api-error-msg": "BAD REQUEST"}"#)
+            .expect(4)
+            .create();
+
+        let neutral = neutral(|builder| {
+            builder.circuit_breaker(CircuitBreakerConfig {
+                failure_threshold: 2,
+                open_for: Duration::from_secs(60),
+                counting: BreakerCounting::PerCall,
+            })
+        });
+
+        for _ in 0..4 {
+            let result = neutral.ip_info().send(ip("203.0.113.240")).await;
+            assert!(matches!(result, Err(Error::Neutrino(_))), "{:?}", result);
+        }
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_wait_does_not_hold_singleflight() {
+        let _mocks: Vec<_> = ["203.0.113.20", "203.0.113.21", "203.0.113.22"]
+            .iter()
+            .map(|ip_addr| {
+                mock("GET", "/ip-info")
+                    .match_query(Matcher::Regex(format!("ip={}$", ip_addr)))
+                    .with_status(200)
+                    .with_body(fixtures::IP_INFO_BODY)
+                    .expect(1)
+                    .create()
+            })
+            .collect();
+
+        let neutral = neutral(|builder| {
+            builder.rate_limit(RateLimitConfig {
+                permits: 1,
+                per: Duration::from_millis(200),
+            })
+        });
+
+        let ip_info = neutral.ip_info();
+        let (first, second, shared, third) = tokio::join!(
+            ip_info.send(ip("203.0.113.20")),
+            ip_info.send(ip("203.0.113.21")),
+            ip_info.send(ip("203.0.113.21")),
+            ip_info.send(ip("203.0.113.22")),
+        );
+        assert!(first.is_ok() && second.is_ok() && shared.is_ok() && third.is_ok());
+
+        let events = neutral.probe.events();
+        let position = |stage: Stage, input: &str| {
+            events
+                .iter()
+                .position(|(entered, entered_input)| *entered == stage && entered_input == input)
+                .unwrap()
+        };
+        assert!(
+            position(Stage::Limiter, "203.0.113.22") < position(Stage::Transport, "203.0.113.21"),
+            "a call waiting for a token blocked the calls of other inputs"
+        );
+        assert_eq!(neutral.probe.count(Stage::Singleflight, "203.0.113.21"), 2);
+        assert_eq!(neutral.probe.count(Stage::Limiter, "203.0.113.21"), 1);
+        for mock in &_mocks {
+            mock.assert();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_with_auth_concurrent_calls() {
+        let mocks: Vec<_> = [("TenantA", "key-a"), ("TenantB", "key-b")]
+            .iter()
+            .map(|(user_id, api_key)| {
+                mock("GET", "/ip-info")
+                    .match_query(Matcher::Regex("ip=203.0.113.227$".into()))
+                    .match_header("user-id", *user_id)
+                    .match_header("api-key", *api_key)
+                    .with_status(200)
+                    .with_body(fixtures::IP_INFO_BODY)
+                    .expect(1)
+                    .create()
+            })
+            .collect();
+
+        let neutral = neutral(|builder| builder);
+        let tenant_a = neutral.with_auth(ApiAuth::new("TenantA".to_owned(), "key-a".to_owned()));
+        let tenant_b = neutral.with_auth(ApiAuth::new("TenantB".to_owned(), "key-b".to_owned()));
+        let (ip_info_a, ip_info_b) = (tenant_a.ip_info(), tenant_b.ip_info());
+        let (a, b) = tokio::join!(
+            ip_info_a.send(ip("203.0.113.227")),
+            ip_info_b.send(ip("203.0.113.227")),
+        );
+        assert!(a.is_ok(), "{:?}", a);
+        assert!(b.is_ok(), "{:?}", b);
+        for mock in mocks {
+            mock.assert();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_per_identity() {
+        let _mocks: Vec<_> = ["203.0.113.30", "203.0.113.31", "203.0.113.32"]
+            .iter()
+            .map(|ip_addr| {
+                mock("GET", "/ip-info")
+                    .match_query(Matcher::Regex(format!("ip={}$", ip_addr)))
+                    .with_status(200)
+                    .with_body(fixtures::IP_INFO_BODY)
+                    .expect(1)
+                    .create()
+            })
+            .collect();
+
+        let first = neutral(|builder| {
+            builder
+                .rate_limit_per_identity(1, Duration::from_secs(60))
+                .rate_limit(RateLimitConfig {
+                    permits: 2,
+                    per: Duration::from_secs(60),
+                })
+        });
+        let with_identity =
+            |user_id: &str| first.with_auth(ApiAuth::new(user_id.to_owned(), "test".to_owned()));
+        let second = with_identity("Other");
+        let third = with_identity("Another");
+        let timeout = |neutral: &Neutral, ip_addr: &'static str| {
+            let neutral = neutral.clone();
+            async move {
+                tokio::time::timeout(
+                    Duration::from_millis(100),
+                    neutral.ip_info().send(ip(ip_addr)),
+                )
+                .await
+            }
+        };
+
+        assert!(matches!(timeout(&first, "203.0.113.30").await, Ok(Ok(_))));
+        assert!(
+            timeout(&first, "203.0.113.33").await.is_err(),
+            "the identity budget is exhausted"
+        );
+        assert!(
+            matches!(timeout(&second, "203.0.113.31").await, Ok(Ok(_))),
+            "identities have independent budgets"
+        );
+        assert!(
+            timeout(&third, "203.0.113.32").await.is_err(),
+            "the global cap binds every identity"
+        );
+
+        let runtime = first.runtime();
+        let identity_limiter = runtime.identity_limiter.as_ref().unwrap();
+        assert_eq!(identity_limiter.len(), 3);
+        assert!(_mocks[..2].iter().all(|mock| mock.matched()));
+        assert!(!_mocks[2].matched());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_in_flight_call() {
+        use hyper::{
+            service::{make_service_fn, service_fn},
+            Response, Server,
+        };
+        use std::{
+            convert::Infallible,
+            sync::atomic::{AtomicUsize, Ordering},
+        };
+
+        let hits = Arc::new(AtomicUsize::new(0));
+        let make_service = make_service_fn({
+            let hits = hits.clone();
+            move |_| {
+                let hits = hits.clone();
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                        hits.fetch_add(1, Ordering::SeqCst);
+                        let delayed = req.uri().query().unwrap_or_default().ends_with(".234");
+                        async move {
+                            if delayed {
+                                tokio::time::sleep(Duration::from_secs(10)).await;
+                            }
+                            Ok::<_, Infallible>(Response::new(Body::from(fixtures::IP_INFO_BODY)))
+                        }
+                    }))
+                }
+            }
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        let neutral = Neutral::try_new(
+            &format!("http://{}", addr),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        let token = CancellationToken::new();
+        let context = CallContext::new().with_cancellation(token.clone());
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            token.cancel();
+        });
+        let start = Instant::now();
+        let result = neutral
+            .ip_info()
+            .context(context.clone())
+            .send(ip("203.0.113.234"))
+            .await;
+        assert!(matches!(result, Err(Error::Cancelled)), "{:?}", result);
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        let result = neutral
+            .ip_info()
+            .context(context)
+            .send(ip("203.0.113.235"))
+            .await;
+        assert!(
+            matches!(result, Err(Error::Cancelled)),
+            "a cancelled token cancels the next calls"
+        );
+        assert_eq!(hits.load(Ordering::SeqCst), 1, "no request is sent");
+
+        neutral.ip_info().send(ip("203.0.113.235")).await.unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,61 @@
+//! # Prelude module
+//! Import the client, its error and the params and response types of every endpoint at once.
+//!
+//! The response types defined by neutral_types are re-exported, so naming them doesn't require a dependency on a matching version of neutral_types.
+//! They stay the same types as the ones of `neutral_types`, so code using the original paths keeps working.
+//!
+//! ```no_run
+//! use neutral::prelude::*;
+//!
+//! async fn country_of(neutral: &Neutral, ip_addr: std::net::IpAddr) -> Result<String, Error> {
+//!     let response: IpInfoResponse = neutral.ip_info().send(ip_addr).await?;
+//!     Ok(response.country)
+//! }
+//!
+//! fn is_mobile(response: &PhoneValidateResponse) -> bool {
+//!     response.kind == PhoneInfoKind::Mobile
+//! }
+//!
+//! fn is_live(response: &HlrLookupResponse) -> bool {
+//!     response.hlr_status == HlrStatus::Ok
+//! }
+//!
+//! fn is_hosting(response: &IpProbeResponse) -> bool {
+//!     response.provider_type == NeutrinoProviderKind::Hosting
+//! }
+//!
+//! fn builder(api_auth: ApiAuth) -> NeutralBuilder {
+//!     Neutral::builder("https://neutrinoapi.net", api_auth)
+//! }
+//!
+//! # fn names(
+//! #     _: BinLookupParams, _: BinLookupResponse, _: EmailVerifyParams, _: EmailVerifyResponse,
+//! #     _: HlrLookupOptions, _: HlrLookupParams, _: HtmlRenderParams, _: WatermarkParams,
+//! #     _: IpBlocklistOptions, _: IpBlocklistParams, _: IpBlocklistResponse, _: IpInfoOptions,
+//! #     _: IpInfoParams, _: IpProbeParams, _: PhoneValidateParams, _: PhoneVerifyOptions,
+//! #     _: PhoneVerifyParams, _: PhoneVerifyResponse, _: SmsMessageParams, _: SmsMessageResponse,
+//! #     _: SmsVerifyOptions, _: SmsVerifyParams, _: SmsVerifyResponse, _: UaLookupParams,
+//! #     _: UaLookupResponse, _: VerifySecurityCodeParams, _: VerifySecurityCodeResponse,
+//! #     _: NeutrinoSensor, _: NeutrinoTimeZoneResponse,
+//! # ) {}
+//! ```
+
+pub use crate::{
+    bin_lookup::{BinLookupParams, BinLookupResponse},
+    email_verify::{EmailVerifyParams, EmailVerifyResponse},
+    error::Error,
+    hlr_lookup::{HlrLookupOptions, HlrLookupParams, HlrLookupResponse, HlrStatus},
+    html_render::HtmlRenderParams,
+    image_watermark::WatermarkParams,
+    ip_blocklist::{IpBlocklistOptions, IpBlocklistParams, IpBlocklistResponse},
+    ip_info::{IpInfoOptions, IpInfoParams, IpInfoResponse},
+    ip_probe::{IpProbeParams, IpProbeResponse},
+    phone_validate::{PhoneValidateParams, PhoneValidateResponse},
+    phone_verify::{PhoneVerifyOptions, PhoneVerifyParams, PhoneVerifyResponse},
+    sms_message::{SmsMessageParams, SmsMessageResponse},
+    sms_verify::{SmsVerifyOptions, SmsVerifyParams, SmsVerifyResponse},
+    ua_lookup::{UaLookupParams, UaLookupResponse},
+    verify_security_code::{VerifySecurityCodeParams, VerifySecurityCodeResponse},
+    ApiAuth, Neutral, NeutralBuilder, NeutrinoProviderKind, NeutrinoSensor,
+    NeutrinoTimeZoneResponse, PhoneInfoKind,
+};
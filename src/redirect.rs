@@ -0,0 +1,309 @@
+//! Follow the redirects answered by neutrinoapi.com or by a gateway in front of it, see [NeutralBuilder::max_redirects](../builder/struct.NeutralBuilder.html#method.max_redirects).
+
+use http::{
+    header::{HeaderName, LOCATION},
+    uri::{Parts, PathAndQuery},
+    HeaderMap, Method, StatusCode, Uri,
+};
+use hyper::{body::Bytes, Body, Request, Response};
+
+use crate::{builder::check_transport, transport::Transport, Error};
+
+const CREDENTIAL_HEADERS: [&str; 2] = ["user-id", "api-key"];
+
+/// Send `req` with `transport`, following up to `max_redirects` redirects.
+///
+/// A redirect is sent with the method and the body of `req`: a 303 answering a request other than a GET is not followed, since it asks for a GET.
+/// The credential headers, and the `signature_header` of the signer when one is registered, are only sent to the scheme and authority of `req`.
+/// When `require_https`, a redirect to plain http fails with [ConfigError::InsecureTransport](../error/enum.ConfigError.html#variant.InsecureTransport), unless to a loopback address.
+pub(crate) async fn follow(
+    transport: &Transport,
+    req: Request<Body>,
+    max_redirects: usize,
+    require_https: bool,
+    signature_header: Option<HeaderName>,
+) -> Result<Response<Body>, Error> {
+    let (parts, body) = req.into_parts();
+    let body = hyper::body::to_bytes(body).await?;
+    let origin = (parts.uri.scheme().cloned(), parts.uri.authority().cloned());
+    let mut stripped = parts.headers.clone();
+    for name in CREDENTIAL_HEADERS {
+        stripped.remove(name);
+    }
+    if let Some(name) = signature_header {
+        stripped.remove(name);
+    }
+
+    let mut uri = parts.uri.clone();
+    let mut redirects = 0;
+    loop {
+        let headers = if (uri.scheme().cloned(), uri.authority().cloned()) == origin {
+            &parts.headers
+        } else {
+            &stripped
+        };
+        let response = transport
+            .request(build(&parts.method, &uri, headers, &body)?)
+            .await?;
+
+        let location = match redirect_location(&parts.method, &uri, &response) {
+            Some(location) => location,
+            None => return Ok(response),
+        };
+        if redirects == max_redirects {
+            return Err(Error::TooManyRedirects {
+                max_redirects,
+                location: location.to_string(),
+            });
+        }
+        check_transport(&location, require_https)?;
+        redirects += 1;
+        uri = location;
+    }
+}
+
+fn build(
+    method: &Method,
+    uri: &Uri,
+    headers: &HeaderMap,
+    body: &Bytes,
+) -> Result<Request<Body>, Error> {
+    let mut req = Request::builder()
+        .method(method.clone())
+        .uri(uri.clone())
+        .body(Body::from(body.clone()))?;
+    *req.headers_mut() = headers.clone();
+    Ok(req)
+}
+
+/// Returns the uri `response` redirects to, `None` when it is not a redirect to follow.
+fn redirect_location(method: &Method, uri: &Uri, response: &Response<Body>) -> Option<Uri> {
+    match response.status() {
+        StatusCode::MOVED_PERMANENTLY
+        | StatusCode::FOUND
+        | StatusCode::TEMPORARY_REDIRECT
+        | StatusCode::PERMANENT_REDIRECT => {}
+        StatusCode::SEE_OTHER if method == Method::GET => {}
+        _ => return None,
+    }
+    let location = response.headers().get(LOCATION)?.to_str().ok()?;
+    resolve(uri, location)
+}
+
+/// Resolve `location`, an absolute uri or an absolute path, against `base`.
+fn resolve(base: &Uri, location: &str) -> Option<Uri> {
+    let location: Uri = location.parse().ok()?;
+    if location.scheme().is_some() && location.authority().is_some() {
+        return Some(location);
+    }
+    let path_and_query: &PathAndQuery = location.path_and_query()?;
+    if !path_and_query.as_str().starts_with('/') {
+        return None;
+    }
+    let mut parts = Parts::default();
+    parts.scheme = base.scheme().cloned();
+    parts.authority = base.authority().cloned();
+    parts.path_and_query = Some(path_and_query.clone());
+    Uri::from_parts(parts).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{error::ConfigError, fixtures::IP_INFO_BODY, ApiAuth, Neutral};
+    use mockito::{mock, Matcher};
+
+    fn neutral(max_redirects: usize) -> Neutral {
+        Neutral::builder(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .max_redirects(max_redirects)
+        .build()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_resolve() {
+        let base: Uri = "https://neutrinoapi.net/ip-info?ip=1.1.1.1"
+            .parse()
+            .unwrap();
+        struct TestingData {
+            pub name: String,
+            pub args: &'static str,
+            pub expected: Option<&'static str>,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Using an absolute uri".to_owned(),
+                args: "https://eu.neutrinoapi.net/ip-info?ip=1.1.1.1",
+                expected: Some("https://eu.neutrinoapi.net/ip-info?ip=1.1.1.1"),
+            },
+            TestingData {
+                name: "Using an absolute path".to_owned(),
+                args: "/eu/ip-info?ip=1.1.1.1",
+                expected: Some("https://neutrinoapi.net/eu/ip-info?ip=1.1.1.1"),
+            },
+            TestingData {
+                name: "Using a relative path".to_owned(),
+                args: "eu/ip-info",
+                expected: None,
+            },
+        ];
+
+        for test in tests {
+            assert_eq!(
+                resolve(&base, test.args).map(|uri| uri.to_string()),
+                test.expected.map(str::to_owned),
+                "{}",
+                test.name
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_redirect_to_the_same_host() {
+        let _redirect = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.252$".into()))
+            .with_status(302)
+            .with_header("location", "/regional/ip-info?ip=203.0.113.252")
+            .expect(2)
+            .create();
+        let _m = mock("GET", "/regional/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.252$".into()))
+            .match_header("user-id", "User")
+            .match_header("api-key", "test")
+            .with_status(200)
+            .with_body(IP_INFO_BODY)
+            .expect(1)
+            .create();
+
+        // Redirects are not followed by default.
+        let ip_addr = "203.0.113.252".parse().unwrap();
+        assert!(matches!(
+            neutral(0).ip_info().send(ip_addr).await,
+            Err(Error::Neutrino(err)) if err.status_code == StatusCode::FOUND
+        ));
+
+        neutral(1).ip_info().send(ip_addr).await.unwrap();
+        _redirect.assert();
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_redirect_keeps_the_method_and_body() {
+        let _redirect = mock("POST", "/hlr-lookup")
+            .match_body(Matcher::UrlEncoded("number".into(), "+33620000098".into()))
+            .with_status(302)
+            .with_header("location", "/regional/hlr-lookup")
+            .create();
+        let _m = mock("POST", "/regional/hlr-lookup")
+            .match_body(Matcher::UrlEncoded("number".into(), "+33620000098".into()))
+            .with_status(200)
+            .with_body(crate::fixtures::HLR_LOOKUP_BODY)
+            .expect(1)
+            .create();
+
+        let neutral = Neutral::builder(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .params_in_body(true)
+        .max_redirects(1)
+        .build()
+        .unwrap();
+        neutral.hlr_lookup().send("+33620000098").await.unwrap();
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_redirect_to_another_host() {
+        // mockito listens on 127.0.0.1, `localhost` is another authority of the same server.
+        let other_host = mockito::server_url().replace("127.0.0.1", "localhost");
+        let _redirect = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.253$".into()))
+            .match_header("x-signature", "signed")
+            .with_status(301)
+            .with_header(
+                "location",
+                &format!("{}/cross/ip-info?ip=203.0.113.253", other_host),
+            )
+            .create();
+        let _m = mock("GET", "/cross/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.253$".into()))
+            .match_header("user-id", Matcher::Missing)
+            .match_header("api-key", Matcher::Missing)
+            .match_header("x-signature", Matcher::Missing)
+            .with_status(200)
+            .with_body(IP_INFO_BODY)
+            .expect(1)
+            .create();
+
+        Neutral::builder(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .max_redirects(3)
+        .sign_with(HeaderName::from_static("x-signature"), |_| {
+            http::HeaderValue::from_static("signed")
+        })
+        .build()
+        .unwrap()
+        .ip_info()
+        .send("203.0.113.253".parse().unwrap())
+        .await
+        .unwrap();
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_redirect_to_plain_http() {
+        let _redirect = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.6$".into()))
+            .with_status(302)
+            .with_header("location", "http://neutrinoapi.net/ip-info?ip=203.0.113.6")
+            .expect(1)
+            .create();
+
+        let result = neutral(3)
+            .ip_info()
+            .send("203.0.113.6".parse().unwrap())
+            .await;
+        assert!(
+            matches!(
+                &result,
+                Err(Error::Config(ConfigError::InsecureTransport(uri)))
+                    if *uri == "http://neutrinoapi.net/ip-info?ip=203.0.113.6"
+            ),
+            "{:?}",
+            result
+        );
+        _redirect.assert();
+    }
+
+    #[tokio::test]
+    async fn test_too_many_redirects() {
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.254$".into()))
+            .with_status(307)
+            .with_header("location", "/ip-info?ip=203.0.113.254")
+            .expect(3)
+            .create();
+
+        let result = neutral(2)
+            .ip_info()
+            .send("203.0.113.254".parse().unwrap())
+            .await;
+        assert!(
+            matches!(
+                &result,
+                Err(Error::TooManyRedirects { max_redirects: 2, location })
+                    if location.ends_with("/ip-info?ip=203.0.113.254")
+            ),
+            "{:?}",
+            result
+        );
+        _m.assert();
+    }
+}
@@ -0,0 +1,231 @@
+//! # Region module
+//! Send the calls to a regional host of neutrinoapi.com and fail over to the next host when one is down.
+//!
+//! A client tries its base uri first, then every failover uri in order, see [NeutralBuilder::failover](../builder/struct.NeutralBuilder.html#method.failover)
+//! and [Neutral::try_new_multi](../struct.Neutral.html#method.try_new_multi). A request moves to the next host when it fails to connect,
+//! or when it answers with a server error (HTTP 5xx) and the call is idempotent. Any other error, such as a 400 or a 403, is returned right away.
+//!
+//! Failover happens within an attempt: a call moving to another host does not count as a retry, and once every host failed
+//! the [RetryConfig](../pipeline/struct.RetryConfig.html) retries start again from the base uri. [ResponseMeta::host](../meta/struct.ResponseMeta.html#structfield.host)
+//! tells which host served the response. Streamed calls, such as downloads, only use the base uri.
+//!
+//! ```ignore
+//! use neutral::region::Region;
+//!
+//! let neutral = Neutral::try_new_multi(vec![Region::Europe.uri(), Region::Global.uri()], api_auth)?;
+//! let (meta, ip_info_response) = neutral.ip_info().send_with_meta(ip_addr).await?;
+//! println!("served by {:?}", meta.host);
+//! ```
+
+use std::fmt;
+
+use http::Uri;
+
+/// A host of neutrinoapi.com.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Region {
+    /// The global host, routing to the closest data center.
+    Global,
+    /// The host keeping the traffic in the European Union.
+    Europe,
+    /// The host keeping the traffic in the United States.
+    Usa,
+    /// The host keeping the traffic in Australia.
+    Australia,
+}
+
+impl Region {
+    /// Every region, the global one first.
+    pub const ALL: [Region; 4] = [
+        Region::Global,
+        Region::Europe,
+        Region::Usa,
+        Region::Australia,
+    ];
+
+    /// Returns the base uri of the region, e.g. `https://eu1.neutrinoapi.net`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Region::Global => "https://neutrinoapi.net",
+            Region::Europe => "https://eu1.neutrinoapi.net",
+            Region::Usa => "https://usa1.neutrinoapi.net",
+            Region::Australia => "https://aus1.neutrinoapi.net",
+        }
+    }
+
+    /// Returns the base uri of the region as an [Uri].
+    pub fn uri(&self) -> Uri {
+        Uri::from_static(self.as_str())
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{error::ConfigError, fixtures::IP_INFO_BODY, ApiAuth, Error, Neutral};
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Body, Response, Server,
+    };
+    use mockito::{mock, Matcher};
+    use std::{
+        convert::Infallible,
+        net::SocketAddr,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    fn api_auth() -> ApiAuth {
+        ApiAuth::new("User".to_string(), "test".to_string())
+    }
+
+    /// Serve every request with an error response of `status`, counting them in `hits`.
+    fn serve(status: u16, hits: Arc<AtomicUsize>) -> SocketAddr {
+        let make_service = make_service_fn(move |_| {
+            let hits = hits.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |_| {
+                    hits.fetch_add(1, Ordering::SeqCst);
+                    async move {
+                        Ok::<_, Infallible>(
+                            Response::builder()
+                                .status(status)
+                                .body(Body::from(r#"{"api-error": 0, "api-error-msg": "failed"}"#))
+                                .unwrap(),
+                        )
+                    }
+                }))
+            }
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        addr
+    }
+
+    fn server_uri() -> Uri {
+        mockito::server_url().parse().unwrap()
+    }
+
+    #[test]
+    fn test_region_uri() {
+        for region in Region::ALL {
+            let uri = region.uri();
+            assert_eq!(uri.scheme_str(), Some("https"), "{}", region);
+            assert_eq!(uri.to_string().trim_end_matches('/'), region.as_str());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failover_on_connection_refused() {
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.228$".into()))
+            .with_status(200)
+            .with_body(IP_INFO_BODY)
+            .expect(1)
+            .create();
+
+        let neutral = Neutral::try_new_multi(
+            vec!["http://127.0.0.1:1".parse().unwrap(), server_uri()],
+            api_auth(),
+        )
+        .unwrap();
+        let (meta, _) = neutral
+            .ip_info()
+            .send_with_meta("203.0.113.228".parse().unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(meta.host, server_uri().authority().cloned());
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_failover_on_server_error() {
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.229$".into()))
+            .with_status(200)
+            .with_body(IP_INFO_BODY)
+            .expect(1)
+            .create();
+        let hits = Arc::new(AtomicUsize::new(0));
+        let failing = serve(500, hits.clone());
+
+        let neutral = Neutral::try_new_multi(
+            vec![format!("http://{}", failing).parse().unwrap(), server_uri()],
+            api_auth(),
+        )
+        .unwrap();
+        let (meta, _) = neutral
+            .ip_info()
+            .send_with_meta("203.0.113.229".parse().unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+        assert_eq!(meta.host, server_uri().authority().cloned());
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_no_failover_on_client_error() {
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.23[01]$".into()))
+            .with_status(200)
+            .with_body(IP_INFO_BODY)
+            .expect(0)
+            .create();
+
+        for (status, ip_addr) in [(400, "203.0.113.230"), (403, "203.0.113.231")] {
+            let hits = Arc::new(AtomicUsize::new(0));
+            let failing = serve(status, hits.clone());
+            let neutral = Neutral::try_new_multi(
+                vec![format!("http://{}", failing).parse().unwrap(), server_uri()],
+                api_auth(),
+            )
+            .unwrap();
+
+            let result = neutral.ip_info().send(ip_addr.parse().unwrap()).await;
+            match (status, result) {
+                (400, Err(Error::Neutrino(_))) | (403, Err(Error::Unauthorized(_))) => {}
+                (status, other) => panic!("unexpected {:?} for {}", other, status),
+            }
+            assert_eq!(hits.load(Ordering::SeqCst), 1, "{}", status);
+        }
+        _m.assert();
+    }
+
+    #[test]
+    fn test_multi_config() {
+        let result = Neutral::try_new_multi(Vec::new(), api_auth());
+        assert!(matches!(result, Err(Error::Config(ConfigError::NoUri))));
+
+        let result = Neutral::try_new_multi(
+            vec![
+                Region::Europe.uri(),
+                "http://eu.gateway.internal".parse().unwrap(),
+            ],
+            api_auth(),
+        );
+        assert!(matches!(
+            result,
+            Err(Error::Config(ConfigError::InsecureTransport(_)))
+        ));
+
+        let neutral =
+            Neutral::try_new_multi(vec![Region::Europe.uri(), Region::Global.uri()], api_auth())
+                .unwrap();
+        let config = neutral.runtime().config.clone();
+        assert_eq!(config.uri, Region::Europe.uri());
+        assert_eq!(config.failover, vec![Region::Global.uri()]);
+    }
+}
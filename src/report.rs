@@ -0,0 +1,396 @@
+//! # Report module
+//! Compose the full report of an ip address, a phone number or an email address from several neutrinoapi.com calls.
+//!
+//! The calls of a report are sent concurrently through every protective layer of the client: cache, rate limits, circuit breaker and retries.
+//! A report fails with the first error of its calls.
+//!
+//! [Neutral::bulk_reports](../struct.Neutral.html#method.bulk_reports) fetches the reports of a list of mixed identifiers with a bounded concurrency,
+//! yielding each result with the index of its request as soon as it completes:
+//!
+//! ```ignore
+//! let items = vec![
+//!     ReportRequest::IpFull("203.0.113.7".parse()?),
+//!     ReportRequest::PhoneFull("+33612345678".to_owned()),
+//!     ReportRequest::EmailFull("jane@example.com".to_owned()),
+//! ];
+//! let mut reports = neutral.bulk_reports(items, 8);
+//! while let Some((index, result)) = reports.next().await {
+//!     // ...
+//! }
+//! ```
+
+use std::{future::Future, net::IpAddr};
+
+use futures::{Stream, StreamExt};
+use neutral_types::{
+    hlr_lookup::HlrLookupResponse, ip_blocklist::IpBlocklistResponse, ip_info::IpInfoResponse,
+    ip_probe::IpProbeResponse, phone_validate::PhoneValidateResponse,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    email_verify::EmailVerifyResponse,
+    hlr_lookup::{HlrLookupExt, RoamingStatus},
+    metrics::MetricsSink,
+    optional_text::{HlrLookupOptionalText, PhoneValidateOptionalText},
+    warning::Warning,
+    Error, Neutral,
+};
+
+/// Result of a single report.
+pub type ReportResult = Result<Report, Error>;
+
+/// Identify the report to fetch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum ReportRequest {
+    /// Ip info, ip blocklist and ip probe of an ip address.
+    IpFull(IpAddr),
+    /// Phone validate and hlr lookup of a phone number.
+    PhoneFull(String),
+    /// Email verify of an email address.
+    EmailFull(String),
+}
+
+/// Full report of an ip address.
+#[derive(Debug, Clone)]
+pub struct IpReport {
+    pub info: IpInfoResponse,
+    pub blocklist: IpBlocklistResponse,
+    pub probe: IpProbeResponse,
+}
+
+/// Full report of a phone number.
+#[derive(Debug, Clone)]
+pub struct PhoneReport {
+    pub validate: PhoneValidateResponse,
+    pub hlr: HlrLookupResponse,
+    /// Roaming status of the device, see [HlrLookupExt::roaming](../hlr_lookup/trait.HlrLookupExt.html#tymethod.roaming).
+    pub roaming: RoamingStatus,
+    /// Network the device is connected to, `None` when unknown.
+    pub current_network: Option<String>,
+    /// Network the number was ported to, `None` when it was not ported.
+    pub ported_network: Option<String>,
+    /// Location of the number, `None` when unknown.
+    pub location: Option<String>,
+    /// Anomalies detected in the responses, also reported to the metrics sinks of the client.
+    pub warnings: Vec<Warning>,
+}
+
+impl PhoneReport {
+    fn new(validate: PhoneValidateResponse, hlr: HlrLookupResponse) -> Self {
+        PhoneReport {
+            roaming: hlr.roaming(),
+            current_network: hlr.current_network_opt().map(str::to_owned),
+            ported_network: hlr.ported_network_opt().map(str::to_owned),
+            location: validate.location_opt().map(str::to_owned),
+            warnings: hlr.warnings(),
+            validate,
+            hlr,
+        }
+    }
+}
+
+/// Full report of an email address.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmailReport {
+    pub verify: EmailVerifyResponse,
+}
+
+/// A report, matching the kind of its [ReportRequest](./enum.ReportRequest.html).
+#[derive(Debug, Clone)]
+pub enum Report {
+    Ip(Box<IpReport>),
+    Phone(Box<PhoneReport>),
+    Email(EmailReport),
+}
+
+impl Neutral {
+    /// Fetch the full report of `request`.
+    pub async fn report(&self, request: ReportRequest) -> ReportResult {
+        match request {
+            ReportRequest::IpFull(ip_addr) => {
+                let (ip_info, ip_blocklist, ip_probe) =
+                    (self.ip_info(), self.ip_blocklist(), self.ip_probe());
+                let (info, blocklist, probe) = futures::try_join!(
+                    ip_info.send(ip_addr),
+                    ip_blocklist.send(ip_addr),
+                    ip_probe.send(ip_addr),
+                )?;
+                Ok(Report::Ip(Box::new(IpReport {
+                    info,
+                    blocklist,
+                    probe,
+                })))
+            }
+            ReportRequest::PhoneFull(phone_number) => {
+                let (phone_validate, hlr_lookup) = (self.phone_validate(), self.hlr_lookup());
+                let (validate, hlr) = futures::try_join!(
+                    phone_validate.send(phone_number.clone()),
+                    hlr_lookup.send(phone_number),
+                )?;
+                let report = PhoneReport::new(validate, hlr);
+                for warning in &report.warnings {
+                    self.metrics.on_warning("hlr-lookup", warning);
+                }
+                Ok(Report::Phone(Box::new(report)))
+            }
+            ReportRequest::EmailFull(email) => {
+                let verify = self.email_verify().send(email).await?;
+                Ok(Report::Email(EmailReport { verify }))
+            }
+        }
+    }
+
+    /// Fetch the reports of `items`, at most `concurrency` at the same time.
+    ///
+    /// Results are yielded as they complete, with the index of their request in `items`.
+    pub fn bulk_reports(
+        &self,
+        items: Vec<ReportRequest>,
+        concurrency: usize,
+    ) -> impl Stream<Item = (usize, ReportResult)> + '_ {
+        indexed_concurrent(items, concurrency, move |request| self.report(request))
+    }
+}
+
+/// Run `f` on every item, at most `concurrency` at the same time, yielding each output with the index of its item.
+pub(crate) fn indexed_concurrent<T, F, Fut>(
+    items: Vec<T>,
+    concurrency: usize,
+    f: F,
+) -> impl Stream<Item = (usize, Fut::Output)>
+where
+    F: Fn(T) -> Fut,
+    Fut: Future,
+{
+    futures::stream::iter(items.into_iter().enumerate())
+        .map(move |(index, item)| {
+            let future = f(item);
+            async move { (index, future.await) }
+        })
+        .buffer_unordered(concurrency.max(1))
+}
+
+/// Run `f` on every item, at most `concurrency` at the same time, returning each item with its output in the order of `items`.
+pub(crate) async fn ordered_concurrent<T, F, Fut>(
+    items: Vec<T>,
+    concurrency: usize,
+    f: F,
+) -> Vec<(T, Fut::Output)>
+where
+    T: Clone,
+    F: Fn(T) -> Fut,
+    Fut: Future,
+{
+    let mut outputs: Vec<_> = indexed_concurrent(items.clone(), concurrency, f)
+        .collect()
+        .await;
+    outputs.sort_by_key(|(index, _)| *index);
+    items
+        .into_iter()
+        .zip(outputs.into_iter().map(|(_, output)| output))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        fixtures::{
+            HLR_LOOKUP_BODY, IP_BLOCKLIST_BODY, IP_INFO_BODY, IP_PROBE_BODY, PHONE_VALIDATE_BODY,
+        },
+        ApiAuth,
+    };
+    use mockito::{mock, Matcher};
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    #[tokio::test]
+    async fn test_indexed_concurrent_bounds_concurrency() {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+
+        let results: Vec<(usize, usize)> = indexed_concurrent((0..20).collect(), 3, |item| {
+            let in_flight = in_flight.clone();
+            let max_in_flight = max_in_flight.clone();
+            async move {
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(current, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(5 * (item % 4) as u64)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                item * 10
+            }
+        })
+        .collect()
+        .await;
+
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 3);
+        assert_eq!(results.len(), 20);
+        for (index, output) in results {
+            assert_eq!(output, index * 10);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bulk_reports() {
+        let mocks = [
+            mock("GET", "/ip-info")
+                .match_query(Matcher::Regex("ip=203.0.113.60(&|$)".into()))
+                .with_status(200)
+                .with_body(IP_INFO_BODY)
+                .create(),
+            mock("GET", "/ip-blocklist")
+                .match_query(Matcher::Regex("ip=203.0.113.60(&|$)".into()))
+                .with_status(200)
+                .with_body(IP_BLOCKLIST_BODY)
+                .create(),
+            mock("GET", "/ip-probe")
+                .match_query(Matcher::Regex("ip=203.0.113.60(&|$)".into()))
+                .with_status(200)
+                .with_body(IP_PROBE_BODY)
+                .create(),
+            mock("GET", "/phone-validate")
+                .match_query(Matcher::Regex("number=%2B33611223344".into()))
+                .match_header("api-key", "report")
+                .with_status(200)
+                .with_body(PHONE_VALIDATE_BODY)
+                .create(),
+            mock("GET", "/hlr-lookup")
+                .match_query(Matcher::Regex("number=%2B33611223344".into()))
+                .match_header("api-key", "report")
+                .with_status(200)
+                .with_body(HLR_LOOKUP_BODY)
+                .create(),
+            mock("GET", "/email-verify")
+                .match_query(Matcher::Regex("email=report%40example.com".into()))
+                .with_status(400)
+                .with_body(r#"{"api-error": 1, "api-error-msg": "MISSING OR INVALID PARAMETER"}"#)
+                .create(),
+        ];
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "report".to_string()),
+        )
+        .unwrap();
+
+        let items = vec![
+            ReportRequest::EmailFull("report@example.com".to_owned()),
+            ReportRequest::IpFull("203.0.113.60".parse().unwrap()),
+            ReportRequest::PhoneFull("+33611223344".to_owned()),
+        ];
+        let mut results: Vec<_> = neutral.bulk_reports(items, 2).collect().await;
+        results.sort_by_key(|(index, _)| *index);
+
+        assert_eq!(results.len(), 3);
+        assert!(matches!(results[0], (0, Err(Error::Neutrino(_)))));
+        assert!(
+            matches!(results[1], (1, Ok(Report::Ip(_)))),
+            "{:?}",
+            results[1]
+        );
+        assert!(matches!(results[2], (2, Ok(Report::Phone(_)))));
+        for mock in mocks {
+            mock.assert();
+        }
+    }
+
+    #[derive(Default)]
+    struct Warnings(std::sync::Mutex<Vec<(String, Warning)>>);
+
+    impl crate::metrics::MetricsSink for Warnings {
+        fn on_warning(&self, endpoint: &str, warning: &Warning) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((endpoint.to_owned(), warning.clone()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_phone_report_with_inconsistent_roaming() {
+        let mut hlr: serde_json::Value = serde_json::from_str(HLR_LOOKUP_BODY).unwrap();
+        hlr["is_roaming"] = true.into();
+        hlr["roaming_country_code"] = "".into();
+        hlr["ported_network"] = "Other operator".into();
+        let _validate = mock("GET", "/phone-validate")
+            .match_query(Matcher::Regex("number=%2B33611220755".into()))
+            .with_status(200)
+            .with_body(PHONE_VALIDATE_BODY)
+            .create();
+        let _hlr = mock("GET", "/hlr-lookup")
+            .match_query(Matcher::Regex("number=%2B33611220755".into()))
+            .with_status(200)
+            .with_body(hlr.to_string())
+            .create();
+
+        let warnings = Arc::new(Warnings::default());
+        let neutral = Neutral::builder(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .metrics(warnings.clone())
+        .build()
+        .unwrap();
+
+        let report = match neutral
+            .report(ReportRequest::PhoneFull("+33611220755".to_owned()))
+            .await
+            .unwrap()
+        {
+            Report::Phone(report) => report,
+            report => panic!("{:?}", report),
+        };
+        let expected = Warning::InconsistentRoaming {
+            is_roaming: true,
+            roaming_country_code: "".to_owned(),
+        };
+        assert_eq!(report.roaming, RoamingStatus::Inconsistent);
+        assert_eq!(report.warnings, vec![expected.clone()]);
+        assert_eq!(
+            *warnings.0.lock().unwrap(),
+            vec![("hlr-lookup".to_owned(), expected)]
+        );
+        assert_eq!(report.current_network.as_deref(), Some("Phone operator"));
+        assert_eq!(report.ported_network.as_deref(), Some("Other operator"));
+        assert_eq!(report.location.as_deref(), Some("ACountry"));
+    }
+
+    #[tokio::test]
+    async fn test_phone_report_not_roaming() {
+        let _validate = mock("GET", "/phone-validate")
+            .match_query(Matcher::Regex("number=%2B33611220756".into()))
+            .with_status(200)
+            .with_body(PHONE_VALIDATE_BODY)
+            .create();
+        let _hlr = mock("GET", "/hlr-lookup")
+            .match_query(Matcher::Regex("number=%2B33611220756".into()))
+            .with_status(200)
+            .with_body(HLR_LOOKUP_BODY)
+            .create();
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+        match neutral
+            .report(ReportRequest::PhoneFull("+33611220756".to_owned()))
+            .await
+            .unwrap()
+        {
+            Report::Phone(report) => {
+                assert_eq!(report.roaming, RoamingStatus::NotRoaming);
+                assert!(report.warnings.is_empty());
+                assert_eq!(report.ported_network, None);
+            }
+            report => panic!("{:?}", report),
+        }
+    }
+}
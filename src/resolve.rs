@@ -0,0 +1,227 @@
+//! # Resolve module
+//! Resolve the host names of neutrinoapi.com and of the proxy with your own resolver, e.g. one with its own cache or servers,
+//! registered with [NeutralBuilder::resolver](../builder/struct.NeutralBuilder.html#method.resolver).
+//!
+//! Without a resolver, names are resolved by the system with `getaddrinfo` on a blocking thread.
+//! A name which can't be resolved fails the call with [Error::Connect](../error/enum.Error.html#variant.Connect),
+//! holding a [ConnectError::Resolve](../error/enum.ConnectError.html#variant.Resolve).
+//!
+//! ```ignore
+//! #[derive(Debug)]
+//! struct TrustDns(TokioAsyncResolver);
+//!
+//! impl Resolve for TrustDns {
+//!     fn resolve(&self, host: &str) -> ResolveFuture {
+//!         let lookup = self.0.lookup_ip(host.to_owned());
+//!         Box::pin(async move { Ok(lookup.await?.iter().collect()) })
+//!     }
+//! }
+//!
+//! let neutral = Neutral::builder("https://neutrinoapi.net", api_auth)
+//!     .resolver(Arc::new(TrustDns(resolver)))
+//!     .connect_timeout(Duration::from_secs(2))
+//!     .build()?;
+//! ```
+
+use std::{
+    fmt,
+    future::Future,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use hyper::{
+    client::connect::dns::{GaiResolver, Name},
+    service::Service,
+};
+
+use crate::error::{BoxError, ConnectError};
+
+/// The addresses of a host, or the reason they can't be found.
+pub type ResolveFuture = Pin<Box<dyn Future<Output = Result<Vec<IpAddr>, BoxError>> + Send>>;
+
+/// Resolve a host name to its addresses.
+pub trait Resolve: fmt::Debug + Send + Sync {
+    /// Returns the addresses of `host`, tried in order until a connection is established.
+    ///
+    /// An empty list fails like an error.
+    fn resolve(&self, host: &str) -> ResolveFuture;
+}
+
+/// The resolver of the connector: a [Resolve] implementation when one is registered, the system resolver otherwise.
+#[derive(Debug, Clone)]
+pub(crate) struct Resolver {
+    custom: Option<Arc<dyn Resolve>>,
+    system: GaiResolver,
+}
+
+impl Resolver {
+    pub(crate) fn new(custom: Option<Arc<dyn Resolve>>) -> Self {
+        Resolver {
+            custom,
+            system: GaiResolver::new(),
+        }
+    }
+}
+
+impl Service<Name> for Resolver {
+    type Response = std::vec::IntoIter<SocketAddr>;
+    type Error = ConnectError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, ConnectError>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), ConnectError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        let host = name.as_str().to_owned();
+        let resolve = match &self.custom {
+            // The port is set by the connector, from the uri.
+            Some(custom) => custom.resolve(&host),
+            None => {
+                let addrs = self.system.call(name);
+                Box::pin(async move { Ok(addrs.await?.map(|addr| addr.ip()).collect()) })
+            }
+        };
+        Box::pin(async move {
+            match resolve.await {
+                Ok(addrs) if !addrs.is_empty() => Ok(addrs
+                    .into_iter()
+                    .map(|ip_addr| SocketAddr::new(ip_addr, 0))
+                    .collect::<Vec<_>>()
+                    .into_iter()),
+                Ok(_) => Err(ConnectError::Resolve {
+                    host,
+                    source: Arc::from(BoxError::from("no address found")),
+                }),
+                Err(err) => Err(ConnectError::Resolve {
+                    host,
+                    source: Arc::from(err),
+                }),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{error::Error, fixtures::IP_INFO_BODY, ApiAuth, Neutral};
+    use mockito::{mock, Matcher};
+    use std::{
+        sync::Mutex,
+        time::{Duration, Instant},
+    };
+
+    /// Resolve every host to `addrs`, recording the names it was asked for.
+    #[derive(Debug, Default)]
+    struct StubResolver {
+        addrs: Vec<IpAddr>,
+        names: Mutex<Vec<String>>,
+    }
+
+    impl Resolve for StubResolver {
+        fn resolve(&self, host: &str) -> ResolveFuture {
+            self.names.lock().unwrap().push(host.to_owned());
+            let addrs = self.addrs.clone();
+            Box::pin(async move { Ok(addrs) })
+        }
+    }
+
+    /// A client reaching the mockito server by the name `localhost`, resolved by `resolver`.
+    fn neutral(resolver: Arc<StubResolver>) -> Neutral {
+        let port = mockito::server_address().port();
+        Neutral::builder(
+            &format!("http://localhost:{}", port),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .resolver(resolver)
+        .build()
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_custom_resolver() {
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.255$".into()))
+            .with_status(200)
+            .with_body(IP_INFO_BODY)
+            .expect(1)
+            .create();
+
+        let resolver = Arc::new(StubResolver {
+            addrs: vec![mockito::server_address().ip()],
+            ..Default::default()
+        });
+        neutral(resolver.clone())
+            .ip_info()
+            .send("203.0.113.255".parse().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(*resolver.names.lock().unwrap(), vec!["localhost"]);
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_unresolved_host() {
+        let resolver = Arc::new(StubResolver::default());
+        let result = neutral(resolver.clone())
+            .ip_info()
+            .send("203.0.113.255".parse().unwrap())
+            .await;
+
+        let err = result.unwrap_err();
+        assert!(
+            matches!(&err, Error::Connect(ConnectError::Resolve { host, .. }) if host == "localhost"),
+            "{:?}",
+            err
+        );
+        assert!(err.is_retryable());
+        assert_eq!(
+            err.to_string(),
+            "cannot connect: cannot resolve localhost: no address found"
+        );
+        assert_eq!(resolver.names.lock().unwrap().len(), 1);
+    }
+
+    /// Never answer, like a stalled DNS server.
+    #[derive(Debug)]
+    struct StalledResolver;
+
+    impl Resolve for StalledResolver {
+        fn resolve(&self, _host: &str) -> ResolveFuture {
+            Box::pin(std::future::pending())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connect_timeout() {
+        let neutral = Neutral::builder(
+            "https://neutrinoapi.net",
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .resolver(Arc::new(StalledResolver))
+        .timeout(Duration::from_secs(30))
+        .connect_timeout(Duration::from_millis(50))
+        .build()
+        .unwrap();
+
+        let start = Instant::now();
+        let result = neutral
+            .ip_info()
+            .send("203.0.113.255".parse().unwrap())
+            .await;
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(
+            matches!(
+                &result,
+                Err(Error::Connect(ConnectError::Timeout { timeout }))
+                    if *timeout == Duration::from_millis(50)
+            ),
+            "{:?}",
+            result
+        );
+    }
+}
@@ -0,0 +1,228 @@
+//! # Runtime module
+//! Change the configuration of a client while it serves calls, e.g. to move to another region.
+//!
+//! [Neutral::reconfigure](../struct.Neutral.html#method.reconfigure) edits a copy of the current [RuntimeConfig](./struct.RuntimeConfig.html) and swaps it atomically.
+//! Every clone of the client sees the change. A call keeps the configuration it started with until it completes, retries included,
+//! while the calls started after the swap use the new one.
+//!
+//! Only the base uri, the failover uris and the rate limits can change at runtime. The other fields of [RuntimeConfig](./struct.RuntimeConfig.html) are informative:
+//! they are fixed when the client is built, like the connector and its TLS settings, and changing them fails with
+//! [ConfigError::NotRuntimeMutable](../error/enum.ConfigError.html#variant.NotRuntimeMutable), leaving the configuration untouched.
+//!
+//! ```ignore
+//! neutral.reconfigure(|config| {
+//!     config.uri = "https://eu.neutrinoapi.net".parse().unwrap();
+//!     config.rate_limit = Some(RateLimitConfig { permits: 20, per: Duration::from_secs(1) });
+//! })?;
+//! ```
+
+use std::sync::Arc;
+
+use http::Uri;
+
+use crate::{
+    builder::{check_base_uri, check_transport},
+    cache::{CacheConfig, NegativeCacheConfig},
+    error::{ConfigError, Error},
+    pipeline::{IdentityRateLimiter, RateLimitConfig, RateLimiter},
+};
+
+/// The configuration of a client, see the [runtime](./index.html) module.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeConfig {
+    /// Base uri of neutrinoapi.com.
+    pub uri: Uri,
+    /// Hosts tried in order when the previous ones failed, see the [region](../region/index.html) module.
+    pub failover: Vec<Uri>,
+    /// Rate limit shared by every call, see [NeutralBuilder::rate_limit](../builder/struct.NeutralBuilder.html#method.rate_limit).
+    pub rate_limit: Option<RateLimitConfig>,
+    /// Rate limit of every identity, see [NeutralBuilder::rate_limit_per_identity](../builder/struct.NeutralBuilder.html#method.rate_limit_per_identity).
+    pub identity_rate_limit: Option<RateLimitConfig>,
+    /// Not runtime mutable, the connector enforces it.
+    pub require_https: bool,
+    /// Not runtime mutable.
+    pub cache: Option<CacheConfig>,
+    /// Not runtime mutable.
+    pub negative_cache: Option<NegativeCacheConfig>,
+}
+
+/// A snapshot of the configuration, with the state built from it.
+#[derive(Debug)]
+pub(crate) struct Runtime {
+    pub(crate) config: RuntimeConfig,
+    pub(crate) limiter: Option<Arc<RateLimiter>>,
+    pub(crate) identity_limiter: Option<Arc<IdentityRateLimiter>>,
+}
+
+impl Runtime {
+    pub(crate) fn new(config: RuntimeConfig) -> Self {
+        Runtime {
+            limiter: config
+                .rate_limit
+                .map(|config| Arc::new(RateLimiter::new(config))),
+            identity_limiter: config
+                .identity_rate_limit
+                .map(|config| Arc::new(IdentityRateLimiter::new(config))),
+            config,
+        }
+    }
+
+    /// Returns the snapshot replacing this one with `config`, keeping the state of the rate limits which did not change.
+    pub(crate) fn update(&self, config: RuntimeConfig) -> Result<Self, Error> {
+        let fixed = [
+            (
+                "require_https",
+                self.config.require_https == config.require_https,
+            ),
+            ("cache", self.config.cache == config.cache),
+            (
+                "negative_cache",
+                self.config.negative_cache == config.negative_cache,
+            ),
+        ];
+        let changed: Vec<&'static str> = fixed
+            .into_iter()
+            .filter(|(_, unchanged)| !unchanged)
+            .map(|(name, _)| name)
+            .collect();
+        if !changed.is_empty() {
+            return Err(ConfigError::NotRuntimeMutable(changed).into());
+        }
+        for uri in std::iter::once(&config.uri).chain(&config.failover) {
+            check_base_uri(uri)?;
+            check_transport(uri, config.require_https)?;
+        }
+
+        let mut runtime = Runtime::new(config);
+        if runtime.config.rate_limit == self.config.rate_limit {
+            runtime.limiter = self.limiter.clone();
+        }
+        if runtime.config.identity_rate_limit == self.config.identity_rate_limit {
+            runtime.identity_limiter = self.identity_limiter.clone();
+        }
+        Ok(runtime)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{fixtures::IP_INFO_BODY, pipeline::RetryConfig, ApiAuth, Neutral};
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Body, Response, Server,
+    };
+    use std::{
+        convert::Infallible,
+        net::SocketAddr,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    /// Serve ip info responses, the first one after a delay and with a server error.
+    fn serve(hits: Arc<AtomicUsize>, fail_first: bool) -> SocketAddr {
+        let make_service = make_service_fn(move |_| {
+            let hits = hits.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |_| {
+                    let hit = hits.fetch_add(1, Ordering::SeqCst);
+                    async move {
+                        if fail_first && hit == 0 {
+                            tokio::time::sleep(Duration::from_millis(100)).await;
+                            return Ok::<_, Infallible>(
+                                Response::builder().status(503).body(Body::empty()).unwrap(),
+                            );
+                        }
+                        Ok(Response::new(Body::from(IP_INFO_BODY)))
+                    }
+                }))
+            }
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_reconfigure_uri_mid_stream() {
+        let (old_hits, new_hits) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let old_host = serve(old_hits.clone(), true);
+        let new_host = serve(new_hits.clone(), false);
+
+        let neutral = Neutral::builder(
+            "http://127.0.0.1",
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .retry(RetryConfig {
+            max_retries: 1,
+            backoff: Duration::from_millis(10),
+            ..RetryConfig::default()
+        })
+        .build()
+        .unwrap();
+        neutral
+            .reconfigure(|config| config.uri = format!("http://{}", old_host).parse().unwrap())
+            .unwrap();
+
+        let in_flight = tokio::spawn({
+            let neutral = neutral.clone();
+            async move {
+                neutral
+                    .ip_info()
+                    .send("203.0.113.100".parse().unwrap())
+                    .await
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(old_hits.load(Ordering::SeqCst), 1);
+
+        neutral
+            .reconfigure(|config| config.uri = format!("http://{}", new_host).parse().unwrap())
+            .unwrap();
+        assert_eq!(neutral.authority().unwrap().as_str(), new_host.to_string());
+        neutral
+            .ip_info()
+            .send("203.0.113.101".parse().unwrap())
+            .await
+            .unwrap();
+
+        in_flight.await.unwrap().unwrap();
+        assert_eq!(
+            old_hits.load(Ordering::SeqCst),
+            2,
+            "the retry keeps the old host"
+        );
+        assert_eq!(new_hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reconfigure_rejects_fixed_settings() {
+        let neutral = Neutral::try_new(
+            "https://neutrinoapi.net",
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+        let before = neutral.runtime().config.clone();
+
+        let result = neutral.reconfigure(|config| {
+            config.uri = "https://eu.neutrinoapi.net".parse().unwrap();
+            config.require_https = false;
+            config.cache = Some(CacheConfig::default());
+        });
+        match result {
+            Err(Error::Config(ConfigError::NotRuntimeMutable(fields))) => {
+                assert_eq!(fields, vec!["require_https", "cache"])
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(neutral.runtime().config, before);
+
+        neutral
+            .reconfigure(|config| {
+                config.rate_limit = Some(RateLimitConfig::default());
+            })
+            .unwrap();
+        assert!(neutral.runtime().limiter.is_some());
+    }
+}
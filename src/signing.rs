@@ -0,0 +1,224 @@
+//! # Signing module
+//! Emit headers in a fixed order and sign requests, for gateways computing a signature over a canonical request.
+//!
+//! With [NeutralBuilder::canonical_header_order](../builder/struct.NeutralBuilder.html#method.canonical_header_order), the headers set by this crate are emitted in this order:
+//!
+//! 1. `user-id`
+//! 2. `api-key`
+//! 3. `user-agent`
+//! 4. `x-request-id`
+//! 5. any other header, sorted by name
+//!
+//! A signer registered with [NeutralBuilder::sign_with](../builder/struct.NeutralBuilder.html#method.sign_with) receives the final [CanonicalRequest](./struct.CanonicalRequest.html) of every request and returns the value of the signature header, emitted last.
+//! Its [canonical string](./struct.CanonicalRequest.html#method.canonical_string) is:
+//!
+//! ```text
+//! <method>\n
+//! <uri>\n
+//! <name>:<value>\n      one line per header, in the order above
+//! <hex encoded sha256 of the body>
+//! ```
+
+use std::{fmt, sync::Arc};
+
+use http::{header::HeaderName, HeaderMap, HeaderValue, Method, Uri};
+use hyper::{Body, Request};
+
+use crate::{tls, Error};
+
+/// Compute the signature header value of a request.
+pub type SignFn = Arc<dyn Fn(&CanonicalRequest) -> HeaderValue + Send + Sync>;
+
+const LEADING_HEADERS: [&str; 4] = ["user-id", "api-key", "user-agent", "x-request-id"];
+
+/// The parts of a request covered by a signature.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CanonicalRequest {
+    pub method: Method,
+    /// The final uri of the request.
+    pub uri: Uri,
+    /// The headers, in emission order.
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+    /// Hex encoded sha256 of the body.
+    pub body_sha256: String,
+}
+
+impl CanonicalRequest {
+    /// Returns the string a gateway signature is computed over, see the [signing](./index.html) module.
+    pub fn canonical_string(&self) -> String {
+        let mut canonical = format!("{}\n{}\n", self.method, self.uri);
+        for (name, value) in &self.headers {
+            canonical.push_str(name.as_str());
+            canonical.push(':');
+            canonical.push_str(&String::from_utf8_lossy(value.as_bytes()));
+            canonical.push('\n');
+        }
+        canonical.push_str(&self.body_sha256);
+        canonical
+    }
+}
+
+/// How requests are canonicalized and signed.
+#[derive(Clone, Default)]
+pub(crate) struct Signing {
+    pub(crate) canonical_order: bool,
+    pub(crate) signer: Option<(HeaderName, SignFn)>,
+}
+
+impl fmt::Debug for Signing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Signing")
+            .field("canonical_order", &self.canonical_order)
+            .field("signer", &self.signer.as_ref().map(|(name, _)| name))
+            .finish()
+    }
+}
+
+/// Returns the headers in canonical order.
+pub(crate) fn canonical_headers(headers: &HeaderMap) -> Vec<(HeaderName, HeaderValue)> {
+    let mut ordered: Vec<_> = headers
+        .iter()
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    ordered.sort_by(|(a, _), (b, _)| {
+        let rank = |name: &HeaderName| {
+            LEADING_HEADERS
+                .iter()
+                .position(|leading| *leading == name.as_str())
+                .unwrap_or(LEADING_HEADERS.len())
+        };
+        rank(a)
+            .cmp(&rank(b))
+            .then_with(|| a.as_str().cmp(b.as_str()))
+    });
+    ordered
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+impl Signing {
+    /// Reorder the headers of `req` and add its signature, buffering its body to hash it.
+    pub(crate) async fn apply(&self, req: Request<Body>) -> Result<Request<Body>, Error> {
+        if !self.canonical_order && self.signer.is_none() {
+            return Ok(req);
+        }
+
+        let (mut parts, body) = req.into_parts();
+        let headers = canonical_headers(&parts.headers);
+        parts.headers.clear();
+        for (name, value) in &headers {
+            parts.headers.append(name, value.clone());
+        }
+
+        let body = hyper::body::to_bytes(body).await?;
+        if let Some((name, signer)) = &self.signer {
+            let canonical = CanonicalRequest {
+                method: parts.method.clone(),
+                uri: parts.uri.clone(),
+                headers,
+                body_sha256: hex(&tls::sha256(&body)),
+            };
+            parts.headers.insert(name, signer(&canonical));
+        }
+        Ok(Request::from_parts(parts, Body::from(body)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ApiAuth, CallContext, Neutral};
+    use mockito::{mock, Matcher};
+    use std::sync::Mutex;
+
+    const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    #[tokio::test]
+    async fn test_canonical_header_order_is_stable() {
+        let signing = Signing {
+            canonical_order: true,
+            signer: None,
+        };
+        let neutral = Neutral::try_new(
+            "https://neutrinoapi.net",
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+        let context = CallContext::new().with_correlation_id("4f6c1b2e");
+
+        for _ in 0..10 {
+            let req = neutral
+                .request_builder("/ip-info?ip=128.0.0.1".to_owned(), &context)
+                .unwrap()
+                .header("zz-custom", "z")
+                .header("accept", "application/json")
+                .header(http::header::USER_AGENT, "neutral")
+                .body(Body::empty())
+                .unwrap();
+            let req = signing.apply(req).await.unwrap();
+            let names: Vec<_> = req.headers().keys().map(HeaderName::as_str).collect();
+            let mut expected = vec!["user-id", "api-key", "user-agent", "x-request-id", "accept"];
+            if cfg!(feature = "gzip") {
+                expected.push("accept-encoding");
+            }
+            expected.push("zz-custom");
+            assert_eq!(names, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sign_with() {
+        let _m = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.50$".into()))
+            .match_header("x-signature", "signed")
+            .with_status(200)
+            .with_body(crate::fixtures::IP_INFO_BODY)
+            .expect(1)
+            .create();
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let neutral = Neutral::builder(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .sign_with(HeaderName::from_static("x-signature"), {
+            let seen = seen.clone();
+            move |canonical: &CanonicalRequest| {
+                seen.lock().unwrap().push(canonical.clone());
+                HeaderValue::from_static("signed")
+            }
+        })
+        .build()
+        .unwrap();
+
+        neutral
+            .ip_info()
+            .send("203.0.113.50".parse().unwrap())
+            .await
+            .unwrap();
+
+        let seen = seen.lock().unwrap();
+        let canonical = &seen[0];
+        assert_eq!(
+            canonical.uri.path_and_query().unwrap().as_str(),
+            "/ip-info?ip=203.0.113.50"
+        );
+        assert_eq!(canonical.body_sha256, EMPTY_SHA256);
+        assert_eq!(
+            canonical.canonical_string(),
+            format!(
+                "GET\n{}\nuser-id:User\napi-key:test\n{}{}",
+                canonical.uri,
+                if cfg!(feature = "gzip") {
+                    "accept-encoding:gzip, deflate\n"
+                } else {
+                    ""
+                },
+                EMPTY_SHA256
+            )
+        );
+        _m.assert();
+    }
+}
@@ -0,0 +1,340 @@
+//! # Sms message module
+//! Quoted from [neutrinoapi.com](https://www.neutrinoapi.com/api/sms-message):
+//!
+//! Send a free-form message to any mobile device via SMS.
+//!
+//! The message is sent form-encoded in the body of a POST request, so unicode, newlines and `&` reach neutrinoapi.com unchanged.
+//! Like [SmsVerify](../sms_verify/struct.SmsVerify.html), an SmsMessage call is one-shot: reusing or cloning it does not compile.
+//!
+//! ```compile_fail
+//! # use neutral::Neutral;
+//! # async fn run(neutral: Neutral) {
+//! let sms_message = neutral.sms_message();
+//! let first = sms_message.send("+33612345678".to_owned(), "Hello".to_owned(), None).await;
+//! let second = sms_message.send("+33612345678".to_owned(), "Hello".to_owned(), None).await;
+//! # }
+//! ```
+//!
+//! ```compile_fail
+//! # use neutral::Neutral;
+//! # async fn run(neutral: Neutral) {
+//! let sms_message = neutral.sms_message();
+//! let first = sms_message.clone().send("+33612345678".to_owned(), "Hello".to_owned(), None).await;
+//! # }
+//! ```
+
+use http::{header::CONTENT_TYPE, Method};
+use hyper::{body::Bytes, Body, Request};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::{
+    context::CallContext,
+    free_text::{self, TextPolicy},
+    meta::ResponseMeta,
+    params,
+    phone_number::{NumberClass, PhoneNumber, PhoneNumberError},
+    pipeline::Fetched,
+    Error, Neutral,
+};
+
+#[cfg(test)]
+use mockito;
+
+/// Maximum size in bytes of a message accepted by neutrinoapi.com.
+pub const SMS_MESSAGE_LIMIT: usize = 1600;
+
+/// Response of sms message neutrinoapi.com endpoint
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SmsMessageResponse {
+    #[serde(alias = "number_valid", alias = "is_number_valid")]
+    pub is_number_valid: bool,
+    /// True when the message was sent.
+    #[serde(alias = "sent", alias = "is_sent")]
+    pub is_sent: bool,
+}
+
+/// Parameters of an sms message call, e.g. to persist a pending call and send it later with [SmsMessage::send_params].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SmsMessageParams {
+    pub number: String,
+    pub message: String,
+    /// See [SmsMessage::send].
+    #[serde(default)]
+    pub country_code: Option<String>,
+}
+
+pub struct SmsMessage<'a> {
+    pub(crate) neutral: &'a Neutral,
+    pub(crate) context: CallContext,
+}
+
+impl<'a> SmsMessage<'a> {
+    /// Attach a [CallContext](../context/struct.CallContext.html) to the call.
+    pub fn context(mut self, context: CallContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Bound each attempt of the call by `timeout` instead of the client default, see [CallContext::timeout](../context/struct.CallContext.html#structfield.timeout).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.context.timeout = Some(timeout);
+        self
+    }
+
+    /// Send an sms message request to neutrinoapi.com, sending `message` to `number`.
+    ///
+    /// `country_code` is the ISO 3166-1 alpha-2 country code used to parse a national number, e.g. `FR`.
+    /// Short codes are rejected with [PhoneNumberError::Unsupported](../phone_number/enum.PhoneNumberError.html#variant.Unsupported)
+    /// and a message larger than [SMS_MESSAGE_LIMIT](./constant.SMS_MESSAGE_LIMIT.html) with [Error::InputTooLarge](../error/enum.Error.html#variant.InputTooLarge).
+    pub async fn send(
+        self,
+        number: String,
+        message: String,
+        country_code: Option<String>,
+    ) -> Result<SmsMessageResponse, Error> {
+        self.send_with_meta(number, message, country_code)
+            .await
+            .map(|(_, response)| response)
+    }
+
+    /// Same as [send](#method.send), with the phone number, message and country code of `params`.
+    pub async fn send_params(self, params: &SmsMessageParams) -> Result<SmsMessageResponse, Error> {
+        self.send(
+            params.number.clone(),
+            params.message.clone(),
+            params.country_code.clone(),
+        )
+        .await
+    }
+
+    /// Same as [send](#method.send), also returning the [ResponseMeta](../meta/struct.ResponseMeta.html) of the response.
+    pub async fn send_with_meta(
+        self,
+        number: String,
+        message: String,
+        country_code: Option<String>,
+    ) -> Result<(ResponseMeta, SmsMessageResponse), Error> {
+        let fetched = self.fetch(number, message, country_code).await?;
+        let response: SmsMessageResponse = self.neutral.decode("sms-message", &fetched.body)?;
+        Ok((fetched.meta, response))
+    }
+
+    /// Same as [send](#method.send), returning the raw body of the response.
+    pub async fn send_raw(
+        self,
+        number: String,
+        message: String,
+        country_code: Option<String>,
+    ) -> Result<Bytes, Error> {
+        Ok(self.fetch(number, message, country_code).await?.body)
+    }
+
+    /// Same as [send](#method.send), decoding the response into any `T`, e.g. a struct holding fields unknown to [SmsMessageResponse].
+    pub async fn send_as<T: DeserializeOwned>(
+        self,
+        number: String,
+        message: String,
+        country_code: Option<String>,
+    ) -> Result<T, Error> {
+        let fetched = self.fetch(number, message, country_code).await?;
+        self.neutral.decode("sms-message", &fetched.body)
+    }
+
+    /// Same as [send](#method.send), decoding the response into a [serde_json::Value].
+    pub async fn send_value(
+        self,
+        number: String,
+        message: String,
+        country_code: Option<String>,
+    ) -> Result<serde_json::Value, Error> {
+        self.send_as(number, message, country_code).await
+    }
+
+    /// Send the request, without decoding its response.
+    async fn fetch(
+        &self,
+        number: String,
+        message: String,
+        country_code: Option<String>,
+    ) -> Result<Fetched, Error> {
+        let number = PhoneNumber::parse(&number)?;
+        if number.class() == NumberClass::ShortCode {
+            return Err(PhoneNumberError::Unsupported(NumberClass::ShortCode).into());
+        }
+        let message = free_text::prepare(&message, SMS_MESSAGE_LIMIT, TextPolicy::Reject)?;
+
+        self.neutral
+            .execute("sms-message", None, &self.context, || {
+                build_request(
+                    self.neutral,
+                    &number,
+                    &message.text,
+                    country_code.as_deref(),
+                    &self.context,
+                )
+            })
+            .await
+    }
+}
+
+/// Build the sms message request of `number`, without sending it.
+pub(crate) fn build_request(
+    neutral: &Neutral,
+    number: &PhoneNumber,
+    message: &str,
+    country_code: Option<&str>,
+    context: &CallContext,
+) -> Result<Request<Body>, Error> {
+    let mut form = params();
+    form.append_pair("number", &number.to_string())
+        .append_pair("message", message);
+    if let Some(country_code) = country_code {
+        form.append_pair("country-code", country_code);
+    }
+
+    Ok(neutral
+        .request_builder("/sms-message".to_owned(), context)?
+        .method(Method::POST)
+        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from(form.finish()))?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ApiAuth;
+    use mockito::{mock, Matcher};
+
+    #[tokio::test]
+    async fn test_sms_message_build_request() {
+        struct TestingData {
+            pub name: String,
+            pub args: (&'static str, Option<&'static str>),
+            pub expected: &'static str,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Plain message".to_owned(),
+                args: ("hello", None),
+                expected: "number=%2B33612345678&message=hello",
+            },
+            TestingData {
+                name: "Reserved characters and newlines".to_owned(),
+                args: ("a&b=c\nd+e", Some("FR")),
+                expected: "number=%2B33612345678&message=a%26b%3Dc%0Ad%2Be&country-code=FR",
+            },
+            TestingData {
+                name: "Unicode".to_owned(),
+                args: ("café 🎉", None),
+                expected: "number=%2B33612345678&message=caf%C3%A9+%F0%9F%8E%89",
+            },
+        ];
+
+        let neutral = Neutral::try_new(
+            "https://neutrinoapi.net",
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+        let number = PhoneNumber::parse("+33612345678").unwrap();
+
+        for test in &tests {
+            let (message, country_code) = test.args;
+            let request = build_request(
+                &neutral,
+                &number,
+                message,
+                country_code,
+                &CallContext::default(),
+            )
+            .unwrap();
+            assert_eq!(request.method(), Method::POST, "{}", test.name);
+            assert_eq!(
+                request.uri().path_and_query().unwrap().as_str(),
+                "/sms-message",
+                "{}",
+                test.name
+            );
+            assert_eq!(
+                request.headers()[CONTENT_TYPE],
+                "application/x-www-form-urlencoded",
+                "{}",
+                test.name
+            );
+            let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+            assert_eq!(body, test.expected, "{}", test.name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sms_message_sends_an_emoji() {
+        let message = "Your order #42 & gift 🎁\nhas shipped";
+        let _m = mock("POST", "/sms-message")
+            .match_header("content-type", "application/x-www-form-urlencoded")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("number".into(), "+33698765432".into()),
+                Matcher::UrlEncoded("message".into(), message.into()),
+                Matcher::UrlEncoded("country-code".into(), "FR".into()),
+            ]))
+            .with_status(200)
+            .with_body(r#"{"number_valid": true, "sent": true}"#)
+            .expect(1)
+            .create();
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        let response = neutral
+            .sms_message()
+            .send(
+                "+33698765432".to_owned(),
+                message.to_owned(),
+                Some("FR".to_owned()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            response,
+            SmsMessageResponse {
+                is_number_valid: true,
+                is_sent: true,
+            }
+        );
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_sms_message_params() {
+        let params = SmsMessageParams {
+            number: "06 11 22 33 99".to_owned(),
+            message: "Your parcel ships today & arrives on 12/05".to_owned(),
+            country_code: Some("FR".to_owned()),
+        };
+        let json = serde_json::to_string(&params).unwrap();
+        assert_eq!(
+            serde_json::from_str::<SmsMessageParams>(&json).unwrap(),
+            params
+        );
+
+        let (neutral, requests) =
+            crate::fixtures::recording(r#"{"number_valid": true, "sent": true}"#);
+        neutral.sms_message().send_params(&params).await.unwrap();
+        neutral
+            .sms_message()
+            .send(
+                params.number.clone(),
+                params.message.clone(),
+                params.country_code.clone(),
+            )
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0], requests[1]);
+    }
+}
@@ -0,0 +1,338 @@
+//! # Sms verify module
+//! Quoted from [neutrinoapi.com](https://www.neutrinoapi.com/api/sms-verify):
+//!
+//! Send a unique security code to any mobile device via SMS.
+//!
+//! An SmsVerify call is one-shot: [send](./struct.SmsVerify.html#method.send) consumes it since every request sends a new SMS.
+//! Reusing it does not compile:
+//!
+//! ```compile_fail
+//! # use neutral::{sms_verify::SmsVerifyOptions, Neutral};
+//! # async fn run(neutral: Neutral) {
+//! let sms_verify = neutral.sms_verify();
+//! let first = sms_verify.send("+33612345678".to_owned(), SmsVerifyOptions::default()).await;
+//! let second = sms_verify.send("+33612345678".to_owned(), SmsVerifyOptions::default()).await;
+//! # }
+//! ```
+//!
+//! It is not `Clone` either, so it can't be copied before `send` to send the SMS twice:
+//!
+//! ```compile_fail
+//! # use neutral::{sms_verify::SmsVerifyOptions, Neutral};
+//! # async fn run(neutral: Neutral) {
+//! let sms_verify = neutral.sms_verify();
+//! let first = sms_verify.clone().send("+33612345678".to_owned(), SmsVerifyOptions::default()).await;
+//! # }
+//! ```
+
+use hyper::{body::Bytes, Body, Request};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::{
+    context::CallContext,
+    meta::ResponseMeta,
+    params,
+    phone_number::{NumberClass, PhoneNumber, PhoneNumberError},
+    pipeline::Fetched,
+    Error, Neutral,
+};
+
+#[cfg(test)]
+use mockito;
+
+/// Options of an sms verify call, every option left to `None` uses the neutrinoapi.com default.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SmsVerifyOptions {
+    /// Number of digits of the generated security code, between 4 and 12.
+    pub code_length: Option<u8>,
+    /// Pass your own security code instead of a generated one.
+    pub security_code: Option<String>,
+    /// ISO 3166-1 alpha-2 country code used to parse a national number, e.g. `FR`.
+    pub country_code: Option<String>,
+    /// Language of the message, e.g. `en`.
+    pub language_code: Option<String>,
+    /// Maximum number of messages to the same number per hour.
+    pub limit: Option<u32>,
+}
+
+/// Response of sms verify neutrinoapi.com endpoint
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SmsVerifyResponse {
+    /// The security code sent to the user.
+    pub security_code: String,
+    #[serde(alias = "number_valid", alias = "is_number_valid")]
+    pub is_number_valid: bool,
+    /// True when the message was sent.
+    #[serde(alias = "sent", alias = "is_sent")]
+    pub is_sent: bool,
+}
+
+/// Parameters of an sms verify call, e.g. to persist a pending call and send it later with [SmsVerify::send_params].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SmsVerifyParams {
+    pub number: String,
+    #[serde(flatten)]
+    pub options: SmsVerifyOptions,
+}
+
+pub struct SmsVerify<'a> {
+    pub(crate) neutral: &'a Neutral,
+    pub(crate) context: CallContext,
+}
+
+impl<'a> SmsVerify<'a> {
+    /// Attach a [CallContext](../context/struct.CallContext.html) to the call.
+    pub fn context(mut self, context: CallContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Bound each attempt of the call by `timeout` instead of the client default, see [CallContext::timeout](../context/struct.CallContext.html#structfield.timeout).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.context.timeout = Some(timeout);
+        self
+    }
+
+    /// Send an sms verify request to neutrinoapi.com, sending a security code to `phone_number`.
+    ///
+    /// Short codes are rejected with [PhoneNumberError::Unsupported](../phone_number/enum.PhoneNumberError.html#variant.Unsupported).
+    pub async fn send(
+        self,
+        phone_number: String,
+        options: SmsVerifyOptions,
+    ) -> Result<SmsVerifyResponse, Error> {
+        self.send_with_meta(phone_number, options)
+            .await
+            .map(|(_, response)| response)
+    }
+
+    /// Same as [send](#method.send), with the phone number and options of `params`.
+    pub async fn send_params(self, params: &SmsVerifyParams) -> Result<SmsVerifyResponse, Error> {
+        self.send(params.number.clone(), params.options.clone())
+            .await
+    }
+
+    /// Same as [send](#method.send), also returning the [ResponseMeta](../meta/struct.ResponseMeta.html) of the response.
+    pub async fn send_with_meta(
+        self,
+        phone_number: String,
+        options: SmsVerifyOptions,
+    ) -> Result<(ResponseMeta, SmsVerifyResponse), Error> {
+        let fetched = self.fetch(phone_number, options).await?;
+        let response: SmsVerifyResponse = self.neutral.decode("sms-verify", &fetched.body)?;
+        Ok((fetched.meta, response))
+    }
+
+    /// Same as [send](#method.send), returning the raw body of the response.
+    pub async fn send_raw(
+        self,
+        phone_number: String,
+        options: SmsVerifyOptions,
+    ) -> Result<Bytes, Error> {
+        Ok(self.fetch(phone_number, options).await?.body)
+    }
+
+    /// Same as [send](#method.send), decoding the response into any `T`, e.g. a struct holding fields unknown to [SmsVerifyResponse].
+    pub async fn send_as<T: DeserializeOwned>(
+        self,
+        phone_number: String,
+        options: SmsVerifyOptions,
+    ) -> Result<T, Error> {
+        let fetched = self.fetch(phone_number, options).await?;
+        self.neutral.decode("sms-verify", &fetched.body)
+    }
+
+    /// Same as [send](#method.send), decoding the response into a [serde_json::Value].
+    pub async fn send_value(
+        self,
+        phone_number: String,
+        options: SmsVerifyOptions,
+    ) -> Result<serde_json::Value, Error> {
+        self.send_as(phone_number, options).await
+    }
+
+    /// Send the request, without decoding its response.
+    async fn fetch(
+        &self,
+        phone_number: String,
+        options: SmsVerifyOptions,
+    ) -> Result<Fetched, Error> {
+        let phone_number = PhoneNumber::parse(&phone_number)?;
+        if phone_number.class() == NumberClass::ShortCode {
+            return Err(PhoneNumberError::Unsupported(NumberClass::ShortCode).into());
+        }
+
+        self.neutral
+            .execute("sms-verify", None, &self.context, || {
+                build_request(self.neutral, &phone_number, &options, &self.context)
+            })
+            .await
+    }
+}
+
+/// Build the sms verify request of `phone_number`, without sending it.
+pub(crate) fn build_request(
+    neutral: &Neutral,
+    phone_number: &PhoneNumber,
+    options: &SmsVerifyOptions,
+    context: &CallContext,
+) -> Result<Request<Body>, Error> {
+    let mut query = params();
+    query.append_pair("number", &phone_number.to_string());
+    if let Some(code_length) = options.code_length {
+        query.append_pair("code-length", &code_length.to_string());
+    }
+    if let Some(security_code) = &options.security_code {
+        query.append_pair("security-code", security_code);
+    }
+    if let Some(country_code) = &options.country_code {
+        query.append_pair("country-code", country_code);
+    }
+    if let Some(language_code) = &options.language_code {
+        query.append_pair("language-code", language_code);
+    }
+    if let Some(limit) = options.limit {
+        query.append_pair("limit", &limit.to_string());
+    }
+
+    neutral.params_request("/sms-verify", query.finish(), context)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ApiAuth;
+    use mockito::{mock, Matcher};
+
+    #[test]
+    fn test_sms_verify_build_request() {
+        struct TestingData {
+            pub name: String,
+            pub args: SmsVerifyOptions,
+            pub expected: &'static str,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Without options".to_owned(),
+                args: SmsVerifyOptions::default(),
+                expected: "/sms-verify?number=%2B33612345678",
+            },
+            TestingData {
+                name: "With every option".to_owned(),
+                args: SmsVerifyOptions {
+                    code_length: Some(8),
+                    security_code: Some("ab/12".to_owned()),
+                    country_code: Some("FR".to_owned()),
+                    language_code: Some("fr".to_owned()),
+                    limit: Some(5),
+                },
+                expected: "/sms-verify?number=%2B33612345678&code-length=8&security-code=ab%2F12&country-code=FR&language-code=fr&limit=5",
+            },
+        ];
+
+        let neutral = Neutral::try_new(
+            "https://neutrinoapi.net",
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+        let phone_number = PhoneNumber::parse("+33612345678").unwrap();
+
+        for test in &tests {
+            let request =
+                build_request(&neutral, &phone_number, &test.args, &CallContext::default())
+                    .unwrap();
+            assert_eq!(
+                request.uri().path_and_query().unwrap().as_str(),
+                test.expected,
+                "{}",
+                test.name
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sms_verify_encodes_the_security_code() {
+        let _m = mock("GET", "/sms-verify")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::Regex("number=%2B33698765432".into()),
+                Matcher::Regex(r"security-code=A%26B\+1%3D2".into()),
+                Matcher::UrlEncoded("security-code".into(), "A&B 1=2".into()),
+                Matcher::UrlEncoded("country-code".into(), "FR".into()),
+            ]))
+            .with_status(200)
+            .with_body(r#"{"security_code": "A&B 1=2", "number_valid": true, "sent": true}"#)
+            .expect(1)
+            .create();
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        let response = neutral
+            .sms_verify()
+            .send(
+                "+33698765432".to_owned(),
+                SmsVerifyOptions {
+                    security_code: Some("A&B 1=2".to_owned()),
+                    country_code: Some("FR".to_owned()),
+                    ..SmsVerifyOptions::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            response,
+            SmsVerifyResponse {
+                security_code: "A&B 1=2".to_owned(),
+                is_number_valid: true,
+                is_sent: true,
+            }
+        );
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_sms_verify_params() {
+        let params = SmsVerifyParams {
+            number: "+33611223399".to_owned(),
+            options: SmsVerifyOptions {
+                code_length: Some(6),
+                country_code: Some("FR".to_owned()),
+                language_code: Some("fr".to_owned()),
+                ..Default::default()
+            },
+        };
+        let json = serde_json::to_value(&params).unwrap();
+        assert_eq!(json["number"], "+33611223399");
+        assert_eq!(json["code_length"], 6);
+        assert_eq!(
+            serde_json::from_value::<SmsVerifyParams>(json).unwrap(),
+            params
+        );
+        assert_eq!(
+            serde_json::from_str::<SmsVerifyParams>(r#"{"number": "+33611223399"}"#)
+                .unwrap()
+                .options,
+            SmsVerifyOptions::default()
+        );
+
+        let (neutral, requests) = crate::fixtures::recording(
+            r#"{"security_code": "482913", "number_valid": true, "sent": true}"#,
+        );
+        neutral.sms_verify().send_params(&params).await.unwrap();
+        neutral
+            .sms_verify()
+            .send(params.number.clone(), params.options.clone())
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0], requests[1]);
+    }
+}
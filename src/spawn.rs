@@ -0,0 +1,125 @@
+//! # Spawn module
+//! Control how [Neutral](../struct.Neutral.html) spawns background tasks.
+//!
+//! Plain requests never spawn anything: they run on the task awaiting them.
+//! APIs running work in the background (prefetching, batches, concurrent pipelines) spawn their tasks through the [Spawner](./enum.Spawner.html) configured with [NeutralBuilder::spawner](../builder/struct.NeutralBuilder.html#method.spawner).
+//!
+//! * [Spawner::Tokio](./enum.Spawner.html#variant.Tokio), the default, requires to be called from within a tokio runtime with the `rt` and `time` features enabled.
+//!   Spawning outside of a runtime returns [Error::SpawnUnavailable](../error/enum.Error.html#variant.SpawnUnavailable) instead of panicking.
+//! * `Spawner::Local`, the default on wasm32 where tokio has no runtime, spawns on the event loop of the JavaScript host with `wasm_bindgen_futures::spawn_local`.
+//! * [Spawner::Deny](./enum.Spawner.html#variant.Deny) forbids spawning, every spawning API returns [Error::SpawnUnavailable](../error/enum.Error.html#variant.SpawnUnavailable).
+//!   Use it on runtimes where `tokio::spawn` is not available.
+//!
+//! Futures returned by the spawning APIs are `Send + 'static` so they can be spawned themselves.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::channel::oneshot;
+
+use crate::Error;
+
+/// Describe how background tasks are spawned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum Spawner {
+    /// Spawn tasks on the current tokio runtime.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[default]
+    Tokio,
+    /// Spawn tasks on the event loop of the JavaScript host.
+    #[cfg(target_arch = "wasm32")]
+    #[default]
+    Local,
+    /// Refuse to spawn any task.
+    Deny,
+}
+
+/// Wait for the output of a spawned task.
+///
+/// Resolves to `None` when the task was dropped before completion, e.g. if it panicked or the runtime shut down.
+#[derive(Debug)]
+pub struct SpawnHandle<T> {
+    receiver: oneshot::Receiver<T>,
+}
+
+impl<T> Future for SpawnHandle<T> {
+    type Output = Option<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.receiver)
+            .poll(cx)
+            .map(|output| output.ok())
+    }
+}
+
+impl Spawner {
+    /// Spawn `future` in the background.
+    pub(crate) fn spawn<F>(&self, future: F) -> Result<SpawnHandle<F::Output>, Error>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            Spawner::Tokio => {
+                let runtime =
+                    tokio::runtime::Handle::try_current().map_err(|_| Error::SpawnUnavailable)?;
+                let (sender, receiver) = oneshot::channel();
+                runtime.spawn(async move {
+                    let _ = sender.send(future.await);
+                });
+                Ok(SpawnHandle { receiver })
+            }
+            #[cfg(target_arch = "wasm32")]
+            Spawner::Local => {
+                let (sender, receiver) = oneshot::channel();
+                wasm_bindgen_futures::spawn_local(async move {
+                    let _ = sender.send(future.await);
+                });
+                Ok(SpawnHandle { receiver })
+            }
+            Spawner::Deny => Err(Error::SpawnUnavailable),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_tokio_spawner() {
+        let handle = Spawner::Tokio.spawn(async { 40 + 2 }).unwrap();
+        assert_eq!(handle.await, Some(42));
+    }
+
+    #[test]
+    fn test_tokio_spawner_outside_of_a_runtime() {
+        let handle = Spawner::Tokio.spawn(async { 42 });
+        assert!(matches!(handle, Err(Error::SpawnUnavailable)));
+    }
+
+    #[tokio::test]
+    async fn test_deny_spawner() {
+        let handle = Spawner::Deny.spawn(async { 42 });
+        assert!(matches!(handle, Err(Error::SpawnUnavailable)));
+    }
+
+    #[tokio::test]
+    async fn test_neutral_spawn_with_deny_spawner() {
+        let neutral = crate::Neutral::builder(
+            "http://localhost:1234",
+            crate::ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .spawner(Spawner::Deny)
+        .build()
+        .unwrap();
+
+        let handle = neutral.spawn(async { 42 });
+        assert!(matches!(handle, Err(Error::SpawnUnavailable)));
+    }
+}
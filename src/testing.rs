@@ -0,0 +1,343 @@
+//! # Testing module
+//! Test the code calling neutrinoapi.com against canned responses, without any server, with the `test-util` feature.
+//!
+//! [StubNeutrino](./struct.StubNeutrino.html) builds a real [Neutral](../struct.Neutral.html) client whose requests never leave the process:
+//! every call is answered with the response programmed for its endpoint and input, so the code under test uses it exactly like the real client,
+//! including through the traits of the [api](../api/index.html) module. A call without a programmed response fails with a 404
+//! [Error::Neutrino](../error/enum.Error.html#variant.Neutrino) naming the endpoint and the input.
+//!
+//! Inputs are matched once normalized like the endpoints send them: ip addresses are parsed and phone numbers are written in international format.
+//! The [fixtures](./fixtures/index.html) module provides a realistic response of every JSON endpoint to start from.
+//!
+//! ```ignore
+//! use neutral::testing::{fixtures, StubNeutrino};
+//!
+//! let mut ip_info_response = fixtures::ip_info();
+//! ip_info_response.country_code = "JP".to_owned();
+//! let neutral = StubNeutrino::new().ip_info_for("1.2.3.4", ip_info_response).build();
+//!
+//! assert_eq!(neutral.ip_info().send("1.2.3.4".parse()?).await?.country_code, "JP");
+//! ```
+
+use std::{collections::HashMap, convert::Infallible, net::IpAddr, sync::Arc};
+
+use http::{header::CONTENT_TYPE, Request, Response, StatusCode};
+use hyper::{body::Bytes, service::service_fn, Body};
+use neutral_types::{
+    hlr_lookup::HlrLookupResponse, ip_blocklist::IpBlocklistResponse, ip_info::IpInfoResponse,
+    ip_probe::IpProbeResponse, phone_validate::PhoneValidateResponse,
+};
+use serde::Serialize;
+
+use crate::{
+    bin_lookup::BinLookupResponse, email_verify::EmailVerifyResponse, endpoint::Endpoint,
+    metrics::endpoint_name, phone_number::PhoneNumber, phone_verify::PhoneVerifyResponse,
+    sms_message::SmsMessageResponse, sms_verify::SmsVerifyResponse, ua_lookup::UaLookupResponse,
+    verify_security_code::VerifySecurityCodeResponse, ApiAuth, Neutral,
+};
+
+pub mod fixtures;
+
+/// The fields of [HlrLookupResponse] serialized under another name than the one neutrinoapi.com sends.
+const HLR_LOOKUP_RENAMED: [(&str, &str); 3] = [
+    ("is_valid", "number_valid"),
+    ("is_hlr_valid", "hlr_valid"),
+    ("kind", "number_type"),
+];
+
+/// A programmed response.
+#[derive(Debug, Clone)]
+struct Stub {
+    status: StatusCode,
+    body: Bytes,
+}
+
+/// Program the responses of a stub [Neutral](../struct.Neutral.html) client, see the [testing](./index.html) module.
+#[derive(Debug, Clone, Default)]
+pub struct StubNeutrino {
+    stubs: HashMap<(Endpoint, String), Stub>,
+}
+
+impl StubNeutrino {
+    /// Create a stub answering no call.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer the ip info calls of `ip_addr` with `response`.
+    ///
+    /// Panics when `ip_addr` is not an ip address.
+    pub fn ip_info_for(self, ip_addr: &str, response: IpInfoResponse) -> Self {
+        self.respond(Endpoint::IpInfo, ip_addr, &response)
+    }
+
+    /// Answer the ip probe calls of `ip_addr` with `response`.
+    ///
+    /// Panics when `ip_addr` is not an ip address.
+    pub fn ip_probe_for(self, ip_addr: &str, response: IpProbeResponse) -> Self {
+        self.respond(Endpoint::IpProbe, ip_addr, &response)
+    }
+
+    /// Answer the ip blocklist calls of `ip_addr` with `response`.
+    ///
+    /// Panics when `ip_addr` is not an ip address.
+    pub fn ip_blocklist_for(self, ip_addr: &str, response: IpBlocklistResponse) -> Self {
+        self.respond(Endpoint::IpBlocklist, ip_addr, &response)
+    }
+
+    /// Answer the hlr lookup calls of `phone_number` with `response`.
+    ///
+    /// Panics when `phone_number` is not a phone number.
+    pub fn hlr_lookup_for(self, phone_number: &str, response: HlrLookupResponse) -> Self {
+        let mut response = serde_json::to_value(response).expect("responses serialize to JSON");
+        // The response serializes some fields under another name than the one neutrinoapi.com sends.
+        if let Some(fields) = response.as_object_mut() {
+            for (serialized, sent) in HLR_LOOKUP_RENAMED {
+                if let Some(value) = fields.remove(serialized) {
+                    fields.insert(sent.to_owned(), value);
+                }
+            }
+        }
+        self.respond(Endpoint::HlrLookup, phone_number, &response)
+    }
+
+    /// Answer the phone validate calls of `phone_number` with `response`.
+    ///
+    /// Panics when `phone_number` is not a phone number.
+    pub fn phone_validate_for(self, phone_number: &str, response: PhoneValidateResponse) -> Self {
+        self.respond(Endpoint::PhoneValidate, phone_number, &response)
+    }
+
+    /// Answer the phone verify calls of `phone_number` with `response`.
+    ///
+    /// Panics when `phone_number` is not a phone number.
+    pub fn phone_verify_for(self, phone_number: &str, response: PhoneVerifyResponse) -> Self {
+        self.respond(Endpoint::PhoneVerify, phone_number, &response)
+    }
+
+    /// Answer the sms verify calls of `phone_number` with `response`.
+    ///
+    /// Panics when `phone_number` is not a phone number.
+    pub fn sms_verify_for(self, phone_number: &str, response: SmsVerifyResponse) -> Self {
+        self.respond(Endpoint::SmsVerify, phone_number, &response)
+    }
+
+    /// Answer the sms message calls to `number` with `response`.
+    ///
+    /// Panics when `number` is not a phone number.
+    pub fn sms_message_for(self, number: &str, response: SmsMessageResponse) -> Self {
+        self.respond(Endpoint::SmsMessage, number, &response)
+    }
+
+    /// Answer the verify security code calls of `security_code` with `response`.
+    pub fn verify_security_code_for(
+        self,
+        security_code: &str,
+        response: VerifySecurityCodeResponse,
+    ) -> Self {
+        self.respond(Endpoint::VerifySecurityCode, security_code, &response)
+    }
+
+    /// Answer the email verify calls of `email` with `response`.
+    pub fn email_verify_for(self, email: &str, response: EmailVerifyResponse) -> Self {
+        self.respond(Endpoint::EmailVerify, email, &response)
+    }
+
+    /// Answer the bin lookup calls of `bin_number` with `response`.
+    pub fn bin_lookup_for(self, bin_number: &str, response: BinLookupResponse) -> Self {
+        self.respond(Endpoint::BinLookup, bin_number, &response)
+    }
+
+    /// Answer the ua lookup calls of `ua` with `response`.
+    pub fn ua_lookup_for(self, ua: &str, response: UaLookupResponse) -> Self {
+        self.respond(Endpoint::UaLookup, ua, &response)
+    }
+
+    /// Answer the calls of `endpoint` with `input` with an error response of `status`, carrying the neutrinoapi.com `api_error` code and message.
+    ///
+    /// Panics when `input` is not valid for `endpoint`, see the methods programming a response.
+    pub fn error_for(
+        mut self,
+        endpoint: Endpoint,
+        input: &str,
+        status: StatusCode,
+        api_error: u16,
+        api_error_msg: &str,
+    ) -> Self {
+        let body = serde_json::json!({ "api-error": api_error, "api-error-msg": api_error_msg });
+        self.stubs.insert(
+            (endpoint, normalize(endpoint, input)),
+            Stub {
+                status,
+                body: Bytes::from(body.to_string()),
+            },
+        );
+        self
+    }
+
+    /// Build the client answering the programmed calls.
+    pub fn build(self) -> Neutral {
+        let stubs = Arc::new(self.stubs);
+        let service = service_fn(move |req: Request<Body>| {
+            let stubs = stubs.clone();
+            async move { Ok::<_, Infallible>(answer(&stubs, req).await) }
+        });
+        Neutral::builder(
+            "https://neutrinoapi.net",
+            ApiAuth::new("stub".to_owned(), "stub".to_owned()),
+        )
+        .service(service)
+        .build()
+        .expect("a stub client always builds")
+    }
+
+    fn respond<T: Serialize>(mut self, endpoint: Endpoint, input: &str, response: &T) -> Self {
+        let body = serde_json::to_vec(response).expect("responses serialize to JSON");
+        self.stubs.insert(
+            (endpoint, normalize(endpoint, input)),
+            Stub {
+                status: StatusCode::OK,
+                body: Bytes::from(body),
+            },
+        );
+        self
+    }
+}
+
+/// Returns the name of the parameter holding the input of `endpoint`.
+fn input_param(endpoint: Endpoint) -> &'static str {
+    match endpoint {
+        Endpoint::IpInfo | Endpoint::IpProbe | Endpoint::IpBlocklist => "ip",
+        Endpoint::EmailVerify => "email",
+        Endpoint::BinLookup => "bin-number",
+        Endpoint::UaLookup => "ua",
+        Endpoint::VerifySecurityCode => "security-code",
+        _ => "number",
+    }
+}
+
+/// Returns `input` written like `endpoint` sends it.
+fn normalize(endpoint: Endpoint, input: &str) -> String {
+    match input_param(endpoint) {
+        "ip" => input
+            .parse::<IpAddr>()
+            .expect("the input is an ip address")
+            .to_string(),
+        "number" => PhoneNumber::parse(input)
+            .expect("the input is a phone number")
+            .to_string(),
+        _ => input.to_owned(),
+    }
+}
+
+/// Answer `req` with its programmed response, or with a 404 when there is none.
+async fn answer(stubs: &HashMap<(Endpoint, String), Stub>, req: Request<Body>) -> Response<Body> {
+    let (parts, body) = req.into_parts();
+    let endpoint = Endpoint::from_name(endpoint_name(parts.uri.path()));
+    let form = hyper::body::to_bytes(body).await.unwrap_or_default();
+    let query = parts.uri.query().unwrap_or_default().as_bytes();
+
+    let (status, body) = match endpoint {
+        Some(endpoint) => {
+            let input = form_urlencoded::parse(query)
+                .chain(form_urlencoded::parse(&form))
+                .find(|(name, _)| name == input_param(endpoint))
+                .map(|(_, value)| value.into_owned())
+                .unwrap_or_default();
+            match stubs.get(&(endpoint, input.clone())) {
+                Some(stub) => (stub.status, stub.body.clone()),
+                None => not_found(&format!(
+                    "no stub for {} {}={}",
+                    endpoint,
+                    input_param(endpoint),
+                    input
+                )),
+            }
+        }
+        None => not_found(&format!("no stub for {}", parts.uri.path())),
+    };
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+fn not_found(msg: &str) -> (StatusCode, Bytes) {
+    let body = serde_json::json!({ "api-error-msg": msg });
+    (StatusCode::NOT_FOUND, Bytes::from(body.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{api::HlrLookupApi, Error};
+
+    #[tokio::test]
+    async fn test_stub_ip_info() {
+        let mut response = fixtures::ip_info();
+        response.country_code = "JP".to_owned();
+        let neutral = StubNeutrino::new()
+            .ip_info_for("203.0.113.1", response.clone())
+            .build();
+
+        let ip_info_response = neutral
+            .ip_info()
+            .send("203.0.113.1".parse().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(ip_info_response, response);
+
+        let result = neutral.ip_info().send("203.0.113.2".parse().unwrap()).await;
+        match result {
+            Err(Error::Neutrino(err)) => {
+                assert_eq!(err.status_code, StatusCode::NOT_FOUND);
+                assert_eq!(
+                    err.api_error_msg.as_deref(),
+                    Some("no stub for ip-info ip=203.0.113.2")
+                );
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stub_hlr_lookup_through_the_trait() {
+        let response = fixtures::hlr_lookup();
+        let neutral = StubNeutrino::new()
+            .hlr_lookup_for("+33 6 12 34 56 78", response.clone())
+            .error_for(
+                Endpoint::HlrLookup,
+                "+33698765432",
+                StatusCode::BAD_REQUEST,
+                5,
+                "ACCESS DENIED",
+            )
+            .build();
+        let api: &dyn HlrLookupApi = &neutral;
+
+        let hlr_lookup_response = api.send("+33612345678".to_owned()).await.unwrap();
+        assert_eq!(hlr_lookup_response, response);
+
+        let result = api.send("+33698765432".to_owned()).await;
+        match result {
+            Err(Error::FeatureNotEnabled { endpoint, error }) => {
+                assert_eq!(endpoint, "hlr-lookup");
+                assert_eq!(error.status_code, StatusCode::BAD_REQUEST);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_fixtures_parse() {
+        fixtures::ip_info();
+        fixtures::ip_probe();
+        fixtures::ip_blocklist();
+        fixtures::hlr_lookup();
+        fixtures::phone_validate();
+        fixtures::email_verify();
+        fixtures::bin_lookup();
+        fixtures::ua_lookup();
+    }
+}
@@ -0,0 +1,272 @@
+//! Realistic responses of every JSON endpoint, to stub the calls without filling every field.
+//!
+//! Every function returns a new response, edit its fields to fit the test:
+//!
+//! ```ignore
+//! let mut ip_info_response = fixtures::ip_info();
+//! ip_info_response.country_code = "JP".to_owned();
+//! ```
+
+use neutral_types::{
+    hlr_lookup::HlrLookupResponse, ip_blocklist::IpBlocklistResponse, ip_info::IpInfoResponse,
+    ip_probe::IpProbeResponse, phone_validate::PhoneValidateResponse,
+};
+use serde::de::DeserializeOwned;
+
+use crate::{
+    bin_lookup::BinLookupResponse, email_verify::EmailVerifyResponse,
+    phone_verify::PhoneVerifyResponse, sms_message::SmsMessageResponse,
+    sms_verify::SmsVerifyResponse, ua_lookup::UaLookupResponse,
+    verify_security_code::VerifySecurityCodeResponse,
+};
+
+const IP_INFO: &str = r#"
+{
+    "ip": "203.0.113.10",
+    "valid": true,
+    "is_v6": false,
+    "is_v4_mapped": false,
+    "is_bogon": false,
+    "country": "France",
+    "country_code": "FR",
+    "country_code3": "FRA",
+    "continent_code": "EU",
+    "currency_code": "EUR",
+    "city": "Paris",
+    "region": "Ile-de-France",
+    "longitude": 2.3522,
+    "latitude": 48.8566,
+    "hostname": "",
+    "host_domain": "",
+    "timezone": {
+        "id": "Europe/Paris",
+        "name": "Central European Standard Time",
+        "abbr": "CET",
+        "date": "2024-01-15",
+        "time": "09:30:00",
+        "offset": "+01:00"
+    }
+}"#;
+
+const IP_PROBE: &str = r#"
+{
+    "ip": "203.0.113.10",
+    "valid": true,
+    "is_v6": false,
+    "is_v4_mapped": false,
+    "is_bogon": false,
+    "country": "France",
+    "country_code": "FR",
+    "country_code3": "FRA",
+    "continent_code": "EU",
+    "currency_code": "EUR",
+    "city": "Paris",
+    "region": "Ile-de-France",
+    "hostname": "",
+    "host_domain": "",
+    "provider_type": "isp",
+    "provider_domain": "example-isp.fr",
+    "provider_website": "https://www.example-isp.fr/",
+    "provider_description": "Example ISP",
+    "is_isp": true,
+    "is_hosting": false,
+    "is_proxy": false,
+    "is_vpn": false,
+    "vpn_domain": "",
+    "asn": "64500",
+    "as_cidr": "203.0.113.0/24",
+    "as_age": 12,
+    "as_country_code": "FR",
+    "as_country_code3": "FRA",
+    "as_description": "EXAMPLE-ISP-AS, FR",
+    "as_domains": ["example-isp.fr"]
+}"#;
+
+const IP_BLOCKLIST: &str = r#"
+{
+    "ip": "203.0.113.10",
+    "is_listed": false,
+    "last_seen": 0,
+    "list_count": 0,
+    "blocklists": [],
+    "sensors": [],
+    "is_proxy": false,
+    "is_tor": false,
+    "is_vpn": false,
+    "is_malware": false,
+    "is_spyware": false,
+    "is_dshield": false,
+    "is_hijacked": false,
+    "is_spider": false,
+    "is_bot": false,
+    "is_spam_bot": false,
+    "is_exploit_bot": false
+}"#;
+
+const HLR_LOOKUP: &str = r#"
+{
+    "number_valid": true,
+    "hlr_valid": true,
+    "hlr_status": "ok",
+    "number_type": "mobile",
+    "is_mobile": true,
+    "is_ported": false,
+    "is_roaming": false,
+    "international_calling_code": "33",
+    "international_number": "+33612345678",
+    "local_number": "06 12 34 56 78",
+    "location": "France",
+    "country": "France",
+    "country_code": "FR",
+    "country_code3": "FRA",
+    "currency_code": "EUR",
+    "current_network": "Orange",
+    "origin_network": "Orange",
+    "ported_network": "",
+    "roaming_country_code": "",
+    "imsi": "208011234567890",
+    "mcc": "208",
+    "mnc": "01",
+    "msc": "33689004000",
+    "msin": "1234567890"
+}"#;
+
+const PHONE_VALIDATE: &str = r#"
+{
+    "valid": true,
+    "type": "mobile",
+    "is_mobile": true,
+    "international_calling_code": "33",
+    "international_number": "+33612345678",
+    "local_number": "06 12 34 56 78",
+    "location": "France",
+    "country": "France",
+    "country_code": "FR",
+    "country_code3": "FRA",
+    "currency_code": "EUR",
+    "prefix_network": "Orange"
+}"#;
+
+const EMAIL_VERIFY: &str = r#"
+{
+    "valid": true,
+    "verified": true,
+    "email": "jane.doe@example.com",
+    "domain": "example.com",
+    "provider": "example.com",
+    "is_freemail": false,
+    "is_disposable": false,
+    "is_personal": true,
+    "is_catch_all": false,
+    "is_deferred": false,
+    "syntax_error": false,
+    "domain_error": false,
+    "typos_fixed": false,
+    "smtp_status": "ok",
+    "smtp_response": "250 2.1.5 Ok"
+}"#;
+
+const BIN_LOOKUP: &str = r#"
+{
+    "valid": true,
+    "card_brand": "VISA",
+    "card_type": "DEBIT",
+    "card_category": "CLASSIC",
+    "issuer": "EXAMPLE BANK",
+    "issuer_website": "https://bank.example",
+    "issuer_phone": "+33 1 23 45 67 89",
+    "country": "France",
+    "country_code": "FR",
+    "country_code3": "FRA",
+    "currency_code": "EUR"
+}"#;
+
+const UA_LOOKUP: &str = r#"
+{
+    "type": "desktop",
+    "browser_name": "Firefox",
+    "browser_version": "121.0",
+    "os_name": "Windows",
+    "os_version": "10",
+    "device_brand": "",
+    "device_model": "",
+    "is_mobile": false,
+    "is_android": false,
+    "is_ios": false
+}"#;
+
+fn parse<T: DeserializeOwned>(json: &str) -> T {
+    serde_json::from_str(json).expect("the fixtures match their response type")
+}
+
+/// An ip address of Paris, France, not listed in any blocklist.
+pub fn ip_info() -> IpInfoResponse {
+    parse(IP_INFO)
+}
+
+/// An ip address of a French ISP, neither a proxy, a VPN nor a hosting provider.
+pub fn ip_probe() -> IpProbeResponse {
+    parse(IP_PROBE)
+}
+
+/// An ip address listed in no blocklist.
+pub fn ip_blocklist() -> IpBlocklistResponse {
+    parse(IP_BLOCKLIST)
+}
+
+/// A reachable French mobile number, neither ported nor roaming.
+pub fn hlr_lookup() -> HlrLookupResponse {
+    parse(HLR_LOOKUP)
+}
+
+/// A valid French mobile number.
+pub fn phone_validate() -> PhoneValidateResponse {
+    parse(PHONE_VALIDATE)
+}
+
+/// A verified personal email address.
+pub fn email_verify() -> EmailVerifyResponse {
+    parse(EMAIL_VERIFY)
+}
+
+/// A French VISA debit card.
+pub fn bin_lookup() -> BinLookupResponse {
+    parse(BIN_LOOKUP)
+}
+
+/// Firefox on a Windows desktop.
+pub fn ua_lookup() -> UaLookupResponse {
+    parse(UA_LOOKUP)
+}
+
+/// A call placed to a valid number, playing the code `123456`.
+pub fn phone_verify() -> PhoneVerifyResponse {
+    PhoneVerifyResponse {
+        security_code: "123456".to_owned(),
+        is_number_valid: true,
+        is_calling: true,
+        calling_code: "33".to_owned(),
+    }
+}
+
+/// A message sent to a valid number, holding the code `123456`.
+pub fn sms_verify() -> SmsVerifyResponse {
+    SmsVerifyResponse {
+        security_code: "123456".to_owned(),
+        is_number_valid: true,
+        is_sent: true,
+    }
+}
+
+/// A message sent to a valid number.
+pub fn sms_message() -> SmsMessageResponse {
+    SmsMessageResponse {
+        is_number_valid: true,
+        is_sent: true,
+    }
+}
+
+/// A verified security code.
+pub fn verify_security_code() -> VerifySecurityCodeResponse {
+    VerifySecurityCodeResponse { is_verified: true }
+}
@@ -0,0 +1,114 @@
+//! # Time module
+//! Clocks and timers of the client: the ones of std and tokio on native targets, the ones of the JavaScript host on wasm32.
+//!
+//! `std::time::Instant::now` and `SystemTime::now` panic on `wasm32-unknown-unknown` and tokio's timers need a tokio runtime,
+//! so the client reads the time and waits through this module only.
+
+use std::{future::Future, time::Duration, time::SystemTime};
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use std::time::Instant;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use tokio::time::error::Elapsed;
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) use web_time::Instant;
+
+/// Returns the current time.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn now() -> SystemTime {
+    SystemTime::now()
+}
+
+/// Returns the current time, read from the clock of the JavaScript host.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn now() -> SystemTime {
+    let since_epoch = web_time::SystemTime::now()
+        .duration_since(web_time::UNIX_EPOCH)
+        .unwrap_or_default();
+    SystemTime::UNIX_EPOCH + since_epoch
+}
+
+/// Wait until `duration` has elapsed.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await
+}
+
+/// Wait until `duration` has elapsed, with a `setTimeout` of the JavaScript host.
+///
+/// The timer outlives the future when it is dropped early, it then fires without effect.
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn sleep(duration: Duration) {
+    let _ = timer(duration).await;
+}
+
+/// Returns a receiver notified once `duration` has elapsed, holding no JavaScript value so its future stays `Send`.
+#[cfg(target_arch = "wasm32")]
+fn timer(duration: Duration) -> futures::channel::oneshot::Receiver<()> {
+    use wasm_bindgen::{closure::Closure, JsValue};
+
+    #[wasm_bindgen::prelude::wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_name = setTimeout)]
+        fn set_timeout(handler: &JsValue, timeout: i32) -> JsValue;
+    }
+
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    let handler = Closure::once_into_js(move || {
+        let _ = sender.send(());
+    });
+    set_timeout(
+        &handler,
+        i32::try_from(duration.as_millis()).unwrap_or(i32::MAX),
+    );
+    receiver
+}
+
+/// Await `future`, failing with [Elapsed] once `deadline` is passed.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn timeout_at<F: Future>(
+    deadline: Instant,
+    future: F,
+) -> Result<F::Output, Elapsed> {
+    tokio::time::timeout_at(deadline.into(), future).await
+}
+
+/// Await `future`, failing with [Elapsed] once `deadline` is passed.
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn timeout_at<F: Future>(
+    deadline: Instant,
+    future: F,
+) -> Result<F::Output, Elapsed> {
+    use futures::future::{select, Either};
+
+    let sleep = sleep(deadline.saturating_duration_since(Instant::now()));
+    futures::pin_mut!(future, sleep);
+    match select(future, sleep).await {
+        Either::Left((output, _)) => Ok(output),
+        Either::Right(_) => Err(Elapsed(())),
+    }
+}
+
+/// Await `future`, failing with [Elapsed] once `duration` has elapsed.
+pub(crate) async fn timeout<F: Future>(
+    duration: Duration,
+    future: F,
+) -> Result<F::Output, Elapsed> {
+    timeout_at(Instant::now() + duration, future).await
+}
+
+/// Error returned once the deadline of a call is passed, the wasm32 counterpart of tokio's `Elapsed`.
+#[cfg(target_arch = "wasm32")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed(());
+
+#[cfg(target_arch = "wasm32")]
+impl std::fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("deadline has elapsed")
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl std::error::Error for Elapsed {}
@@ -0,0 +1,125 @@
+//! # Tls module
+//! Select the TLS backend of the client, with exactly one of these cargo features:
+//!
+//! * `native-tls`, enabled by default: the TLS library of the platform, OpenSSL on Linux.
+//! * `rustls`: a pure rust implementation, trusting the root certificates of the platform. Build with `default-features = false` to drop OpenSSL.
+//!
+//! On `wasm32-unknown-unknown`, neither backend is enabled: build with `default-features = false` and the `wasm` feature,
+//! the requests are then sent with the `fetch` of the JavaScript host, which handles TLS and the connections, see the [platform support](../index.html#platform-support).
+//!
+//! The HTTP protocol is chosen with [NeutralBuilder::http1_only](../builder/struct.NeutralBuilder.html#method.http1_only) and [NeutralBuilder::http2_only](../builder/struct.NeutralBuilder.html#method.http2_only).
+//! By default it is adaptive: with `rustls`, HTTP/2 is offered through ALPN and used when the server accepts it, so concurrent calls share a single connection.
+//! The `native-tls` backend cannot report the negotiated protocol: adaptive then means HTTP/1.1, and HTTP/2 only is spoken with prior knowledge.
+//! Plain http to a loopback address always starts with HTTP/1.1 unless HTTP/2 is required.
+
+#[cfg(all(feature = "native-tls", feature = "rustls"))]
+compile_error!("the `native-tls` and `rustls` features are mutually exclusive, disable the default features to use `rustls`");
+
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    not(any(feature = "native-tls", feature = "rustls"))
+))]
+compile_error!("enable one of the `native-tls` or `rustls` features to select a TLS backend");
+
+#[cfg(all(
+    target_arch = "wasm32",
+    any(feature = "native-tls", feature = "rustls")
+))]
+compile_error!("the TLS backends are not available on wasm32, disable the default features and enable the `wasm` feature");
+
+#[cfg(all(target_arch = "wasm32", not(feature = "wasm")))]
+compile_error!("enable the `wasm` feature to target wasm32");
+
+#[cfg(all(feature = "wasm", not(target_arch = "wasm32")))]
+compile_error!("the `wasm` feature only targets wasm32, enable one of the `native-tls` or `rustls` features instead");
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::proxy::ProxyConnector;
+
+#[cfg(all(feature = "native-tls", not(target_arch = "wasm32")))]
+pub(crate) type HttpsConnector = hyper_tls::HttpsConnector<ProxyConnector>;
+
+#[cfg(all(
+    feature = "rustls",
+    not(feature = "native-tls"),
+    not(target_arch = "wasm32")
+))]
+pub(crate) type HttpsConnector = hyper_rustls::HttpsConnector<ProxyConnector>;
+
+/// The HTTP protocol spoken to neutrinoapi.com.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum HttpProtocol {
+    /// HTTP/2 when negotiated with ALPN, HTTP/1.1 otherwise.
+    #[default]
+    Adaptive,
+    Http1Only,
+    /// HTTP/2 only, with prior knowledge over plain http.
+    Http2Only,
+}
+
+/// Returns a connector wrapping `connector` and refusing plain http when `https_only`.
+///
+/// The protocol is not negotiated, only [HttpProtocol::Http2Only] changes the protocol, on the client.
+#[cfg(all(feature = "native-tls", not(target_arch = "wasm32")))]
+pub(crate) fn connector(
+    https_only: bool,
+    connector: ProxyConnector,
+    _protocol: HttpProtocol,
+) -> HttpsConnector {
+    let mut https = hyper_tls::HttpsConnector::new_with_connector(connector);
+    https.https_only(https_only);
+    https
+}
+
+/// Returns a connector wrapping `connector` and refusing plain http when `https_only`, offering `protocol` through ALPN.
+#[cfg(all(
+    feature = "rustls",
+    not(feature = "native-tls"),
+    not(target_arch = "wasm32")
+))]
+pub(crate) fn connector(
+    https_only: bool,
+    connector: ProxyConnector,
+    protocol: HttpProtocol,
+) -> HttpsConnector {
+    let builder = hyper_rustls::HttpsConnectorBuilder::new().with_native_roots();
+    let builder = if https_only {
+        builder.https_only()
+    } else {
+        builder.https_or_http()
+    };
+    match protocol {
+        HttpProtocol::Adaptive => builder
+            .enable_http1()
+            .enable_http2()
+            .wrap_connector(connector),
+        HttpProtocol::Http1Only => builder.enable_http1().wrap_connector(connector),
+        HttpProtocol::Http2Only => builder.enable_http2().wrap_connector(connector),
+    }
+}
+
+/// Returns the sha256 digest of `bytes`.
+#[cfg(all(feature = "native-tls", not(target_arch = "wasm32")))]
+pub(crate) fn sha256(bytes: &[u8]) -> [u8; 32] {
+    openssl::sha::sha256(bytes)
+}
+
+/// Returns the sha256 digest of `bytes`.
+#[cfg(all(
+    feature = "rustls",
+    not(feature = "native-tls"),
+    not(target_arch = "wasm32")
+))]
+pub(crate) fn sha256(bytes: &[u8]) -> [u8; 32] {
+    let digest = ring::digest::digest(&ring::digest::SHA256, bytes);
+    digest.as_ref().try_into().unwrap()
+}
+
+/// Returns the sha256 digest of `bytes`.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn sha256(bytes: &[u8]) -> [u8; 32] {
+    use sha2::Digest;
+
+    sha2::Sha256::digest(bytes).into()
+}
@@ -0,0 +1,482 @@
+//! Send requests with any hyper client, whatever its connector, or with any tower service.
+//!
+//! With the `reqwest-backend` feature, the requests can also be sent with a reqwest client, see [Neutral::from_reqwest](../struct.Neutral.html#method.from_reqwest).
+//! On wasm32, the requests are sent with the `fetch` of the JavaScript host unless a tower service is given.
+//! Whatever the transport, its responses go through the same status code handling and decoding.
+
+use std::{fmt, future::Future, pin::Pin, sync::Arc};
+
+#[cfg(not(target_arch = "wasm32"))]
+use hyper::{client::connect::Connect, Client};
+use hyper::{service::Service, Body, Request, Response};
+
+use crate::{error::BoxError, time::Elapsed, Error};
+
+pub(crate) type ResponseFuture =
+    Pin<Box<dyn Future<Output = Result<Response<Body>, Error>> + Send>>;
+
+pub(crate) trait SendRequest: Send + Sync {
+    fn send(&self, req: Request<Body>) -> ResponseFuture;
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<C> SendRequest for Client<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    fn send(&self, req: Request<Body>) -> ResponseFuture {
+        let response = self.request(req);
+        Box::pin(async move { Ok(response.await?) })
+    }
+}
+
+/// A tower service, cloned for every request as its `call` needs exclusive access.
+struct ServiceTransport<S>(S);
+
+impl<S> SendRequest for ServiceTransport<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>> + Clone + Send + Sync + 'static,
+    S::Future: Send,
+    S::Error: Into<BoxError>,
+{
+    fn send(&self, req: Request<Body>) -> ResponseFuture {
+        let mut service = self.0.clone();
+        Box::pin(async move {
+            futures::future::poll_fn(|cx| service.poll_ready(cx))
+                .await
+                .map_err(service_error)?;
+            service.call(req).await.map_err(service_error)
+        })
+    }
+}
+
+/// Returns the error of a tower service, keeping the hyper errors and the timeouts so they are retried like the ones of a client.
+fn service_error(err: impl Into<BoxError>) -> Error {
+    let err = match err.into().downcast::<hyper::Error>() {
+        Ok(err) => return Error::Hyper(*err),
+        Err(err) => err,
+    };
+    match err.downcast::<Elapsed>() {
+        Ok(err) => Error::Timeout(*err),
+        Err(err) => Error::Service(err),
+    }
+}
+
+/// Sends the requests with a reqwest client, streaming its response bodies so the size limit still applies while reading them.
+#[cfg(feature = "reqwest-backend")]
+impl SendRequest for reqwest::Client {
+    fn send(&self, req: Request<Body>) -> ResponseFuture {
+        let client = self.clone();
+        Box::pin(async move {
+            let (parts, body) = req.into_parts();
+            let body = hyper::body::to_bytes(body).await?;
+            let req = reqwest::Request::try_from(Request::from_parts(parts, body))
+                .map_err(reqwest_error)?;
+            let res = client.execute(req).await.map_err(reqwest_error)?;
+            let mut response = Response::builder()
+                .status(res.status())
+                .version(res.version());
+            if let Some(headers) = response.headers_mut() {
+                *headers = res.headers().clone();
+            }
+            Ok(response.body(Body::wrap_stream(res.bytes_stream()))?)
+        })
+    }
+}
+
+/// Returns the error of a reqwest client as the error of a service, see [is_reqwest_retryable].
+#[cfg(feature = "reqwest-backend")]
+fn reqwest_error(err: reqwest::Error) -> Error {
+    Error::Service(Box::new(err))
+}
+
+/// Returns true when `err`, the error of a service, is a reqwest connection failure: the request was never received.
+#[cfg(feature = "reqwest-backend")]
+pub(crate) fn is_reqwest_connect(err: &BoxError) -> bool {
+    err.downcast_ref::<reqwest::Error>()
+        .is_some_and(|err| err.is_connect())
+}
+
+/// Returns true when `err`, the error of a service, is a reqwest error retried like a hyper error:
+/// a connection failure, a timeout, or a connection closed before the response.
+#[cfg(feature = "reqwest-backend")]
+pub(crate) fn is_reqwest_retryable(err: &BoxError) -> bool {
+    err.downcast_ref::<reqwest::Error>().is_some_and(|err| {
+        err.is_connect()
+            || err.is_timeout()
+            || std::error::Error::source(err)
+                .and_then(|source| source.downcast_ref::<hyper::Error>())
+                .is_some_and(|source| {
+                    source.is_incomplete_message() || crate::error::is_connection_reset(source)
+                })
+    })
+}
+
+/// Sends the requests with the `fetch` of the JavaScript host, the default transport on wasm32.
+///
+/// The JavaScript futures are not `Send`: each request runs on the event loop of the host and its response comes back through a channel.
+#[cfg(target_arch = "wasm32")]
+struct Fetch;
+
+#[cfg(target_arch = "wasm32")]
+impl SendRequest for Fetch {
+    fn send(&self, req: Request<Body>) -> ResponseFuture {
+        let (sender, receiver) = futures::channel::oneshot::channel();
+        wasm_bindgen_futures::spawn_local(async move {
+            let _ = sender.send(fetch(req).await);
+        });
+        Box::pin(async move {
+            receiver
+                .await
+                .map_err(|_| Error::Service("the request was dropped by the host".into()))?
+        })
+    }
+}
+
+/// Send `req` with `fetch`, leaving the redirects to the crate, see [NeutralBuilder::max_redirects](../builder/struct.NeutralBuilder.html#method.max_redirects).
+///
+/// The body of the response is received before it is returned.
+#[cfg(target_arch = "wasm32")]
+async fn fetch(req: Request<Body>) -> Result<Response<Body>, Error> {
+    use http::StatusCode;
+    use js_sys::{Array, Uint8Array};
+    use wasm_bindgen::JsCast;
+    use wasm_bindgen_futures::JsFuture;
+
+    #[wasm_bindgen::prelude::wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_name = fetch)]
+        fn fetch_with_request(request: &web_sys::Request) -> js_sys::Promise;
+    }
+
+    let (parts, body) = req.into_parts();
+    let body = hyper::body::to_bytes(body).await?;
+    let headers = web_sys::Headers::new().map_err(FetchError::from_js)?;
+    for (name, value) in &parts.headers {
+        let value = value.to_str().map_err(|err| Error::Service(err.into()))?;
+        headers
+            .append(name.as_str(), value)
+            .map_err(FetchError::from_js)?;
+    }
+    let init = web_sys::RequestInit::new();
+    init.set_method(parts.method.as_str());
+    init.set_headers(&headers);
+    init.set_redirect(web_sys::RequestRedirect::Manual);
+    if !body.is_empty() {
+        init.set_body(&Uint8Array::from(&body[..]));
+    }
+    let request = web_sys::Request::new_with_str_and_init(&parts.uri.to_string(), &init)
+        .map_err(FetchError::from_js)?;
+
+    let response: web_sys::Response = JsFuture::from(fetch_with_request(&request))
+        .await
+        .map_err(FetchError::from_js)?
+        .unchecked_into();
+    // A browser hides the redirects of a manual fetch behind an opaque response of status 0.
+    let status = StatusCode::from_u16(response.status())
+        .map_err(|_| Error::Service("the response was hidden by the host".into()))?;
+    let mut builder = Response::builder().status(status);
+    if let Some(entries) = js_sys::try_iter(&response.headers()).map_err(FetchError::from_js)? {
+        for entry in entries {
+            let entry: Array = entry.map_err(FetchError::from_js)?.unchecked_into();
+            if let (Some(name), Some(value)) = (entry.get(0).as_string(), entry.get(1).as_string())
+            {
+                builder = builder.header(name, value);
+            }
+        }
+    }
+    let buffer = response.array_buffer().map_err(FetchError::from_js)?;
+    let buffer = JsFuture::from(buffer).await.map_err(FetchError::from_js)?;
+    Ok(builder.body(Body::from(Uint8Array::new(&buffer).to_vec()))?)
+}
+
+/// The `fetch` of the JavaScript host failed, e.g. on a network error or a request blocked by the host.
+///
+/// It is returned as [Error::Service](../error/enum.Error.html#variant.Service) and retried like a connection failure.
+#[cfg(target_arch = "wasm32")]
+#[derive(Debug)]
+pub(crate) struct FetchError(String);
+
+#[cfg(target_arch = "wasm32")]
+impl FetchError {
+    fn from_js(value: wasm_bindgen::JsValue) -> Error {
+        use wasm_bindgen::JsCast;
+
+        let message = match value.dyn_ref::<js_sys::Error>() {
+            Some(err) => String::from(err.message()),
+            None => format!("{:?}", value),
+        };
+        Error::Service(Box::new(FetchError(message)))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "fetch failed: {}", self.0)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl std::error::Error for FetchError {}
+
+/// A hyper client or a tower service with its type erased.
+#[derive(Clone)]
+pub(crate) struct Transport(Arc<dyn SendRequest>);
+
+impl Transport {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn new<C>(client: Client<C>) -> Self
+    where
+        C: Connect + Clone + Send + Sync + 'static,
+    {
+        Transport(Arc::new(client))
+    }
+
+    /// Send the requests with the `fetch` of the JavaScript host.
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) fn fetch() -> Self {
+        Transport(Arc::new(Fetch))
+    }
+
+    pub(crate) fn from_service<S>(service: S) -> Self
+    where
+        S: Service<Request<Body>, Response = Response<Body>> + Clone + Send + Sync + 'static,
+        S::Future: Send,
+        S::Error: Into<BoxError>,
+    {
+        Transport(Arc::new(ServiceTransport(service)))
+    }
+
+    /// Send the requests with `send`, e.g. a layer wrapping another transport.
+    #[cfg(feature = "record-replay")]
+    pub(crate) fn from_send<T: SendRequest + 'static>(send: T) -> Self {
+        Transport(Arc::new(send))
+    }
+
+    /// Send the requests with a reqwest `client`, see [Neutral::from_reqwest](../struct.Neutral.html#method.from_reqwest).
+    #[cfg(feature = "reqwest-backend")]
+    pub(crate) fn from_reqwest(client: reqwest::Client) -> Self {
+        Transport(Arc::new(client))
+    }
+
+    pub(crate) fn request(&self, req: Request<Body>) -> ResponseFuture {
+        self.0.send(req)
+    }
+}
+
+impl fmt::Debug for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Transport").finish()
+    }
+}
+
+#[cfg(all(test, feature = "reqwest-backend"))]
+mod test {
+    use super::*;
+    use crate::{
+        fixtures::{
+            HLR_LOOKUP_BODY, IP_BLOCKLIST_BODY, IP_INFO_BODY, IP_PROBE_BODY, PHONE_VALIDATE_BODY,
+        },
+        ApiAuth, Neutral,
+    };
+    use mockito::{mock, Matcher};
+    use std::time::Duration;
+
+    /// Returns clients of `uri` sending their requests with hyper and with reqwest, configured alike.
+    fn backends(uri: &str) -> Vec<(&'static str, Neutral)> {
+        let auth = ApiAuth::new("User".to_string(), "test".to_string());
+        let reqwest = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap();
+        vec![
+            (
+                "hyper",
+                Neutral::builder(uri, auth.clone())
+                    .max_response_size(8192)
+                    .build()
+                    .unwrap(),
+            ),
+            (
+                "reqwest",
+                Neutral::builder(uri, auth)
+                    .max_response_size(8192)
+                    .reqwest(reqwest)
+                    .build()
+                    .unwrap(),
+            ),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_backends_handle_responses_alike() {
+        struct TestingData {
+            pub name: String,
+            pub args: (
+                &'static str,
+                usize,
+                Vec<(&'static str, &'static str)>,
+                String,
+            ),
+            pub expected: fn(&Result<crate::ip_info::IpInfoResponse, Error>) -> bool,
+        }
+
+        let json = vec![("content-type", "application/json")];
+        let tests = vec![
+            TestingData {
+                name: "Using a successful response".to_owned(),
+                args: ("203.0.113.11", 200, json.clone(), IP_INFO_BODY.to_owned()),
+                expected: |result| result.is_ok(),
+            },
+            TestingData {
+                name: "Using rejected credentials".to_owned(),
+                args: (
+                    "203.0.113.12",
+                    401,
+                    json.clone(),
+                    r#"{"api-error": 1, "api-error-msg": "ACCESS DENIED"}"#.to_owned(),
+                ),
+                expected: |result| matches!(result, Err(Error::Unauthorized(_))),
+            },
+            TestingData {
+                name: "Using a throttled call".to_owned(),
+                args: (
+                    "203.0.113.13",
+                    429,
+                    vec![("content-type", "application/json"), ("retry-after", "120")],
+                    r#"{"api-error": 2, "api-error-msg": "TOO MANY REQUESTS"}"#.to_owned(),
+                ),
+                expected: |result| matches!(result, Err(Error::RateLimited { retry_after, .. }) if *retry_after == Some(Duration::from_secs(120))),
+            },
+            TestingData {
+                name: "Using a server error".to_owned(),
+                args: ("203.0.113.14", 503, json.clone(), "unavailable".to_owned()),
+                expected: |result| matches!(result, Err(Error::ServerError(_))),
+            },
+            TestingData {
+                name: "Using the HTML page of a gateway".to_owned(),
+                args: (
+                    "203.0.113.15",
+                    200,
+                    vec![("content-type", "text/html")],
+                    "<html>maintenance</html>".to_owned(),
+                ),
+                expected: |result| matches!(result, Err(Error::UnexpectedResponse { .. })),
+            },
+            TestingData {
+                name: "Using a malformed body".to_owned(),
+                args: ("203.0.113.16", 200, json.clone(), r#"{"ip": 1}"#.to_owned()),
+                expected: |result| matches!(result, Err(Error::Decode { endpoint, .. }) if *endpoint == "ip-info"),
+            },
+            TestingData {
+                name: "Using a body larger than the limit".to_owned(),
+                args: ("203.0.113.17", 200, json.clone(), " ".repeat(16384)),
+                expected: |result| matches!(result, Err(Error::BodyTooLarge { limit, .. }) if *limit == 8192),
+            },
+            TestingData {
+                name: "Using a redirect which is not followed".to_owned(),
+                args: (
+                    "203.0.113.18",
+                    302,
+                    vec![("location", "https://neutrinoapi.net/ip-info")],
+                    String::new(),
+                ),
+                expected: |result| matches!(result, Err(Error::Neutrino(err)) if err.status_code == 302),
+            },
+        ];
+
+        for test in &tests {
+            let (ip, status, headers, body) = &test.args;
+            let _m = headers
+                .iter()
+                .fold(
+                    mock("GET", "/ip-info")
+                        .match_query(Matcher::Regex(format!("ip={}", ip)))
+                        .with_status(*status),
+                    |mock, (name, value)| mock.with_header(name, value),
+                )
+                .with_body(body)
+                .expect(2)
+                .create();
+
+            let mut results = Vec::new();
+            for (backend, neutral) in backends(&mockito::server_url()) {
+                let result = neutral.ip_info().send(ip.parse().unwrap()).await;
+                assert!(
+                    (test.expected)(&result),
+                    "{} with {}: {:?}",
+                    test.name,
+                    backend,
+                    result
+                );
+                results.push(format!("{:?}", result));
+            }
+            assert_eq!(results[0], results[1], "{}", test.name);
+            _m.assert();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_backends_decode_every_endpoint_alike() {
+        let _mocks = [
+            ("/ip-info", "ip=203.0.113.24", IP_INFO_BODY),
+            ("/ip-probe", "ip=203.0.113.24", IP_PROBE_BODY),
+            ("/ip-blocklist", "ip=203.0.113.24", IP_BLOCKLIST_BODY),
+            (
+                "/phone-validate",
+                "number=%2B33611220757",
+                PHONE_VALIDATE_BODY,
+            ),
+            ("/hlr-lookup", "number=%2B33611220757", HLR_LOOKUP_BODY),
+        ]
+        .map(|(path, query, body)| {
+            mock("GET", path)
+                .match_query(Matcher::Regex(query.into()))
+                .with_status(200)
+                .with_body(body)
+                .expect(2)
+                .create()
+        });
+
+        let mut responses = Vec::new();
+        for (_, neutral) in backends(&mockito::server_url()) {
+            let ip = "203.0.113.24".parse().unwrap();
+            let number = "+33611220757".to_owned();
+            responses.push(vec![
+                format!("{:?}", neutral.ip_info().send(ip).await.unwrap()),
+                format!("{:?}", neutral.ip_probe().send(ip).await.unwrap()),
+                format!("{:?}", neutral.ip_blocklist().send(ip).await.unwrap()),
+                format!(
+                    "{:?}",
+                    neutral.phone_validate().send(number.clone()).await.unwrap()
+                ),
+                format!("{:?}", neutral.hlr_lookup().send(number).await.unwrap()),
+            ]);
+        }
+        assert_eq!(responses[0], responses[1]);
+        for mock in &_mocks {
+            mock.assert();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_backends_retry_connection_failures() {
+        for (backend, neutral) in backends("http://127.0.0.1:1") {
+            let err = neutral
+                .ip_info()
+                .send("203.0.113.25".parse().unwrap())
+                .await
+                .unwrap_err();
+            assert!(err.is_retryable(), "{}: {:?}", backend, err);
+            match backend {
+                "hyper" => assert!(matches!(err, Error::Hyper(_)), "{:?}", err),
+                _ => assert!(
+                    matches!(&err, Error::Service(err) if is_reqwest_connect(err)),
+                    "{:?}",
+                    err
+                ),
+            }
+        }
+    }
+}
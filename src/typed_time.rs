@@ -0,0 +1,203 @@
+//! # Typed time module
+//! Read the timestamps and the timezones of neutrinoapi.com responses as [chrono](https://docs.rs/chrono/0.4) values, with the `chrono` feature.
+//!
+//! The response types keep their raw fields, the extension traits of this module parse them on demand.
+//!
+//! ```ignore
+//! use neutral::typed_time::{IpBlocklistTime, TimeZoneTime};
+//!
+//! let ip_info_response = neutral.ip_info().send(ip_addr).await?;
+//! if let Some(offset) = ip_info_response.timezone.as_ref().and_then(|timezone| timezone.fixed_offset()) {
+//!     println!("UTC{}", offset);
+//! }
+//! let ip_blocklist_response = neutral.ip_blocklist().send(ip_addr).await?;
+//! if let Some(last_seen) = ip_blocklist_response.last_seen_at() {
+//!     println!("last seen on {}", last_seen);
+//! }
+//! ```
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use neutral_types::{ip_blocklist::IpBlocklistResponse, NeutrinoTimeZoneResponse};
+
+/// Typed values of a timezone.
+pub trait TimeZoneTime {
+    /// Returns the offset from UTC, e.g. `+01:00` or `-09:30`, or `None` when it can't be parsed.
+    fn fixed_offset(&self) -> Option<FixedOffset>;
+
+    /// Returns the local date and time of the timezone when the response was built, or `None` when they can't be parsed.
+    fn local_date_time(&self) -> Option<NaiveDateTime>;
+
+    /// Returns the local date and time along with the offset of the timezone, or `None` when one of them can't be parsed.
+    fn date_time(&self) -> Option<DateTime<FixedOffset>> {
+        self.fixed_offset()?
+            .from_local_datetime(&self.local_date_time()?)
+            .single()
+    }
+}
+
+impl TimeZoneTime for NeutrinoTimeZoneResponse {
+    fn fixed_offset(&self) -> Option<FixedOffset> {
+        parse_offset(&self.offset)
+    }
+
+    fn local_date_time(&self) -> Option<NaiveDateTime> {
+        let date = NaiveDate::parse_from_str(self.date.trim(), "%Y-%m-%d").ok()?;
+        let time = NaiveTime::parse_from_str(self.time.trim(), "%H:%M:%S%.f").ok()?;
+        Some(date.and_time(time))
+    }
+}
+
+/// Typed values of an ip blocklist response.
+pub trait IpBlocklistTime {
+    /// Returns the time at which the ip address was last seen, or `None` when it was never seen.
+    fn last_seen_at(&self) -> Option<DateTime<Utc>>;
+}
+
+impl IpBlocklistTime for IpBlocklistResponse {
+    fn last_seen_at(&self) -> Option<DateTime<Utc>> {
+        if self.last_seen == 0 {
+            return None;
+        }
+        let seconds = i64::try_from(self.last_seen).ok()?;
+        Utc.timestamp_opt(seconds, 0).single()
+    }
+}
+
+/// Parse an offset written `±HH:MM`, `±HHMM` or `±HH`.
+fn parse_offset(offset: &str) -> Option<FixedOffset> {
+    let offset = offset.trim();
+    let (sign, rest) = match offset.as_bytes().first()? {
+        b'+' => (1, &offset[1..]),
+        b'-' => (-1, &offset[1..]),
+        _ => return None,
+    };
+    let (hours, minutes) = match rest.split_once(':') {
+        Some((hours, minutes)) => (hours, minutes),
+        None if rest.len() == 4 => rest.split_at(2),
+        None => (rest, "00"),
+    };
+    if hours.len() != 2 || minutes.len() != 2 {
+        return None;
+    }
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    if minutes >= 60 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::{IP_BLOCKLIST_BODY, IP_INFO_BODY};
+    use neutral_types::ip_info::IpInfoResponse;
+
+    #[test]
+    fn test_parse_offset() {
+        struct TestingData {
+            pub name: String,
+            pub args: &'static str,
+            pub expected: Option<i32>,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Positive offset".to_owned(),
+                args: "+01:00",
+                expected: Some(3600),
+            },
+            TestingData {
+                name: "Negative offset with minutes".to_owned(),
+                args: "-09:30",
+                expected: Some(-(9 * 3600 + 30 * 60)),
+            },
+            TestingData {
+                name: "UTC".to_owned(),
+                args: "+00:00",
+                expected: Some(0),
+            },
+            TestingData {
+                name: "Offset without colon".to_owned(),
+                args: "+0545",
+                expected: Some(5 * 3600 + 45 * 60),
+            },
+            TestingData {
+                name: "Offset in hours".to_owned(),
+                args: "-03",
+                expected: Some(-3 * 3600),
+            },
+            TestingData {
+                name: "Missing sign".to_owned(),
+                args: "01:00",
+                expected: None,
+            },
+            TestingData {
+                name: "Invalid minutes".to_owned(),
+                args: "+01:75",
+                expected: None,
+            },
+            TestingData {
+                name: "Empty offset".to_owned(),
+                args: "",
+                expected: None,
+            },
+        ];
+
+        for test in &tests {
+            assert_eq!(
+                parse_offset(test.args).map(|offset| offset.local_minus_utc()),
+                test.expected,
+                "{}",
+                test.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_timezone_time() {
+        let response: IpInfoResponse = serde_json::from_str(IP_INFO_BODY).unwrap();
+        let mut timezone = response.timezone.unwrap();
+
+        assert_eq!(
+            timezone.fixed_offset(),
+            FixedOffset::east_opt(3600),
+            "{}",
+            timezone.offset
+        );
+        let local = NaiveDate::from_ymd_opt(2021, 11, 24)
+            .unwrap()
+            .and_hms_micro_opt(12, 47, 33, 825588)
+            .unwrap();
+        assert_eq!(timezone.local_date_time(), Some(local));
+        assert_eq!(
+            timezone.date_time().map(|date_time| date_time.naive_utc()),
+            Some(local - chrono::Duration::hours(1))
+        );
+
+        timezone.offset = "-09:30".to_owned();
+        timezone.time = "02:17:33".to_owned();
+        assert_eq!(
+            timezone.date_time().map(|date_time| date_time.to_rfc3339()),
+            Some("2021-11-24T02:17:33-09:30".to_owned())
+        );
+
+        timezone.offset = String::new();
+        assert_eq!(timezone.date_time(), None);
+    }
+
+    #[test]
+    fn test_ip_blocklist_time() {
+        let mut response: IpBlocklistResponse = serde_json::from_str(IP_BLOCKLIST_BODY).unwrap();
+        assert_eq!(response.last_seen, 0);
+        assert_eq!(response.last_seen_at(), None, "Never seen");
+
+        response.last_seen = 1_637_758_053;
+        assert_eq!(
+            response
+                .last_seen_at()
+                .map(|last_seen| last_seen.to_rfc3339()),
+            Some("2021-11-24T12:47:33+00:00".to_owned())
+        );
+    }
+}
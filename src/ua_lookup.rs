@@ -0,0 +1,331 @@
+//! # Ua lookup module
+//! Quoted from [neutrinoapi.com](https://www.neutrinoapi.com/api/ua-lookup):
+//!
+//! Parse, validate and get detailed user-agent information from a user agent string or from client hints.
+//!
+//! User agent strings are full of spaces, slashes, semicolons and parentheses: they are sent form-encoded in the body of a POST request.
+
+use http::{header::CONTENT_TYPE, Method};
+use hyper::{body::Bytes, Body, Request};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::{context::CallContext, meta::ResponseMeta, params, pipeline::Fetched, Error, Neutral};
+
+#[cfg(test)]
+use mockito;
+
+/// Response of ua lookup neutrinoapi.com endpoint
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct UaLookupResponse {
+    /// The user agent type, e.g. `desktop`, `phone`, `tablet`, `robot`.
+    #[serde(rename = "type")]
+    pub ua_type: String,
+    #[serde(default, alias = "name")]
+    pub browser_name: String,
+    #[serde(default, alias = "version")]
+    pub browser_version: String,
+    #[serde(default, alias = "os")]
+    pub os_name: String,
+    #[serde(default)]
+    pub os_version: String,
+    #[serde(default)]
+    pub device_brand: String,
+    #[serde(default)]
+    pub device_model: String,
+    #[serde(default)]
+    pub is_mobile: bool,
+    #[serde(default)]
+    pub is_android: bool,
+    #[serde(default)]
+    pub is_ios: bool,
+}
+
+/// Hints completing a user agent string, e.g. taken from the `Sec-CH-UA-*` client hints.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct UaHints {
+    device_brand: Option<String>,
+    device_model: Option<String>,
+    os: Option<String>,
+}
+
+/// Parameters of a ua lookup call, e.g. to persist a pending call and send it later with [UaLookup::send_params].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UaLookupParams {
+    pub ua: String,
+    /// See [UaLookup::device_brand].
+    #[serde(default)]
+    pub device_brand: Option<String>,
+    /// See [UaLookup::device_model].
+    #[serde(default)]
+    pub device_model: Option<String>,
+    /// See [UaLookup::os].
+    #[serde(default)]
+    pub os: Option<String>,
+}
+
+pub struct UaLookup<'a> {
+    pub(crate) neutral: &'a Neutral,
+    pub(crate) context: CallContext,
+    hints: UaHints,
+}
+
+impl<'a> UaLookup<'a> {
+    pub(crate) fn new(neutral: &'a Neutral) -> Self {
+        UaLookup {
+            neutral,
+            context: CallContext::default(),
+            hints: UaHints::default(),
+        }
+    }
+
+    /// Attach a [CallContext](../context/struct.CallContext.html) to the calls.
+    pub fn context(mut self, context: CallContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Bound each attempt of the call by `timeout` instead of the client default, see [CallContext::timeout](../context/struct.CallContext.html#structfield.timeout).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.context.timeout = Some(timeout);
+        self
+    }
+
+    /// Hint the brand of the device, e.g. `Samsung`.
+    pub fn device_brand(mut self, device_brand: String) -> Self {
+        self.hints.device_brand = Some(device_brand);
+        self
+    }
+
+    /// Hint the model of the device, e.g. `SM-G998B`.
+    pub fn device_model(mut self, device_model: String) -> Self {
+        self.hints.device_model = Some(device_model);
+        self
+    }
+
+    /// Hint the operating system of the device, e.g. `Android 12`.
+    pub fn os(mut self, os: String) -> Self {
+        self.hints.os = Some(os);
+        self
+    }
+
+    /// Send a ua lookup request to neutrinoapi.com
+    pub async fn send(&self, ua: String) -> Result<UaLookupResponse, Error> {
+        self.send_with_meta(ua).await.map(|(_, response)| response)
+    }
+
+    /// Same as [send](#method.send), with the user agent and hints of `params`, which replace the hints set on the call.
+    pub async fn send_params(&self, params: &UaLookupParams) -> Result<UaLookupResponse, Error> {
+        let call = UaLookup {
+            neutral: self.neutral,
+            context: self.context.clone(),
+            hints: UaHints {
+                device_brand: params.device_brand.clone(),
+                device_model: params.device_model.clone(),
+                os: params.os.clone(),
+            },
+        };
+        call.send(params.ua.clone()).await
+    }
+
+    /// Same as [send](#method.send), also returning the [ResponseMeta](../meta/struct.ResponseMeta.html) of the response.
+    pub async fn send_with_meta(
+        &self,
+        ua: String,
+    ) -> Result<(ResponseMeta, UaLookupResponse), Error> {
+        let fetched = self.fetch(ua).await?;
+        let response: UaLookupResponse = self.neutral.decode("ua-lookup", &fetched.body)?;
+        Ok((fetched.meta, response))
+    }
+
+    /// Same as [send](#method.send), returning the raw body of the response.
+    pub async fn send_raw(&self, ua: String) -> Result<Bytes, Error> {
+        Ok(self.fetch(ua).await?.body)
+    }
+
+    /// Same as [send](#method.send), decoding the response into any `T`, e.g. a struct holding fields unknown to [UaLookupResponse].
+    pub async fn send_as<T: DeserializeOwned>(&self, ua: String) -> Result<T, Error> {
+        let fetched = self.fetch(ua).await?;
+        self.neutral.decode("ua-lookup", &fetched.body)
+    }
+
+    /// Same as [send](#method.send), decoding the response into a [serde_json::Value].
+    pub async fn send_value(&self, ua: String) -> Result<serde_json::Value, Error> {
+        self.send_as(ua).await
+    }
+
+    /// Send the request, without decoding its response.
+    async fn fetch(&self, ua: String) -> Result<Fetched, Error> {
+        let body = form(&ua, &self.hints);
+        self.neutral
+            .execute("ua-lookup", Some(&body), &self.context, || {
+                build_request(self.neutral, &body, &self.context)
+            })
+            .await
+    }
+}
+
+/// Returns the form-encoded body of the lookup of `ua`, which is also its canonical input.
+fn form(ua: &str, hints: &UaHints) -> String {
+    let mut form = params();
+    form.append_pair("ua", ua);
+    if let Some(device_brand) = &hints.device_brand {
+        form.append_pair("device-brand", device_brand);
+    }
+    if let Some(device_model) = &hints.device_model {
+        form.append_pair("device-model", device_model);
+    }
+    if let Some(os) = &hints.os {
+        form.append_pair("os", os);
+    }
+    form.finish()
+}
+
+/// Build the ua lookup request of a form-encoded `body`, without sending it.
+pub(crate) fn build_request(
+    neutral: &Neutral,
+    body: &str,
+    context: &CallContext,
+) -> Result<Request<Body>, Error> {
+    Ok(neutral
+        .request_builder("/ua-lookup".to_owned(), context)?
+        .method(Method::POST)
+        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from(body.to_owned()))?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ApiAuth;
+    use mockito::{mock, Matcher};
+
+    const CHROME_ANDROID: &str = "Mozilla/5.0 (Linux; Android 12; SM-G998B) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/104.0.0.0 Mobile Safari/537.36";
+
+    #[test]
+    fn test_ua_lookup_form() {
+        struct TestingData {
+            pub name: String,
+            pub args: (&'static str, UaHints),
+            pub expected: &'static str,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Spaces, slashes, semicolons and parentheses".to_owned(),
+                args: ("Mozilla/5.0 (X11; Linux x86_64)", UaHints::default()),
+                expected: "ua=Mozilla%2F5.0+%28X11%3B+Linux+x86_64%29",
+            },
+            TestingData {
+                name: "With hints".to_owned(),
+                args: (
+                    "curl/7.84.0",
+                    UaHints {
+                        device_brand: Some("Samsung".to_owned()),
+                        device_model: Some("SM-G998B".to_owned()),
+                        os: Some("Android 12".to_owned()),
+                    },
+                ),
+                expected:
+                    "ua=curl%2F7.84.0&device-brand=Samsung&device-model=SM-G998B&os=Android+12",
+            },
+        ];
+
+        for test in &tests {
+            let (ua, hints) = &test.args;
+            assert_eq!(form(ua, hints), test.expected, "{}", test.name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ua_lookup() {
+        let _m = mock("POST", "/ua-lookup")
+            .match_header("content-type", "application/x-www-form-urlencoded")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("ua".into(), CHROME_ANDROID.into()),
+                Matcher::UrlEncoded("device-brand".into(), "Samsung".into()),
+            ]))
+            .with_status(200)
+            .with_body(
+                r#"
+                {
+                    "ua": "Mozilla/5.0 (Linux; Android 12; SM-G998B) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/104.0.0.0 Mobile Safari/537.36",
+                    "type": "phone",
+                    "name": "Chrome Mobile",
+                    "version": "104.0.0.0",
+                    "os": "Android",
+                    "os_version": "12",
+                    "device_brand": "Samsung",
+                    "device_model": "Galaxy S21 Ultra",
+                    "is_mobile": true,
+                    "is_android": true,
+                    "is_ios": false
+                }"#,
+            )
+            .expect(1)
+            .create();
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        let response = neutral
+            .ua_lookup()
+            .device_brand("Samsung".to_owned())
+            .send(CHROME_ANDROID.to_owned())
+            .await
+            .unwrap();
+        assert_eq!(
+            response,
+            UaLookupResponse {
+                ua_type: "phone".to_owned(),
+                browser_name: "Chrome Mobile".to_owned(),
+                browser_version: "104.0.0.0".to_owned(),
+                os_name: "Android".to_owned(),
+                os_version: "12".to_owned(),
+                device_brand: "Samsung".to_owned(),
+                device_model: "Galaxy S21 Ultra".to_owned(),
+                is_mobile: true,
+                is_android: true,
+                is_ios: false,
+            }
+        );
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_ua_lookup_params() {
+        let params = UaLookupParams {
+            ua: CHROME_ANDROID.to_owned(),
+            device_brand: Some("Samsung".to_owned()),
+            device_model: None,
+            os: Some("Android 12".to_owned()),
+        };
+        let json = serde_json::to_string(&params).unwrap();
+        assert_eq!(
+            serde_json::from_str::<UaLookupParams>(&json).unwrap(),
+            params
+        );
+
+        let (neutral, requests) = crate::fixtures::recording(r#"{"type": "phone"}"#);
+        neutral
+            .ua_lookup()
+            .device_model("SM-G998B".to_owned())
+            .send_params(&params)
+            .await
+            .unwrap();
+        neutral
+            .ua_lookup()
+            .device_brand("Samsung".to_owned())
+            .os("Android 12".to_owned())
+            .send(CHROME_ANDROID.to_owned())
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0], requests[1]);
+    }
+}
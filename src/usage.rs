@@ -0,0 +1,169 @@
+//! # Usage module
+//! Keep a running tally of the requests sent to neutrinoapi.com by a client.
+//!
+//! [Neutral::usage](../struct.Neutral.html#method.usage) returns a [Usage](./struct.Usage.html) snapshot, shared by every clone of the client.
+//! Every attempt is counted, retries included, while a response served from a cache is not.
+//!
+//! ```ignore
+//! for ip_addr in ip_addrs {
+//!     if neutral.usage().rate_limit.remaining.map_or(false, |remaining| remaining < 100) {
+//!         break;
+//!     }
+//!     neutral.ip_info().send(ip_addr).await?;
+//! }
+//! ```
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+};
+
+use http::HeaderMap;
+
+use crate::meta::RateLimit;
+
+/// A snapshot of the requests sent by a client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Usage {
+    /// Number of requests sent, whatever their outcome.
+    pub requests: u64,
+    /// Number of requests which failed: a transport error, an error response or a malformed successful response.
+    pub failures: u64,
+    /// Most recent value of each rate limit header, kept until a later response reports it again.
+    pub rate_limit: RateLimit,
+}
+
+/// Count the requests of a client and its clones.
+#[derive(Debug, Default)]
+pub(crate) struct UsageTracker {
+    requests: AtomicU64,
+    failures: AtomicU64,
+    rate_limit: Mutex<RateLimit>,
+}
+
+impl UsageTracker {
+    /// Count a request, with the headers of its response when one was received.
+    pub(crate) fn record(&self, headers: Option<&HeaderMap>, is_failure: bool) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        if is_failure {
+            self.failures.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(headers) = headers {
+            let observed = RateLimit::from_headers(headers);
+            let mut rate_limit = self.rate_limit.lock().unwrap();
+            rate_limit.limit = observed.limit.or(rate_limit.limit);
+            rate_limit.remaining = observed.remaining.or(rate_limit.remaining);
+            rate_limit.reset = observed.reset.or(rate_limit.reset);
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> Usage {
+        Usage {
+            requests: self.requests.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+            rate_limit: *self.rate_limit.lock().unwrap(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{fixtures::IP_INFO_BODY, ApiAuth, Neutral};
+    use mockito::{mock, Matcher};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_usage() {
+        let _first = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.242$".into()))
+            .with_status(200)
+            .with_header("x-ratelimit-limit", "1000")
+            .with_header("x-ratelimit-remaining", "42")
+            .with_header("x-ratelimit-reset", "60")
+            .with_body(IP_INFO_BODY)
+            .create();
+        let _second = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.243$".into()))
+            .with_status(200)
+            .with_header("x-ratelimit-remaining", "41")
+            .with_body(IP_INFO_BODY)
+            .create();
+        let _denied = mock("GET", "/ip-info")
+            .match_query(Matcher::Regex("ip=203.0.113.244$".into()))
+            .with_status(429)
+            .with_header("x-ratelimit-remaining", "0")
+            .with_body(r#"{"api-error": 3, "api-error-msg": "RATE LIMIT EXCEEDED"}"#)
+            .create();
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+        assert_eq!(neutral.usage(), Usage::default());
+
+        neutral
+            .ip_info()
+            .send("203.0.113.242".parse().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            neutral.usage(),
+            Usage {
+                requests: 1,
+                failures: 0,
+                rate_limit: RateLimit {
+                    limit: Some(1000),
+                    remaining: Some(42),
+                    reset: Some(Duration::from_secs(60)),
+                },
+            }
+        );
+
+        // A clone shares the tally of the client.
+        let clone = neutral.clone();
+        clone
+            .ip_info()
+            .send("203.0.113.243".parse().unwrap())
+            .await
+            .unwrap();
+        assert!(clone
+            .ip_info()
+            .send("203.0.113.244".parse().unwrap())
+            .await
+            .is_err());
+
+        assert_eq!(
+            neutral.usage(),
+            Usage {
+                requests: 3,
+                failures: 1,
+                rate_limit: RateLimit {
+                    limit: Some(1000),
+                    remaining: Some(0),
+                    reset: Some(Duration::from_secs(60)),
+                },
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_usage_counts_transport_errors() {
+        // Nothing listens on this port.
+        let neutral = Neutral::try_new(
+            "http://127.0.0.1:1",
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+        assert!(neutral
+            .ip_info()
+            .send("203.0.113.245".parse().unwrap())
+            .await
+            .is_err());
+
+        let usage = neutral.usage();
+        assert_eq!((usage.requests, usage.failures), (1, 1));
+        assert_eq!(usage.rate_limit, RateLimit::default());
+    }
+}
@@ -0,0 +1,312 @@
+//! # Verify security code module
+//! Quoted from [neutrinoapi.com](https://www.neutrinoapi.com/api/verify-security-code):
+//!
+//! Check if a security code sent via SMS Verify or Phone Verify is valid.
+//!
+//! A full phone verification round trip:
+//!
+//! ```
+//! # use mockito::{mock, Matcher};
+//! # use neutral::{phone_verify::PhoneVerifyOptions, ApiAuth, Neutral};
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), neutral::error::Error> {
+//! let _phone_verify = mock("GET", "/phone-verify")
+//!     .match_query(Matcher::Regex("number=%2B33612345678".into()))
+//!     .with_body(r#"{"security_code": "123456", "number_valid": true, "calling": true}"#)
+//!     .create();
+//! let _verify_security_code = mock("GET", "/verify-security-code")
+//!     .match_query(Matcher::Regex("security-code=123456".into()))
+//!     .with_body(r#"{"verified": true}"#)
+//!     .create();
+//!
+//! let api_auth = ApiAuth::new("user".to_owned(), "api-key".to_owned());
+//! let neutral = Neutral::try_new(&mockito::server_url(), api_auth)?;
+//!
+//! let call = neutral
+//!     .phone_verify()
+//!     .send("+33612345678".to_owned(), PhoneVerifyOptions::default())
+//!     .await?;
+//! assert!(call.is_calling);
+//!
+//! // Later, with the code typed by the user
+//! let typed_code = call.security_code;
+//! let response = neutral
+//!     .verify_security_code()
+//!     .send(typed_code, Some("+33612345678".to_owned()))
+//!     .await?;
+//! assert!(response.is_verified);
+//! # Ok(())
+//! # }
+//! ```
+
+use hyper::{body::Bytes, Body, Request};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::{context::CallContext, meta::ResponseMeta, params, pipeline::Fetched, Error, Neutral};
+
+#[cfg(test)]
+use mockito;
+
+/// Response of verify security code neutrinoapi.com endpoint
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct VerifySecurityCodeResponse {
+    /// True when the code is valid and not expired.
+    #[serde(alias = "verified", alias = "is_verified")]
+    pub is_verified: bool,
+}
+
+/// Parameters of a verify security code call, e.g. to persist a pending call and send it later with [VerifySecurityCode::send_params].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifySecurityCodeParams {
+    pub security_code: String,
+    /// See [VerifySecurityCode::send].
+    #[serde(default)]
+    pub limit_by_phone_number: Option<String>,
+}
+
+pub struct VerifySecurityCode<'a> {
+    pub(crate) neutral: &'a Neutral,
+    pub(crate) context: CallContext,
+}
+
+impl<'a> VerifySecurityCode<'a> {
+    /// Attach a [CallContext](../context/struct.CallContext.html) to the call.
+    pub fn context(mut self, context: CallContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Bound each attempt of the call by `timeout` instead of the client default, see [CallContext::timeout](../context/struct.CallContext.html#structfield.timeout).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.context.timeout = Some(timeout);
+        self
+    }
+
+    /// Send a verify security code request to neutrinoapi.com.
+    ///
+    /// With `limit_by_phone_number`, failed attempts are counted per phone number, limiting brute force attempts.
+    /// The request is never cached nor retried since neutrinoapi.com counts every attempt.
+    pub async fn send(
+        &self,
+        security_code: String,
+        limit_by_phone_number: Option<String>,
+    ) -> Result<VerifySecurityCodeResponse, Error> {
+        self.send_with_meta(security_code, limit_by_phone_number)
+            .await
+            .map(|(_, response)| response)
+    }
+
+    /// Same as [send](#method.send), with the security code and phone number of `params`.
+    pub async fn send_params(
+        &self,
+        params: &VerifySecurityCodeParams,
+    ) -> Result<VerifySecurityCodeResponse, Error> {
+        self.send(
+            params.security_code.clone(),
+            params.limit_by_phone_number.clone(),
+        )
+        .await
+    }
+
+    /// Same as [send](#method.send), also returning the [ResponseMeta](../meta/struct.ResponseMeta.html) of the response.
+    pub async fn send_with_meta(
+        &self,
+        security_code: String,
+        limit_by_phone_number: Option<String>,
+    ) -> Result<(ResponseMeta, VerifySecurityCodeResponse), Error> {
+        let fetched = self.fetch(security_code, limit_by_phone_number).await?;
+        let response: VerifySecurityCodeResponse =
+            self.neutral.decode("verify-security-code", &fetched.body)?;
+        Ok((fetched.meta, response))
+    }
+
+    /// Same as [send](#method.send), returning the raw body of the response.
+    pub async fn send_raw(
+        &self,
+        security_code: String,
+        limit_by_phone_number: Option<String>,
+    ) -> Result<Bytes, Error> {
+        Ok(self.fetch(security_code, limit_by_phone_number).await?.body)
+    }
+
+    /// Same as [send](#method.send), decoding the response into any `T`, e.g. a struct holding fields unknown to [VerifySecurityCodeResponse].
+    pub async fn send_as<T: DeserializeOwned>(
+        &self,
+        security_code: String,
+        limit_by_phone_number: Option<String>,
+    ) -> Result<T, Error> {
+        let fetched = self.fetch(security_code, limit_by_phone_number).await?;
+        self.neutral.decode("verify-security-code", &fetched.body)
+    }
+
+    /// Same as [send](#method.send), decoding the response into a [serde_json::Value].
+    pub async fn send_value(
+        &self,
+        security_code: String,
+        limit_by_phone_number: Option<String>,
+    ) -> Result<serde_json::Value, Error> {
+        self.send_as(security_code, limit_by_phone_number).await
+    }
+
+    /// Send the request, without decoding its response.
+    async fn fetch(
+        &self,
+        security_code: String,
+        limit_by_phone_number: Option<String>,
+    ) -> Result<Fetched, Error> {
+        self.neutral
+            .execute("verify-security-code", None, &self.context, || {
+                build_request(
+                    self.neutral,
+                    &security_code,
+                    limit_by_phone_number.as_deref(),
+                    &self.context,
+                )
+            })
+            .await
+    }
+}
+
+/// Build the verify security code request of `security_code`, without sending it.
+pub(crate) fn build_request(
+    neutral: &Neutral,
+    security_code: &str,
+    limit_by_phone_number: Option<&str>,
+    context: &CallContext,
+) -> Result<Request<Body>, Error> {
+    let mut query = params();
+    query.append_pair("security-code", security_code);
+    if let Some(phone_number) = limit_by_phone_number {
+        query.append_pair("limit-by", phone_number);
+    }
+
+    neutral.params_request("/verify-security-code", query.finish(), context)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ApiAuth;
+    use mockito::{mock, Matcher};
+
+    #[test]
+    fn test_verify_security_code_build_request() {
+        struct TestingData {
+            pub name: String,
+            pub args: (&'static str, Option<&'static str>),
+            pub expected: &'static str,
+        }
+
+        let tests = vec![
+            TestingData {
+                name: "Without limit".to_owned(),
+                args: ("123456", None),
+                expected: "/verify-security-code?security-code=123456",
+            },
+            TestingData {
+                name: "Limited by phone number".to_owned(),
+                args: ("123456", Some("+33612345678")),
+                expected: "/verify-security-code?security-code=123456&limit-by=%2B33612345678",
+            },
+        ];
+
+        let neutral = Neutral::try_new(
+            "https://neutrinoapi.net",
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        for test in &tests {
+            let (security_code, limit_by) = test.args;
+            let request =
+                build_request(&neutral, security_code, limit_by, &CallContext::default()).unwrap();
+            assert_eq!(
+                request.uri().path_and_query().unwrap().as_str(),
+                test.expected,
+                "{}",
+                test.name
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_security_code() {
+        struct TestingData {
+            pub name: String,
+            pub security_code: &'static str,
+            pub expected: bool,
+        }
+
+        let _matching = mock("GET", "/verify-security-code")
+            .match_query(Matcher::Regex("security-code=482913".into()))
+            .with_status(200)
+            .with_body(r#"{"verified": true}"#)
+            .create();
+        let _non_matching = mock("GET", "/verify-security-code")
+            .match_query(Matcher::Regex("security-code=000000".into()))
+            .with_status(200)
+            .with_body(r#"{"verified": false}"#)
+            .create();
+
+        let tests = vec![
+            TestingData {
+                name: "Matching code".to_owned(),
+                security_code: "482913",
+                expected: true,
+            },
+            TestingData {
+                name: "Non matching code".to_owned(),
+                security_code: "000000",
+                expected: false,
+            },
+        ];
+
+        let neutral = Neutral::try_new(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .unwrap();
+
+        for test in &tests {
+            let response = neutral
+                .verify_security_code()
+                .send(test.security_code.to_owned(), None)
+                .await
+                .unwrap();
+            assert_eq!(response.is_verified, test.expected, "{}", test.name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_security_code_params() {
+        let params = VerifySecurityCodeParams {
+            security_code: "482913".to_owned(),
+            limit_by_phone_number: Some("+33611223399".to_owned()),
+        };
+        let json = serde_json::to_string(&params).unwrap();
+        assert_eq!(
+            serde_json::from_str::<VerifySecurityCodeParams>(&json).unwrap(),
+            params
+        );
+
+        let (neutral, requests) = crate::fixtures::recording(r#"{"verified": true}"#);
+        neutral
+            .verify_security_code()
+            .send_params(&params)
+            .await
+            .unwrap();
+        neutral
+            .verify_security_code()
+            .send(
+                params.security_code.clone(),
+                params.limit_by_phone_number.clone(),
+            )
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0], requests[1]);
+    }
+}
@@ -0,0 +1,241 @@
+//! # Warmer module
+//! Fill the response cache ahead of traffic from a seed list of ip addresses, e.g. yesterday's top talkers.
+//!
+//! The [CacheWarmer](./struct.CacheWarmer.html) sends ip info and ip blocklist requests for every seed which is not cached yet.
+//! Each upstream request consumes one credit of the budget, responses already cached are free.
+//! The credits of a seed are reserved at once: a seed is skipped rather than half warmed when the budget runs out.
+//! Warming stops cleanly once the seeds, the budget or the time are exhausted, and its progress is published on a watch channel.
+//!
+//! ```ignore
+//! let neutral = Neutral::builder("https://neutrinoapi.net", api_auth)
+//!     .cache(CacheConfig::default())
+//!     .build()?;
+//!
+//! let warmer = CacheWarmer::new(WarmerConfig {
+//!     concurrency: 4,
+//!     budget: 1000,
+//!     deadline: Duration::from_secs(300),
+//! });
+//! let mut progress = warmer.progress();
+//! let report = warmer.run(&neutral, futures::stream::iter(top_talkers)).await;
+//! ```
+
+use std::{
+    net::IpAddr,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use futures::{Stream, StreamExt};
+use tokio::sync::watch;
+
+use crate::Neutral;
+
+/// Configure a [CacheWarmer](./struct.CacheWarmer.html).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WarmerConfig {
+    /// Maximum number of seeds warmed at the same time.
+    pub concurrency: usize,
+    /// Maximum number of upstream requests.
+    pub budget: usize,
+    /// Maximum duration of the warming.
+    pub deadline: Duration,
+}
+
+/// Describes why the warming stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WarmerState {
+    /// The warming is still running.
+    Running,
+    /// Every seed was processed.
+    Completed,
+    /// The budget is exhausted, remaining seeds were skipped.
+    BudgetExhausted,
+    /// The deadline was reached, in-flight requests were abandoned.
+    DeadlineReached,
+}
+
+/// Progress of a [CacheWarmer](./struct.CacheWarmer.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WarmerProgress {
+    /// Current state of the warming.
+    pub state: WarmerState,
+    /// Number of upstream requests sent, i.e. consumed budget.
+    pub upstream_calls: usize,
+    /// Number of responses stored in the cache.
+    pub warmed: usize,
+    /// Number of responses which were already cached.
+    pub already_cached: usize,
+    /// Number of upstream requests which failed.
+    pub failed: usize,
+    /// Number of requests skipped because the budget was exhausted.
+    pub skipped: usize,
+}
+
+impl Default for WarmerProgress {
+    fn default() -> Self {
+        WarmerProgress {
+            state: WarmerState::Running,
+            upstream_calls: 0,
+            warmed: 0,
+            already_cached: 0,
+            failed: 0,
+            skipped: 0,
+        }
+    }
+}
+
+/// Fill the caches of a [Neutral](../struct.Neutral.html) instance.
+#[derive(Debug)]
+pub struct CacheWarmer {
+    config: WarmerConfig,
+    progress: watch::Sender<WarmerProgress>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Target {
+    IpInfo,
+    IpBlocklist,
+}
+
+impl Target {
+    fn endpoint(&self) -> &'static str {
+        match self {
+            Target::IpInfo => "ip-info",
+            Target::IpBlocklist => "ip-blocklist",
+        }
+    }
+}
+
+impl CacheWarmer {
+    /// Create a cache warmer.
+    pub fn new(config: WarmerConfig) -> Self {
+        let (progress, _) = watch::channel(WarmerProgress::default());
+        CacheWarmer { config, progress }
+    }
+
+    /// Subscribe to the progress of the warming.
+    pub fn progress(&self) -> watch::Receiver<WarmerProgress> {
+        self.progress.subscribe()
+    }
+
+    /// Warm the caches of `neutral` using `seeds`, returns the final progress.
+    pub async fn run<S>(&self, neutral: &Neutral, seeds: S) -> WarmerProgress
+    where
+        S: Stream<Item = IpAddr>,
+    {
+        let remaining = AtomicUsize::new(self.config.budget);
+
+        let warming = seeds
+            .take_while(|_| futures::future::ready(remaining.load(Ordering::SeqCst) > 0))
+            .for_each_concurrent(self.config.concurrency.max(1), |ip_addr| {
+                self.warm(neutral, &remaining, ip_addr)
+            });
+
+        let state = match crate::time::timeout(self.config.deadline, warming).await {
+            Err(_) => WarmerState::DeadlineReached,
+            Ok(()) if remaining.load(Ordering::SeqCst) == 0 => WarmerState::BudgetExhausted,
+            Ok(()) => WarmerState::Completed,
+        };
+
+        self.progress.send_modify(|progress| progress.state = state);
+        *self.progress.borrow()
+    }
+
+    /// Warm every target of `ip_addr` which is not cached yet, reserving their credits at once so a seed is never half warmed.
+    async fn warm(&self, neutral: &Neutral, remaining: &AtomicUsize, ip_addr: IpAddr) {
+        let canonical_input = ip_addr.to_string();
+        let targets: Vec<Target> = [Target::IpInfo, Target::IpBlocklist]
+            .into_iter()
+            .filter(|target| !neutral.cache_contains(target.endpoint(), &canonical_input))
+            .collect();
+
+        let already_cached = 2 - targets.len();
+        self.progress
+            .send_modify(|progress| progress.already_cached += already_cached);
+
+        let credits = remaining.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |remaining| {
+            remaining.checked_sub(targets.len())
+        });
+        if credits.is_err() {
+            self.progress
+                .send_modify(|progress| progress.skipped += targets.len());
+            return;
+        }
+
+        for target in targets {
+            self.progress
+                .send_modify(|progress| progress.upstream_calls += 1);
+            let is_ok = match target {
+                Target::IpInfo => neutral.ip_info().send(ip_addr).await.is_ok(),
+                Target::IpBlocklist => neutral.ip_blocklist().send(ip_addr).await.is_ok(),
+            };
+            self.progress.send_modify(|progress| {
+                if is_ok {
+                    progress.warmed += 1;
+                } else {
+                    progress.failed += 1;
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        cache::CacheConfig,
+        fixtures::{IP_BLOCKLIST_BODY, IP_INFO_BODY},
+        ApiAuth,
+    };
+    use mockito::{mock, Matcher};
+    use std::net::Ipv4Addr;
+
+    #[tokio::test]
+    async fn test_cache_warmer_respects_budget() {
+        let _ip_info = mock("GET", "/ip-info")
+            .match_query(Matcher::Any)
+            .with_status(200)
+            .with_body(IP_INFO_BODY)
+            .expect(15)
+            .create();
+
+        let _ip_blocklist = mock("GET", "/ip-blocklist")
+            .match_query(Matcher::Any)
+            .with_status(200)
+            .with_body(IP_BLOCKLIST_BODY)
+            .expect(15)
+            .create();
+
+        let neutral = Neutral::builder(
+            &mockito::server_url(),
+            ApiAuth::new("User".to_string(), "test".to_string()),
+        )
+        .cache(CacheConfig::default())
+        .build()
+        .unwrap();
+
+        let seeds: Vec<IpAddr> = (1..=50)
+            .map(|i| IpAddr::V4(Ipv4Addr::new(128, 0, 0, i)))
+            .collect();
+
+        let warmer = CacheWarmer::new(WarmerConfig {
+            concurrency: 4,
+            budget: 30,
+            deadline: Duration::from_secs(30),
+        });
+        let progress = warmer.progress();
+        let report = warmer.run(&neutral, futures::stream::iter(seeds)).await;
+
+        assert_eq!(report.state, WarmerState::BudgetExhausted);
+        assert_eq!(report.upstream_calls, 30);
+        assert_eq!(report.warmed, 30);
+        assert_eq!(report.failed, 0);
+        assert_eq!(report.already_cached, 0);
+        assert_eq!(*progress.borrow(), report);
+
+        _ip_info.assert();
+        _ip_blocklist.assert();
+    }
+}
@@ -0,0 +1,22 @@
+//! # Warning module
+//! Non fatal anomalies detected by this crate in neutrinoapi.com responses or in the inputs sent to it.
+
+/// Describe a non fatal anomaly.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Warning {
+    /// An hlr lookup response reports a roaming status which does not match its roaming country code.
+    InconsistentRoaming {
+        is_roaming: bool,
+        roaming_country_code: String,
+    },
+    /// A cache store record has an incompatible version, it was ignored and the response fetched again.
+    IncompatibleCacheRecord { version: u32 },
+    /// A response field holds a value unknown to its enum, it was read as the `Unknown` variant of the enum.
+    UnknownVariant { field: String, value: String },
+    /// A free text input was larger than the limit of its endpoint and was truncated.
+    Truncated {
+        original_len: usize,
+        truncated_len: usize,
+    },
+}
@@ -0,0 +1,147 @@
+//! Run the client built for wasm32 against a mock server of the JavaScript host, sending its requests with `fetch`:
+//!
+//! ```text
+//! wasm-pack test --node --no-default-features --features wasm -- --test wasm
+//! ```
+#![cfg(target_arch = "wasm32")]
+
+use std::time::Duration;
+
+use js_sys::{Function, Promise};
+use neutral::{error::Error, ApiAuth, Neutral};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_futures::JsFuture;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+const IP_INFO_BODY: &str = r#"
+{
+    "ip": "203.0.113.9",
+    "valid": true,
+    "is_v6": false,
+    "is_v4_mapped": false,
+    "is_bogon": false,
+    "country": "ACountry",
+    "country_code": "AC",
+    "country_code3": "ACO",
+    "continent_code": "EU",
+    "currency_code": "ABC",
+    "city": "Roubaix",
+    "region": "Hauts-de-ACountry",
+    "longitude": 1.00000,
+    "latitude": 1.00000,
+    "hostname": "",
+    "host_domain": "",
+    "timezone": {
+        "id": "Europe/Paris",
+        "name": "Central European Standard Time",
+        "abbr": "CET",
+        "date": "2021-11-24",
+        "time": "12:47:33.825588",
+        "offset": "+01:00"
+    }
+}"#;
+
+/// Start a node http server answering every request with `status`, `headers` and `body` after `delay_ms`,
+/// when it carries the `ip=203.0.113.9` query and the credentials of [auth]. Resolves to its base uri.
+const SERVE: &str = r#"
+const http = process.getBuiltinModule('http');
+return new Promise((resolve) => {
+    const server = http.createServer((req, res) => {
+        const authorized = req.headers['user-id'] === 'User' && req.headers['api-key'] === 'test';
+        if (!authorized || req.url !== '/ip-info?ip=203.0.113.9') {
+            res.writeHead(404);
+            res.end();
+            return;
+        }
+        setTimeout(() => {
+            res.writeHead(status, JSON.parse(headers));
+            res.end(body);
+        }, delay_ms);
+    });
+    server.listen(0, '127.0.0.1', () => {
+        server.unref();
+        resolve('http://127.0.0.1:' + server.address().port);
+    });
+});
+"#;
+
+async fn serve(status: u16, headers: &str, body: &str, delay_ms: u32) -> String {
+    let serve = Function::new_with_args("status, headers, body, delay_ms", SERVE);
+    let args = js_sys::Array::of4(
+        &JsValue::from(status),
+        &JsValue::from(headers),
+        &JsValue::from(body),
+        &JsValue::from(delay_ms),
+    );
+    let promise: Promise = serve.apply(&JsValue::NULL, &args).unwrap().into();
+    JsFuture::from(promise).await.unwrap().as_string().unwrap()
+}
+
+fn auth() -> ApiAuth {
+    ApiAuth::new("User".to_string(), "test".to_string())
+}
+
+#[wasm_bindgen_test]
+async fn test_ip_info_with_fetch() {
+    let uri = serve(
+        200,
+        r#"{"content-type": "application/json"}"#,
+        IP_INFO_BODY,
+        0,
+    )
+    .await;
+    let neutral = Neutral::try_new(&uri, auth()).unwrap();
+
+    let ip_info = neutral
+        .ip_info()
+        .send("203.0.113.9".parse().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(ip_info.city, "Roubaix");
+    assert_eq!(ip_info.country_code, "AC");
+}
+
+#[wasm_bindgen_test]
+async fn test_rate_limited_with_fetch() {
+    let uri = serve(
+        429,
+        r#"{"content-type": "application/json", "retry-after": "120"}"#,
+        r#"{"api-error": 2, "api-error-msg": "TOO MANY REQUESTS"}"#,
+        0,
+    )
+    .await;
+    let neutral = Neutral::try_new(&uri, auth()).unwrap();
+
+    let err = neutral
+        .ip_info()
+        .send("203.0.113.9".parse().unwrap())
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, Error::RateLimited { retry_after, .. } if retry_after == Some(Duration::from_secs(120))),
+        "{:?}",
+        err
+    );
+}
+
+#[wasm_bindgen_test]
+async fn test_timeout_with_fetch() {
+    let uri = serve(
+        200,
+        r#"{"content-type": "application/json"}"#,
+        IP_INFO_BODY,
+        500,
+    )
+    .await;
+    let neutral = Neutral::builder(&uri, auth())
+        .timeout(Duration::from_millis(50))
+        .build()
+        .unwrap();
+
+    let err = neutral
+        .ip_info()
+        .send("203.0.113.9".parse().unwrap())
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::Timeout(_)), "{:?}", err);
+}